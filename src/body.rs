@@ -0,0 +1,115 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Adapters of [ResponseStream] and [BodyReader] implementing
+//! `http_body::Body`.
+
+use crate::{
+    response::{parse::BodyReader, ContentKind, ResponseStream},
+    ClientError,
+};
+use bytes::Bytes;
+use http_body::{Body, Frame};
+use http_body_util::{combinators::UnsyncBoxBody, BodyExt, Full};
+use std::{
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use tokio::io::{AsyncRead, ReadBuf};
+use tracing::debug;
+
+/// Type-erased body, used as the body of responses built by this crate.
+pub type BoxBody = UnsyncBoxBody<Bytes, ClientError>;
+
+/// Create [BoxBody] of the buffered data.
+pub fn full(data: impl Into<Bytes>) -> BoxBody {
+    Full::new(data.into())
+        .map_err(|never| match never {})
+        .boxed_unsync()
+}
+
+/// Stream the stdout of [ResponseStream] as `http_body::Body`, the stdout
+/// chunks are yielded as data frames as soon as they are received, and the
+/// body is terminated on `EndRequest`.
+///
+/// The stderr chunks are discarded.
+///
+/// Note that the stdout still contains the CGI headers.
+pub struct ResponseBody<S: AsyncRead + Unpin> {
+    stream: ResponseStream<S>,
+}
+
+impl<S: AsyncRead + Unpin> ResponseBody<S> {
+    pub fn new(stream: ResponseStream<S>) -> Self {
+        Self { stream }
+    }
+
+    pub fn into_inner(self) -> ResponseStream<S> {
+        self.stream
+    }
+}
+
+impl<S: AsyncRead + Unpin> From<ResponseStream<S>> for ResponseBody<S> {
+    fn from(stream: ResponseStream<S>) -> Self {
+        Self::new(stream)
+    }
+}
+
+impl<S: AsyncRead + Unpin> Body for ResponseBody<S> {
+    type Data = Bytes;
+    type Error = ClientError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>, cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let stream = &mut self.get_mut().stream;
+        loop {
+            match ready!(stream.poll_chunk(cx)) {
+                Some(Ok((ContentKind::Stdout, read))) => {
+                    let data = stream.split_chunk(read);
+                    return Poll::Ready(Some(Ok(Frame::data(data))));
+                }
+                Some(Ok((ContentKind::Stderr, read))) => {
+                    debug!(stderr = ?Bytes::copy_from_slice(stream.chunk(read)), "Discard stderr.");
+                }
+                Some(Ok((ContentKind::End { .. }, _))) => {}
+                Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.stream.ended
+    }
+}
+
+/// Stream the body after the header section as data frames, the chunks are
+/// read up to 8KiB at a time.
+impl<S: AsyncRead + Unpin> Body for BodyReader<S> {
+    type Data = Bytes;
+    type Error = ClientError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>, cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let mut buf = [0; 8192];
+        let mut buf = ReadBuf::new(&mut buf);
+        ready!(self.poll_read(cx, &mut buf))?;
+        if buf.filled().is_empty() {
+            return Poll::Ready(None);
+        }
+        Poll::Ready(Some(Ok(Frame::data(Bytes::copy_from_slice(buf.filled())))))
+    }
+}
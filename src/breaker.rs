@@ -0,0 +1,173 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Circuit breaker, reject the requests fast while the fastcgi server keeps
+//! failing.
+
+use crate::{ClientError, ClientResult};
+use std::{
+    future::Future,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+/// Config of [CircuitBreaker].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct BreakerConfig {
+    /// The count of consecutive failures (connecting, io or timeout) to open
+    /// the circuit.
+    pub failure_threshold: u32,
+    /// The duration of rejecting requests after opened, then a trial request
+    /// is let through, the circuit is closed if it succeeds.
+    pub cooldown: Duration,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cooldown: Duration::from_secs(10),
+        }
+    }
+}
+
+impl BreakerConfig {
+    pub fn failure_threshold(mut self, failure_threshold: u32) -> Self {
+        self.failure_threshold = failure_threshold;
+        self
+    }
+
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Closed { failures: u32 },
+    Open { since: Instant },
+    HalfOpen { since: Instant },
+}
+
+/// Circuit breaker of a fastcgi server, used by [Pool](crate::pool::Pool) if
+/// configured.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{
+///     breaker::{BreakerConfig, CircuitBreaker},
+///     Client, Params, Request,
+/// };
+/// use tokio::io;
+///
+/// async fn breaker(breaker: &CircuitBreaker) {
+///     let output = breaker
+///         .call(async {
+///             let client = Client::connect("tcp://127.0.0.1:9000").await?;
+///             client
+///                 .execute_once(Request::new(Params::default(), io::empty()))
+///                 .await
+///         })
+///         .await;
+/// }
+/// ```
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    config: BreakerConfig,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: BreakerConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    pub fn config(&self) -> &BreakerConfig {
+        &self.config
+    }
+
+    /// Whether the requests are rejected now.
+    pub fn is_open(&self) -> bool {
+        match *self.state.lock().unwrap() {
+            State::Closed { .. } => false,
+            State::Open { since } | State::HalfOpen { since } => {
+                since.elapsed() < self.config.cooldown
+            }
+        }
+    }
+
+    /// Run the request future if the circuit isn't open, otherwise return
+    /// [ClientError::CircuitOpen], the result is recorded.
+    pub async fn call<T>(&self, fut: impl Future<Output = ClientResult<T>>) -> ClientResult<T> {
+        self.acquire()?;
+        let result = fut.await;
+        match &result {
+            Err(
+                ClientError::Io(_)
+                | ClientError::ConnectTimeout
+                | ClientError::ConnectionClosedByPeer { .. }
+                | ClientError::PartialResponse { .. }
+                | ClientError::RequestTimeout,
+            ) => self.on_failure(),
+            _ => self.on_success(),
+        }
+        result
+    }
+
+    fn acquire(&self) -> ClientResult<()> {
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            State::Closed { .. } => Ok(()),
+            // Let one trial request through after cooldown, the trial is
+            // given again if it didn't finish in another cooldown.
+            State::Open { since } | State::HalfOpen { since }
+                if since.elapsed() >= self.config.cooldown =>
+            {
+                *state = State::HalfOpen {
+                    since: Instant::now(),
+                };
+                Ok(())
+            }
+            State::Open { .. } | State::HalfOpen { .. } => Err(ClientError::CircuitOpen),
+        }
+    }
+
+    fn on_success(&self) {
+        *self.state.lock().unwrap() = State::Closed { failures: 0 };
+    }
+
+    fn on_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        *state = match *state {
+            State::Closed { failures } if failures + 1 < self.config.failure_threshold => {
+                State::Closed {
+                    failures: failures + 1,
+                }
+            }
+            _ => {
+                warn!("Circuit breaker opened.");
+                State::Open {
+                    since: Instant::now(),
+                }
+            }
+        };
+    }
+}
@@ -0,0 +1,64 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Pool of the heap buffers for writing records, reused across records and
+//! requests, instead of allocating on the stack which bloats the futures.
+
+use crate::meta::MAX_LENGTH;
+use std::{
+    ops::{Deref, DerefMut},
+    sync::Mutex,
+};
+
+/// Max count of the idle buffers kept in pool.
+const MAX_IDLE: usize = 16;
+
+static POOL: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
+
+/// Buffer of [MAX_LENGTH] bytes taken from pool, put back on drop.
+pub(crate) struct PooledBuffer(Vec<u8>);
+
+impl PooledBuffer {
+    pub(crate) fn take() -> Self {
+        let buf = POOL
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .pop()
+            .unwrap_or_else(|| vec![0; MAX_LENGTH]);
+        Self(buf)
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        let mut pool = POOL.lock().unwrap_or_else(|err| err.into_inner());
+        if pool.len() < MAX_IDLE {
+            pool.push(std::mem::take(&mut self.0));
+        }
+    }
+}
+
+impl Deref for PooledBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
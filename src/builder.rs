@@ -0,0 +1,302 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Builder of [Client] with the consolidated config, so the knobs can be
+//! added without new constructors.
+
+use crate::{
+    conn::{KeepAlive, Mode, ShortConn},
+    connect::{Address, AnyStream, Connect},
+    lenient::{Anomaly, AnomalyHandler},
+    params::ParamsLimits,
+    stderr::StderrHandler,
+    Client, ClientError, ClientResult, Response,
+};
+use std::{fmt, marker::PhantomData, sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, BufStream},
+    time,
+};
+use tracing::Level;
+
+/// Callback invoked after every request executed by [Client], for logging
+/// or metrics.
+pub type CompletionHook = Arc<dyn Fn(&Completion<'_>) + Send + Sync>;
+
+/// The finished request passed to the [CompletionHook].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Completion<'a> {
+    pub id: u16,
+    /// The address of server, if executed by [Pool](crate::pool::Pool).
+    pub upstream: Option<&'a str>,
+    pub elapsed: Duration,
+    pub result: &'a ClientResult<Response>,
+}
+
+/// Config of [Client], see [ClientBuilder].
+#[derive(Clone, Default)]
+#[non_exhaustive]
+pub struct ClientConfig {
+    /// Bound connecting by [ClientBuilder::connect].
+    pub connect_timeout: Option<Duration>,
+    /// The timeout of the requests without their own, see
+    /// [Request::with_timeout](crate::Request::with_timeout).
+    pub request_timeout: Option<Duration>,
+    /// The capacity of read and write buffers of
+    /// [ClientBuilder::build_buffered], `None` means the default of
+    /// `BufStream`.
+    pub buffer_capacity: Option<usize>,
+    /// Disable the coalescing for the progressive output, such as the
+    /// server-sent events flushed by PHP `flush()`, the buffers of
+    /// [ClientBuilder::build_buffered] are bypassed.
+    pub low_latency: bool,
+    /// Enable strict mode for all requests, see
+    /// [Request::strict](crate::Request::strict).
+    pub strict: bool,
+    /// The params limits of the requests without their own, see
+    /// [Request::with_params_limits](crate::Request::with_params_limits).
+    pub params_limits: Option<ParamsLimits>,
+    /// Log the stderr of requests at the level by `tracing`, with the request
+    /// id and `SCRIPT_FILENAME`, instead of collecting into
+    /// [Response::stderr], such as the PHP notices.
+    pub log_stderr: Option<Level>,
+    /// Pass the stderr of requests to the handler instead of collecting into
+    /// [Response::stderr], see [StderrHandler].
+    pub stderr_handler: Option<Arc<dyn StderrHandler>>,
+    /// Enable lenient mode, see [Client::lenient].
+    pub anomaly_handler: Option<AnomalyHandler>,
+    pub completion_hook: Option<CompletionHook>,
+}
+
+impl ClientConfig {
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    pub fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = Some(request_timeout);
+        self
+    }
+
+    pub fn buffer_capacity(mut self, buffer_capacity: usize) -> Self {
+        self.buffer_capacity = Some(buffer_capacity);
+        self
+    }
+
+    pub fn low_latency(mut self, low_latency: bool) -> Self {
+        self.low_latency = low_latency;
+        self
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn params_limits(mut self, params_limits: ParamsLimits) -> Self {
+        self.params_limits = Some(params_limits);
+        self
+    }
+
+    pub fn log_stderr(mut self, level: Level) -> Self {
+        self.log_stderr = Some(level);
+        self
+    }
+
+    pub fn on_stderr(mut self, handler: impl StderrHandler + 'static) -> Self {
+        self.stderr_handler = Some(Arc::new(handler));
+        self
+    }
+
+    pub fn lenient(mut self, handler: impl Fn(&Anomaly) + Send + Sync + 'static) -> Self {
+        self.anomaly_handler = Some(Arc::new(handler));
+        self
+    }
+
+    pub fn on_complete(mut self, hook: impl Fn(&Completion<'_>) + Send + Sync + 'static) -> Self {
+        self.completion_hook = Some(Arc::new(hook));
+        self
+    }
+}
+
+impl fmt::Debug for ClientConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientConfig")
+            .field("connect_timeout", &self.connect_timeout)
+            .field("request_timeout", &self.request_timeout)
+            .field("buffer_capacity", &self.buffer_capacity)
+            .field("low_latency", &self.low_latency)
+            .field("strict", &self.strict)
+            .field("params_limits", &self.params_limits)
+            .field("log_stderr", &self.log_stderr)
+            .field("on_stderr", &self.stderr_handler.is_some())
+            .field("lenient", &self.anomaly_handler.is_some())
+            .field("on_complete", &self.completion_hook.is_some())
+            .finish()
+    }
+}
+
+/// Builder of [Client], created by [Client::builder], under short connection
+/// mode unless [keep_alive](ClientBuilder::keep_alive).
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{Client, Params, Request};
+/// use std::time::Duration;
+/// use tokio::io;
+/// use tracing::info;
+///
+/// async fn builder() {
+///     let mut client = Client::builder()
+///         .keep_alive()
+///         .connect_timeout(Duration::from_secs(1))
+///         .request_timeout(Duration::from_secs(30))
+///         .on_complete(|completion| info!(elapsed = ?completion.elapsed, "Completed."))
+///         .connect("tcp://127.0.0.1:9000")
+///         .await
+///         .unwrap();
+///
+///     let output = client
+///         .execute(Request::new(Params::default(), io::empty()))
+///         .await
+///         .unwrap();
+/// }
+/// ```
+pub struct ClientBuilder<M> {
+    config: ClientConfig,
+    _mode: PhantomData<M>,
+}
+
+impl ClientBuilder<ShortConn> {
+    pub(crate) fn new() -> Self {
+        Self {
+            config: ClientConfig::default(),
+            _mode: PhantomData,
+        }
+    }
+
+    /// Switch to keep alive connection mode.
+    pub fn keep_alive(self) -> ClientBuilder<KeepAlive> {
+        ClientBuilder {
+            config: self.config,
+            _mode: PhantomData,
+        }
+    }
+}
+
+impl<M: Mode> ClientBuilder<M> {
+    /// Replace the whole config.
+    pub fn config(mut self, config: ClientConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// See [ClientConfig::connect_timeout].
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.config.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// See [ClientConfig::request_timeout].
+    pub fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.config.request_timeout = Some(request_timeout);
+        self
+    }
+
+    /// See [ClientConfig::buffer_capacity].
+    pub fn buffer_capacity(mut self, buffer_capacity: usize) -> Self {
+        self.config.buffer_capacity = Some(buffer_capacity);
+        self
+    }
+
+    /// See [ClientConfig::low_latency].
+    pub fn low_latency(mut self, low_latency: bool) -> Self {
+        self.config.low_latency = low_latency;
+        self
+    }
+
+    /// See [ClientConfig::strict].
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.config.strict = strict;
+        self
+    }
+
+    /// See [ClientConfig::params_limits].
+    pub fn params_limits(mut self, params_limits: ParamsLimits) -> Self {
+        self.config.params_limits = Some(params_limits);
+        self
+    }
+
+    /// See [ClientConfig::log_stderr].
+    pub fn log_stderr(mut self, level: Level) -> Self {
+        self.config.log_stderr = Some(level);
+        self
+    }
+
+    /// See [ClientConfig::stderr_handler].
+    pub fn on_stderr(mut self, handler: impl StderrHandler + 'static) -> Self {
+        self.config.stderr_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// See [Client::lenient].
+    pub fn lenient(mut self, handler: impl Fn(&Anomaly) + Send + Sync + 'static) -> Self {
+        self.config.anomaly_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Invoke the hook after every request executed, for logging or metrics,
+    /// the streaming requests aren't included.
+    pub fn on_complete(mut self, hook: impl Fn(&Completion<'_>) + Send + Sync + 'static) -> Self {
+        self.config.completion_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Build the client with stream.
+    pub fn build<S: AsyncRead + AsyncWrite + Unpin>(self, stream: S) -> Client<S, M> {
+        Client::with_config(stream, self.config)
+    }
+
+    /// Build the client with stream wrapped in `BufStream` of
+    /// [ClientConfig::buffer_capacity], the buffers are of zero capacity
+    /// under [ClientConfig::low_latency], so the reads and writes pass
+    /// through.
+    pub fn build_buffered<S: AsyncRead + AsyncWrite + Unpin>(
+        self, stream: S,
+    ) -> Client<BufStream<S>, M> {
+        let stream = match self.config.buffer_capacity {
+            _ if self.config.low_latency => BufStream::with_capacity(0, 0, stream),
+            Some(capacity) => BufStream::with_capacity(capacity, capacity, stream),
+            None => BufStream::new(stream),
+        };
+        self.build(stream)
+    }
+
+    /// Connect to the address, see [Address] for the formats, bounded by
+    /// [ClientConfig::connect_timeout].
+    pub async fn connect(self, address: &str) -> ClientResult<Client<AnyStream, M>> {
+        let address = address.parse::<Address>()?;
+        let stream = match self.config.connect_timeout {
+            Some(timeout) => time::timeout(timeout, address.connect())
+                .await
+                .map_err(|_| ClientError::ConnectTimeout)?,
+            None => address.connect().await,
+        }
+        .map_err(ClientError::connect)?;
+        Ok(self.build(stream))
+    }
+}
@@ -0,0 +1,327 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! In-memory cache of responses, like nginx `fastcgi_cache`, so the repeated
+//! `GET` requests are served without touching the fastcgi server.
+
+use crate::ClientResult;
+use bytes::Bytes;
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::{debug, warn};
+
+/// Config of [ResponseCache].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct CacheConfig {
+    /// The duration of response fresh after stored.
+    pub ttl: Duration,
+    /// The duration after `ttl` the stale response is still served, while
+    /// revalidated in background, like `stale-while-revalidate`.
+    pub stale_while_revalidate: Duration,
+    /// The max count of entries, the oldest is evicted once exceeded.
+    pub max_entries: usize,
+    /// The request headers included in the key besides method and uri, like
+    /// `Accept-Encoding`.
+    pub vary: Vec<http::HeaderName>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(1),
+            stale_while_revalidate: Duration::ZERO,
+            max_entries: 1024,
+            vary: Vec::new(),
+        }
+    }
+}
+
+impl CacheConfig {
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn stale_while_revalidate(mut self, stale_while_revalidate: Duration) -> Self {
+        self.stale_while_revalidate = stale_while_revalidate;
+        self
+    }
+
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Add the request header into the key, the requests with
+    /// `Authorization` or `Cookie` are cached only if the header is added.
+    pub fn vary(mut self, name: http::HeaderName) -> Self {
+        self.vary.push(name);
+        self
+    }
+}
+
+struct Entry {
+    response: http::Response<Bytes>,
+    stored: Instant,
+    revalidating: bool,
+}
+
+struct Inner {
+    config: CacheConfig,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+/// Cache of responses keyed by method, uri and the
+/// [vary](CacheConfig::vary) headers, which is `Clone`, the clones share the
+/// same entries, used by [forward](crate::gateway::forward) if configured.
+///
+/// Only the `GET` and `HEAD` requests are cached, the requests with
+/// `Authorization` or `Cookie` are bypassed unless the header is in
+/// [vary](CacheConfig::vary), and only the responses of
+/// status `200`, `301` and `302` without `Set-Cookie`, `Vary: *` or
+/// `Cache-Control` of `no-store`, `no-cache` or `private` are stored.
+///
+/// # Examples
+///
+/// ```
+/// use bytes::Bytes;
+/// use fastcgi_client::{
+///     cache::{CacheConfig, ResponseCache},
+///     ClientResult,
+/// };
+/// use std::time::Duration;
+///
+/// async fn cache(cache: &ResponseCache, request: http::Request<()>) -> ClientResult<()> {
+///     let (parts, _) = request.into_parts();
+///     let response = cache
+///         .get_or_fetch(&parts, || async {
+///             // Forward the request to fastcgi server.
+///             Ok(http::Response::new(Bytes::from("hello")))
+///         })
+///         .await?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone)]
+pub struct ResponseCache {
+    inner: Arc<Inner>,
+}
+
+impl ResponseCache {
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                config,
+                entries: Default::default(),
+            }),
+        }
+    }
+
+    pub fn config(&self) -> &CacheConfig {
+        &self.inner.config
+    }
+
+    /// The count of entries, including the stale ones.
+    pub fn len(&self) -> usize {
+        self.inner.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove all entries.
+    pub fn clear(&self) {
+        self.inner.entries.lock().unwrap().clear();
+    }
+
+    /// The key of request, `None` if the request isn't cacheable.
+    pub fn key(&self, parts: &http::request::Parts) -> Option<String> {
+        if parts.method != http::Method::GET && parts.method != http::Method::HEAD {
+            return None;
+        }
+        // The response may be personalized by the credentials.
+        let vary = &self.inner.config.vary;
+        let personalized = [http::header::AUTHORIZATION, http::header::COOKIE]
+            .iter()
+            .any(|name| parts.headers.contains_key(name) && !vary.contains(name));
+        if personalized {
+            return None;
+        }
+        let mut key = format!("{} {}", parts.method, parts.uri);
+        for name in &self.inner.config.vary {
+            key.push('\n');
+            key.push_str(name.as_str());
+            key.push(':');
+            for value in parts.headers.get_all(name) {
+                key.push_str(&String::from_utf8_lossy(value.as_bytes()));
+                key.push(',');
+            }
+        }
+        Some(key)
+    }
+
+    /// Serve the fresh response cached, or the stale one while revalidating
+    /// by `fetch` in background, otherwise call `fetch` and store the
+    /// response if cacheable.
+    ///
+    /// Must be called in the context of tokio runtime, the revalidation is
+    /// spawned.
+    pub async fn get_or_fetch<F, Fut>(
+        &self, parts: &http::request::Parts, fetch: F,
+    ) -> ClientResult<http::Response<Bytes>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = ClientResult<http::Response<Bytes>>> + Send + 'static,
+    {
+        let Some(key) = self.key(parts) else {
+            return fetch().await;
+        };
+
+        let revalidate = {
+            let mut entries = self.inner.entries.lock().unwrap();
+            match entries.get_mut(&key) {
+                Some(entry) if entry.stored.elapsed() < self.inner.config.ttl => {
+                    debug!(key, "Serve fresh response from cache.");
+                    return Ok(entry.response.clone());
+                }
+                Some(entry) if entry.stored.elapsed() < self.stale_deadline() => {
+                    debug!(key, "Serve stale response from cache.");
+                    let response = entry.response.clone();
+                    let revalidate = !entry.revalidating;
+                    entry.revalidating = true;
+                    Some((response, revalidate))
+                }
+                Some(_) => {
+                    entries.remove(&key);
+                    None
+                }
+                None => None,
+            }
+        };
+
+        match revalidate {
+            Some((response, revalidate)) => {
+                if revalidate {
+                    let cache = self.clone();
+                    let fut = fetch();
+                    tokio::spawn(async move {
+                        match fut.await {
+                            Ok(response) => cache.store(key, &response),
+                            Err(err) => {
+                                warn!(key, ?err, "Failed to revalidate cached response.");
+                                if let Some(entry) =
+                                    cache.inner.entries.lock().unwrap().get_mut(&key)
+                                {
+                                    entry.revalidating = false;
+                                }
+                            }
+                        }
+                    });
+                }
+                Ok(response)
+            }
+            None => {
+                let response = fetch().await?;
+                self.store(key, &response);
+                Ok(response)
+            }
+        }
+    }
+
+    fn stale_deadline(&self) -> Duration {
+        self.inner.config.ttl + self.inner.config.stale_while_revalidate
+    }
+
+    /// Store the response if cacheable, or remove the entry otherwise.
+    fn store(&self, key: String, response: &http::Response<Bytes>) {
+        let mut entries = self.inner.entries.lock().unwrap();
+        if !is_cacheable(response) {
+            entries.remove(&key);
+            return;
+        }
+
+        if !entries.contains_key(&key) && entries.len() >= self.inner.config.max_entries {
+            let deadline = self.stale_deadline();
+            entries.retain(|_, entry| entry.stored.elapsed() < deadline);
+            if entries.len() >= self.inner.config.max_entries {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.stored)
+                    .map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+        if self.inner.config.max_entries > 0 {
+            entries.insert(
+                key,
+                Entry {
+                    response: response.clone(),
+                    stored: Instant::now(),
+                    revalidating: false,
+                },
+            );
+        }
+    }
+}
+
+impl fmt::Debug for ResponseCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseCache")
+            .field("config", &self.inner.config)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+fn is_cacheable(response: &http::Response<Bytes>) -> bool {
+    use http::{header, StatusCode};
+
+    if !matches!(
+        response.status(),
+        StatusCode::OK | StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND
+    ) {
+        return false;
+    }
+    let headers = response.headers();
+    if headers.contains_key(header::SET_COOKIE) {
+        return false;
+    }
+    if headers
+        .get_all(header::VARY)
+        .iter()
+        .any(|value| value.as_bytes().trim_ascii() == b"*")
+    {
+        return false;
+    }
+    !headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|directive| {
+            let directive = directive.trim();
+            ["no-store", "no-cache", "private"]
+                .iter()
+                .any(|name| directive.eq_ignore_ascii_case(name))
+        })
+}
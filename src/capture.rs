@@ -0,0 +1,274 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Capture the records sent and received on the wire, for debugging, such as
+//! diffing against the records sent by nginx.
+
+use crate::meta::{HEADER_LEN, MAX_LENGTH};
+use std::{
+    io::{self, IoSlice, Read, Write},
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Magic bytes at the beginning of dump file written by [DumpSink].
+const DUMP_MAGIC: &[u8; 8] = b"FCGIDUMP";
+
+/// Direction of the captured record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+/// Receive the captured records, the record is the raw bytes of header,
+/// content and padding.
+///
+/// Called synchronously in polling the stream, so shouldn't block for long.
+pub trait CaptureSink: Send {
+    fn capture(&mut self, direction: Direction, record: &[u8]);
+}
+
+impl<F: FnMut(Direction, &[u8]) + Send> CaptureSink for F {
+    fn capture(&mut self, direction: Direction, record: &[u8]) {
+        self(direction, record)
+    }
+}
+
+/// Stream wrapper capturing the records passing through to the sink.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{
+///     capture::{Capture, DumpSink},
+///     Client, Params, Request,
+/// };
+/// use std::fs::File;
+/// use tokio::{io, net::TcpStream};
+///
+/// async fn capture() {
+///     let stream = TcpStream::connect(("127.0.0.1", 9000)).await.unwrap();
+///     let sink = DumpSink::new(File::create("fastcgi.dump").unwrap()).unwrap();
+///     let client = Client::new(Capture::new(stream, sink));
+///     let output = client
+///         .execute_once(Request::new(Params::default(), io::empty()))
+///         .await
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Capture<S, K> {
+    stream: S,
+    sink: K,
+    sent: Vec<u8>,
+    received: Vec<u8>,
+}
+
+impl<S, K: CaptureSink> Capture<S, K> {
+    pub fn new(stream: S, sink: K) -> Self {
+        Self {
+            stream,
+            sink,
+            sent: Vec::new(),
+            received: Vec::new(),
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    pub fn sink(&self) -> &K {
+        &self.sink
+    }
+
+    pub fn into_inner(self) -> (S, K) {
+        (self.stream, self.sink)
+    }
+}
+
+/// Append the bytes to buffer, and pass the complete records to sink.
+fn split_records(
+    sink: &mut impl CaptureSink, direction: Direction, buf: &mut Vec<u8>, bytes: &[u8],
+) {
+    buf.extend_from_slice(bytes);
+    let mut start = 0;
+    while buf.len() - start >= HEADER_LEN {
+        let header = &buf[start..start + HEADER_LEN];
+        let content_length = u16::from_be_bytes([header[4], header[5]]) as usize;
+        let len = HEADER_LEN + content_length + header[6] as usize;
+        if buf.len() - start < len {
+            break;
+        }
+        sink.capture(direction, &buf[start..start + len]);
+        start += len;
+    }
+    buf.drain(..start);
+}
+
+impl<S: AsyncRead + Unpin, K: CaptureSink + Unpin> AsyncRead for Capture<S, K> {
+    fn poll_read(
+        self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        let result = Pin::new(&mut this.stream).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = result {
+            split_records(
+                &mut this.sink,
+                Direction::Received,
+                &mut this.received,
+                &buf.filled()[filled..],
+            );
+        }
+        result
+    }
+}
+
+impl<S: AsyncWrite + Unpin, K: CaptureSink + Unpin> AsyncWrite for Capture<S, K> {
+    fn poll_write(
+        self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let result = Pin::new(&mut this.stream).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = result {
+            split_records(&mut this.sink, Direction::Sent, &mut this.sent, &buf[..n]);
+        }
+        result
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let result = Pin::new(&mut this.stream).poll_write_vectored(cx, bufs);
+        if let Poll::Ready(Ok(mut n)) = result {
+            for buf in bufs {
+                if n == 0 {
+                    break;
+                }
+                let len = n.min(buf.len());
+                split_records(&mut this.sink, Direction::Sent, &mut this.sent, &buf[..len]);
+                n -= len;
+            }
+        }
+        result
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.stream.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+/// Write the captured records into the dump format, which begins with the
+/// magic `FCGIDUMP`, followed by the entries of direction (`0` sent, `1`
+/// received, u8), timestamp (microseconds since unix epoch, u64 big endian),
+/// length (u32 big endian) and record, read by [read_dump].
+///
+/// The write errors are ignored, so the requests aren't affected.
+#[derive(Debug)]
+pub struct DumpSink<W> {
+    writer: W,
+}
+
+impl<W: Write + Send> DumpSink<W> {
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(DUMP_MAGIC)?;
+        Ok(Self { writer })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write + Send> CaptureSink for DumpSink<W> {
+    fn capture(&mut self, direction: Direction, record: &[u8]) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        let mut entry = Vec::with_capacity(13 + record.len());
+        entry.push(direction as u8);
+        entry.extend_from_slice(&timestamp.to_be_bytes());
+        entry.extend_from_slice(&(record.len() as u32).to_be_bytes());
+        entry.extend_from_slice(record);
+        let _ = self.writer.write_all(&entry);
+    }
+}
+
+/// Entry of dump file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DumpEntry {
+    pub direction: Direction,
+    /// Duration since unix epoch.
+    pub timestamp: Duration,
+    /// Raw bytes of header, content and padding.
+    pub record: Vec<u8>,
+}
+
+/// Read the entries of dump file written by [DumpSink].
+pub fn read_dump(mut reader: impl Read) -> io::Result<Vec<DumpEntry>> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_owned());
+
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != DUMP_MAGIC {
+        return Err(invalid("invalid dump magic"));
+    }
+
+    let mut entries = Vec::new();
+    loop {
+        let mut direction = [0; 1];
+        if reader.read(&mut direction)? == 0 {
+            return Ok(entries);
+        }
+        let direction = match direction[0] {
+            0 => Direction::Sent,
+            1 => Direction::Received,
+            _ => return Err(invalid("invalid dump direction")),
+        };
+
+        let mut buf = [0; 12];
+        reader.read_exact(&mut buf)?;
+        let timestamp = u64::from_be_bytes(buf[..8].try_into().unwrap());
+        let len = u32::from_be_bytes(buf[8..].try_into().unwrap()) as usize;
+        if len > HEADER_LEN + MAX_LENGTH + u8::MAX as usize {
+            return Err(invalid("invalid dump record length"));
+        }
+        let mut record = vec![0; len];
+        reader.read_exact(&mut record)?;
+
+        entries.push(DumpEntry {
+            direction,
+            timestamp: Duration::from_micros(timestamp),
+            record,
+        });
+    }
+}
@@ -13,15 +13,49 @@
 // limitations under the License.
 
 use crate::{
+    builder::{ClientBuilder, ClientConfig, Completion},
     conn::{KeepAlive, Mode, ShortConn},
-    meta::{BeginRequestRec, EndRequestRec, Header, ParamPairs, RequestType, Role},
-    params::Params,
-    request::Request,
-    response::ResponseStream,
+    connect::{Address, AnyStream, Connect},
+    id::{AllocRequestId, FixRequestIdAllocator},
+    lenient::Anomaly,
+    meta::{
+        handle_management_record, parse_unknown_type, BeginRequestRec, EndRequestRec, Header,
+        ParamPairs, RequestType, Role, HEADER_LEN, NULL_REQUEST_ID, VERSION_1,
+    },
+    params::{Params, ParamsLimits},
+    request::{BoxedData, Request},
+    response::{
+        authorizer::{parse_authorization, Authorization},
+        parse::{self, BodyReader, ResponseHead},
+        ResponseStream,
+    },
+    retry::RetryPolicy,
+    status::FpmStatus,
+    stderr::StderrRoute,
+    values::{ValueName, Values},
     ClientError, ClientResult, Response,
 };
-use std::marker::PhantomData;
-use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use std::{
+    future::{poll_fn, Future},
+    io::{self, IoSlice},
+    iter,
+    marker::PhantomData,
+    num::NonZeroU16,
+    pin::{pin, Pin},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{
+        split, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufStream, BufWriter, ReadBuf,
+    },
+    sync::mpsc,
+    time,
+};
 use tracing::debug;
 
 /// I refer to nginx fastcgi implementation, found the request id is always 1.
@@ -30,8 +64,16 @@ use tracing::debug;
 const REQUEST_ID: u16 = 1;
 
 /// Async client for handling communication between fastcgi server.
-pub struct Client<S, M> {
+///
+/// The request ids are allocated by `A` unless specified by
+/// [Request::request_id], the fixed id `1` by default, see
+/// [with_id_allocator](Client::with_id_allocator).
+pub struct Client<S, M, A = FixRequestIdAllocator> {
     stream: S,
+    config: ClientConfig,
+    /// Set during the request, and left set if the request is cancelled.
+    poisoned: Arc<AtomicBool>,
+    id_allocator: A,
     _mode: PhantomData<M>,
 }
 
@@ -39,12 +81,18 @@ impl<S: AsyncRead + AsyncWrite + Unpin> Client<S, ShortConn> {
     /// Construct a `Client` Object with stream, such as `tokio::net::TcpStream`
     /// or `tokio::net::UnixStream`, under short connection mode.
     pub fn new(stream: S) -> Self {
-        Self {
-            stream,
-            _mode: PhantomData,
-        }
+        Self::with_config(stream, ClientConfig::default())
     }
 
+    /// Like [new](Client::new), but the stream is wrapped in `BufStream`, so
+    /// the raw stream like `TcpStream` is read and written in batches, the
+    /// stream is flushed after each request is sent.
+    pub fn new_buffered(stream: S) -> Client<BufStream<S>, ShortConn> {
+        Client::new(BufStream::new(stream))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin, A: AllocRequestId> Client<S, ShortConn, A> {
     /// Send request and receive response from fastcgi server, under short
     /// connection mode.
     pub async fn execute_once<I: AsyncRead + Unpin>(
@@ -53,9 +101,17 @@ impl<S: AsyncRead + AsyncWrite + Unpin> Client<S, ShortConn> {
         self.inner_execute(request).await
     }
 
+    /// Send Authorizer request with params and empty stdin, and parse the
+    /// response, under short connection mode.
+    pub async fn authorize_once(mut self, params: Params<'_>) -> ClientResult<Authorization> {
+        self.inner_authorize(params).await
+    }
+
     /// Send request and receive response stream from fastcgi server, under
     /// short connection mode.
     ///
+    /// The timeout of request only bounds the sending of request.
+    ///
     /// # Examples
     ///
     /// ```
@@ -76,15 +132,83 @@ impl<S: AsyncRead + AsyncWrite + Unpin> Client<S, ShortConn> {
     ///         match content {
     ///             Content::Stdout(out) => todo!(),
     ///             Content::Stderr(out) => todo!(),
+    ///             Content::End { app_status, .. } => todo!(),
     ///         }
     ///     }
     /// }
     /// ```
-    pub async fn execute_once_stream<'a, I: AsyncRead + Unpin>(
-        mut self, request: Request<'_, I>,
+    pub async fn execute_once_stream<I: AsyncRead + Unpin>(
+        mut self, mut request: Request<'_, I>,
     ) -> ClientResult<ResponseStream<S>> {
-        Self::handle_request(&mut self.stream, REQUEST_ID, request.params, request.stdin).await?;
-        Ok(ResponseStream::new(self.stream, REQUEST_ID))
+        apply_config(&self.config, &mut request);
+        let timeout = request.timeout;
+        let id = self.alloc_id(&request)?;
+        let keep_alive = request.keep_alive.unwrap_or(ShortConn::is_keep_alive());
+        let result = with_timeout(
+            timeout,
+            handle_request(&mut self.stream, id, keep_alive, &mut request),
+        )
+        .await;
+        // The connection serves no other request, so the id is released once
+        // sent.
+        self.release_id(&request, id);
+        result?;
+        Ok(ResponseStream::new(self.stream, id))
+    }
+
+    /// Send request and receive the status and headers as soon as the header
+    /// section is received, and the reader of the remaining body, under short
+    /// connection mode, see [parse_head](parse::parse_head).
+    pub async fn execute_once_parsed<I: AsyncRead + Unpin>(
+        self, request: Request<'_, I>,
+    ) -> ClientResult<(ResponseHead, BodyReader<S>)> {
+        parse::parse_head(self.execute_once_stream(request).await?).await
+    }
+}
+
+impl Client<AnyStream, ShortConn> {
+    /// Create the [ClientBuilder] with default config, under short connection
+    /// mode unless [keep_alive](ClientBuilder::keep_alive).
+    pub fn builder() -> ClientBuilder<ShortConn> {
+        ClientBuilder::new()
+    }
+
+    /// Connect to the address like `tcp://127.0.0.1:9000` or
+    /// `unix:///run/php/php-fpm.sock`, see [Address], under short connection
+    /// mode.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fastcgi_client::{Client, Params, Request};
+    ///
+    /// async fn connect() {
+    ///     let client = Client::connect("tcp://127.0.0.1:9000").await.unwrap();
+    ///     let output = client
+    ///         .execute_once(Request::new(Params::default(), tokio::io::empty()))
+    ///         .await
+    ///         .unwrap();
+    /// }
+    /// ```
+    pub async fn connect(address: &str) -> ClientResult<Self> {
+        let stream = address
+            .parse::<Address>()?
+            .connect()
+            .await
+            .map_err(ClientError::connect)?;
+        Ok(Self::new(stream))
+    }
+}
+
+impl Client<AnyStream, KeepAlive> {
+    /// Like [connect](Client::connect), but under keep alive connection mode.
+    pub async fn connect_keep_alive(address: &str) -> ClientResult<Self> {
+        let stream = address
+            .parse::<Address>()?
+            .connect()
+            .await
+            .map_err(ClientError::connect)?;
+        Ok(Self::new_keep_alive(stream))
     }
 }
 
@@ -92,10 +216,31 @@ impl<S: AsyncRead + AsyncWrite + Unpin> Client<S, KeepAlive> {
     /// Construct a `Client` Object with stream, such as `tokio::net::TcpStream`
     /// or `tokio::net::UnixStream`, under keep alive connection mode.
     pub fn new_keep_alive(stream: S) -> Self {
-        Self {
-            stream,
-            _mode: PhantomData,
+        Self::with_config(stream, ClientConfig::default())
+    }
+
+    /// Like [new_keep_alive](Client::new_keep_alive), but the stream is
+    /// wrapped in `BufStream`, see [new_buffered](Client::new_buffered).
+    pub fn new_keep_alive_buffered(stream: S) -> Client<BufStream<S>, KeepAlive> {
+        Client::new_keep_alive(BufStream::new(stream))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin, A: AllocRequestId> Client<S, KeepAlive, A> {
+    /// Whether the idle connection can't be reused, that is poisoned, closed
+    /// by the fastcgi server (such as php-fpm closes the idle connections),
+    /// or unexpected data received, checked without waiting, so the stale
+    /// connection is dropped before sending the request.
+    pub fn is_stale(&mut self) -> bool {
+        if self.is_poisoned() {
+            return true;
         }
+        let mut buf = [0; 1];
+        let mut buf = ReadBuf::new(&mut buf);
+        let mut cx = Context::from_waker(Waker::noop());
+        Pin::new(&mut self.stream)
+            .poll_read(&mut cx, &mut buf)
+            .is_ready()
     }
 
     /// Send request and receive response from fastcgi server, under keep alive
@@ -106,9 +251,34 @@ impl<S: AsyncRead + AsyncWrite + Unpin> Client<S, KeepAlive> {
         self.inner_execute(request).await
     }
 
+    /// Like [execute](Client::execute), but retry by the policy if the server
+    /// is overloaded, the request is built by `f` for every attempt.
+    pub async fn execute_with_retry<'a, I: AsyncRead + Unpin>(
+        &mut self, policy: &RetryPolicy, mut f: impl FnMut() -> Request<'a, I>,
+    ) -> ClientResult<Response> {
+        let mut retries = 0;
+        loop {
+            match self.inner_execute(f()).await {
+                Err(err) if policy.should_retry(&err, retries) => {
+                    policy.sleep(retries).await;
+                    retries += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Send Authorizer request with params and empty stdin, and parse the
+    /// response, under keep alive connection mode.
+    pub async fn authorize(&mut self, params: Params<'_>) -> ClientResult<Authorization> {
+        self.inner_authorize(params).await
+    }
+
     /// Send request and receive response stream from fastcgi server, under
     /// keep alive connection mode.
     ///
+    /// The timeout of request only bounds the sending of request.
+    ///
     /// # Examples
     ///
     /// ```
@@ -131,141 +301,569 @@ impl<S: AsyncRead + AsyncWrite + Unpin> Client<S, KeepAlive> {
     ///             match content {
     ///                 Content::Stdout(out) => todo!(),
     ///                 Content::Stderr(out) => todo!(),
+    ///                 Content::End { app_status, .. } => todo!(),
     ///             }
     ///         }
     ///     }
     /// }
     /// ```
     pub async fn execute_stream<I: AsyncRead + Unpin>(
-        &mut self, request: Request<'_, I>,
+        &mut self, mut request: Request<'_, I>,
     ) -> ClientResult<ResponseStream<&mut S>> {
-        Self::handle_request(&mut self.stream, REQUEST_ID, request.params, request.stdin).await?;
-        Ok(ResponseStream::new(&mut self.stream, REQUEST_ID))
+        apply_config(&self.config, &mut request);
+        let timeout = request.timeout;
+        let id = self.alloc_id(&request)?;
+        let keep_alive = request.keep_alive.unwrap_or(KeepAlive::is_keep_alive());
+        if let Err(err) = self.poison() {
+            self.release_id(&request, id);
+            return Err(err);
+        }
+        let result = with_timeout(
+            timeout,
+            handle_request(&mut self.stream, id, keep_alive, &mut request),
+        )
+        .await;
+        // The next request can't be sent until the stream is dropped, so the
+        // id is released once sent.
+        self.release_id(&request, id);
+        self.heal(&result);
+        result?;
+        // Healed by the stream after the response is read completely.
+        self.poisoned.store(true, Ordering::Release);
+        Ok(ResponseStream::new(&mut self.stream, id).poisoned(self.poisoned.clone()))
+    }
+
+    /// Send request and receive the status and headers as soon as the header
+    /// section is received, and the reader of the remaining body, under keep
+    /// alive connection mode, see [parse_head](parse::parse_head).
+    ///
+    /// The body should be read to end before the next request, like
+    /// [execute_stream](Client::execute_stream).
+    pub async fn execute_parsed<I: AsyncRead + Unpin>(
+        &mut self, request: Request<'_, I>,
+    ) -> ClientResult<(ResponseHead, BodyReader<&mut S>)> {
+        parse::parse_head(self.execute_stream(request).await?).await
+    }
+
+    /// Send the requests back to back, and receive the responses in order,
+    /// under keep alive connection mode, so the round trips are amortized for
+    /// batch workloads, even if the server can't multiplex the connection.
+    ///
+    /// The responses are received while sending, so the server isn't blocked
+    /// by the full buffers. The server is expected to process the requests one
+    /// by one like php-fpm, use
+    /// [MultiplexClient](crate::multiplex::MultiplexClient) for the servers
+    /// multiplexing connections.
+    ///
+    /// The results are returned in the order of requests, the timeout of
+    /// request only bounds the receiving of its response. After the
+    /// connection fails, the remaining requests fail with
+    /// [ClientError::ConnectionPoisoned].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fastcgi_client::{Client, Params, Request};
+    /// use tokio::{io, net::TcpStream};
+    ///
+    /// async fn execute_many() {
+    ///     let stream = TcpStream::connect(("127.0.0.1", 9000)).await.unwrap();
+    ///     let mut client = Client::new_keep_alive(stream);
+    ///     let requests = (0..3)
+    ///         .map(|_| Request::new(Params::default(), io::empty()))
+    ///         .collect();
+    ///
+    ///     for result in client.execute_many(requests).await {
+    ///         let output = result.unwrap();
+    ///     }
+    /// }
+    /// ```
+    pub async fn execute_many<I: AsyncRead + Unpin>(
+        &mut self, mut requests: Vec<Request<'_, I>>,
+    ) -> Vec<ClientResult<Response>> {
+        let count = requests.len();
+        for request in &mut requests {
+            apply_config(&self.config, request);
+        }
+        // Allocate the ids of all requests up front as the hints, the batch
+        // isn't sent if any allocation fails.
+        let mut allocated = Vec::new();
+        for request in &mut requests {
+            if request.request_id.is_some() {
+                continue;
+            }
+            match self.id_allocator.alloc() {
+                Ok(id) => {
+                    request.request_id = NonZeroU16::new(id);
+                    allocated.push(id);
+                }
+                Err(_) => {
+                    for id in allocated {
+                        self.id_allocator.release(id);
+                    }
+                    return iter::repeat_with(|| Err(ClientError::RequestIdExhausted))
+                        .take(count)
+                        .collect();
+                }
+            }
+        }
+        let release = |id_allocator: &A| {
+            for id in &allocated {
+                id_allocator.release(*id);
+            }
+        };
+
+        if let Err(err) = self.poison() {
+            release(&self.id_allocator);
+            return iter::once(Err(err))
+                .chain(iter::repeat_with(|| Err(ClientError::ConnectionPoisoned)))
+                .take(count)
+                .collect();
+        }
+
+        let (mut reader, mut writer) = split(&mut self.stream);
+        let anomaly_handler = self.config.anomaly_handler.as_deref();
+        let config = &self.config;
+        let collecting = StderrRoute::new(config, None).is_collecting();
+        let (sent_tx, mut sent_rx) = mpsc::unbounded_channel();
+        let mut results = iter::repeat_with(|| None).take(count).collect::<Vec<_>>();
+
+        // The write errors, the requests after the fatal one aren't sent.
+        let mut write = pin!(async move {
+            let mut errors = Vec::new();
+            for (index, mut request) in requests.into_iter().enumerate() {
+                let id = request_id(&request);
+                let timeout = request.timeout;
+                let keep_alive = request.keep_alive.unwrap_or(KeepAlive::is_keep_alive());
+                let script = (!collecting)
+                    .then(|| script_filename(&request).map(ToOwned::to_owned))
+                    .flatten();
+                match handle_request(&mut writer, id, keep_alive, &mut request).await {
+                    Ok(()) => {
+                        let _ = sent_tx.send((index, id, timeout, script));
+                    }
+                    Err(err) => {
+                        let clean = err.is_clean();
+                        errors.push((index, err));
+                        if !clean {
+                            break;
+                        }
+                    }
+                }
+            }
+            errors
+        });
+        // The responses of the sent requests, stop at the fatal error.
+        let mut read = pin!(async {
+            let mut responses = Vec::new();
+            while let Some((index, id, timeout, script)) = sent_rx.recv().await {
+                let stderr_route = StderrRoute::new(config, script.as_deref());
+                let result = with_timeout(
+                    timeout,
+                    Self::handle_response(&mut reader, id, anomaly_handler, stderr_route),
+                )
+                .await;
+                let clean = result.as_ref().map_or_else(ClientError::is_clean, |_| true);
+                responses.push((index, result));
+                if !clean {
+                    break;
+                }
+            }
+            responses
+        });
+
+        let mut errors = None;
+        let responses = poll_fn(|cx| {
+            if errors.is_none() {
+                if let Poll::Ready(written) = write.as_mut().poll(cx) {
+                    errors = Some(written);
+                }
+            }
+            read.as_mut().poll(cx)
+        })
+        .await;
+
+        for (index, result) in errors
+            .into_iter()
+            .flatten()
+            .map(|(index, err)| (index, Err(err)))
+            .chain(responses)
+        {
+            results[index] = Some(result);
+        }
+        let clean = results.iter().all(|result| {
+            result
+                .as_ref()
+                .is_some_and(|result| result.as_ref().map_or_else(ClientError::is_clean, |_| true))
+        });
+        if clean {
+            self.poisoned.store(false, Ordering::Release);
+        }
+        release(&self.id_allocator);
+        results
+            .into_iter()
+            .map(|result| result.unwrap_or(Err(ClientError::ConnectionPoisoned)))
+            .collect()
     }
 }
 
 impl<S: AsyncRead + AsyncWrite + Unpin, M: Mode> Client<S, M> {
-    async fn inner_execute<I: AsyncRead + Unpin>(
-        &mut self, request: Request<'_, I>,
-    ) -> ClientResult<Response> {
-        Self::handle_request(&mut self.stream, REQUEST_ID, request.params, request.stdin).await?;
-        Self::handle_response(&mut self.stream, REQUEST_ID).await
+    /// Construct with stream and config, see [Client::builder].
+    pub(crate) fn with_config(stream: S, config: ClientConfig) -> Self {
+        Self {
+            stream,
+            config,
+            poisoned: Default::default(),
+            id_allocator: FixRequestIdAllocator,
+            _mode: PhantomData,
+        }
     }
+}
 
-    async fn handle_request<'a, I: AsyncRead + Unpin>(
-        stream: &mut S, id: u16, params: Params<'a>, mut body: I,
-    ) -> ClientResult<()> {
-        Self::handle_request_start(stream, id).await?;
-        Self::handle_request_params(stream, id, params).await?;
-        Self::handle_request_body(stream, id, &mut body).await?;
-        Self::handle_request_flush(stream).await?;
-        Ok(())
+impl<S: AsyncRead + AsyncWrite + Unpin, M: Mode, A: AllocRequestId> Client<S, M, A> {
+    /// Replace the allocator of request ids, such as
+    /// [PooledRequestIdAllocator](crate::id::PooledRequestIdAllocator) to
+    /// use distinct ids across requests, or the custom one shared with other
+    /// clients.
+    pub fn with_id_allocator<B: AllocRequestId>(self, id_allocator: B) -> Client<S, M, B> {
+        Client {
+            stream: self.stream,
+            config: self.config,
+            poisoned: self.poisoned,
+            id_allocator,
+            _mode: PhantomData,
+        }
     }
 
-    async fn handle_request_start(stream: &mut S, id: u16) -> ClientResult<()> {
-        debug!(id, "Start handle request");
+    pub fn id_allocator(&self) -> &A {
+        &self.id_allocator
+    }
 
-        let begin_request_rec =
-            BeginRequestRec::new(id, Role::Responder, <M>::is_keep_alive()).await?;
+    /// The request id hint of request, or allocated by the allocator.
+    fn alloc_id<I: AsyncRead + Unpin>(&self, request: &Request<'_, I>) -> ClientResult<u16> {
+        match request.request_id {
+            Some(id) => Ok(id.get()),
+            None => self.id_allocator.alloc(),
+        }
+    }
 
-        debug!(id, ?begin_request_rec, "Send to stream.");
+    /// Give back the request id allocated by [alloc_id](Client::alloc_id).
+    fn release_id<I: AsyncRead + Unpin>(&self, request: &Request<'_, I>, id: u16) {
+        if request.request_id.is_none() {
+            self.id_allocator.release(id);
+        }
+    }
 
-        begin_request_rec.write_to_stream(stream).await?;
+    /// Enable lenient mode, the protocol quirks in response (nonzero reserved
+    /// bytes, stray padding, unknown record types and so on) are tolerated
+    /// and reported to `handler`, instead of failing the request.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fastcgi_client::{Client, Params, Request};
+    /// use tokio::{io, net::TcpStream};
+    /// use tracing::warn;
+    ///
+    /// async fn lenient() {
+    ///     let stream = TcpStream::connect(("127.0.0.1", 9000)).await.unwrap();
+    ///     let client = Client::new(stream).lenient(|anomaly| warn!(%anomaly, "Tolerated."));
+    ///     let output = client
+    ///         .execute_once(Request::new(Params::default(), io::empty()))
+    ///         .await
+    ///         .unwrap();
+    /// }
+    /// ```
+    pub fn lenient(mut self, handler: impl Fn(&Anomaly) + Send + Sync + 'static) -> Self {
+        self.config.anomaly_handler = Some(Arc::new(handler));
+        self
+    }
+
+    pub fn is_lenient(&self) -> bool {
+        self.config.anomaly_handler.is_some()
+    }
 
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    /// Whether the previous request was cancelled in the middle (the future or
+    /// [ResponseStream] is dropped), then the connection can't be reused, and
+    /// the requests fail with [ClientError::ConnectionPoisoned].
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Acquire)
+    }
+
+    /// Shutdown the write side of stream, so the connection is closed
+    /// gracefully.
+    pub async fn close(mut self) -> ClientResult<()> {
+        self.stream.shutdown().await?;
         Ok(())
     }
 
-    async fn handle_request_params<'a>(
-        stream: &mut S, id: u16, params: Params<'a>,
-    ) -> ClientResult<()> {
-        let param_pairs = ParamPairs::new(params);
-        debug!(id, ?param_pairs, "Params will be sent.");
+    /// Query the management values of fastcgi server by `FCGI_GET_VALUES`,
+    /// return [ClientError::UnsupportedRecordType] if the server replies
+    /// `FCGI_UNKNOWN_TYPE`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fastcgi_client::{values::ValueName, Client};
+    /// use tokio::net::TcpStream;
+    ///
+    /// async fn get_values() {
+    ///     let stream = TcpStream::connect(("127.0.0.1", 9000)).await.unwrap();
+    ///     let mut client = Client::new_keep_alive(stream);
+    ///     let values = client.get_values(&ValueName::ALL).await.unwrap();
+    ///     let max_reqs = values.max_reqs;
+    /// }
+    /// ```
+    pub async fn get_values(&mut self, names: &[ValueName]) -> ClientResult<Values> {
+        self.poison()?;
+        let result = get_values(&mut self.stream, names).await;
+        self.heal(&result);
+        result
+    }
+
+    /// Check the liveness of php-fpm by requesting the `ping.path` configured
+    /// in the pool, such as `/ping`, succeeds if the response body is `pong`,
+    /// the default `ping.response`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fastcgi_client::Client;
+    ///
+    /// async fn ping() {
+    ///     let mut client = Client::connect_keep_alive("tcp://127.0.0.1:9000")
+    ///         .await
+    ///         .unwrap();
+    ///     client.ping("/ping").await.unwrap();
+    /// }
+    /// ```
+    pub async fn ping(&mut self, ping_path: &str) -> ClientResult<()> {
+        let params = Params::default()
+            .request_method("GET")
+            .script_name(ping_path)
+            .script_filename(ping_path)
+            .request_uri(ping_path);
+        let response = self
+            .inner_execute(Request::new(params, tokio::io::empty()))
+            .await?;
+        let parsed = parse::parse(response.stdout.as_deref().unwrap_or_default())?;
+        let body = String::from_utf8_lossy(&parsed.body);
+        if parsed.status == 200 && body.trim() == "pong" {
+            Ok(())
+        } else {
+            Err(ClientError::PingFailed {
+                status: parsed.status,
+                body: body.into_owned(),
+            })
+        }
+    }
 
-        Header::write_to_stream_batches(
-            RequestType::Params,
-            id,
-            stream,
-            &mut &param_pairs.to_content().await?[..],
-            Some(|header| {
-                debug!(id, ?header, "Send to stream for Params.");
-                header
-            }),
-        )
-        .await?;
+    /// Query the php-fpm status page by the `pm.status_path` configured in the
+    /// pool, such as `/status`, the query string is passed such as
+    /// `/status?json`, and both the plain text and json output are parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fastcgi_client::Client;
+    ///
+    /// async fn status() {
+    ///     let mut client = Client::connect_keep_alive("tcp://127.0.0.1:9000")
+    ///         .await
+    ///         .unwrap();
+    ///     let status = client.fpm_status("/status").await.unwrap();
+    ///     let listen_queue = status.listen_queue;
+    /// }
+    /// ```
+    pub async fn fpm_status(&mut self, status_path: &str) -> ClientResult<FpmStatus> {
+        let (path, query) = status_path.split_once('?').unwrap_or((status_path, ""));
+        let params = Params::default()
+            .request_method("GET")
+            .script_name(path)
+            .script_filename(path)
+            .request_uri(status_path)
+            .query_string(query);
+        let response = self
+            .inner_execute(Request::new(params, tokio::io::empty()))
+            .await?;
+        let parsed = parse::parse(response.stdout.as_deref().unwrap_or_default())?;
+        if parsed.status != 200 {
+            return Err(ClientError::InvalidFpmStatus {
+                reason: format!("status code {}", parsed.status),
+            });
+        }
+        String::from_utf8_lossy(&parsed.body).parse()
+    }
 
-        Header::write_to_stream_batches(
-            RequestType::Params,
-            id,
-            stream,
-            &mut tokio::io::empty(),
-            Some(|header| {
-                debug!(id, ?header, "Send to stream for Params.");
-                header
-            }),
-        )
-        .await?;
+    async fn inner_execute<I: AsyncRead + Unpin>(
+        &mut self, request: Request<'_, I>,
+    ) -> ClientResult<Response> {
+        self.execute_upstream(request, None).await
+    }
 
-        Ok(())
+    /// Execute the request, the upstream is recorded in the trace span.
+    pub(crate) async fn execute_upstream<I: AsyncRead + Unpin>(
+        &mut self, request: Request<'_, I>, upstream: Option<&str>,
+    ) -> ClientResult<Response> {
+        self.execute_unsent(request, upstream).await.0
     }
 
-    async fn handle_request_body<I: AsyncRead + Unpin>(
-        stream: &mut S, id: u16, body: &mut I,
-    ) -> ClientResult<()> {
-        Header::write_to_stream_batches(
-            RequestType::Stdin,
-            id,
-            stream,
-            body,
-            Some(|header| {
-                debug!(id, ?header, "Send to stream for Stdin.");
-                header
-            }),
-        )
-        .await?;
+    /// Like [execute_upstream](Client::execute_upstream), but the request is
+    /// given back if the connection is closed by peer before any of it is
+    /// sent, that is nothing written to the stream and the stdin isn't read,
+    /// so it can be retried on another connection.
+    pub(crate) async fn execute_unsent<'a, I: AsyncRead + Unpin>(
+        &mut self, mut request: Request<'a, I>, upstream: Option<&str>,
+    ) -> (ClientResult<Response>, Option<Request<'a, I>>) {
+        apply_config(&self.config, &mut request);
+        let timeout = request.timeout;
+        let id = match self.alloc_id(&request) {
+            Ok(id) => id,
+            Err(err) => return (Err(err), None),
+        };
+        let keep_alive = request.keep_alive.unwrap_or(M::is_keep_alive());
+        #[cfg(feature = "trace")]
+        let span = crate::trace::request_span(id, &request.params, upstream);
 
-        Header::write_to_stream_batches(
-            RequestType::Stdin,
-            id,
-            stream,
-            &mut tokio::io::empty(),
-            Some(|header| {
-                debug!(id, ?header, "Send to stream for Stdin.");
-                header
-            }),
-        )
-        .await?;
+        if let Err(err) = self.poison() {
+            self.release_id(&request, id);
+            return (Err(err), None);
+        }
+        let start = Instant::now();
+        let mut request = request.map_stdin(Probe::new);
+        let mut stream = Probe::new(&mut self.stream);
+        let anomaly_handler = self.config.anomaly_handler.as_deref();
+        let script = script_filename(&request).map(ToOwned::to_owned);
+        let stderr_route = StderrRoute::new(&self.config, script.as_deref());
+        let fut = with_timeout(timeout, async {
+            handle_request(&mut stream, id, keep_alive, &mut request).await?;
+            Self::handle_response(&mut stream, id, anomaly_handler, stderr_route).await
+        });
+        #[cfg(feature = "trace")]
+        let fut = crate::trace::instrument(span, fut);
+        let result = fut.await;
+        let unsent = matches!(result, Err(ClientError::ConnectionClosedByPeer { .. }))
+            && stream.written == 0
+            && request.stdin.read == 0
+            && request.data.is_none();
+
+        self.release_id(&request, id);
+        self.heal(&result);
+        if let Some(hook) = &self.config.completion_hook {
+            hook(&Completion {
+                id,
+                upstream,
+                elapsed: start.elapsed(),
+                result: &result,
+            });
+        }
+        if unsent {
+            debug!(id, "Connection closed by peer before request sent.");
+            return (result, Some(request.map_stdin(Probe::into_inner)));
+        }
+        (result, None)
+    }
 
+    /// Mark the connection poisoned before the request, so it stays poisoned
+    /// if the request future is dropped in the middle.
+    fn poison(&self) -> ClientResult<()> {
+        if self.poisoned.swap(true, Ordering::AcqRel) {
+            return Err(ClientError::ConnectionPoisoned);
+        }
         Ok(())
     }
 
-    async fn handle_request_flush(stream: &mut S) -> ClientResult<()> {
-        stream.flush().await?;
+    /// Clear the poisoned mark if the connection is still clean after the
+    /// request.
+    fn heal<T>(&self, result: &ClientResult<T>) {
+        if result.as_ref().map_or_else(ClientError::is_clean, |_| true) {
+            self.poisoned.store(false, Ordering::Release);
+        }
+    }
 
-        Ok(())
+    async fn inner_authorize(&mut self, params: Params<'_>) -> ClientResult<Authorization> {
+        let mut request = Request::new(params, tokio::io::empty());
+        *request.role_mut() = Role::Authorizer;
+        let response = self.inner_execute(request).await?;
+        parse_authorization(response.stdout.as_deref().unwrap_or_default())
     }
 
-    async fn handle_response(stream: &mut S, id: u16) -> ClientResult<Response> {
+    async fn handle_response<R: AsyncRead + Unpin>(
+        stream: &mut R, id: u16, anomaly_handler: Option<&(dyn Fn(&Anomaly) + Send + Sync)>,
+        stderr_route: StderrRoute<'_>,
+    ) -> ClientResult<Response> {
         let mut response = Response::default();
 
         let mut stderr = Vec::new();
         let mut stdout = Vec::new();
 
         loop {
-            let header = Header::new_from_stream(stream).await?;
+            // Keep the output received before if the connection fails.
+            let mut buf = [0; HEADER_LEN];
+            if let Err(err) = stream.read_exact(&mut buf).await {
+                return Err(ClientError::partial_response(err, stdout, stderr));
+            }
+            let header = Header::new_from_buf(&buf);
+            if header.request_id == NULL_REQUEST_ID {
+                let content = match header.read_content_from_stream(stream).await {
+                    Ok(content) => content,
+                    Err(err) => return Err(ClientError::partial_response(err, stdout, stderr)),
+                };
+                handle_management_record(&header, &content);
+                continue;
+            }
             if header.request_id != id {
                 return Err(ClientError::ResponseNotFound { id });
             }
             debug!(id, ?header, "Receive from stream.");
 
-            match header.r#type {
-                RequestType::Stdout => {
-                    stdout.extend(header.read_content_from_stream(stream).await?);
+            let r#type = buf[1];
+            let (content, padding) = match header.read_content_and_padding_from_stream(stream).await
+            {
+                Ok(read) => read,
+                Err(err) => return Err(ClientError::partial_response(err, stdout, stderr)),
+            };
+            if let Some(handler) = anomaly_handler {
+                if header.version != VERSION_1 {
+                    handler(&Anomaly::UnknownVersion {
+                        id,
+                        version: header.version,
+                    });
+                }
+                if header.reserved != 0 {
+                    handler(&Anomaly::NonzeroReserved { id, r#type });
                 }
-                RequestType::Stderr => {
-                    stderr.extend(header.read_content_from_stream(stream).await?);
+                if padding.len() > 7 || padding.iter().any(|b| *b != 0) {
+                    handler(&Anomaly::StrayPadding {
+                        id,
+                        r#type,
+                        padding_length: header.padding_length,
+                    });
                 }
+            }
+
+            match header.r#type {
+                RequestType::Stdout => stdout.extend(content),
+                RequestType::Stderr if stderr_route.is_collecting() => stderr.extend(content),
+                RequestType::Stderr => stderr_route.route(id, content).await,
                 RequestType::EndRequest => {
-                    let end_request_rec = EndRequestRec::from_header(&header, stream).await?;
+                    if content.len() < EndRequestRec::CONTENT_LEN {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "EndRequest content too short",
+                        )
+                        .into());
+                    }
+                    if let Some(handler) = anomaly_handler {
+                        if content[5..8].iter().any(|b| *b != 0) {
+                            handler(&Anomaly::NonzeroReserved { id, r#type });
+                        }
+                    }
+                    let end_request_rec = EndRequestRec::new_from_buf(header, &content);
                     debug!(id, ?end_request_rec, "Receive from stream.");
 
                     end_request_rec
@@ -273,6 +871,7 @@ impl<S: AsyncRead + AsyncWrite + Unpin, M: Mode> Client<S, M> {
                         .protocol_status
                         .convert_to_client_result(end_request_rec.end_request.app_status)?;
 
+                    response.app_status = end_request_rec.end_request.app_status;
                     response.stdout = if stdout.is_empty() {
                         None
                     } else {
@@ -286,12 +885,270 @@ impl<S: AsyncRead + AsyncWrite + Unpin, M: Mode> Client<S, M> {
 
                     return Ok(response);
                 }
-                r#type => {
-                    return Err(ClientError::UnknownRequestType {
-                        request_type: r#type,
-                    })
-                }
+                request_type => match anomaly_handler {
+                    Some(handler) => handler(&Anomaly::UnknownType { id, r#type }),
+                    None => return Err(ClientError::UnknownRequestType { request_type }),
+                },
             }
         }
     }
 }
+
+/// Query the management values by `FCGI_GET_VALUES` on the stream.
+pub(crate) async fn get_values<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S, names: &[ValueName],
+) -> ClientResult<Values> {
+    let content = Values::encode_names(names).await?;
+    debug!(?names, "Send GetValues to stream.");
+    Header::write_record(RequestType::GetValues, NULL_REQUEST_ID, stream, &content).await?;
+    stream.flush().await?;
+
+    let header = Header::new_from_stream(stream).await?;
+    debug!(?header, "Receive from stream.");
+    let content = header.read_content_from_stream(stream).await?;
+    match header.r#type {
+        RequestType::GetValuesResult => Ok(Values::decode(&content)?),
+        RequestType::UnknownType => Err(ClientError::UnsupportedRecordType {
+            request_type: parse_unknown_type(&content)?,
+        }),
+        r#type => Err(ClientError::UnknownRequestType {
+            request_type: r#type,
+        }),
+    }
+}
+
+/// Fill the request by the config of client, the settings of request take
+/// precedence.
+fn apply_config<I: AsyncRead + Unpin>(config: &ClientConfig, request: &mut Request<'_, I>) {
+    if request.timeout.is_none() {
+        request.timeout = config.request_timeout;
+    }
+    request.strict |= config.strict;
+    if let Some(params_limits) = config.params_limits {
+        if request.params_limits == ParamsLimits::default() {
+            request.params_limits = params_limits;
+        }
+    }
+}
+
+/// The `SCRIPT_FILENAME` of request, logged along with the stderr.
+fn script_filename<'a, I: AsyncRead + Unpin>(request: &'a Request<'_, I>) -> Option<&'a str> {
+    request
+        .params
+        .get("SCRIPT_FILENAME")
+        .map(|script| &**script)
+}
+
+/// Run the future within the timeout if specified.
+async fn with_timeout<T>(
+    timeout: Option<Duration>, fut: impl Future<Output = ClientResult<T>>,
+) -> ClientResult<T> {
+    match timeout {
+        Some(timeout) => time::timeout(timeout, fut)
+            .await
+            .map_err(|_| ClientError::RequestTimeout)?,
+        None => fut.await,
+    }
+}
+
+/// The request id hint of request, or the default one.
+pub(crate) fn request_id<I: AsyncRead + Unpin>(request: &Request<'_, I>) -> u16 {
+    request.request_id.map_or(REQUEST_ID, NonZeroU16::get)
+}
+
+pub(crate) async fn handle_request<W: AsyncWrite + Unpin, I: AsyncRead + Unpin>(
+    stream: &mut W, id: u16, keep_alive: bool, request: &mut Request<'_, I>,
+) -> ClientResult<()> {
+    if request.strict {
+        request.params.validate()?;
+    }
+    request.params.check_limits(&request.params_limits)?;
+    if let (Some(stdin_len), Some(content_length)) =
+        (request.stdin_len, request.params.get("CONTENT_LENGTH"))
+    {
+        if content_length.parse::<usize>() != Ok(stdin_len) {
+            return Err(ClientError::ContentLengthMismatch {
+                content_length: content_length.to_string(),
+                stdin_len,
+            });
+        }
+    }
+
+    // Coalesce the records into large batches, so the small request is sent in
+    // one write.
+    let mut stream = BufWriter::new(stream);
+    handle_request_start(&mut stream, id, request.role, keep_alive).await?;
+    handle_request_params(&mut stream, id, &request.params).await?;
+    handle_request_body(&mut stream, id, &mut request.stdin, request.stdin_len).await?;
+    if let Some(data) = &mut request.data {
+        handle_request_data(&mut stream, id, data).await?;
+    }
+    handle_request_flush(&mut stream).await?;
+    Ok(())
+}
+
+async fn handle_request_start<W: AsyncWrite + Unpin>(
+    stream: &mut W, id: u16, role: Role, keep_alive: bool,
+) -> ClientResult<()> {
+    debug!(id, ?role, "Start handle request");
+
+    let begin_request_rec = BeginRequestRec::new(id, role, keep_alive).await?;
+
+    debug!(id, ?begin_request_rec, "Send to stream.");
+
+    begin_request_rec.write_to_stream(stream).await?;
+
+    Ok(())
+}
+
+async fn handle_request_params<W: AsyncWrite + Unpin>(
+    stream: &mut W, id: u16, params: &Params<'_>,
+) -> ClientResult<()> {
+    let param_pairs = ParamPairs::new(params);
+    debug!(id, ?param_pairs, "Params will be sent.");
+
+    Header::write_to_stream_batches(
+        RequestType::Params,
+        id,
+        stream,
+        &mut &param_pairs.to_content().await?[..],
+        Some(|header| {
+            debug!(id, ?header, "Send to stream for Params.");
+            header
+        }),
+    )
+    .await?;
+
+    debug!(id, "Send the end of Params to stream.");
+    Header::write_record(RequestType::Params, id, stream, &[]).await?;
+
+    Ok(())
+}
+
+async fn handle_request_body<W: AsyncWrite + Unpin, I: AsyncRead + Unpin>(
+    stream: &mut W, id: u16, body: &mut I, length: Option<usize>,
+) -> ClientResult<()> {
+    let before_write = Some(|header| {
+        debug!(id, ?header, "Send to stream for Stdin.");
+        header
+    });
+    match length {
+        Some(length) => {
+            Header::write_to_stream_sized(
+                RequestType::Stdin,
+                id,
+                stream,
+                body,
+                length,
+                before_write,
+            )
+            .await?
+        }
+        None => {
+            Header::write_to_stream_batches(RequestType::Stdin, id, stream, body, before_write)
+                .await?
+        }
+    }
+
+    debug!(id, "Send the end of Stdin to stream.");
+    Header::write_record(RequestType::Stdin, id, stream, &[]).await?;
+
+    Ok(())
+}
+
+async fn handle_request_data<W: AsyncWrite + Unpin>(
+    stream: &mut W, id: u16, data: &mut BoxedData,
+) -> ClientResult<()> {
+    Header::write_to_stream_batches(
+        RequestType::Data,
+        id,
+        stream,
+        data,
+        Some(|header| {
+            debug!(id, ?header, "Send to stream for Data.");
+            header
+        }),
+    )
+    .await?;
+
+    debug!(id, "Send the end of Data to stream.");
+    Header::write_record(RequestType::Data, id, stream, &[]).await?;
+
+    Ok(())
+}
+
+async fn handle_request_flush<W: AsyncWrite + Unpin>(stream: &mut W) -> ClientResult<()> {
+    stream.flush().await?;
+
+    Ok(())
+}
+
+/// Stream wrapper counting the bytes read and written through, so the request
+/// is known unsent.
+struct Probe<T> {
+    inner: T,
+    read: usize,
+    written: usize,
+}
+
+impl<T> Probe<T> {
+    fn new(inner: T) -> Self {
+        Self {
+            inner,
+            read: 0,
+            written: 0,
+        }
+    }
+
+    fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Probe<T> {
+    fn poll_read(
+        self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        let result = Pin::new(&mut this.inner).poll_read(cx, buf);
+        this.read += buf.filled().len() - filled;
+        result
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Probe<T> {
+    fn poll_write(
+        self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let result = Pin::new(&mut this.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = result {
+            this.written += n;
+        }
+        result
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let result = Pin::new(&mut this.inner).poll_write_vectored(cx, bufs);
+        if let Poll::Ready(Ok(n)) = result {
+            this.written += n;
+        }
+        result
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,142 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compat layer for the streams implemented `futures_io` traits, so the client
+//! can be used under async-std or smol runtime.
+//!
+//! The client itself doesn't require tokio runtime, except for
+//! [SharedClient](crate::shared::SharedClient), [Server](crate::server::Server)
+//! and the request timeout.
+
+use crate::{
+    conn::{KeepAlive, ShortConn},
+    Client, Params, Request,
+};
+use std::{
+    borrow::Cow,
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use tokio::io::ReadBuf;
+
+/// Wrapper of the stream implemented `futures_io::AsyncRead` and
+/// `futures_io::AsyncWrite`, implements the tokio ones.
+///
+/// # Examples
+///
+/// ```
+/// use async_std::net::TcpStream;
+/// use fastcgi_client::{compat::Compat, Client, Params, Request};
+///
+/// async fn execute() {
+///     let stream = TcpStream::connect(("127.0.0.1", 9000)).await.unwrap();
+///     let client = Client::new(Compat::new(stream));
+///     let stdin = Compat::new(async_std::io::empty());
+///     let output = client
+///         .execute_once(Request::new(Params::default(), stdin))
+///         .await
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Compat<T> {
+    inner: T,
+}
+
+impl<T> Compat<T> {
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<S: futures_io::AsyncRead + futures_io::AsyncWrite + Unpin> Client<Compat<S>, ShortConn> {
+    /// Construct a `Client` Object with the stream implemented `futures_io`
+    /// traits, such as `async_std::net::TcpStream`, under short connection
+    /// mode, the stream is wrapped in [Compat].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_std::{io, net::TcpStream};
+    /// use fastcgi_client::{Client, Params, Request};
+    ///
+    /// async fn execute() {
+    ///     let stream = TcpStream::connect(("127.0.0.1", 9000)).await.unwrap();
+    ///     let client = Client::new_compat(stream);
+    ///     let output = client
+    ///         .execute_once(Request::new_compat(Params::default(), io::empty()))
+    ///         .await
+    ///         .unwrap();
+    /// }
+    /// ```
+    pub fn new_compat(stream: S) -> Self {
+        Client::new(Compat::new(stream))
+    }
+}
+
+impl<S: futures_io::AsyncRead + futures_io::AsyncWrite + Unpin> Client<Compat<S>, KeepAlive> {
+    /// Like [new_compat](Client::new_compat), but under keep alive connection
+    /// mode.
+    pub fn new_keep_alive_compat(stream: S) -> Self {
+        Client::new_keep_alive(Compat::new(stream))
+    }
+}
+
+impl<'a, I: futures_io::AsyncRead + Unpin> Request<'a, Compat<I>> {
+    /// Construct the request with the stdin implemented
+    /// `futures_io::AsyncRead`, the stdin is wrapped in [Compat].
+    pub fn new_compat(params: impl Into<Cow<'a, Params<'a>>>, stdin: I) -> Self {
+        Request::new(params, Compat::new(stdin))
+    }
+}
+
+impl<T: futures_io::AsyncRead + Unpin> tokio::io::AsyncRead for Compat<T> {
+    fn poll_read(
+        self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let read =
+            ready!(Pin::new(&mut self.get_mut().inner).poll_read(cx, buf.initialize_unfilled()))?;
+        buf.advance(read);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: futures_io::AsyncWrite + Unpin> tokio::io::AsyncWrite for Compat<T> {
+    fn poll_write(
+        self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_close(cx)
+    }
+}
@@ -0,0 +1,343 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conformance checks derived from the fastcgi specification, enabled by the
+//! `conformance` feature, for the people implementing fastcgi servers to
+//! verify the servers against this client.
+
+use crate::{
+    connect::Connect,
+    meta::{ProtocolStatus, RequestType, HEADER_LEN, NULL_REQUEST_ID, VERSION_1},
+    record::{encode_request, parse_record},
+    ClientError, ClientResult, Params, Request,
+};
+use std::{fmt, num::NonZeroU16, time::Duration};
+use tokio::{
+    io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    time,
+};
+
+/// The time waiting for each record, or for the connection to be closed.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The stdin length of the large request, spanning several records.
+const LARGE_STDIN_LEN: usize = 100_000;
+
+/// The role value undefined by specification.
+const UNKNOWN_ROLE: u16 = 0xffff;
+
+/// The check violated by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Check {
+    /// The version of record must be `1`.
+    Version,
+    /// The reserved byte of record must be `0`.
+    Reserved,
+    /// The fixed-size bodies, such as `FCGI_EndRequestBody`, must have the
+    /// size defined.
+    RecordSize,
+    /// The record should be padded to a multiple of 8 bytes.
+    PaddingAlignment,
+    /// The application records must have the request id of request.
+    RequestId,
+    /// The response of Responder must consist of `FCGI_STDOUT`,
+    /// `FCGI_STDERR` and `FCGI_END_REQUEST` records only.
+    RecordType,
+    /// The `FCGI_STDOUT` and `FCGI_STDERR` streams must be terminated by an
+    /// empty record before `FCGI_END_REQUEST`, without records after.
+    StreamTermination,
+    /// The request must end with the expected protocol status.
+    EndRequest,
+    /// The connection must be closed after the request without
+    /// `FCGI_KEEP_CONN`, and kept open otherwise.
+    ConnectionClose,
+}
+
+/// Violation of [Check] found by [run].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub check: Check,
+    pub detail: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.check, self.detail)
+    }
+}
+
+/// Report of [run].
+#[derive(Debug, Default, Clone)]
+pub struct Report {
+    violations: Vec<Violation>,
+}
+
+impl Report {
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    pub fn is_conformant(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    fn violate(&mut self, check: Check, detail: impl Into<String>) {
+        self.violations.push(Violation {
+            check,
+            detail: detail.into(),
+        });
+    }
+}
+
+/// Run the conformance checks against the server, each scenario on a new
+/// connection established by the connector.
+///
+/// The `params` are sent with the Responder requests, so should point to a
+/// script served by the server if needed. The scenarios are:
+///
+/// - A request without `FCGI_KEEP_CONN`, then the connection must be closed.
+/// - Two requests with `FCGI_KEEP_CONN` on the same connection, the first one
+///   has stdin spanning several records.
+/// - A request of undefined role, which must be rejected with
+///   `FCGI_UNKNOWN_ROLE`.
+///
+/// Returns error only if the connection fails, the violations are collected
+/// in [Report].
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{conformance, connect::TcpConnector, Params};
+///
+/// async fn conformance() {
+///     let params = Params::default().script_filename("/var/www/index.php");
+///     let report = conformance::run(&TcpConnector::new("127.0.0.1:9000"), params)
+///         .await
+///         .unwrap();
+///     for violation in report.violations() {
+///         println!("{}", violation);
+///     }
+/// }
+/// ```
+pub async fn run<C: Connect>(connector: &C, params: Params<'_>) -> ClientResult<Report> {
+    let mut report = Report::default();
+
+    let mut stream = connector.connect().await.map_err(ClientError::connect)?;
+    let request = Request::builder().params(&params).keep_alive(false).build();
+    send(&mut stream, request).await?;
+    check_response(&mut stream, &mut report, 1, ProtocolStatus::RequestComplete).await?;
+    check_closed(&mut stream, &mut report).await?;
+
+    let mut stream = connector.connect().await.map_err(ClientError::connect)?;
+    let stdin = vec![b'x'; LARGE_STDIN_LEN];
+    let request = Request::builder()
+        .params(params.clone().content_length(LARGE_STDIN_LEN))
+        .stdin(&stdin[..])
+        .keep_alive(true)
+        .build();
+    send(&mut stream, request).await?;
+    check_response(&mut stream, &mut report, 1, ProtocolStatus::RequestComplete).await?;
+    let request = Request::builder()
+        .params(&params)
+        .request_id(NonZeroU16::new(2).unwrap())
+        .keep_alive(true)
+        .build();
+    send(&mut stream, request).await?;
+    if !check_response(&mut stream, &mut report, 2, ProtocolStatus::RequestComplete).await? {
+        report.violate(
+            Check::ConnectionClose,
+            "connection closed with FCGI_KEEP_CONN",
+        );
+    }
+
+    let mut stream = connector.connect().await.map_err(ClientError::connect)?;
+    let mut buf = encode_request(Request::builder().params(&params).build()).await?;
+    // Replace the role in the content of `FCGI_BEGIN_REQUEST`.
+    buf[HEADER_LEN..HEADER_LEN + 2].copy_from_slice(&UNKNOWN_ROLE.to_be_bytes());
+    stream.write_all(&buf).await?;
+    stream.flush().await?;
+    check_response(&mut stream, &mut report, 1, ProtocolStatus::UnknownRole).await?;
+
+    Ok(report)
+}
+
+async fn send<S: AsyncWrite + Unpin>(
+    stream: &mut S, request: Request<'_, impl AsyncRead + Unpin>,
+) -> ClientResult<()> {
+    let buf = encode_request(request).await?;
+    stream.write_all(&buf).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+/// Read the whole record, or `None` if the connection is closed.
+async fn read_record<S: AsyncRead + Unpin>(stream: &mut S) -> ClientResult<Option<Vec<u8>>> {
+    let mut buf = vec![0; HEADER_LEN];
+    let read = async {
+        if let Err(err) = stream.read_exact(&mut buf).await {
+            return match err.kind() {
+                io::ErrorKind::UnexpectedEof => Ok(None),
+                _ => Err(err),
+            };
+        }
+        let content_length = u16::from_be_bytes([buf[4], buf[5]]) as usize;
+        buf.resize(HEADER_LEN + content_length + buf[6] as usize, 0);
+        stream.read_exact(&mut buf[HEADER_LEN..]).await?;
+        Ok(Some(()))
+    };
+    match time::timeout(READ_TIMEOUT, read).await {
+        Ok(read) => Ok(read?.map(|_| buf)),
+        Err(_) => Err(ClientError::RequestTimeout),
+    }
+}
+
+/// Check the records of response until `FCGI_END_REQUEST`, return false if
+/// the connection is closed before any record.
+async fn check_response<S: AsyncRead + Unpin>(
+    stream: &mut S, report: &mut Report, id: u16, expected_status: ProtocolStatus,
+) -> ClientResult<bool> {
+    let mut stdout_ended = false;
+    let mut stderr = None;
+    let mut received = false;
+
+    loop {
+        let Some(buf) = read_record(stream).await? else {
+            if received {
+                report.violate(
+                    Check::EndRequest,
+                    format!(
+                        "connection closed before FCGI_END_REQUEST of request {}",
+                        id
+                    ),
+                );
+            }
+            return Ok(received);
+        };
+        received = true;
+        let (record, _) = parse_record(&buf)?;
+
+        if record.version != VERSION_1 {
+            report.violate(
+                Check::Version,
+                format!("record version is {}", record.version),
+            );
+        }
+        if record.reserved != 0 {
+            report.violate(
+                Check::Reserved,
+                format!("reserved byte is {}", record.reserved),
+            );
+        }
+        if (record.content.len() + record.padding.len()) % 8 != 0 {
+            report.violate(
+                Check::PaddingAlignment,
+                format!(
+                    "content length {} with padding length {} isn't aligned to 8 bytes",
+                    record.content.len(),
+                    record.padding.len()
+                ),
+            );
+        }
+        if record.request_id == NULL_REQUEST_ID && record.r#type >= RequestType::GetValues as u8 {
+            // Management records aren't requested in the scenarios.
+            continue;
+        }
+        if record.request_id != id {
+            report.violate(
+                Check::RequestId,
+                format!("request id is {}, expected {}", record.request_id, id),
+            );
+        }
+
+        match RequestType::from_u8(record.r#type) {
+            RequestType::Stdout => {
+                if stdout_ended {
+                    report.violate(
+                        Check::StreamTermination,
+                        "FCGI_STDOUT received after the stream terminated",
+                    );
+                }
+                stdout_ended = record.content.is_empty();
+            }
+            RequestType::Stderr => {
+                if stderr == Some(true) {
+                    report.violate(
+                        Check::StreamTermination,
+                        "FCGI_STDERR received after the stream terminated",
+                    );
+                }
+                stderr = Some(record.content.is_empty());
+            }
+            RequestType::EndRequest => {
+                if expected_status == ProtocolStatus::RequestComplete && !stdout_ended {
+                    report.violate(
+                        Check::StreamTermination,
+                        "FCGI_STDOUT isn't terminated by an empty record",
+                    );
+                }
+                if stderr == Some(false) {
+                    report.violate(
+                        Check::StreamTermination,
+                        "FCGI_STDERR isn't terminated by an empty record",
+                    );
+                }
+                if record.content.len() != 8 {
+                    report.violate(
+                        Check::RecordSize,
+                        format!(
+                            "FCGI_END_REQUEST content length is {}, expected 8",
+                            record.content.len()
+                        ),
+                    );
+                }
+                match record.content.get(4) {
+                    Some(&status) if status == expected_status as u8 => {}
+                    status => report.violate(
+                        Check::EndRequest,
+                        format!(
+                            "protocol status is {:?}, expected {:?}",
+                            status.copied().map(ProtocolStatus::from_u8),
+                            expected_status
+                        ),
+                    ),
+                }
+                return Ok(true);
+            }
+            _ => report.violate(
+                Check::RecordType,
+                format!("unexpected record type {}", record.r#type),
+            ),
+        }
+    }
+}
+
+/// Check the connection is closed by server.
+async fn check_closed<S: AsyncRead + Unpin>(
+    stream: &mut S, report: &mut Report,
+) -> ClientResult<()> {
+    match read_record(stream).await {
+        Ok(None) => {}
+        Ok(Some(_)) => report.violate(
+            Check::StreamTermination,
+            "record received after FCGI_END_REQUEST",
+        ),
+        Err(ClientError::RequestTimeout) => report.violate(
+            Check::ConnectionClose,
+            "connection kept open without FCGI_KEEP_CONN",
+        ),
+        Err(err) => return Err(err),
+    }
+    Ok(())
+}
@@ -0,0 +1,426 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Connectors establishing the transport streams to fastcgi server.
+
+use crate::{ClientError, ClientResult};
+use socket2::{SockRef, TcpKeepalive};
+use std::{
+    future::Future,
+    io::{self, IoSlice},
+    net::SocketAddr,
+    pin::Pin,
+    str::FromStr,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::{self, TcpSocket, TcpStream},
+    time,
+};
+
+/// Boxed future returned by the built-in connectors.
+pub type ConnectFuture<S> = Pin<Box<dyn Future<Output = io::Result<S>> + Send>>;
+
+/// Establish a new stream to fastcgi server, implemented for async functions
+/// `Fn() -> impl Future<Output = io::Result<S>>`, so the custom transports
+/// (proxies, tunnels, in-memory streams) can be plugged in.
+pub trait Connect: Send + Sync + 'static {
+    type Stream: AsyncRead + AsyncWrite + Send + Unpin + 'static;
+    type Future: Future<Output = io::Result<Self::Stream>> + Send + 'static;
+
+    fn connect(&self) -> Self::Future;
+
+    /// Describe the address of server, such as `127.0.0.1:9000`, used in
+    /// logs and traces.
+    fn address(&self) -> Option<String> {
+        None
+    }
+}
+
+impl<F, Fut, S> Connect for F
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = io::Result<S>> + Send + 'static,
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    type Future = Fut;
+    type Stream = S;
+
+    fn connect(&self) -> Self::Future {
+        self()
+    }
+}
+
+/// Socket options of the tcp connections established by [TcpConnector].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct TcpOptions {
+    /// Set `TCP_NODELAY`, so the small records are sent without delay, true
+    /// by default.
+    pub nodelay: bool,
+    /// Enable tcp keepalive, the probes are sent after the connection is
+    /// idle for the duration, so the dead connections are detected.
+    pub keepalive: Option<Duration>,
+    /// Set `SO_SNDBUF`, the os default if `None`.
+    pub send_buffer_size: Option<u32>,
+    /// Set `SO_RCVBUF`, the os default if `None`.
+    pub recv_buffer_size: Option<u32>,
+}
+
+impl Default for TcpOptions {
+    fn default() -> Self {
+        Self {
+            nodelay: true,
+            keepalive: None,
+            send_buffer_size: None,
+            recv_buffer_size: None,
+        }
+    }
+}
+
+impl TcpOptions {
+    pub fn nodelay(mut self, nodelay: bool) -> Self {
+        self.nodelay = nodelay;
+        self
+    }
+
+    pub fn keepalive(mut self, keepalive: Duration) -> Self {
+        self.keepalive = Some(keepalive);
+        self
+    }
+
+    pub fn send_buffer_size(mut self, send_buffer_size: u32) -> Self {
+        self.send_buffer_size = Some(send_buffer_size);
+        self
+    }
+
+    pub fn recv_buffer_size(mut self, recv_buffer_size: u32) -> Self {
+        self.recv_buffer_size = Some(recv_buffer_size);
+        self
+    }
+
+    /// Create the socket for the address, the options are set before
+    /// connecting, so the buffer sizes take effect in handshake.
+    fn socket(&self, addr: &SocketAddr) -> io::Result<TcpSocket> {
+        let socket = match addr {
+            SocketAddr::V4(_) => TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => TcpSocket::new_v6()?,
+        };
+        socket.set_nodelay(self.nodelay)?;
+        if let Some(keepalive) = self.keepalive {
+            SockRef::from(&socket).set_tcp_keepalive(&TcpKeepalive::new().with_time(keepalive))?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+        Ok(socket)
+    }
+}
+
+/// Connect to fastcgi server by tcp.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{
+///     connect::{Connect, TcpConnector},
+///     Client,
+/// };
+///
+/// async fn connect() {
+///     let connector = TcpConnector::new("127.0.0.1:9000");
+///     let client = Client::new(connector.connect().await.unwrap());
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TcpConnector {
+    addr: String,
+    connect_timeout: Option<Duration>,
+    options: TcpOptions,
+}
+
+impl TcpConnector {
+    /// Construct a `TcpConnector` Object with the address, such as
+    /// `127.0.0.1:9000`, the host is resolved on every connecting, and the
+    /// resolved addresses are tried in order until one succeeds.
+    pub fn new(addr: impl Into<String>) -> Self {
+        Self {
+            addr: addr.into(),
+            connect_timeout: None,
+            options: TcpOptions::default(),
+        }
+    }
+
+    /// Timeout of connecting to each resolved address, exceeded then the next
+    /// address is tried.
+    pub fn with_connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// Socket options set on the connections, see [TcpOptions].
+    pub fn with_options(mut self, options: TcpOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn options(&self) -> &TcpOptions {
+        &self.options
+    }
+}
+
+impl Connect for TcpConnector {
+    type Future = ConnectFuture<TcpStream>;
+    type Stream = TcpStream;
+
+    fn connect(&self) -> Self::Future {
+        let addr = self.addr.clone();
+        let connect_timeout = self.connect_timeout;
+        let options = self.options.clone();
+        Box::pin(async move {
+            let mut last_err = None;
+            for addr in net::lookup_host(addr).await? {
+                let socket = match options.socket(&addr) {
+                    Ok(socket) => socket,
+                    Err(err) => {
+                        last_err = Some(err);
+                        continue;
+                    }
+                };
+                match with_timeout(connect_timeout, socket.connect(addr), addr).await {
+                    Ok(stream) => return Ok(stream),
+                    Err(err) => last_err = Some(err),
+                }
+            }
+            Err(last_err.unwrap_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "could not resolve to any addresses",
+                )
+            }))
+        })
+    }
+
+    fn address(&self) -> Option<String> {
+        Some(self.addr.clone())
+    }
+}
+
+/// Bound the connecting by the timeout if any, exceeded then returns the
+/// error of kind `TimedOut`, classified as [ClientError::ConnectTimeout].
+pub(crate) async fn with_timeout<S>(
+    timeout: Option<Duration>, future: impl Future<Output = io::Result<S>>,
+    addr: impl std::fmt::Display,
+) -> io::Result<S> {
+    match timeout {
+        Some(timeout) => time::timeout(timeout, future).await.unwrap_or_else(|_| {
+            Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("connect to {} timed out", addr),
+            ))
+        }),
+        None => future.await,
+    }
+}
+
+/// Connect to fastcgi server by unix domain socket.
+#[cfg(unix)]
+#[derive(Debug, Clone)]
+pub struct UnixConnector {
+    path: std::path::PathBuf,
+    connect_timeout: Option<Duration>,
+}
+
+#[cfg(unix)]
+impl UnixConnector {
+    /// Construct a `UnixConnector` Object with the socket path, such as
+    /// `/run/php/php-fpm.sock`.
+    pub fn new(path: impl Into<std::path::PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            connect_timeout: None,
+        }
+    }
+
+    /// Timeout of connecting, such as the listen backlog of php-fpm is full.
+    pub fn with_connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+}
+
+#[cfg(unix)]
+impl Connect for UnixConnector {
+    type Future = ConnectFuture<tokio::net::UnixStream>;
+    type Stream = tokio::net::UnixStream;
+
+    fn connect(&self) -> Self::Future {
+        let path = self.path.clone();
+        let connect_timeout = self.connect_timeout;
+        Box::pin(async move {
+            let future = tokio::net::UnixStream::connect(&path);
+            with_timeout(connect_timeout, future, path.display()).await
+        })
+    }
+
+    fn address(&self) -> Option<String> {
+        Some(self.path.display().to_string())
+    }
+}
+
+/// Address of fastcgi server, parsed from `tcp://127.0.0.1:9000` or
+/// `unix:///run/php/php-fpm.sock`, the bare `127.0.0.1:9000` is treated as
+/// tcp and the bare absolute path `/run/php/php-fpm.sock` as unix.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::connect::Address;
+///
+/// let address = "tcp://127.0.0.1:9000".parse::<Address>().unwrap();
+/// assert_eq!(address, Address::Tcp("127.0.0.1:9000".to_owned()));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Address {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(std::path::PathBuf),
+}
+
+impl FromStr for Address {
+    type Err = ClientError;
+
+    fn from_str(address: &str) -> ClientResult<Self> {
+        let invalid = |reason: &str| ClientError::InvalidAddress {
+            address: address.to_owned(),
+            reason: reason.to_owned(),
+        };
+
+        let (scheme, rest) = match address.split_once("://") {
+            Some((scheme, rest)) => (Some(scheme), rest),
+            None => (None, address),
+        };
+        if rest.is_empty() {
+            return Err(invalid("empty address"));
+        }
+
+        match scheme {
+            Some("tcp") => Ok(Address::Tcp(rest.to_owned())),
+            None if !rest.starts_with('/') => Ok(Address::Tcp(rest.to_owned())),
+            #[cfg(unix)]
+            Some("unix") | None => Ok(Address::Unix(rest.into())),
+            #[cfg(not(unix))]
+            Some("unix") | None => Err(invalid("unix domain socket is unsupported")),
+            Some(_) => Err(invalid("unknown scheme")),
+        }
+    }
+}
+
+impl Connect for Address {
+    type Future = ConnectFuture<AnyStream>;
+    type Stream = AnyStream;
+
+    fn connect(&self) -> Self::Future {
+        match self {
+            Address::Tcp(addr) => {
+                let future = TcpConnector::new(addr.clone()).connect();
+                Box::pin(async move { future.await.map(AnyStream::Tcp) })
+            }
+            #[cfg(unix)]
+            Address::Unix(path) => {
+                let future = UnixConnector::new(path.clone()).connect();
+                Box::pin(async move { future.await.map(AnyStream::Unix) })
+            }
+        }
+    }
+
+    fn address(&self) -> Option<String> {
+        match self {
+            Address::Tcp(addr) => Some(format!("tcp://{}", addr)),
+            #[cfg(unix)]
+            Address::Unix(path) => Some(format!("unix://{}", path.display())),
+        }
+    }
+}
+
+/// Stream connected by [Address], either tcp or unix domain socket.
+#[derive(Debug)]
+pub enum AnyStream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixStream),
+}
+
+impl AsyncRead for AnyStream {
+    fn poll_read(
+        self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            AnyStream::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(unix)]
+            AnyStream::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for AnyStream {
+    fn poll_write(
+        self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            AnyStream::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(unix)]
+            AnyStream::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            AnyStream::Tcp(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+            #[cfg(unix)]
+            AnyStream::Unix(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        match self {
+            AnyStream::Tcp(stream) => stream.is_write_vectored(),
+            #[cfg(unix)]
+            AnyStream::Unix(stream) => stream.is_write_vectored(),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            AnyStream::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(unix)]
+            AnyStream::Unix(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            AnyStream::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(unix)]
+            AnyStream::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
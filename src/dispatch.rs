@@ -0,0 +1,189 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Dispatcher of requests over several keep alive connections, owned by
+//! background workers.
+
+use crate::{
+    conn::KeepAlive,
+    connect::Connect,
+    request::{BoxedStdin, Request},
+    Client, ClientError, ClientResult, Response,
+};
+use std::sync::Arc;
+use tokio::{
+    io::AsyncRead,
+    sync::{mpsc, oneshot, Mutex},
+};
+use tracing::debug;
+
+type Job = (
+    Box<Request<'static, BoxedStdin>>,
+    oneshot::Sender<ClientResult<Response>>,
+);
+
+/// Config of [Dispatcher].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct DispatcherConfig {
+    /// The count of workers, every worker owns a keep alive connection.
+    pub workers: usize,
+    /// The capacity of work queue, [send](Dispatcher::send) waits if the
+    /// queue is full.
+    pub queue_capacity: usize,
+}
+
+impl Default for DispatcherConfig {
+    fn default() -> Self {
+        Self {
+            workers: 4,
+            queue_capacity: 128,
+        }
+    }
+}
+
+impl DispatcherConfig {
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    pub fn queue_capacity(mut self, queue_capacity: usize) -> Self {
+        self.queue_capacity = queue_capacity;
+        self
+    }
+}
+
+/// Dispatcher of requests, which is `Clone + Send`, the clones share the same
+/// work queue.
+///
+/// The requests are queued and taken by the idle workers, every worker owns a
+/// keep alive connection, which is established on demand by the connector,
+/// and reestablished after the connection failed.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{
+///     connect::TcpConnector,
+///     dispatch::{Dispatcher, DispatcherConfig},
+///     Params, Request,
+/// };
+/// use tokio::io;
+///
+/// async fn dispatch() {
+///     let dispatcher = Dispatcher::with_config(
+///         TcpConnector::new("127.0.0.1:9000"),
+///         DispatcherConfig::default().workers(8),
+///     );
+///
+///     let output = dispatcher
+///         .send(Request::new(Params::default(), io::empty()))
+///         .await
+///         .unwrap();
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Dispatcher {
+    sender: mpsc::Sender<Job>,
+    config: Arc<DispatcherConfig>,
+}
+
+impl Dispatcher {
+    /// Construct with the connector and default config, spawn the workers, so
+    /// must be called in the context of tokio runtime.
+    pub fn new<C: Connect>(connector: C) -> Self {
+        Self::with_config(connector, DispatcherConfig::default())
+    }
+
+    /// Like [new](Dispatcher::new), but with the custom config.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the workers or queue capacity is zero.
+    pub fn with_config<C: Connect>(connector: C, config: DispatcherConfig) -> Self {
+        assert!(config.workers > 0, "workers can't be zero");
+        let (sender, receiver) = mpsc::channel(config.queue_capacity);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let connector = Arc::new(connector);
+        for worker in 0..config.workers {
+            tokio::spawn(work(worker, connector.clone(), receiver.clone()));
+        }
+        Self {
+            sender,
+            config: Arc::new(config),
+        }
+    }
+
+    pub fn config(&self) -> &DispatcherConfig {
+        &self.config
+    }
+
+    /// Queue the request and receive response from fastcgi server, waits if
+    /// the queue is full.
+    pub async fn send<I>(&self, request: Request<'static, I>) -> ClientResult<Response>
+    where
+        I: AsyncRead + Send + Unpin + 'static,
+    {
+        let request = request.map_stdin(|stdin| Box::new(stdin) as BoxedStdin);
+        let (responder, receiver) = oneshot::channel();
+
+        self.sender
+            .send((Box::new(request), responder))
+            .await
+            .map_err(|_| ClientError::ClientClosed)?;
+
+        receiver.await.map_err(|_| ClientError::ClientClosed)?
+    }
+}
+
+/// Take the requests from queue and execute on the connection of worker,
+/// until all dispatchers are dropped.
+async fn work<C: Connect>(
+    worker: usize, connector: Arc<C>, receiver: Arc<Mutex<mpsc::Receiver<Job>>>,
+) {
+    let mut client: Option<Client<C::Stream, KeepAlive>> = None;
+    let upstream = connector.address();
+
+    loop {
+        let job = receiver.lock().await.recv().await;
+        let Some((request, responder)) = job else {
+            break;
+        };
+
+        let conn = match &mut client {
+            Some(conn) => conn,
+            None => {
+                debug!(worker, "Establish new connection for worker.");
+                match connector.connect().await {
+                    Ok(stream) => client.insert(Client::new_keep_alive(stream)),
+                    Err(err) => {
+                        let _ = responder.send(Err(ClientError::connect(err)));
+                        continue;
+                    }
+                }
+            }
+        };
+
+        let reusable = request.keep_alive.unwrap_or(true);
+        let result = conn.execute_upstream(*request, upstream.as_deref()).await;
+        // Reestablish the connection if it's closed or broken.
+        let broken = result.as_ref().is_err_and(|err| !err.is_clean());
+        let _ = responder.send(result);
+        if !reusable || broken {
+            debug!(worker, broken, "Drop connection of worker.");
+            client = None;
+        }
+    }
+}
@@ -12,15 +12,55 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::meta::{ProtocolStatus, RequestType};
+use crate::{
+    meta::{ProtocolStatus, RequestType},
+    Response,
+};
+use std::io;
 
 pub type ClientResult<T> = Result<T, ClientError>;
 
 #[derive(Debug, thiserror::Error)]
 pub enum ClientError {
-    /// Wapper of `tokio::io::Error`
+    /// Wapper of `tokio::io::Error`, which isn't classified as the variants
+    /// below.
     #[error(transparent)]
-    Io(#[from] tokio::io::Error),
+    Io(tokio::io::Error),
+
+    /// Connecting to the fastcgi server timed out.
+    #[error("Connect timed out")]
+    ConnectTimeout,
+
+    /// The connection is closed or reset by the fastcgi server.
+    #[error("Connection closed by peer: {source}")]
+    ConnectionClosedByPeer { source: tokio::io::Error },
+
+    /// The records received violate the fastcgi protocol.
+    #[error("Protocol error: {detail}")]
+    ProtocolError { detail: String },
+
+    /// The buffer passed to [parse_record](crate::record::parse_record)
+    /// doesn't contain the whole record, `needed` more bytes at least.
+    #[error("Incomplete record, {needed} more bytes needed")]
+    IncompleteRecord { needed: usize },
+
+    /// The connection failed in the middle of response, the output received
+    /// before is kept in `response`, so it can be logged or served.
+    #[error("Connection failed in the middle of response: {source}")]
+    PartialResponse {
+        source: tokio::io::Error,
+        response: Response,
+    },
+
+    /// Wapper of `http::Error`, when converting to http types.
+    #[cfg(feature = "http")]
+    #[error(transparent)]
+    Http(#[from] http::Error),
+
+    /// Failed to read the body of `http::Request`.
+    #[cfg(feature = "http-body")]
+    #[error("Request body error: {0}")]
+    RequestBody(Box<dyn std::error::Error + Send + Sync>),
 
     /// Usually not happen.
     #[error("Response not found of request id `{id}`")]
@@ -30,10 +70,104 @@ pub enum ClientError {
     #[error("Response not found of request id `{id}`")]
     ResponseNotFound { id: u16 },
 
+    /// All request ids are in use, too many concurrent requests.
+    #[error("No request id available, too many concurrent requests")]
+    RequestIdExhausted,
+
+    /// The request isn't finished within the timeout.
+    #[error("Request timed out")]
+    RequestTimeout,
+
+    /// The address can't be parsed by [Address](crate::connect::Address).
+    #[error("Invalid address `{address}`: {reason}")]
+    InvalidAddress { address: String, reason: String },
+
+    /// The circuit breaker is open, the request is rejected without sending.
+    #[error("Circuit breaker is open")]
+    CircuitOpen,
+
+    /// The requests in flight reach the limit, the request is rejected
+    /// without sending, see
+    /// [ConcurrencyLimiter](crate::limit::ConcurrencyLimiter).
+    #[error("Requests in flight exceed limit `{max_in_flight}`")]
+    LimitExceeded { max_in_flight: usize },
+
+    /// The rate of requests exceeds the limit, the request is rejected
+    /// without sending, see [RateLimiter](crate::limit::RateLimiter).
+    #[error("Request rate exceeds limit `{rate}` per second")]
+    RateLimited { rate: f64 },
+
+    /// The previous request was cancelled in the middle, so the connection is
+    /// left in an undefined state and can't be reused.
+    #[error("Connection poisoned by cancelled request")]
+    ConnectionPoisoned,
+
+    /// The background task owning the connection is stopped.
+    #[error("Client is closed")]
+    ClientClosed,
+
+    /// The server replied `FCGI_UNKNOWN_TYPE`, the management record of
+    /// `request_type` isn't supported.
+    #[error("Record type `{request_type}` is unsupported by server")]
+    UnsupportedRecordType { request_type: u8 },
+
     /// Maybe unimplemented request type received fom response.
     #[error("Response not found of request id `{request_type}`")]
     UnknownRequestType { request_type: RequestType },
 
+    /// The param is rejected by [Params::validate](crate::Params::validate).
+    #[error("Invalid param `{name}`: {reason}")]
+    InvalidParam { name: String, reason: String },
+
+    /// The path of http request is rejected by the
+    /// [gateway](crate::gateway), which may escape the document root.
+    #[cfg(feature = "http")]
+    #[error("Invalid path `{path}`: {reason}")]
+    InvalidPath { path: String, reason: String },
+
+    /// The param named `name`, or the params in total if `name` is `None`,
+    /// exceeds the [ParamsLimits](crate::params::ParamsLimits).
+    #[error("Params too large, length `{length}` exceeds limit `{limit}`")]
+    ParamsTooLarge {
+        name: Option<String>,
+        length: usize,
+        limit: usize,
+    },
+
+    /// The `CONTENT_LENGTH` param doesn't match the known length of stdin.
+    #[error("Content length `{content_length}` mismatches stdin length `{stdin_len}`")]
+    ContentLengthMismatch {
+        content_length: String,
+        stdin_len: usize,
+    },
+
+    /// The php-fpm ping doesn't respond `pong`.
+    #[error("Ping failed with status `{status}`: {body}")]
+    PingFailed { status: u16, body: String },
+
+    /// The php-fpm status page can't be parsed.
+    #[error("Invalid php-fpm status: {reason}")]
+    InvalidFpmStatus { reason: String },
+
+    /// The CGI local redirects exceed the limit, maybe a redirect loop, see
+    /// [GatewayConfig::max_local_redirects](crate::gateway::GatewayConfig::max_local_redirects).
+    #[error("Too many local redirects, exceed limit `{max_redirects}`")]
+    TooManyRedirects { max_redirects: usize },
+
+    /// The header section of CGI response is longer than the
+    /// [HeaderLimits::max_bytes](crate::response::parse::HeaderLimits::max_bytes).
+    #[error("Header section too large, exceeds limit `{limit}` bytes")]
+    HeadersTooLarge { limit: usize },
+
+    /// The CGI response has more headers than the
+    /// [HeaderLimits::max_count](crate::response::parse::HeaderLimits::max_count).
+    #[error("Too many headers, exceed limit `{limit}`")]
+    TooManyHeaders { limit: usize },
+
+    /// The stdout isn't a valid CGI response.
+    #[error("Invalid CGI response: {reason}")]
+    InvalidCgiResponse { reason: String },
+
     /// Response not complete, first is protocol status and second is app
     /// status, see fastcgi protocol.
     #[error("This app can't multiplex [CantMpxConn]; AppStatus: {app_status}")]
@@ -51,6 +185,46 @@ pub enum ClientError {
 }
 
 impl ClientError {
+    /// Classify the error of connecting.
+    pub(crate) fn connect(err: tokio::io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::TimedOut => ClientError::ConnectTimeout,
+            _ => err.into(),
+        }
+    }
+
+    /// Whether the connection is still clean after the error, that is the
+    /// request isn't sent, or the response is read completely.
+    pub(crate) fn is_clean(&self) -> bool {
+        matches!(
+            self,
+            ClientError::InvalidParam { .. }
+                | ClientError::ParamsTooLarge { .. }
+                | ClientError::ContentLengthMismatch { .. }
+                | ClientError::UnsupportedRecordType { .. }
+                | ClientError::EndRequestCantMpxConn { .. }
+                | ClientError::EndRequestOverloaded { .. }
+                | ClientError::EndRequestUnknownRole { .. }
+        )
+    }
+
+    /// Attach the output received before to the io error, if any.
+    pub(crate) fn partial_response(
+        err: tokio::io::Error, stdout: Vec<u8>, stderr: Vec<u8>,
+    ) -> Self {
+        if stdout.is_empty() && stderr.is_empty() {
+            return err.into();
+        }
+        ClientError::PartialResponse {
+            source: err,
+            response: Response {
+                stdout: Some(stdout).filter(|stdout| !stdout.is_empty()),
+                stderr: Some(stderr).filter(|stderr| !stderr.is_empty()),
+                app_status: 0,
+            },
+        }
+    }
+
     pub(crate) fn new_end_request_with_protocol_status(
         protocol_status: ProtocolStatus, app_status: u32,
     ) -> Self {
@@ -61,3 +235,32 @@ impl ClientError {
         }
     }
 }
+
+impl From<io::Error> for ClientError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe => ClientError::ConnectionClosedByPeer { source: err },
+            io::ErrorKind::InvalidData => ClientError::ProtocolError {
+                detail: err.to_string(),
+            },
+            _ => ClientError::Io(err),
+        }
+    }
+}
+
+impl From<ClientError> for io::Error {
+    fn from(err: ClientError) -> Self {
+        match err {
+            ClientError::Io(err)
+            | ClientError::ConnectionClosedByPeer { source: err }
+            | ClientError::PartialResponse { source: err, .. } => err,
+            ClientError::ProtocolError { detail } => {
+                io::Error::new(io::ErrorKind::InvalidData, detail)
+            }
+            err => io::Error::other(err),
+        }
+    }
+}
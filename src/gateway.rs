@@ -0,0 +1,511 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Gateway from http requests (such as hyper `Request<Incoming>`) to fastcgi
+//! server, works like nginx `fastcgi_pass` to php-fpm.
+
+#[cfg(feature = "http-body")]
+use crate::{
+    body::{self, BoxBody},
+    connect::Connect,
+    shared::SharedClient,
+    Client,
+};
+use crate::{
+    cache::ResponseCache, response::parse::HeaderLimits, ClientError, ClientResult, Params, Request,
+};
+#[cfg(any(feature = "http-body", feature = "warp"))]
+use crate::{response::parse, Response};
+#[cfg(any(feature = "http-body", feature = "warp"))]
+use bytes::Bytes;
+#[cfg(feature = "http-body")]
+use http_body::Body;
+#[cfg(feature = "http-body")]
+use http_body_util::{BodyExt, Limited};
+#[cfg(any(feature = "http-body", feature = "warp"))]
+use std::future::Future;
+use std::{borrow::Cow, io::Cursor, time::Duration};
+#[cfg(feature = "http-body")]
+use tracing::warn;
+
+/// Config of gateway, used to resolve the script and fill the params.
+#[derive(Debug, Clone)]
+pub struct GatewayConfig {
+    document_root: String,
+    index: String,
+    params: Params<'static>,
+    max_local_redirects: usize,
+    header_limits: HeaderLimits,
+    timeout: Option<Duration>,
+    cache: Option<ResponseCache>,
+    max_body_size: u64,
+}
+
+impl GatewayConfig {
+    /// Construct with the document root, which the scripts are resolved in.
+    pub fn new(document_root: impl Into<String>) -> Self {
+        Self {
+            document_root: document_root.into().trim_end_matches('/').to_owned(),
+            index: "index.php".to_owned(),
+            params: Params::default(),
+            max_local_redirects: 0,
+            header_limits: HeaderLimits::default(),
+            timeout: None,
+            cache: None,
+            max_body_size: 1024 * 1024,
+        }
+    }
+
+    /// Script used when the request path ends with `/`, like nginx
+    /// `fastcgi_index`, default is `index.php`.
+    pub fn index(mut self, index: impl Into<String>) -> Self {
+        self.index = index.into();
+        self
+    }
+
+    /// Base params, the params mapped from the request are merged into it.
+    pub fn params(mut self, params: Params<'static>) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Follow the CGI local redirect (the response only has `Location`
+    /// header of local path) by [forward] at most `max` times, like nginx,
+    /// the request is reissued by `GET` for the new uri, disabled by default.
+    pub fn max_local_redirects(mut self, max: usize) -> Self {
+        self.max_local_redirects = max;
+        self
+    }
+
+    /// Limits of the header section of responses parsed by [forward].
+    pub fn header_limits(mut self, header_limits: HeaderLimits) -> Self {
+        self.header_limits = header_limits;
+        self
+    }
+
+    /// Timeout of the requests sent by [forward] and the warp filter, or of
+    /// receiving the header section by [serve_php], none by default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Serve the repeated requests by [forward] from the cache, see
+    /// [ResponseCache].
+    pub fn cache(mut self, cache: ResponseCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Max size of the request body collected by [forward], [serve_php] and
+    /// the warp filter, like nginx `client_max_body_size`, default is 1 MiB.
+    pub fn max_body_size(mut self, max_body_size: u64) -> Self {
+        self.max_body_size = max_body_size;
+        self
+    }
+
+    #[cfg(feature = "warp")]
+    pub(crate) fn get_max_body_size(&self) -> u64 {
+        self.max_body_size
+    }
+
+    /// Map the parts of `http::Request` into fastcgi params, the script is
+    /// resolved from the path like nginx `fastcgi_split_path_info
+    /// ^(.+\.php)(/.+)$`.
+    ///
+    /// The path is percent-decoded first, and rejected with
+    /// [ClientError::InvalidPath] if it contains `..` segments, NUL bytes or
+    /// backslashes, so the script can't escape the document root.
+    pub fn request_params(
+        &self, parts: &http::request::Parts, content_length: usize,
+    ) -> ClientResult<Params<'static>> {
+        let path = decode_path(parts.uri.path())?;
+        let path = path.as_str();
+        let (script_name, path_info) = match path.find(".php/") {
+            Some(pos) => path.split_at(pos + ".php".len()),
+            None => (path, ""),
+        };
+        let script_name = if script_name.ends_with('/') {
+            format!("{}{}", script_name, self.index)
+        } else {
+            script_name.to_owned()
+        };
+
+        let mut params = self
+            .params
+            .clone()
+            .document_root(self.document_root.clone())
+            .script_filename(format!("{}{}", self.document_root, script_name))
+            .script_name(script_name)
+            .document_uri(path.to_owned());
+        if !path_info.is_empty() {
+            params.insert("PATH_INFO".into(), path_info.to_owned().into());
+        }
+        if let Some(host) = parts
+            .headers
+            .get(http::header::HOST)
+            .and_then(|host| host.to_str().ok())
+        {
+            let server_name = host.split(':').next().unwrap_or_default();
+            params = params.server_name(server_name.to_owned());
+        }
+
+        Ok(http_params(params, parts, content_length))
+    }
+
+    /// Build the validated request of the http request parts and the
+    /// collected body, with the timeout if any.
+    #[cfg(any(feature = "http-body", feature = "warp"))]
+    pub(crate) fn request(
+        &self, parts: &http::request::Parts, body: Bytes,
+    ) -> ClientResult<Request<'static, Cursor<Bytes>>> {
+        let content_length = body.len();
+        let params = self.request_params(parts, content_length)?;
+        params.validate()?;
+        let mut request = Request::new(params, Cursor::new(body)).with_stdin_len(content_length);
+        if let Some(timeout) = self.timeout {
+            request = request.with_timeout(timeout);
+        }
+        Ok(request)
+    }
+}
+
+/// Map the method, uri and headers into params by [GatewayConfig], and the
+/// body into stdin of known length.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{gateway::GatewayConfig, Request};
+///
+/// let request = http::Request::post("/index.php?name=jmjoy")
+///     .body(b"body".to_vec())
+///     .unwrap();
+/// let request = Request::try_from((request, &GatewayConfig::new("/var/www"))).unwrap();
+/// assert_eq!(request.params()["SCRIPT_FILENAME"], "/var/www/index.php");
+/// assert_eq!(request.stdin_len(), Some(4));
+/// ```
+impl<B: AsRef<[u8]> + Unpin> TryFrom<(http::Request<B>, &GatewayConfig)>
+    for Request<'static, Cursor<B>>
+{
+    type Error = ClientError;
+
+    fn try_from(
+        (request, config): (http::Request<B>, &GatewayConfig),
+    ) -> Result<Self, Self::Error> {
+        let (parts, body) = request.into_parts();
+        let content_length = body.as_ref().len();
+        let params = config.request_params(&parts, content_length)?;
+        params.validate()?;
+        Ok(Request::new(params, Cursor::new(body)).with_stdin_len(content_length))
+    }
+}
+
+/// Forward the http request to fastcgi server, the body is collected as
+/// stdin, and the stdout is parsed as CGI response. The CGI local redirects
+/// are followed if enabled by [GatewayConfig::max_local_redirects].
+///
+/// The body longer than [GatewayConfig::max_body_size] fails with
+/// [ClientError::RequestBody].
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{
+///     body::BoxBody,
+///     gateway::{forward, GatewayConfig},
+///     shared::SharedClient,
+///     ClientResult,
+/// };
+///
+/// async fn handle(
+///     client: &SharedClient, request: http::Request<String>,
+/// ) -> ClientResult<http::Response<BoxBody>> {
+///     forward(client, &GatewayConfig::new("/var/www"), request).await
+/// }
+/// ```
+#[cfg(feature = "http-body")]
+pub async fn forward<B>(
+    client: &SharedClient, config: &GatewayConfig, request: http::Request<B>,
+) -> ClientResult<http::Response<BoxBody>>
+where
+    B: Body,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let (parts, body) = request.into_parts();
+    let body = Limited::new(body, config.max_body_size as usize)
+        .collect()
+        .await
+        .map_err(ClientError::RequestBody)?
+        .to_bytes();
+    let response = forward_collected(client, config, parts, body).await?;
+    Ok(response.map(body::full))
+}
+
+/// The client executing the requests forwarded with the collected body.
+#[cfg(any(feature = "http-body", feature = "warp"))]
+pub(crate) trait Upstream: Clone + Send + Sync + 'static {
+    fn execute(
+        &self, request: Request<'static, Cursor<Bytes>>,
+    ) -> impl Future<Output = ClientResult<Response>> + Send;
+}
+
+#[cfg(feature = "http-body")]
+impl Upstream for SharedClient {
+    fn execute(
+        &self, request: Request<'static, Cursor<Bytes>>,
+    ) -> impl Future<Output = ClientResult<Response>> + Send {
+        SharedClient::execute(self, request)
+    }
+}
+
+/// Forward the request with the collected body, served from the cache if
+/// enabled by [GatewayConfig::cache].
+#[cfg(any(feature = "http-body", feature = "warp"))]
+pub(crate) async fn forward_collected<U: Upstream>(
+    upstream: &U, config: &GatewayConfig, parts: http::request::Parts, body: Bytes,
+) -> ClientResult<http::Response<Bytes>> {
+    match &config.cache {
+        Some(cache) => {
+            let upstream = upstream.clone();
+            let config = config.clone();
+            let fetch_parts = parts.clone();
+            cache
+                .get_or_fetch(&parts, move || async move {
+                    fetch(&upstream, &config, fetch_parts, body).await
+                })
+                .await
+        }
+        None => fetch(upstream, config, parts, body).await,
+    }
+}
+
+/// Execute the request and parse the response, following the CGI local
+/// redirects.
+#[cfg(any(feature = "http-body", feature = "warp"))]
+async fn fetch<U: Upstream>(
+    upstream: &U, config: &GatewayConfig, mut parts: http::request::Parts, mut body: Bytes,
+) -> ClientResult<http::Response<Bytes>> {
+    let mut redirects = 0;
+    loop {
+        let request = config.request(&parts, std::mem::take(&mut body))?;
+        let response = upstream.execute(request).await?;
+        let parsed = parse::parse_with_limits(
+            response.stdout.as_deref().unwrap_or_default(),
+            &config.header_limits,
+        )?;
+
+        let location = parsed
+            .local_redirect()
+            .filter(|_| config.max_local_redirects > 0);
+        let Some(location) = location else {
+            let response = http::Response::try_from(parsed)?;
+            return Ok(response.map(Bytes::from));
+        };
+        if redirects == config.max_local_redirects {
+            return Err(ClientError::TooManyRedirects {
+                max_redirects: redirects,
+            });
+        }
+        redirects += 1;
+
+        // Reissue by `GET` without body, like nginx.
+        parts.uri = location.parse().map_err(http::Error::from)?;
+        parts.method = http::Method::GET;
+        parts.headers.remove(http::header::CONTENT_TYPE);
+        parts.headers.remove(http::header::CONTENT_LENGTH);
+    }
+}
+
+/// Serve the http request by the php script resolved by [GatewayConfig], on
+/// a new connection established by `upstream`, like nginx `fastcgi_pass`.
+///
+/// The body is collected as stdin, and the body of response is streamed as
+/// soon as the CGI headers are parsed. The errors are mapped into responses
+/// instead, `400 Bad Request` for the invalid request, `413 Payload Too
+/// Large` for the body exceeding [GatewayConfig::max_body_size], `504 Gateway
+/// Timeout` if the header section isn't received within
+/// [GatewayConfig::timeout], and `502 Bad Gateway` for the others.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{
+///     body::BoxBody,
+///     connect::TcpConnector,
+///     gateway::{serve_php, GatewayConfig},
+/// };
+/// use std::time::Duration;
+///
+/// async fn handle(request: http::Request<String>) -> http::Response<BoxBody> {
+///     let config = GatewayConfig::new("/var/www").timeout(Duration::from_secs(30));
+///     serve_php(request, &config, &TcpConnector::new("127.0.0.1:9000")).await
+/// }
+/// ```
+#[cfg(feature = "http-body")]
+pub async fn serve_php<B, C>(
+    request: http::Request<B>, config: &GatewayConfig, upstream: &C,
+) -> http::Response<BoxBody>
+where
+    B: Body,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    C: Connect,
+{
+    let (parts, body) = request.into_parts();
+    let body = match Limited::new(body, config.max_body_size as usize)
+        .collect()
+        .await
+    {
+        Ok(body) => body.to_bytes(),
+        Err(err) => {
+            let status = if err.is::<http_body_util::LengthLimitError>() {
+                http::StatusCode::PAYLOAD_TOO_LARGE
+            } else {
+                http::StatusCode::BAD_REQUEST
+            };
+            return error_response(status, ClientError::RequestBody(err));
+        }
+    };
+
+    let request = match config.request(&parts, body) {
+        Ok(request) => request,
+        Err(err) => return error_response(http::StatusCode::BAD_REQUEST, err),
+    };
+
+    let result = async {
+        let stream = upstream.connect().await.map_err(ClientError::connect)?;
+        let (head, body) = Client::new(stream).execute_once_parsed(request).await?;
+        let mut response = http::Response::new(body.boxed_unsync());
+        *response.status_mut() = head.status_code()?;
+        *response.headers_mut() = head.headers.to_header_map()?;
+        Ok::<_, ClientError>(response)
+    };
+    // The timeout of request only bounds the sending, so bound the waiting of
+    // the header section here.
+    let result = match config.timeout {
+        Some(timeout) => tokio::time::timeout(timeout, result)
+            .await
+            .unwrap_or(Err(ClientError::RequestTimeout)),
+        None => result.await,
+    };
+    match result {
+        Ok(response) => response,
+        Err(err @ ClientError::RequestTimeout) => {
+            error_response(http::StatusCode::GATEWAY_TIMEOUT, err)
+        }
+        Err(err) => error_response(http::StatusCode::BAD_GATEWAY, err),
+    }
+}
+
+#[cfg(feature = "http-body")]
+fn error_response(status: http::StatusCode, err: ClientError) -> http::Response<BoxBody> {
+    warn!(%status, ?err, "Failed to serve php request.");
+    let mut response =
+        http::Response::new(body::full(status.canonical_reason().unwrap_or_default()));
+    *response.status_mut() = status;
+    response
+}
+
+/// Map the method, uri, version and headers of `http::Request` into fastcgi
+/// params.
+pub(crate) fn http_params(
+    mut params: Params<'static>, parts: &http::request::Parts, content_length: usize,
+) -> Params<'static> {
+    params = params
+        .request_method(parts.method.to_string())
+        .request_uri(
+            parts
+                .uri
+                .path_and_query()
+                .map(|p| p.to_string())
+                .unwrap_or_else(|| "/".to_owned()),
+        )
+        .query_string(parts.uri.query().unwrap_or_default().to_owned())
+        .server_protocol(format!("{:?}", parts.version))
+        .content_length(content_length);
+
+    if let Some(content_type) = parts.headers.get(http::header::CONTENT_TYPE) {
+        params = params.content_type(String::from_utf8_lossy(content_type.as_bytes()).into_owned());
+    }
+
+    for name in parts.headers.keys() {
+        // `Proxy` would become `HTTP_PROXY` and be taken as the proxy of
+        // outgoing requests (httpoxy), names with `_` are indistinguishable
+        // from the ones with `-`, and the content headers are mapped above.
+        if name.as_str() == "proxy"
+            || name.as_str().contains('_')
+            || name == http::header::CONTENT_TYPE
+            || name == http::header::CONTENT_LENGTH
+        {
+            continue;
+        }
+        let value = parts
+            .headers
+            .get_all(name)
+            .iter()
+            .map(|value| String::from_utf8_lossy(value.as_bytes()))
+            .collect::<Vec<_>>()
+            .join(if name == http::header::COOKIE {
+                "; "
+            } else {
+                ", "
+            });
+        let name = format!(
+            "HTTP_{}",
+            name.as_str().to_ascii_uppercase().replace('-', "_")
+        );
+        params.insert(Cow::Owned(name), Cow::Owned(value));
+    }
+
+    params
+}
+
+/// Percent-decode the path, and reject the one which may escape the document
+/// root.
+fn decode_path(path: &str) -> ClientResult<String> {
+    let invalid = |reason: &str| ClientError::InvalidPath {
+        path: path.to_owned(),
+        reason: reason.to_owned(),
+    };
+
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes
+                .get(i + 1..i + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| invalid("invalid percent-encoding"))?;
+            decoded.push(hex);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    let decoded = String::from_utf8(decoded).map_err(|_| invalid("not valid UTF-8"))?;
+
+    if decoded.contains('\0') {
+        return Err(invalid("contains NUL byte"));
+    }
+    if decoded.contains('\\') {
+        return Err(invalid("contains backslash"));
+    }
+    if decoded.split('/').any(|segment| segment == "..") {
+        return Err(invalid("contains `..` segment"));
+    }
+    Ok(decoded)
+}
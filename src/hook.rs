@@ -0,0 +1,303 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Hooks invoked for every record sent and received, for custom logging,
+//! mutation or policy checks.
+
+use crate::{
+    capture::Direction,
+    meta::{HEADER_LEN, MAX_LENGTH},
+};
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// The record passing through [Hooked], the content can be modified, and the
+/// header is rebuilt by the modified content.
+#[derive(Debug)]
+pub struct Record<'a> {
+    direction: Direction,
+    r#type: u8,
+    request_id: u16,
+    content: &'a mut Vec<u8>,
+}
+
+impl Record<'_> {
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// The raw record type, such as `6` for `FCGI_STDOUT`.
+    pub fn r#type(&self) -> u8 {
+        self.r#type
+    }
+
+    pub fn request_id(&self) -> u16 {
+        self.request_id
+    }
+
+    pub fn set_request_id(&mut self, request_id: u16) {
+        self.request_id = request_id;
+    }
+
+    pub fn content(&self) -> &[u8] {
+        self.content
+    }
+
+    /// The length mustn't exceed 65535 after modified, otherwise the stream
+    /// fails.
+    pub fn content_mut(&mut self) -> &mut Vec<u8> {
+        self.content
+    }
+}
+
+/// Hook invoked for every record, returning error fails the stream, so the
+/// request fails with [ClientError::Io](crate::ClientError::Io).
+pub trait RecordHook: Send {
+    fn on_record(&mut self, record: &mut Record<'_>) -> io::Result<()>;
+}
+
+impl<F: FnMut(&mut Record<'_>) -> io::Result<()> + Send> RecordHook for F {
+    fn on_record(&mut self, record: &mut Record<'_>) -> io::Result<()> {
+        self(record)
+    }
+}
+
+/// Stream wrapper invoking the hook for every record passing through.
+///
+/// The sent records are held until complete, so they are written after the
+/// hook returns, the records are released by flushing.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{
+///     hook::{Hooked, Record},
+///     Client, Params, Request,
+/// };
+/// use std::io;
+/// use tokio::net::TcpStream;
+///
+/// async fn hook() {
+///     let stream = TcpStream::connect(("127.0.0.1", 9000)).await.unwrap();
+///     let stream = Hooked::new(stream, |record: &mut Record<'_>| {
+///         // Reject the records of `FCGI_DATA`.
+///         if record.r#type() == 8 {
+///             return Err(io::Error::other("data isn't allowed"));
+///         }
+///         Ok(())
+///     });
+///     let client = Client::new(stream);
+///     let output = client
+///         .execute_once(Request::new(Params::default(), tokio::io::empty()))
+///         .await
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Hooked<S, H> {
+    stream: S,
+    hook: H,
+    /// The incomplete record written by client.
+    write_in: Vec<u8>,
+    /// The hooked records waiting to be written to stream.
+    write_out: Vec<u8>,
+    written: usize,
+    /// The incomplete record read from stream.
+    read_in: Vec<u8>,
+    /// The hooked records waiting to be read by client.
+    read_out: Vec<u8>,
+    read: usize,
+}
+
+impl<S, H: RecordHook> Hooked<S, H> {
+    pub fn new(stream: S, hook: H) -> Self {
+        Self {
+            stream,
+            hook,
+            write_in: Vec::new(),
+            write_out: Vec::new(),
+            written: 0,
+            read_in: Vec::new(),
+            read_out: Vec::new(),
+            read: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    pub fn hook(&self) -> &H {
+        &self.hook
+    }
+
+    pub fn into_inner(self) -> (S, H) {
+        (self.stream, self.hook)
+    }
+}
+
+impl<S: AsyncWrite + Unpin, H> Hooked<S, H> {
+    /// Write the hooked records to stream.
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.written < self.write_out.len() {
+            let n =
+                ready!(Pin::new(&mut self.stream).poll_write(cx, &self.write_out[self.written..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.written += n;
+        }
+        self.write_out.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Take the complete records from `input`, invoke the hook and append the
+/// rebuilt records to `output`.
+fn process(
+    hook: &mut impl RecordHook, direction: Direction, input: &mut Vec<u8>, output: &mut Vec<u8>,
+) -> io::Result<()> {
+    let mut start = 0;
+    while input.len() - start >= HEADER_LEN {
+        let mut header: [u8; HEADER_LEN] = input[start..start + HEADER_LEN].try_into().unwrap();
+        let content_length = u16::from_be_bytes([header[4], header[5]]) as usize;
+        let padding_start = start + HEADER_LEN + content_length;
+        let end = padding_start + header[6] as usize;
+        if input.len() < end {
+            break;
+        }
+
+        let mut content = input[start + HEADER_LEN..padding_start].to_vec();
+        let mut record = Record {
+            direction,
+            r#type: header[1],
+            request_id: u16::from_be_bytes([header[2], header[3]]),
+            content: &mut content,
+        };
+        hook.on_record(&mut record)?;
+        let request_id = record.request_id;
+
+        if content.len() > MAX_LENGTH {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "hooked record content too long",
+            ));
+        }
+        // Keep the original padding if the length isn't changed.
+        let zeros = [0; 8];
+        let padding = if content.len() == content_length {
+            &input[padding_start..end]
+        } else {
+            &zeros[..(8 - content.len() % 8) % 8]
+        };
+        header[2..4].copy_from_slice(&request_id.to_be_bytes());
+        header[4..6].copy_from_slice(&(content.len() as u16).to_be_bytes());
+        header[6] = padding.len() as u8;
+        output.extend_from_slice(&header);
+        output.extend_from_slice(&content);
+        output.extend_from_slice(padding);
+        start = end;
+    }
+    input.drain(..start);
+    Ok(())
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin, H: RecordHook + Unpin> AsyncRead for Hooked<S, H> {
+    fn poll_read(
+        self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        // Make sure the request is sent even if not flushed, the pending
+        // write wakes the task as well.
+        if let Poll::Ready(Err(err)) = this.poll_drain(cx) {
+            return Poll::Ready(Err(err));
+        }
+
+        loop {
+            if this.read < this.read_out.len() {
+                let n = buf.remaining().min(this.read_out.len() - this.read);
+                buf.put_slice(&this.read_out[this.read..this.read + n]);
+                this.read += n;
+                if this.read == this.read_out.len() {
+                    this.read_out.clear();
+                    this.read = 0;
+                }
+                return Poll::Ready(Ok(()));
+            }
+
+            let mut chunk = [0; 4096];
+            let mut chunk = ReadBuf::new(&mut chunk);
+            ready!(Pin::new(&mut this.stream).poll_read(cx, &mut chunk))?;
+            if chunk.filled().is_empty() {
+                // Pass the incomplete record through at eof, let the client
+                // report it.
+                if this.read_in.is_empty() {
+                    return Poll::Ready(Ok(()));
+                }
+                this.read_out.append(&mut this.read_in);
+                continue;
+            }
+            this.read_in.extend_from_slice(chunk.filled());
+            process(
+                &mut this.hook,
+                Direction::Received,
+                &mut this.read_in,
+                &mut this.read_out,
+            )?;
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin, H: RecordHook + Unpin> AsyncWrite for Hooked<S, H> {
+    fn poll_write(
+        self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+
+        this.write_in.extend_from_slice(buf);
+        process(
+            &mut this.hook,
+            Direction::Sent,
+            &mut this.write_in,
+            &mut this.write_out,
+        )?;
+        if let Poll::Ready(Err(err)) = this.poll_drain(cx) {
+            return Poll::Ready(Err(err));
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.stream).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,139 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Request id allocators, decide which request id is used for the records of a
+//! fastcgi request.
+
+use crate::{ClientError, ClientResult};
+use std::{
+    collections::LinkedList,
+    fmt,
+    sync::{
+        atomic::{AtomicU16, AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+/// Allocate and release request ids.
+pub trait AllocRequestId {
+    /// Allocate an unused request id.
+    fn alloc(&self) -> ClientResult<u16>;
+
+    /// Give back the request id allocated by
+    /// [alloc](AllocRequestId::alloc).
+    fn release(&self, id: u16);
+}
+
+/// Always allocate the request id `1`, like nginx does, only suitable for one
+/// request at a time.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FixRequestIdAllocator;
+
+impl AllocRequestId for FixRequestIdAllocator {
+    fn alloc(&self) -> ClientResult<u16> {
+        Ok(1)
+    }
+
+    fn release(&self, _id: u16) {}
+}
+
+/// Allocate distinct request ids from the pool of all valid ids
+/// (`1..=65535`), used for multiplexing.
+#[derive(Debug)]
+pub struct PooledRequestIdAllocator {
+    ids: Mutex<LinkedList<u16>>,
+}
+
+impl Default for PooledRequestIdAllocator {
+    fn default() -> Self {
+        Self {
+            ids: Mutex::new((1..=u16::MAX).collect()),
+        }
+    }
+}
+
+impl AllocRequestId for PooledRequestIdAllocator {
+    fn alloc(&self) -> ClientResult<u16> {
+        self.ids
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or(ClientError::RequestIdExhausted)
+    }
+
+    fn release(&self, id: u16) {
+        self.ids.lock().unwrap().push_back(id);
+    }
+}
+
+/// Allocate distinct request ids (`1..=65535`) without locking, the ids are
+/// taken by a wrapping counter skipping `0`, and the ids in use are marked in
+/// a bitmap, so it can be shared by many tasks.
+pub struct AtomicRequestIdAllocator {
+    next: AtomicU16,
+    in_use: Box<[AtomicU64]>,
+}
+
+impl Default for AtomicRequestIdAllocator {
+    fn default() -> Self {
+        Self {
+            next: AtomicU16::new(1),
+            in_use: (0..(u16::MAX as usize + 1) / 64)
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+        }
+    }
+}
+
+impl AtomicRequestIdAllocator {
+    /// The count of ids in use.
+    pub fn in_use(&self) -> usize {
+        self.in_use
+            .iter()
+            .map(|bits| bits.load(Ordering::Relaxed).count_ones() as usize)
+            .sum()
+    }
+}
+
+impl AllocRequestId for AtomicRequestIdAllocator {
+    fn alloc(&self) -> ClientResult<u16> {
+        // Walk around the whole ring once at most, including the skipped `0`.
+        for _ in 0..=u16::MAX {
+            let id = self.next.fetch_add(1, Ordering::Relaxed);
+            if id == 0 {
+                continue;
+            }
+            let bit = 1 << (id % 64);
+            let bits = &self.in_use[id as usize / 64];
+            if bits.fetch_or(bit, Ordering::AcqRel) & bit == 0 {
+                return Ok(id);
+            }
+        }
+        Err(ClientError::RequestIdExhausted)
+    }
+
+    fn release(&self, id: u16) {
+        let bit = 1 << (id % 64);
+        self.in_use[id as usize / 64].fetch_and(!bit, Ordering::AcqRel);
+    }
+}
+
+impl fmt::Debug for AtomicRequestIdAllocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AtomicRequestIdAllocator")
+            .field("next", &self.next)
+            .field("in_use", &self.in_use())
+            .finish()
+    }
+}
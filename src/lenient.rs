@@ -0,0 +1,70 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Lenient mode of [Client](crate::Client), tolerating the protocol quirks
+//! of fastcgi servers seen in the wild instead of failing the request.
+
+use std::{fmt, sync::Arc};
+
+/// Protocol quirk tolerated in lenient mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Anomaly {
+    /// The version of record header isn't `1`.
+    UnknownVersion { id: u16, version: u8 },
+    /// The reserved bytes of record header or `EndRequest` body aren't zero.
+    NonzeroReserved { id: u16, r#type: u8 },
+    /// The padding is longer than needed for 8 bytes alignment, or the padding
+    /// bytes aren't zero.
+    StrayPadding {
+        id: u16,
+        r#type: u8,
+        padding_length: u8,
+    },
+    /// The record type is unknown or unexpected in response, the record is
+    /// skipped.
+    UnknownType { id: u16, r#type: u8 },
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Anomaly::UnknownVersion { id, version } => {
+                write!(f, "unknown version {} of request {}", version, id)
+            }
+            Anomaly::NonzeroReserved { id, r#type } => {
+                write!(
+                    f,
+                    "nonzero reserved bytes in record {} of request {}",
+                    r#type, id
+                )
+            }
+            Anomaly::StrayPadding {
+                id,
+                r#type,
+                padding_length,
+            } => write!(
+                f,
+                "stray padding of length {} in record {} of request {}",
+                padding_length, r#type, id
+            ),
+            Anomaly::UnknownType { id, r#type } => {
+                write!(f, "skipped unknown record {} of request {}", r#type, id)
+            }
+        }
+    }
+}
+
+/// Callback reporting the anomalies tolerated in lenient mode.
+pub type AnomalyHandler = Arc<dyn Fn(&Anomaly) + Send + Sync>;
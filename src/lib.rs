@@ -16,12 +16,62 @@
 #![warn(clippy::dbg_macro, clippy::print_stdout)]
 #![doc = include_str!("../README.md")]
 
+#[cfg(feature = "http-body")]
+pub mod body;
+pub mod breaker;
+mod buffer;
+pub mod builder;
+#[cfg(feature = "http")]
+pub mod cache;
+pub mod capture;
 pub mod client;
+#[cfg(feature = "futures-io")]
+pub mod compat;
+#[cfg(feature = "conformance")]
+pub mod conformance;
 pub mod conn;
+pub mod connect;
+pub mod dispatch;
 mod error;
+#[cfg(feature = "http")]
+pub mod gateway;
+pub mod hook;
+pub mod id;
+pub mod lenient;
+pub mod limit;
+pub mod logging;
 mod meta;
+pub mod multiplex;
 pub mod params;
+pub mod pool;
+#[cfg(all(feature = "process", unix))]
+pub mod process;
+#[cfg(feature = "opentelemetry")]
+pub mod propagation;
+pub mod record;
 pub mod request;
 pub mod response;
+pub mod retry;
+pub mod server;
+#[cfg(feature = "tower")]
+pub mod service;
+pub mod shared;
+pub mod status;
+pub mod stderr;
+#[cfg(feature = "trace")]
+mod trace;
+pub mod upstream;
+#[cfg(all(feature = "tokio-uring", target_os = "linux"))]
+pub mod uring;
+pub mod values;
+#[cfg(feature = "warp")]
+pub mod warp;
 
-pub use crate::{client::Client, error::*, params::Params, request::Request, response::Response};
+pub use crate::{
+    client::Client,
+    error::*,
+    meta::{ProtocolStatus, Role},
+    params::Params,
+    request::Request,
+    response::Response,
+};
@@ -0,0 +1,285 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Concurrency and rate limiters, cap the requests in flight and the rate
+//! of requests, so a burst of traffic can't exceed what the worker pool of
+//! fastcgi server can absorb.
+
+use crate::{ClientError, ClientResult};
+use std::{
+    future::Future,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tokio::{sync::Semaphore, time};
+use tracing::debug;
+
+/// What to do with the requests exceeding the limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// Wait for the requests in flight to complete.
+    #[default]
+    Queue,
+    /// Reject with [ClientError::LimitExceeded] or [ClientError::RateLimited]
+    /// immediately.
+    FailFast,
+}
+
+/// Config of [ConcurrencyLimiter].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct LimitConfig {
+    /// The max count of requests in flight, such as `pm.max_children` of
+    /// php-fpm.
+    pub max_in_flight: usize,
+    pub overflow: Overflow,
+}
+
+impl Default for LimitConfig {
+    fn default() -> Self {
+        Self {
+            max_in_flight: 32,
+            overflow: Overflow::Queue,
+        }
+    }
+}
+
+impl LimitConfig {
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = max_in_flight;
+        self
+    }
+
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+}
+
+/// Concurrency limiter of requests, used by
+/// [MultiplexClient](crate::multiplex::MultiplexClient) and
+/// [SharedClient](crate::shared::SharedClient) if configured.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{
+///     limit::{ConcurrencyLimiter, LimitConfig, Overflow},
+///     Client, Params, Request,
+/// };
+/// use tokio::io;
+///
+/// async fn limit() {
+///     let limiter = ConcurrencyLimiter::new(
+///         LimitConfig::default()
+///             .max_in_flight(8)
+///             .overflow(Overflow::FailFast),
+///     );
+///     let output = limiter
+///         .call(async {
+///             let client = Client::connect("tcp://127.0.0.1:9000").await?;
+///             client
+///                 .execute_once(Request::new(Params::default(), io::empty()))
+///                 .await
+///         })
+///         .await;
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ConcurrencyLimiter {
+    config: LimitConfig,
+    semaphore: Semaphore,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(config: LimitConfig) -> Self {
+        Self {
+            semaphore: Semaphore::new(config.max_in_flight),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &LimitConfig {
+        &self.config
+    }
+
+    /// The count of requests in flight.
+    pub fn in_flight(&self) -> usize {
+        self.config.max_in_flight - self.semaphore.available_permits()
+    }
+
+    /// Run the request future if under the limit, otherwise wait or return
+    /// [ClientError::LimitExceeded] by the [Overflow].
+    pub async fn call<T>(&self, fut: impl Future<Output = ClientResult<T>>) -> ClientResult<T> {
+        let _permit = match self.config.overflow {
+            Overflow::Queue => self
+                .semaphore
+                .acquire()
+                .await
+                .map_err(|_| ClientError::ClientClosed)?,
+            Overflow::FailFast => {
+                self.semaphore
+                    .try_acquire()
+                    .map_err(|_| ClientError::LimitExceeded {
+                        max_in_flight: self.config.max_in_flight,
+                    })?
+            }
+        };
+        fut.await
+    }
+}
+
+/// Config of [RateLimiter].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct RateConfig {
+    /// The requests allowed per second on average.
+    pub rate: f64,
+    /// The max requests allowed in a burst, that is the capacity of token
+    /// bucket, which is full initially.
+    pub burst: u32,
+    /// [Overflow::Queue] delays the requests until the tokens are refilled.
+    pub overflow: Overflow,
+}
+
+impl Default for RateConfig {
+    fn default() -> Self {
+        Self {
+            rate: 100.0,
+            burst: 100,
+            overflow: Overflow::Queue,
+        }
+    }
+}
+
+impl RateConfig {
+    pub fn rate(mut self, rate: f64) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    pub fn burst(mut self, burst: u32) -> Self {
+        self.burst = burst;
+        self
+    }
+
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+/// Token bucket rate limiter of requests, used by [Pool](crate::pool::Pool)
+/// if configured, so every upstream of
+/// [Upstreams](crate::upstream::Upstreams) is limited separately.
+///
+/// The token is taken before the request is sent, the delayed request keeps
+/// its token even if cancelled.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{
+///     limit::{Overflow, RateConfig, RateLimiter},
+///     Client, Params, Request,
+/// };
+/// use tokio::io;
+///
+/// async fn rate() {
+///     let limiter = RateLimiter::new(
+///         RateConfig::default()
+///             .rate(20.0)
+///             .burst(5)
+///             .overflow(Overflow::FailFast),
+///     );
+///     let output = limiter
+///         .call(async {
+///             let client = Client::connect("tcp://127.0.0.1:9000").await?;
+///             client
+///                 .execute_once(Request::new(Params::default(), io::empty()))
+///                 .await
+///         })
+///         .await;
+/// }
+/// ```
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateConfig,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    /// # Panics
+    ///
+    /// Panics if the rate isn't positive.
+    pub fn new(config: RateConfig) -> Self {
+        assert!(config.rate > 0.0, "rate must be positive");
+        Self {
+            bucket: Mutex::new(Bucket {
+                tokens: config.burst as f64,
+                refilled: Instant::now(),
+            }),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &RateConfig {
+        &self.config
+    }
+
+    /// Run the request future if a token is available, otherwise wait for
+    /// the token or return [ClientError::RateLimited] by the [Overflow].
+    pub async fn call<T>(&self, fut: impl Future<Output = ClientResult<T>>) -> ClientResult<T> {
+        if let Some(delay) = self.acquire()? {
+            debug!(?delay, "Delay request by rate limit.");
+            time::sleep(delay).await;
+        }
+        fut.await
+    }
+
+    /// Take a token, return the delay until the token is refilled if it's
+    /// taken in advance.
+    fn acquire(&self) -> ClientResult<Option<Duration>> {
+        let RateConfig {
+            rate,
+            burst,
+            overflow,
+        } = self.config;
+        let mut bucket = self.bucket.lock().unwrap();
+
+        let now = Instant::now();
+        let refill = now.duration_since(bucket.refilled).as_secs_f64() * rate;
+        bucket.tokens = (bucket.tokens + refill).min(burst as f64);
+        bucket.refilled = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(None);
+        }
+        match overflow {
+            Overflow::Queue => {
+                let delay = Duration::from_secs_f64((1.0 - bucket.tokens) / rate);
+                bucket.tokens -= 1.0;
+                Ok(Some(delay))
+            }
+            Overflow::FailFast => Err(ClientError::RateLimited { rate }),
+        }
+    }
+}
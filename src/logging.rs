@@ -0,0 +1,226 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Access log of requests, one event per request recording the method,
+//! script, upstream, duration, status and sizes.
+
+use crate::{
+    response::parse::{self, HeaderLimits},
+    ClientResult, Request, Response,
+};
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+use tokio::io::AsyncRead;
+use tracing::Level;
+
+/// Emit the event at the level not known at compile time.
+macro_rules! event_at {
+    ($level:expr, $($arg:tt)+) => {
+        match $level {
+            Level::ERROR => tracing::error!($($arg)+),
+            Level::WARN => tracing::warn!($($arg)+),
+            Level::INFO => tracing::info!($($arg)+),
+            Level::DEBUG => tracing::debug!($($arg)+),
+            Level::TRACE => tracing::trace!($($arg)+),
+        }
+    };
+}
+
+/// Config of [RequestLogger].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct LogConfig {
+    /// Level of the requests succeeded.
+    pub level: Level,
+    /// Level of the requests failed, or responded status `5xx`.
+    pub error_level: Level,
+    /// Log the requests taking longer than the duration at `slow_level`,
+    /// unless failed.
+    pub slow_threshold: Option<Duration>,
+    pub slow_level: Level,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: Level::INFO,
+            error_level: Level::WARN,
+            slow_threshold: None,
+            slow_level: Level::WARN,
+        }
+    }
+}
+
+impl LogConfig {
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    pub fn error_level(mut self, error_level: Level) -> Self {
+        self.error_level = error_level;
+        self
+    }
+
+    pub fn slow_threshold(mut self, slow_threshold: Duration) -> Self {
+        self.slow_threshold = Some(slow_threshold);
+        self
+    }
+
+    pub fn slow_level(mut self, slow_level: Level) -> Self {
+        self.slow_level = slow_level;
+        self
+    }
+}
+
+/// Log every request executed by [call](RequestLogger::call) as one event
+/// with the fields `method`, `script`, `upstream`, `duration_ms`,
+/// `request_bytes`, and `status`, `stdout_bytes`, `stderr_bytes` and
+/// `app_status` if succeeded, or `error` if failed, used by
+/// [Pool](crate::pool::Pool) if configured.
+///
+/// The status is taken from the `Status` header of stdout, `200` if absent,
+/// and `0` if the stdout isn't a CGI response.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{
+///     connect::TcpConnector,
+///     logging::{LogConfig, RequestLogger},
+///     pool::Pool,
+///     Params, Request,
+/// };
+/// use std::time::Duration;
+/// use tokio::io;
+///
+/// async fn log(pool: &Pool<TcpConnector>) {
+///     let logger =
+///         RequestLogger::new(LogConfig::default().slow_threshold(Duration::from_secs(1)))
+///             .upstream("127.0.0.1:9000");
+///     let params = Params::default().request_method("GET");
+///     let output = logger
+///         .call(Request::new(params, io::empty()), |request| {
+///             pool.execute(request)
+///         })
+///         .await;
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RequestLogger {
+    config: LogConfig,
+    upstream: Option<String>,
+}
+
+impl RequestLogger {
+    pub fn new(config: LogConfig) -> Self {
+        Self {
+            config,
+            upstream: None,
+        }
+    }
+
+    /// The address of server logged as `upstream`.
+    pub fn upstream(mut self, upstream: impl Into<String>) -> Self {
+        self.upstream = Some(upstream.into());
+        self
+    }
+
+    pub fn config(&self) -> &LogConfig {
+        &self.config
+    }
+
+    /// Execute the request by `execute` and log the result.
+    pub async fn call<'a, I, F, Fut>(
+        &self, request: Request<'a, I>, execute: F,
+    ) -> ClientResult<Response>
+    where
+        I: AsyncRead + Unpin,
+        F: FnOnce(Request<'a, I>) -> Fut,
+        Fut: Future<Output = ClientResult<Response>>,
+    {
+        let params = request.params();
+        let method = params
+            .get("REQUEST_METHOD")
+            .map(|method| method.to_string());
+        let script = params
+            .get("SCRIPT_FILENAME")
+            .or_else(|| params.get("SCRIPT_NAME"))
+            .map(|script| script.to_string());
+        let request_bytes = request.stdin_len();
+
+        let start = Instant::now();
+        let result = execute(request).await;
+        let duration = start.elapsed();
+
+        let method = method.as_deref().unwrap_or("-");
+        let script = script.as_deref().unwrap_or("-");
+        let upstream = self.upstream.as_deref().unwrap_or("-");
+        let duration_ms = duration.as_millis() as u64;
+        match &result {
+            Ok(response) => {
+                let stdout = response.stdout.as_deref().unwrap_or_default();
+                let status = status(stdout);
+                let level = if status >= 500 {
+                    self.config.error_level
+                } else if self
+                    .config
+                    .slow_threshold
+                    .is_some_and(|threshold| duration >= threshold)
+                {
+                    self.config.slow_level
+                } else {
+                    self.config.level
+                };
+                event_at!(
+                    level,
+                    method,
+                    script,
+                    upstream,
+                    duration_ms,
+                    request_bytes,
+                    status,
+                    stdout_bytes = stdout.len(),
+                    stderr_bytes = response.stderr.as_ref().map_or(0, Vec::len),
+                    app_status = response.app_status,
+                    "FastCGI request completed."
+                );
+            }
+            Err(err) => {
+                event_at!(
+                    self.config.error_level,
+                    method,
+                    script,
+                    upstream,
+                    duration_ms,
+                    request_bytes,
+                    error = %err,
+                    "FastCGI request failed."
+                );
+            }
+        }
+        result
+    }
+}
+
+/// The status of CGI response, `0` if invalid.
+fn status(stdout: &[u8]) -> u16 {
+    parse::split_header_section(stdout)
+        .and_then(|(header_section, _)| {
+            parse::parse_header_section(header_section, &HeaderLimits::default())
+        })
+        .map_or(0, |head| head.status)
+}
@@ -13,22 +13,28 @@
 // limitations under the License.
 
 use crate::{
+    buffer::PooledBuffer,
     error::{ClientError, ClientResult},
-    Params,
+    params::{self, Params},
 };
 use std::{
     borrow::Cow,
     cmp::min,
-    collections::HashMap,
     fmt::{self, Debug, Display},
+    io::IoSlice,
     mem::size_of,
     ops::{Deref, DerefMut},
 };
 use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::{debug, warn};
 
 pub(crate) const VERSION_1: u8 = 1;
 pub(crate) const MAX_LENGTH: usize = 0xffff;
+/// Max content length of the record which needn't padding.
+const MAX_UNPADDED_LENGTH: usize = MAX_LENGTH & !7;
 pub(crate) const HEADER_LEN: usize = size_of::<Header>();
+/// Request id of management records.
+pub(crate) const NULL_REQUEST_ID: u16 = 0;
 
 #[derive(Debug, Clone)]
 #[repr(u8)]
@@ -47,7 +53,7 @@ pub enum RequestType {
 }
 
 impl RequestType {
-    fn from_u8(u: u8) -> Self {
+    pub(crate) fn from_u8(u: u8) -> Self {
         match u {
             1 => RequestType::BeginRequest,
             2 => RequestType::AbortRequest,
@@ -84,18 +90,18 @@ impl Header {
     pub(crate) async fn write_to_stream_batches<F, R, W>(
         r#type: RequestType, request_id: u16, writer: &mut W, content: &mut R,
         before_write: Option<F>,
-    ) -> io::Result<()>
+    ) -> ClientResult<()>
     where
         F: Fn(Header) -> Header,
         R: AsyncRead + Unpin,
         W: AsyncWrite + Unpin,
     {
-        let mut buf: [u8; MAX_LENGTH] = [0; MAX_LENGTH];
-        let mut had_written = false;
+        let mut buf = PooledBuffer::take();
 
         loop {
-            let read = content.read(&mut buf).await?;
-            if had_written && read == 0 {
+            // The content is read locally, so the errors aren't of the peer.
+            let read = content.read(&mut buf).await.map_err(ClientError::Io)?;
+            if read == 0 {
                 break;
             }
 
@@ -105,8 +111,37 @@ impl Header {
                 header = f(header);
             }
             header.write_to_stream(writer, buf).await?;
+        }
+        Ok(())
+    }
+
+    /// Like [write_to_stream_batches](Header::write_to_stream_batches), but
+    /// the content length is known, so read exact `length` bytes in the
+    /// records without padding, and skip the final empty read.
+    pub(crate) async fn write_to_stream_sized<F, R, W>(
+        r#type: RequestType, request_id: u16, writer: &mut W, content: &mut R, length: usize,
+        before_write: Option<F>,
+    ) -> ClientResult<()>
+    where
+        F: Fn(Header) -> Header,
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut buf = PooledBuffer::take();
+        let mut remaining = length;
+
+        while remaining > 0 {
+            let buf = &mut buf[..min(remaining, MAX_UNPADDED_LENGTH)];
+            // The stdin shorter than the length fails with `UnexpectedEof`,
+            // which isn't the connection closed by peer.
+            content.read_exact(buf).await.map_err(ClientError::Io)?;
 
-            had_written = true;
+            let mut header = Self::new(r#type.clone(), request_id, buf);
+            if let Some(ref f) = before_write {
+                header = f(header);
+            }
+            header.write_to_stream(writer, buf).await?;
+            remaining -= buf.len();
         }
         Ok(())
     }
@@ -118,29 +153,49 @@ impl Header {
             r#type,
             request_id,
             content_length,
-            padding_length: (-(content_length as i16) & 7) as u8,
+            padding_length: ((8 - content_length % 8) % 8) as u8,
             reserved: 0,
         }
     }
 
+    /// Write a whole record with the content, which length must not be
+    /// greater than [MAX_LENGTH].
+    pub(crate) async fn write_record<W: AsyncWrite + Unpin>(
+        r#type: RequestType, request_id: u16, writer: &mut W, content: &[u8],
+    ) -> io::Result<()> {
+        Self::new(r#type, request_id, content)
+            .write_to_stream(writer, content)
+            .await
+    }
+
+    /// Write the header, content and padding in one vectored write if
+    /// possible.
     async fn write_to_stream<W: AsyncWrite + Unpin>(
         self, writer: &mut W, content: &[u8],
     ) -> io::Result<()> {
-        let mut buf: Vec<u8> = Vec::new();
-        buf.push(self.version);
-        buf.push(self.r#type as u8);
-        buf.write_u16(self.request_id).await?;
-        buf.write_u16(self.content_length).await?;
-        buf.push(self.padding_length);
-        buf.push(self.reserved);
-
-        writer.write_all(&buf).await?;
-        writer.write_all(content).await?;
-        writer
-            .write_all(&vec![0; self.padding_length as usize])
-            .await?;
+        let header = self.to_buf();
+        let padding = [0; 8];
+        let mut bufs = [
+            IoSlice::new(&header),
+            IoSlice::new(content),
+            IoSlice::new(&padding[..self.padding_length as usize]),
+        ];
+        write_all_vectored(writer, &mut bufs).await
+    }
 
-        Ok(())
+    fn to_buf(&self) -> [u8; HEADER_LEN] {
+        let [request_id_hi, request_id_lo] = self.request_id.to_be_bytes();
+        let [content_length_hi, content_length_lo] = self.content_length.to_be_bytes();
+        [
+            self.version,
+            self.r#type.clone() as u8,
+            request_id_hi,
+            request_id_lo,
+            content_length_hi,
+            content_length_lo,
+            self.padding_length,
+            self.reserved,
+        ]
     }
 
     pub(crate) async fn new_from_stream<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Self> {
@@ -165,14 +220,64 @@ impl Header {
     pub(crate) async fn read_content_from_stream<R: AsyncRead + Unpin>(
         &self, reader: &mut R,
     ) -> io::Result<Vec<u8>> {
+        Ok(self.read_content_and_padding_from_stream(reader).await?.0)
+    }
+
+    /// Like [read_content_from_stream](Header::read_content_from_stream), but
+    /// the padding is returned too.
+    pub(crate) async fn read_content_and_padding_from_stream<R: AsyncRead + Unpin>(
+        &self, reader: &mut R,
+    ) -> io::Result<(Vec<u8>, Vec<u8>)> {
         let mut buf = vec![0; self.content_length as usize];
         reader.read_exact(&mut buf).await?;
         let mut padding_buf = vec![0; self.padding_length as usize];
         reader.read_exact(&mut padding_buf).await?;
-        Ok(buf)
+        Ok((buf, padding_buf))
+    }
+}
+
+/// Handle the management record (request id 0) received among the records
+/// of requests, which doesn't belong to any request, so it's logged and
+/// skipped.
+pub(crate) fn handle_management_record(header: &Header, content: &[u8]) {
+    match header.r#type {
+        RequestType::UnknownType => match parse_unknown_type(content) {
+            Ok(request_type) => {
+                warn!(request_type, "Record type is unsupported by server.");
+            }
+            Err(err) => debug!(?header, ?err, "Skip invalid UnknownType record."),
+        },
+        _ => debug!(?header, ?content, "Skip management record."),
     }
 }
 
+/// Parse the content of `UnknownType` record, return the record type
+/// unsupported by server.
+pub(crate) fn parse_unknown_type(content: &[u8]) -> io::Result<u8> {
+    if content.len() < 8 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "UnknownType content too short",
+        ));
+    }
+    Ok(content[0])
+}
+
+/// Write all the buffers by `write_vectored`, which is the same as `write_all`
+/// if the writer doesn't support vectored writes.
+async fn write_all_vectored<W: AsyncWrite + Unpin>(
+    writer: &mut W, mut bufs: &mut [IoSlice<'_>],
+) -> io::Result<()> {
+    while !bufs.is_empty() {
+        let n = writer.write_vectored(bufs).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        IoSlice::advance_slices(&mut bufs, n);
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone, Copy)]
 #[repr(u16)]
 #[allow(dead_code)]
@@ -182,6 +287,17 @@ pub enum Role {
     Filter = 3,
 }
 
+impl Role {
+    pub(crate) fn from_u16(u: u16) -> Option<Self> {
+        match u {
+            1 => Some(Role::Responder),
+            2 => Some(Role::Authorizer),
+            3 => Some(Role::Filter),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct BeginRequest {
     pub(crate) role: Role,
@@ -198,6 +314,18 @@ impl BeginRequest {
         }
     }
 
+    /// Parse the content of `BeginRequest` record, return the raw role and
+    /// the keep alive flag.
+    pub(crate) fn parse_content(buf: &[u8]) -> io::Result<(u16, bool)> {
+        if buf.len() < 8 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "BeginRequest content too short",
+            ));
+        }
+        Ok((be_buf_to_u16(&buf[0..2]), buf[2] & 1 == 1))
+    }
+
     pub(crate) async fn to_content(&self) -> io::Result<Vec<u8>> {
         let mut buf: Vec<u8> = Vec::new();
         buf.write_u16(self.role as u16).await?;
@@ -251,6 +379,8 @@ pub enum ParamLength {
 }
 
 impl ParamLength {
+    /// The length must not exceed 31 bits, which is checked by
+    /// [Params::check_limits] before sending.
     pub fn new(length: usize) -> Self {
         if length < 128 {
             ParamLength::Short(length as u8)
@@ -271,16 +401,15 @@ impl ParamLength {
     }
 }
 
-#[derive(Debug)]
 pub struct ParamPair<'a> {
     name_length: ParamLength,
     value_length: ParamLength,
-    name_data: Cow<'a, str>,
-    value_data: Cow<'a, str>,
+    name_data: Cow<'a, [u8]>,
+    value_data: Cow<'a, [u8]>,
 }
 
 impl<'a> ParamPair<'a> {
-    fn new(name: Cow<'a, str>, value: Cow<'a, str>) -> Self {
+    fn new(name: Cow<'a, [u8]>, value: Cow<'a, [u8]>) -> Self {
         let name_length = ParamLength::new(name.len());
         let value_length = ParamLength::new(value.len());
         Self {
@@ -296,25 +425,80 @@ impl<'a> ParamPair<'a> {
         writer
             .write_all(&self.value_length.content().await?)
             .await?;
-        writer.write_all(self.name_data.as_bytes()).await?;
-        writer.write_all(self.value_data.as_bytes()).await?;
+        writer.write_all(&self.name_data).await?;
+        writer.write_all(&self.value_data).await?;
         Ok(())
     }
 }
 
+impl Debug for ParamPair<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ParamPair")
+            .field("name_length", &self.name_length)
+            .field("value_length", &self.value_length)
+            .field("name_data", &String::from_utf8_lossy(&self.name_data))
+            .field(
+                "value_data",
+                &params::debug_value(&self.name_data, &self.value_data),
+            )
+            .finish()
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct ParamPairs<'a>(Vec<ParamPair<'a>>);
 
 impl<'a> ParamPairs<'a> {
-    pub(crate) fn new(params: Params<'a>) -> Self {
+    pub(crate) fn new(params: &'a Params<'_>) -> Self {
+        Self(
+            params
+                .iter_bytes()
+                .map(|(name, value)| ParamPair::new(Cow::Borrowed(name), Cow::Borrowed(value)))
+                .collect(),
+        )
+    }
+
+    /// Decode the name-value pairs of `Params` stream content.
+    pub(crate) fn from_content(mut buf: &[u8]) -> io::Result<ParamPairs<'static>> {
+        fn read_length(buf: &mut &[u8]) -> io::Result<usize> {
+            let invalid = || io::Error::new(io::ErrorKind::InvalidData, "truncated param length");
+            let first = *buf.first().ok_or_else(invalid)?;
+            if first >> 7 == 0 {
+                *buf = &buf[1..];
+                Ok(first as usize)
+            } else {
+                let bytes = buf.get(..4).ok_or_else(invalid)?;
+                let length = u32::from_be_bytes([first & 0x7f, bytes[1], bytes[2], bytes[3]]);
+                *buf = &buf[4..];
+                Ok(length as usize)
+            }
+        }
+
         let mut param_pairs = Vec::new();
-        let params: HashMap<Cow<'a, str>, Cow<'a, str>> = params.into();
-        for (name, value) in params.into_iter() {
-            let param_pair = ParamPair::new(name, value);
-            param_pairs.push(param_pair);
+        while !buf.is_empty() {
+            let name_length = read_length(&mut buf)?;
+            let value_length = read_length(&mut buf)?;
+            if buf.len() < name_length + value_length {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "truncated param data",
+                ));
+            }
+            let name = buf[..name_length].to_vec();
+            let value = buf[name_length..name_length + value_length].to_vec();
+            buf = &buf[name_length + value_length..];
+            param_pairs.push(ParamPair::new(name.into(), value.into()));
         }
 
-        Self(param_pairs)
+        Ok(ParamPairs(param_pairs))
+    }
+
+    pub(crate) fn into_params(self) -> Params<'a> {
+        Params::from_pairs(
+            self.0
+                .into_iter()
+                .map(|param_pair| (param_pair.name_data, param_pair.value_data)),
+        )
     }
 
     pub(crate) async fn to_content(&self) -> io::Result<Vec<u8>> {
@@ -342,7 +526,7 @@ impl<'a> DerefMut for ParamPairs<'a> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum ProtocolStatus {
     RequestComplete = 0,
@@ -379,6 +563,23 @@ pub struct EndRequest {
     reserved: [u8; 3],
 }
 
+impl EndRequest {
+    pub(crate) fn new(app_status: u32, protocol_status: ProtocolStatus) -> Self {
+        Self {
+            app_status,
+            protocol_status,
+            reserved: [0; 3],
+        }
+    }
+
+    pub(crate) fn to_content(&self) -> Vec<u8> {
+        let mut buf = self.app_status.to_be_bytes().to_vec();
+        buf.push(self.protocol_status as u8);
+        buf.extend_from_slice(&self.reserved);
+        buf
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct EndRequestRec {
     #[allow(dead_code)]
@@ -387,13 +588,7 @@ pub(crate) struct EndRequestRec {
 }
 
 impl EndRequestRec {
-    pub(crate) async fn from_header<R: AsyncRead + Unpin>(
-        header: &Header, reader: &mut R,
-    ) -> io::Result<Self> {
-        let header = header.clone();
-        let content = &*header.read_content_from_stream(reader).await?;
-        Ok(Self::new_from_buf(header, content))
-    }
+    pub(crate) const CONTENT_LEN: usize = 8;
 
     pub(crate) fn new_from_buf(header: Header, buf: &[u8]) -> Self {
         let app_status = u32::from_be_bytes(<[u8; 4]>::try_from(&buf[0..4]).unwrap());
@@ -0,0 +1,396 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Multiplexed client, send several requests concurrently over a single
+//! connection.
+
+use crate::{
+    client::{get_values, handle_request},
+    id::{AllocRequestId, PooledRequestIdAllocator},
+    limit::{ConcurrencyLimiter, LimitConfig},
+    meta::{handle_management_record, EndRequestRec, Header, RequestType, NULL_REQUEST_ID},
+    request::Request,
+    values::{ValueName, Values},
+    ClientError, ClientResult, Response,
+};
+use std::{
+    collections::HashMap,
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex as StdMutex,
+    },
+};
+use tokio::{
+    io::{split, AsyncRead, AsyncWrite, ReadHalf, WriteHalf},
+    sync::Mutex,
+};
+use tracing::debug;
+
+/// Async client which multiplexes requests over one connection.
+///
+/// Every request gets a distinct request id from the allocator, the records
+/// received are demultiplexed by request id, so several
+/// [execute](MultiplexClient::execute) futures can be awaited concurrently.
+///
+/// The fastcgi server must support multiplexing (`FCGI_MPXS_CONNS`), php-fpm
+/// doesn't, or construct by [discover](MultiplexClient::discover) to adapt to
+/// the server.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{multiplex::MultiplexClient, Params, Request};
+/// use tokio::{io, net::TcpStream};
+///
+/// async fn multiplex() {
+///     let stream = TcpStream::connect(("127.0.0.1", 9000)).await.unwrap();
+///     let client = MultiplexClient::new(stream);
+///
+///     let (first, second) = tokio::join!(
+///         client.execute(Request::new(Params::default(), io::empty())),
+///         client.execute(Request::new(Params::default(), io::empty())),
+///     );
+/// }
+/// ```
+pub struct MultiplexClient<S, A = PooledRequestIdAllocator> {
+    reader: Mutex<ReadHalf<S>>,
+    writer: Mutex<WriteHalf<S>>,
+    slots: StdMutex<HashMap<u16, Slot>>,
+    broken: StdMutex<Option<(io::ErrorKind, String)>>,
+    /// Set if a request is cancelled in the middle of writing or reading.
+    poisoned: AtomicBool,
+    allocator: A,
+    limiter: Option<ConcurrencyLimiter>,
+}
+
+#[derive(Default)]
+struct Slot {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    end: Option<ClientResult<u32>>,
+}
+
+impl<S: AsyncRead + AsyncWrite> MultiplexClient<S> {
+    /// Construct a `MultiplexClient` Object with stream, such as
+    /// `tokio::net::TcpStream` or `tokio::net::UnixStream`, request ids are
+    /// allocated by [PooledRequestIdAllocator].
+    pub fn new(stream: S) -> Self {
+        Self::with_allocator(stream, Default::default())
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> MultiplexClient<S> {
+    /// Like [new](MultiplexClient::new), but query `FCGI_MPXS_CONNS` and
+    /// `FCGI_MAX_REQS` by `FCGI_GET_VALUES` first, the concurrent requests are
+    /// capped by `FCGI_MAX_REQS`, or executed one by one if the server doesn't
+    /// support multiplexing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fastcgi_client::multiplex::MultiplexClient;
+    /// use tokio::net::TcpStream;
+    ///
+    /// async fn discover() {
+    ///     let stream = TcpStream::connect(("127.0.0.1", 9000)).await.unwrap();
+    ///     let client = MultiplexClient::discover(stream).await.unwrap();
+    ///     let max_concurrency = client.max_concurrency();
+    /// }
+    /// ```
+    pub async fn discover(mut stream: S) -> ClientResult<Self> {
+        let values =
+            match get_values(&mut stream, &[ValueName::MpxsConns, ValueName::MaxReqs]).await {
+                Ok(values) => values,
+                Err(ClientError::UnsupportedRecordType { .. }) => Values::default(),
+                Err(err) => return Err(err),
+            };
+        let max_concurrency = match values.mpxs_conns {
+            Some(true) => values.max_reqs.map(|max_reqs| max_reqs.max(1) as usize),
+            _ => Some(1),
+        };
+        debug!(
+            ?values,
+            ?max_concurrency,
+            "Discover the capabilities of server."
+        );
+
+        let mut client = Self::new(stream);
+        client.limiter = max_concurrency.map(|max_concurrency| {
+            ConcurrencyLimiter::new(LimitConfig::default().max_in_flight(max_concurrency))
+        });
+        Ok(client)
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite, A: AllocRequestId> MultiplexClient<S, A> {
+    /// Construct a `MultiplexClient` Object with stream and custom request id
+    /// allocator.
+    pub fn with_allocator(stream: S, allocator: A) -> Self {
+        let (reader, writer) = split(stream);
+        Self {
+            reader: Mutex::new(reader),
+            writer: Mutex::new(writer),
+            slots: Default::default(),
+            broken: Default::default(),
+            poisoned: Default::default(),
+            allocator,
+            limiter: None,
+        }
+    }
+
+    /// Limit the requests in flight by [ConcurrencyLimiter], the limit is
+    /// capped by the one discovered by [discover](MultiplexClient::discover).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fastcgi_client::{
+    ///     limit::{LimitConfig, Overflow},
+    ///     multiplex::MultiplexClient,
+    /// };
+    /// use tokio::net::TcpStream;
+    ///
+    /// async fn limit() {
+    ///     let stream = TcpStream::connect(("127.0.0.1", 9000)).await.unwrap();
+    ///     let client = MultiplexClient::new(stream).limit(
+    ///         LimitConfig::default()
+    ///             .max_in_flight(8)
+    ///             .overflow(Overflow::FailFast),
+    ///     );
+    /// }
+    /// ```
+    pub fn limit(mut self, mut config: LimitConfig) -> Self {
+        if let Some(max_concurrency) = self.max_concurrency() {
+            config.max_in_flight = config.max_in_flight.min(max_concurrency);
+        }
+        self.limiter = Some(ConcurrencyLimiter::new(config));
+        self
+    }
+
+    /// The max count of concurrent requests, the requests exceeded wait for
+    /// the previous ones or are rejected by the
+    /// [Overflow](crate::limit::Overflow), `None` means unlimited.
+    pub fn max_concurrency(&self) -> Option<usize> {
+        self.limiter
+            .as_ref()
+            .map(|limiter| limiter.config().max_in_flight)
+    }
+
+    /// Send request and receive response from fastcgi server, can be called
+    /// concurrently.
+    pub async fn execute<I: AsyncRead + Unpin>(
+        &self, request: Request<'_, I>,
+    ) -> ClientResult<Response> {
+        match &self.limiter {
+            Some(limiter) => limiter.call(self.inner_execute(request)).await,
+            None => self.inner_execute(request).await,
+        }
+    }
+
+    async fn inner_execute<I: AsyncRead + Unpin>(
+        &self, mut request: Request<'_, I>,
+    ) -> ClientResult<Response> {
+        let id = self.allocator.alloc()?;
+        let _guard = SlotGuard::new(self, id);
+
+        {
+            let mut writer = self.writer.lock().await;
+            if self.is_poisoned() {
+                return Err(ClientError::ConnectionPoisoned);
+            }
+            let guard = PoisonGuard::new(&self.poisoned);
+            let result = handle_request(&mut *writer, id, true, &mut request).await;
+            if result.as_ref().map_or_else(ClientError::is_clean, |_| true) {
+                guard.disarm();
+            }
+            result?;
+        }
+
+        loop {
+            if let Some(result) = self.take_finished(id) {
+                return result;
+            }
+
+            let mut reader = self.reader.lock().await;
+
+            // The response maybe received by other task during waiting for the lock.
+            if let Some(result) = self.take_finished(id) {
+                return result;
+            }
+
+            let guard = PoisonGuard::new(&self.poisoned);
+            let result = self.read_record(&mut reader).await;
+            guard.disarm();
+            if let Err(err) = result {
+                *self.broken.lock().unwrap() = Some((err.kind(), err.to_string()));
+                return Err(self.partial_response(id, err));
+            }
+        }
+    }
+
+    /// Attach the output of request received before to the io error.
+    fn partial_response(&self, id: u16, err: io::Error) -> ClientError {
+        match self.slots.lock().unwrap().get_mut(&id) {
+            Some(slot) => ClientError::partial_response(
+                err,
+                std::mem::take(&mut slot.stdout),
+                std::mem::take(&mut slot.stderr),
+            ),
+            None => err.into(),
+        }
+    }
+
+    /// Whether a request was cancelled in the middle of writing or reading,
+    /// then the connection can't be used, and the requests fail with
+    /// [ClientError::ConnectionPoisoned].
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Acquire)
+    }
+
+    fn take_finished(&self, id: u16) -> Option<ClientResult<Response>> {
+        if self.is_poisoned() {
+            return Some(Err(ClientError::ConnectionPoisoned));
+        }
+        if let Some((kind, message)) = &*self.broken.lock().unwrap() {
+            return Some(Err(
+                self.partial_response(id, io::Error::new(*kind, message.clone()))
+            ));
+        }
+
+        let mut slots = self.slots.lock().unwrap();
+        let slot = slots.get_mut(&id)?;
+        let end = slot.end.take()?;
+        Some(end.map(|app_status| {
+            let mut response = Response {
+                app_status,
+                ..Default::default()
+            };
+            let stdout = std::mem::take(&mut slot.stdout);
+            let stderr = std::mem::take(&mut slot.stderr);
+            response.stdout = if stdout.is_empty() {
+                None
+            } else {
+                Some(stdout)
+            };
+            response.stderr = if stderr.is_empty() {
+                None
+            } else {
+                Some(stderr)
+            };
+            response
+        }))
+    }
+
+    async fn read_record(&self, reader: &mut ReadHalf<S>) -> io::Result<()> {
+        let header = Header::new_from_stream(reader).await?;
+        let id = header.request_id;
+        debug!(id, ?header, "Receive from stream.");
+
+        if id == NULL_REQUEST_ID {
+            let content = header.read_content_from_stream(reader).await?;
+            handle_management_record(&header, &content);
+            return Ok(());
+        }
+
+        match header.r#type.clone() {
+            RequestType::EndRequest => {
+                let content = header.read_content_from_stream(reader).await?;
+                if content.len() < EndRequestRec::CONTENT_LEN {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "EndRequest content too short",
+                    ));
+                }
+                let end_request_rec = EndRequestRec::new_from_buf(header, &content);
+                debug!(id, ?end_request_rec, "Receive from stream.");
+
+                if let Some(slot) = self.slots.lock().unwrap().get_mut(&id) {
+                    let app_status = end_request_rec.end_request.app_status;
+                    slot.end = Some(
+                        end_request_rec
+                            .end_request
+                            .protocol_status
+                            .convert_to_client_result(app_status)
+                            .map(|_| app_status),
+                    );
+                }
+            }
+            r#type => {
+                let content = header.read_content_from_stream(reader).await?;
+                let mut slots = self.slots.lock().unwrap();
+                match (slots.get_mut(&id), r#type) {
+                    (Some(slot), RequestType::Stdout) => slot.stdout.extend(content),
+                    (Some(slot), RequestType::Stderr) => slot.stderr.extend(content),
+                    (Some(slot), request_type) => {
+                        slot.end = Some(Err(ClientError::UnknownRequestType { request_type }));
+                    }
+                    (None, _) => {
+                        debug!(id, "Discard record of unknown request id.");
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Poison the connection if dropped before disarmed, such as the request
+/// future is cancelled in the middle of writing or reading.
+struct PoisonGuard<'a> {
+    poisoned: Option<&'a AtomicBool>,
+}
+
+impl<'a> PoisonGuard<'a> {
+    fn new(poisoned: &'a AtomicBool) -> Self {
+        Self {
+            poisoned: Some(poisoned),
+        }
+    }
+
+    fn disarm(mut self) {
+        self.poisoned = None;
+    }
+}
+
+impl Drop for PoisonGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(poisoned) = self.poisoned {
+            debug!("Connection poisoned.");
+            poisoned.store(true, Ordering::Release);
+        }
+    }
+}
+
+/// Register the slot of request id, remove the slot and release the id when
+/// dropped.
+struct SlotGuard<'a, S, A: AllocRequestId> {
+    client: &'a MultiplexClient<S, A>,
+    id: u16,
+}
+
+impl<'a, S, A: AllocRequestId> SlotGuard<'a, S, A> {
+    fn new(client: &'a MultiplexClient<S, A>, id: u16) -> Self {
+        client.slots.lock().unwrap().insert(id, Slot::default());
+        Self { client, id }
+    }
+}
+
+impl<S, A: AllocRequestId> Drop for SlotGuard<'_, S, A> {
+    fn drop(&mut self) {
+        self.client.slots.lock().unwrap().remove(&self.id);
+        self.client.allocator.release(self.id);
+    }
+}
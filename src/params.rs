@@ -12,15 +12,177 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::{ClientError, ClientResult};
+use indexmap::IndexMap;
 use std::{
     borrow::Cow,
+    cmp::min,
     collections::HashMap,
-    ops::{Deref, DerefMut},
+    fmt::{self, Debug},
+    ops::Index,
+    str,
+    sync::RwLock,
 };
 
+/// Patterns of the param names which values are redacted in `Debug` output
+/// by default, such as credentials and cookies.
+pub const DEFAULT_REDACTED_PATTERNS: &[&str] = &[
+    "HTTP_AUTHORIZATION",
+    "HTTP_PROXY_AUTHORIZATION",
+    "HTTP_COOKIE",
+    "PHP_AUTH_PW",
+    "*PASSWORD*",
+    "*SECRET*",
+    "*TOKEN*",
+];
+
+const REDACTED: &str = "<redacted>";
+
+/// `None` means [DEFAULT_REDACTED_PATTERNS].
+static REDACTED_PATTERNS: RwLock<Option<Vec<String>>> = RwLock::new(None);
+
+/// Replace the patterns of the param names which values are redacted in the
+/// `Debug` output of params (so in the logs of `tracing`), default is
+/// [DEFAULT_REDACTED_PATTERNS].
+///
+/// The patterns are matched case-insensitively, and `*` matches any
+/// characters, pass empty patterns to disable the redaction.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::params::{set_redacted_patterns, DEFAULT_REDACTED_PATTERNS};
+///
+/// set_redacted_patterns(
+///     DEFAULT_REDACTED_PATTERNS
+///         .iter()
+///         .copied()
+///         .chain(["HTTP_X_API_KEY"]),
+/// );
+/// ```
+pub fn set_redacted_patterns<I, S>(patterns: I)
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    let patterns = patterns.into_iter().map(Into::into).collect();
+    *REDACTED_PATTERNS
+        .write()
+        .unwrap_or_else(|err| err.into_inner()) = Some(patterns);
+}
+
+/// Whether the value of param named `name` should be redacted.
+pub(crate) fn is_redacted(name: &[u8]) -> bool {
+    let patterns = REDACTED_PATTERNS
+        .read()
+        .unwrap_or_else(|err| err.into_inner());
+    match &*patterns {
+        Some(patterns) => patterns
+            .iter()
+            .any(|pattern| glob_match(pattern.as_bytes(), name)),
+        None => DEFAULT_REDACTED_PATTERNS
+            .iter()
+            .any(|pattern| glob_match(pattern.as_bytes(), name)),
+    }
+}
+
+/// Match the name with the pattern case-insensitively, `*` matches any
+/// characters.
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|i| glob_match(rest, &name[i..])),
+        Some((c, rest)) => match name.split_first() {
+            Some((n, name)) => c.eq_ignore_ascii_case(n) && glob_match(rest, name),
+            None => false,
+        },
+    }
+}
+
+/// Value shown in `Debug` output, redacted if the name is matched.
+pub(crate) fn debug_value<'b>(name: &[u8], value: &'b [u8]) -> Cow<'b, str> {
+    if is_redacted(name) {
+        Cow::Borrowed(REDACTED)
+    } else {
+        String::from_utf8_lossy(value)
+    }
+}
+
+/// Max length of param name or value, which is encoded in 31 bits.
+pub const MAX_PARAM_LENGTH: usize = 0x7fff_ffff;
+
+/// Limits of params size, checked before sending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ParamsLimits {
+    /// Max length of each param value, can't exceed [MAX_PARAM_LENGTH].
+    pub max_value_length: usize,
+    /// Max length of the encoded params in total.
+    pub max_total_length: usize,
+}
+
+impl Default for ParamsLimits {
+    fn default() -> Self {
+        Self {
+            max_value_length: MAX_PARAM_LENGTH,
+            max_total_length: usize::MAX,
+        }
+    }
+}
+
+impl ParamsLimits {
+    pub fn max_value_length(mut self, max_value_length: usize) -> Self {
+        self.max_value_length = max_value_length;
+        self
+    }
+
+    pub fn max_total_length(mut self, max_total_length: usize) -> Self {
+        self.max_total_length = max_total_length;
+        self
+    }
+}
+
 /// Fastcgi params, please reference to nginx-php-fpm fastcgi_params.
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Params<'a>(HashMap<Cow<'a, str>, Cow<'a, str>>);
+///
+/// The params are kept in insertion order, so are sent in a stable order.
+/// The values are UTF-8 usually, but can be arbitrary bytes by
+/// [binary_param](Params::binary_param), such as binary-safe headers and
+/// locale-encoded paths.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Params<'a> {
+    params: IndexMap<Cow<'a, [u8]>, ParamValue<'a>>,
+}
+
+/// Value of param, the UTF-8 one is kept as `str`, so it can be borrowed by
+/// [Params::get].
+#[derive(Clone, PartialEq, Eq)]
+enum ParamValue<'a> {
+    Text(Cow<'a, str>),
+    Binary(Cow<'a, [u8]>),
+}
+
+impl<'a> ParamValue<'a> {
+    fn from_bytes(value: Cow<'a, [u8]>) -> Self {
+        match bytes_to_str(value) {
+            Ok(value) => ParamValue::Text(value),
+            Err(value) => ParamValue::Binary(value),
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            ParamValue::Text(value) => value.as_bytes(),
+            ParamValue::Binary(value) => value,
+        }
+    }
+
+    fn into_bytes(self) -> Cow<'a, [u8]> {
+        match self {
+            ParamValue::Text(value) => str_to_bytes(value),
+            ParamValue::Binary(value) => value,
+        }
+    }
+}
 
 impl<'a> Params<'a> {
     #[inline]
@@ -124,33 +286,380 @@ impl<'a> Params<'a> {
         self.insert("CONTENT_LENGTH".into(), content_length.to_string().into());
         self
     }
+
+    #[inline]
+    pub fn http_host<S: Into<Cow<'a, str>>>(mut self, http_host: S) -> Self {
+        self.insert("HTTP_HOST".into(), http_host.into());
+        self
+    }
+
+    /// Inject the trace context of the current span, see
+    /// [propagation::inject](crate::propagation::inject).
+    #[cfg(feature = "opentelemetry")]
+    pub fn trace_context(mut self) -> Self {
+        crate::propagation::inject(&mut self);
+        self
+    }
+
+    /// Construct the params for front controller frameworks, such as
+    /// WordPress, Laravel and Symfony, which route all requests to
+    /// `index.php` under the document root, like nginx `try_files $uri
+    /// /index.php?$query_string`.
+    ///
+    /// The `request_uri` is the original uri of http request, the `PATH_INFO`
+    /// is set if the uri is like `/index.php/foo`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fastcgi_client::Params;
+    ///
+    /// let params = Params::for_front_controller("/var/www/public", "/posts/1?page=2")
+    ///     .request_method("GET")
+    ///     .http_host("example.com")
+    ///     .server_name("example.com");
+    ///
+    /// assert_eq!(params["SCRIPT_FILENAME"], "/var/www/public/index.php");
+    /// assert_eq!(params["SCRIPT_NAME"], "/index.php");
+    /// assert_eq!(params["QUERY_STRING"], "page=2");
+    /// ```
+    pub fn for_front_controller<D, U>(document_root: D, request_uri: U) -> Self
+    where
+        D: Into<Cow<'a, str>>,
+        U: Into<Cow<'a, str>>,
+    {
+        const INDEX: &str = "/index.php";
+
+        let document_root = document_root.into();
+        let request_uri = request_uri.into();
+        let (path, query) = request_uri.split_once('?').unwrap_or((&request_uri, ""));
+        let path_info = path
+            .strip_prefix(INDEX)
+            .filter(|path_info| path_info.starts_with('/'))
+            .map(ToOwned::to_owned);
+        let query = query.to_owned();
+
+        let mut params = Params::default()
+            .script_filename(format!("{}{}", document_root.trim_end_matches('/'), INDEX))
+            .script_name(INDEX)
+            .document_root(document_root)
+            .document_uri(INDEX)
+            .query_string(query)
+            .request_uri(request_uri);
+        if let Some(path_info) = path_info {
+            params.insert("PATH_INFO".into(), path_info.into());
+        }
+        params
+    }
+
+    /// Check the params before sending, return
+    /// [ClientError::InvalidParam] if the
+    /// name is empty or contains NUL bytes or `=`, or the value contains NUL
+    /// bytes, so unexpected fastcgi variables can't be smuggled.
+    pub fn validate(&self) -> ClientResult<()> {
+        for (name, value) in self.iter_bytes() {
+            let reason = if name.is_empty() {
+                "empty name"
+            } else if name.contains(&0) {
+                "NUL byte in name"
+            } else if name.contains(&b'=') {
+                "'=' in name"
+            } else if value.contains(&0) {
+                "NUL byte in value"
+            } else {
+                continue;
+            };
+            return Err(ClientError::InvalidParam {
+                name: String::from_utf8_lossy(name).into_owned(),
+                reason: reason.to_owned(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Check the params against the limits, return
+    /// [ClientError::ParamsTooLarge] if
+    /// any name or value, or the encoded params in total, is too large.
+    pub fn check_limits(&self, limits: &ParamsLimits) -> ClientResult<()> {
+        let too_large = |name: Option<&[u8]>, length, limit| ClientError::ParamsTooLarge {
+            name: name.map(|name| String::from_utf8_lossy(name).into_owned()),
+            length,
+            limit,
+        };
+
+        let mut total = 0usize;
+        for (name, value) in self.iter_bytes() {
+            let max_value_length = min(limits.max_value_length, MAX_PARAM_LENGTH);
+            if name.len() > MAX_PARAM_LENGTH {
+                return Err(too_large(Some(name), name.len(), MAX_PARAM_LENGTH));
+            }
+            if value.len() > max_value_length {
+                return Err(too_large(Some(name), value.len(), max_value_length));
+            }
+            total = total.saturating_add(
+                encoded_length(name.len()) + encoded_length(value.len()) + name.len() + value.len(),
+            );
+        }
+        if total > limits.max_total_length {
+            return Err(too_large(None, total, limits.max_total_length));
+        }
+        Ok(())
+    }
+
+    /// Insert the UTF-8 param, the param with the same name is replaced in
+    /// place, and its value is returned.
+    pub fn insert(&mut self, name: Cow<'a, str>, value: Cow<'a, str>) -> Option<Cow<'a, [u8]>> {
+        self.params
+            .insert(str_to_bytes(name), ParamValue::Text(value))
+            .map(ParamValue::into_bytes)
+    }
+
+    /// Insert the param which name or value may not be valid UTF-8, the
+    /// param with the same name is replaced in place.
+    #[inline]
+    pub fn binary_param<N, V>(mut self, name: N, value: V) -> Self
+    where
+        N: Into<Cow<'a, [u8]>>,
+        V: Into<Cow<'a, [u8]>>,
+    {
+        self.params
+            .insert(name.into(), ParamValue::from_bytes(value.into()));
+        self
+    }
+
+    /// The value of param, `None` if not found or not valid UTF-8, see
+    /// [get_bytes](Params::get_bytes).
+    pub fn get(&self, name: &str) -> Option<&Cow<'a, str>> {
+        match self.params.get(name.as_bytes())? {
+            ParamValue::Text(value) => Some(value),
+            ParamValue::Binary(_) => None,
+        }
+    }
+
+    /// The value of param as bytes, even if not valid UTF-8.
+    pub fn get_bytes(&self, name: impl AsRef<[u8]>) -> Option<&[u8]> {
+        self.params.get(name.as_ref()).map(ParamValue::as_bytes)
+    }
+
+    pub fn contains_key(&self, name: impl AsRef<[u8]>) -> bool {
+        self.params.contains_key(name.as_ref())
+    }
+
+    /// Remove the param and return its value, the following params are
+    /// shifted, so the order is kept.
+    pub fn shift_remove(&mut self, name: impl AsRef<[u8]>) -> Option<Cow<'a, [u8]>> {
+        self.params
+            .shift_remove(name.as_ref())
+            .map(ParamValue::into_bytes)
+    }
+
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.params.clear();
+    }
+
+    /// Iterate the params which name and value are valid UTF-8 in order, see
+    /// [iter_bytes](Params::iter_bytes).
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.params.iter().filter_map(|(name, value)| match value {
+            ParamValue::Text(value) => Some((str::from_utf8(name).ok()?, &**value)),
+            ParamValue::Binary(_) => None,
+        })
+    }
+
+    /// Iterate all params as bytes in order.
+    pub fn iter_bytes(&self) -> impl Iterator<Item = (&[u8], &[u8])> {
+        self.params
+            .iter()
+            .map(|(name, value)| (&**name, value.as_bytes()))
+    }
+
+    /// Convert into the `'static` version by cloning the borrowed names and
+    /// values, so the params can be sent to other tasks.
+    pub fn into_owned(self) -> Params<'static> {
+        Params {
+            params: self
+                .params
+                .into_iter()
+                .map(|(name, value)| {
+                    let value = match value {
+                        ParamValue::Text(value) => ParamValue::Text(Cow::Owned(value.into_owned())),
+                        ParamValue::Binary(value) => {
+                            ParamValue::Binary(Cow::Owned(value.into_owned()))
+                        }
+                    };
+                    (Cow::Owned(name.into_owned()), value)
+                })
+                .collect(),
+        }
+    }
+
+    /// Collect the params received from stream.
+    pub(crate) fn from_pairs(
+        pairs: impl IntoIterator<Item = (Cow<'a, [u8]>, Cow<'a, [u8]>)>,
+    ) -> Self {
+        Self {
+            params: pairs
+                .into_iter()
+                .map(|(name, value)| (name, ParamValue::from_bytes(value)))
+                .collect(),
+        }
+    }
+}
+
+impl Debug for Params<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        struct Map<'b, 'a>(&'b Params<'a>);
+
+        impl Debug for Map<'_, '_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_map()
+                    .entries(self.0.iter_bytes().map(|(name, value)| {
+                        (String::from_utf8_lossy(name), debug_value(name, value))
+                    }))
+                    .finish()
+            }
+        }
+
+        f.debug_tuple("Params").field(&Map(self)).finish()
+    }
 }
 
 impl<'a> Default for Params<'a> {
     fn default() -> Self {
-        Params(HashMap::new())
-            .gateway_interface("FastCGI/1.0")
-            .server_software("fastcgi-client-rs")
-            .server_protocol("HTTP/1.1")
+        Params {
+            params: IndexMap::new(),
+        }
+        .gateway_interface("FastCGI/1.0")
+        .server_software("fastcgi-client-rs")
+        .server_protocol("HTTP/1.1")
     }
 }
 
-impl<'a> Deref for Params<'a> {
-    type Target = HashMap<Cow<'a, str>, Cow<'a, str>>;
+impl<'a> FromIterator<(Cow<'a, str>, Cow<'a, str>)> for Params<'a> {
+    fn from_iter<T: IntoIterator<Item = (Cow<'a, str>, Cow<'a, str>)>>(iter: T) -> Self {
+        Params {
+            params: iter
+                .into_iter()
+                .map(|(name, value)| (str_to_bytes(name), ParamValue::Text(value)))
+                .collect(),
+        }
+    }
+}
+
+/// Panics if the param isn't found or not valid UTF-8, see [Params::get].
+impl<'a> Index<&str> for Params<'a> {
+    type Output = Cow<'a, str>;
+
+    fn index(&self, name: &str) -> &Self::Output {
+        self.get(name)
+            .unwrap_or_else(|| panic!("param `{}` not found or not valid UTF-8", name))
+    }
+}
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
+impl<'a> From<Params<'a>> for Cow<'a, Params<'a>> {
+    fn from(params: Params<'a>) -> Self {
+        Cow::Owned(params)
     }
 }
 
-impl<'a> DerefMut for Params<'a> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+impl<'a> From<&'a Params<'a>> for Cow<'a, Params<'a>> {
+    fn from(params: &'a Params<'a>) -> Self {
+        Cow::Borrowed(params)
     }
 }
 
+/// The params which name or value isn't valid UTF-8 are skipped.
 impl<'a> From<Params<'a>> for HashMap<Cow<'a, str>, Cow<'a, str>> {
     fn from(params: Params<'a>) -> Self {
-        params.0
+        params
+            .params
+            .into_iter()
+            .filter_map(|(name, value)| match (bytes_to_str(name), value) {
+                (Ok(name), ParamValue::Text(value)) => Some((name, value)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Construct [Params] tersely, mixing the well-known setters (`setter:
+/// value`) and custom keys (`"NAME" => value`), based on [Params::default].
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::params;
+///
+/// let params = params! {
+///     request_method: "GET",
+///     script_name: "/index.php",
+///     content_length: 0,
+///     "HTTP_HOST" => "example.com",
+///     "HTTP_X_REQUEST_ID" => format!("{}", 1),
+/// };
+///
+/// assert_eq!(params["REQUEST_METHOD"], "GET");
+/// assert_eq!(params["HTTP_X_REQUEST_ID"], "1");
+/// ```
+#[macro_export]
+macro_rules! params {
+    ($($tt:tt)*) => {{
+        #[allow(unused_mut)]
+        let mut params = $crate::Params::default();
+        $crate::__params_inner!(params; $($tt)*);
+        params
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __params_inner {
+    ($params:ident;) => {};
+    ($params:ident; $setter:ident : $value:expr $(, $($rest:tt)*)?) => {
+        $params = $params.$setter($value);
+        $crate::__params_inner!($params; $($($rest)*)?);
+    };
+    ($params:ident; $name:expr => $value:expr $(, $($rest:tt)*)?) => {
+        $params.insert(
+            ::std::borrow::Cow::from($name),
+            ::std::borrow::Cow::from($value),
+        );
+        $crate::__params_inner!($params; $($($rest)*)?);
+    };
+}
+
+/// Length of the encoded length, see
+/// [fastcgi spec section 3.4](https://fastcgi-archives.github.io/FastCGI_Specification.html#S3.4).
+fn encoded_length(length: usize) -> usize {
+    if length < 128 {
+        1
+    } else {
+        4
+    }
+}
+
+fn str_to_bytes(s: Cow<'_, str>) -> Cow<'_, [u8]> {
+    match s {
+        Cow::Borrowed(s) => Cow::Borrowed(s.as_bytes()),
+        Cow::Owned(s) => Cow::Owned(s.into_bytes()),
+    }
+}
+
+fn bytes_to_str(b: Cow<'_, [u8]>) -> Result<Cow<'_, str>, Cow<'_, [u8]>> {
+    match b {
+        Cow::Borrowed(b) => str::from_utf8(b)
+            .map(Cow::Borrowed)
+            .map_err(|_| Cow::Borrowed(b)),
+        Cow::Owned(b) => String::from_utf8(b)
+            .map(Cow::Owned)
+            .map_err(|err| Cow::Owned(err.into_bytes())),
     }
 }
@@ -0,0 +1,456 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Pool of keep alive connections, the connections are reused across
+//! requests and established by [Connect] on demand.
+
+#[cfg(feature = "bb8")]
+pub mod bb8;
+#[cfg(feature = "deadpool")]
+pub mod deadpool;
+
+use crate::{
+    breaker::{BreakerConfig, CircuitBreaker},
+    conn::KeepAlive,
+    connect::{self, Connect},
+    limit::{RateConfig, RateLimiter},
+    logging::{LogConfig, RequestLogger},
+    request::Request,
+    Client, ClientError, ClientResult, Response,
+};
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{io::AsyncRead, sync::Notify, time};
+use tracing::debug;
+
+/// Config of [Pool].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct PoolConfig {
+    /// The max count of idle connections kept in pool, the exceeded
+    /// connections are closed after used.
+    pub max_idle: usize,
+    /// Reject the requests fast after the server failed continuously, see
+    /// [CircuitBreaker].
+    pub breaker: Option<BreakerConfig>,
+    /// Limit the rate of requests to the server, see [RateLimiter].
+    pub rate_limit: Option<RateConfig>,
+    /// Retire the connection after serving the count of requests, like
+    /// php-fpm `pm.max_requests`, so the connection isn't closed by server
+    /// unexpectedly.
+    pub max_requests: Option<usize>,
+    /// Retire the connection after established for the duration.
+    pub max_lifetime: Option<Duration>,
+    /// Timeout of establishing a new connection, exceeded then the request
+    /// fails with [ClientError::ConnectTimeout], distinct from the timeout
+    /// of request.
+    pub connect_timeout: Option<Duration>,
+    /// Close the connection after idle in pool for the duration, should be
+    /// shorter than the idle timeout of server or the proxies in between.
+    pub idle_timeout: Option<Duration>,
+    /// Log every request, see [RequestLogger], the upstream is the
+    /// [address](Connect::address) of connector.
+    pub log: Option<LogConfig>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_idle: 16,
+            breaker: None,
+            rate_limit: None,
+            max_requests: None,
+            max_lifetime: None,
+            connect_timeout: None,
+            idle_timeout: None,
+            log: None,
+        }
+    }
+}
+
+impl PoolConfig {
+    pub fn max_idle(mut self, max_idle: usize) -> Self {
+        self.max_idle = max_idle;
+        self
+    }
+
+    pub fn breaker(mut self, breaker: BreakerConfig) -> Self {
+        self.breaker = Some(breaker);
+        self
+    }
+
+    pub fn rate_limit(mut self, rate_limit: RateConfig) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
+    pub fn max_requests(mut self, max_requests: usize) -> Self {
+        self.max_requests = Some(max_requests);
+        self
+    }
+
+    pub fn max_lifetime(mut self, max_lifetime: Duration) -> Self {
+        self.max_lifetime = Some(max_lifetime);
+        self
+    }
+
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    pub fn log(mut self, log: LogConfig) -> Self {
+        self.log = Some(log);
+        self
+    }
+}
+
+/// Pool of keep alive clients, which is `Clone`, the clones share the same
+/// connections.
+///
+/// An idle connection is taken for each request, or a new one is established
+/// if none, and is put back after the response is received, the connection
+/// is closed instead if the request failed. The idle connection closed by
+/// server is dropped before reused, and the request is retried once on a new
+/// connection if none of it is sent before the closing found.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{connect::TcpConnector, pool::Pool, Params, Request};
+/// use tokio::io;
+///
+/// async fn pool() {
+///     let pool = Pool::new(TcpConnector::new("127.0.0.1:9000"));
+///
+///     for _ in 0..3 {
+///         let output = pool
+///             .execute(Request::new(Params::default(), io::empty()))
+///             .await
+///             .unwrap();
+///     }
+/// }
+/// ```
+pub struct Pool<C: Connect> {
+    inner: Arc<Inner<C>>,
+}
+
+struct Inner<C: Connect> {
+    connector: C,
+    config: PoolConfig,
+    idle: Mutex<Vec<Conn<C::Stream>>>,
+    in_flight: AtomicUsize,
+    drained: Notify,
+    closed: AtomicBool,
+    breaker: Option<CircuitBreaker>,
+    rate_limiter: Option<RateLimiter>,
+    logger: Option<RequestLogger>,
+}
+
+impl<C: Connect> Pool<C> {
+    /// Construct a `Pool` Object with the connector and default config.
+    pub fn new(connector: C) -> Self {
+        Self::with_config(connector, PoolConfig::default())
+    }
+
+    pub fn with_config(connector: C, config: PoolConfig) -> Self {
+        let logger = config.log.clone().map(|log| {
+            let logger = RequestLogger::new(log);
+            match connector.address() {
+                Some(upstream) => logger.upstream(upstream),
+                None => logger,
+            }
+        });
+        Self {
+            inner: Arc::new(Inner {
+                connector,
+                breaker: config.breaker.clone().map(CircuitBreaker::new),
+                rate_limiter: config.rate_limit.clone().map(RateLimiter::new),
+                logger,
+                config,
+                idle: Default::default(),
+                in_flight: Default::default(),
+                drained: Default::default(),
+                closed: Default::default(),
+            }),
+        }
+    }
+
+    pub fn connector(&self) -> &C {
+        &self.inner.connector
+    }
+
+    pub fn config(&self) -> &PoolConfig {
+        &self.inner.config
+    }
+
+    /// The count of idle connections.
+    pub fn idle(&self) -> usize {
+        self.inner.idle.lock().unwrap().len()
+    }
+
+    /// The count of requests being executed.
+    pub fn in_flight(&self) -> usize {
+        self.inner.in_flight.load(Ordering::Acquire)
+    }
+
+    /// Whether the circuit breaker is open, so the requests are rejected.
+    pub fn is_open(&self) -> bool {
+        self.inner
+            .breaker
+            .as_ref()
+            .is_some_and(|breaker| breaker.is_open())
+    }
+
+    /// Whether the pool is shut down by [shutdown](Pool::shutdown).
+    pub fn is_closed(&self) -> bool {
+        self.inner.closed.load(Ordering::Acquire)
+    }
+
+    /// Send request and receive response from fastcgi server by a pooled
+    /// connection.
+    pub async fn execute<I: AsyncRead + Unpin>(
+        &self, request: Request<'_, I>,
+    ) -> ClientResult<Response> {
+        match &self.inner.logger {
+            Some(logger) => {
+                logger
+                    .call(request, |request| self.guarded_execute(request))
+                    .await
+            }
+            None => self.guarded_execute(request).await,
+        }
+    }
+
+    async fn guarded_execute<I: AsyncRead + Unpin>(
+        &self, request: Request<'_, I>,
+    ) -> ClientResult<Response> {
+        if self.is_closed() {
+            return Err(ClientError::ClientClosed);
+        }
+        let execute = async {
+            match &self.inner.breaker {
+                Some(breaker) => breaker.call(self.inner_execute(request)).await,
+                None => self.inner_execute(request).await,
+            }
+        };
+        match &self.inner.rate_limiter {
+            Some(rate_limiter) => rate_limiter.call(execute).await,
+            None => execute.await,
+        }
+    }
+
+    async fn inner_execute<I: AsyncRead + Unpin>(
+        &self, request: Request<'_, I>,
+    ) -> ClientResult<Response> {
+        let _in_flight = InFlight::new(&self.inner.in_flight, &self.inner.drained);
+
+        let mut conn = self.take().await?;
+        let reusable = request.keep_alive.unwrap_or(true);
+        let upstream = self.inner.connector.address();
+        let (result, unsent) = conn
+            .client
+            .execute_unsent(request, upstream.as_deref())
+            .await;
+        let response = match unsent {
+            // The reused connection was closed by server before the request
+            // sent, so nothing reached the app, retry once on a new
+            // connection.
+            Some(request) if conn.requests > 0 => {
+                debug!(requests = conn.requests, "Retry on new connection.");
+                conn = self.connect().await?;
+                conn.client
+                    .execute_upstream(request, upstream.as_deref())
+                    .await?
+            }
+            _ => result?,
+        };
+        conn.requests += 1;
+        if reusable {
+            self.put(conn).await;
+        }
+        Ok(response)
+    }
+
+    /// Stop accepting requests, then wait for the requests in flight to
+    /// complete within the deadline, and close the idle connections.
+    ///
+    /// Returns [ClientError::RequestTimeout] if the requests in flight aren't
+    /// completed before deadline, the connections of them are closed after
+    /// completed anyway.
+    pub async fn shutdown(&self, deadline: Duration) -> ClientResult<()> {
+        self.inner.closed.store(true, Ordering::Release);
+        debug!(in_flight = self.in_flight(), "Shutdown pool.");
+
+        let drained = time::timeout(deadline, async {
+            while self.in_flight() > 0 {
+                self.inner.drained.notified().await;
+            }
+        })
+        .await;
+
+        let idle = std::mem::take(&mut *self.inner.idle.lock().unwrap());
+        for conn in idle {
+            let _ = conn.client.close().await;
+        }
+
+        drained.map_err(|_| ClientError::RequestTimeout)
+    }
+
+    async fn take(&self) -> ClientResult<Conn<C::Stream>> {
+        loop {
+            let conn = self.inner.idle.lock().unwrap().pop();
+            let Some(mut conn) = conn else {
+                break;
+            };
+            if self.is_expired(&conn) || self.is_idle_expired(&conn) {
+                debug!(requests = conn.requests, "Retire expired connection.");
+                let _ = conn.client.close().await;
+            } else if conn.client.is_stale() {
+                debug!(
+                    requests = conn.requests,
+                    "Drop connection closed by server."
+                );
+            } else {
+                return Ok(conn);
+            }
+        }
+
+        self.connect().await
+    }
+
+    async fn connect(&self) -> ClientResult<Conn<C::Stream>> {
+        debug!("Establish new connection for pool.");
+        let upstream = self.inner.connector.address().unwrap_or_default();
+        let stream = connect::with_timeout(
+            self.inner.config.connect_timeout,
+            self.inner.connector.connect(),
+            &upstream,
+        )
+        .await
+        .map_err(ClientError::connect)?;
+        Ok(Conn {
+            client: Client::new_keep_alive(stream),
+            created: Instant::now(),
+            idle_since: Instant::now(),
+            requests: 0,
+        })
+    }
+
+    /// Put the connection back to idle, or close it if the pool is closed,
+    /// full or the connection is expired, the connections idle for too long
+    /// are closed as well.
+    async fn put(&self, mut conn: Conn<C::Stream>) {
+        conn.idle_since = Instant::now();
+        let mut closing = Vec::new();
+        if self.is_closed() || self.is_expired(&conn) {
+            closing.push(conn);
+        } else {
+            let mut idle = self.inner.idle.lock().unwrap();
+            // The oldest connections are at the front, as the latest ones are
+            // taken first.
+            let expired = idle
+                .iter()
+                .take_while(|conn| self.is_idle_expired(conn))
+                .count();
+            closing.extend(idle.drain(..expired));
+            if idle.len() < self.inner.config.max_idle {
+                idle.push(conn);
+            } else {
+                closing.push(conn);
+            }
+        }
+        for conn in closing {
+            debug!(requests = conn.requests, "Close connection.");
+            let _ = conn.client.close().await;
+        }
+    }
+
+    fn is_expired(&self, conn: &Conn<C::Stream>) -> bool {
+        let config = &self.inner.config;
+        config
+            .max_requests
+            .is_some_and(|max_requests| conn.requests >= max_requests)
+            || config
+                .max_lifetime
+                .is_some_and(|max_lifetime| conn.created.elapsed() >= max_lifetime)
+    }
+
+    fn is_idle_expired(&self, conn: &Conn<C::Stream>) -> bool {
+        self.inner
+            .config
+            .idle_timeout
+            .is_some_and(|idle_timeout| conn.idle_since.elapsed() >= idle_timeout)
+    }
+}
+
+/// Pooled connection.
+struct Conn<S> {
+    client: Client<S, KeepAlive>,
+    created: Instant,
+    idle_since: Instant,
+    requests: usize,
+}
+
+impl<C: Connect> Clone for Pool<C> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<C: Connect + fmt::Debug> fmt::Debug for Pool<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pool")
+            .field("connector", &self.inner.connector)
+            .field("config", &self.inner.config)
+            .field("idle", &self.idle())
+            .field("in_flight", &self.in_flight())
+            .finish()
+    }
+}
+
+/// Count the request in flight until dropped, so the cancelled requests are
+/// counted correctly, notify the shutdown waiting if drained.
+struct InFlight<'a> {
+    count: &'a AtomicUsize,
+    drained: &'a Notify,
+}
+
+impl<'a> InFlight<'a> {
+    fn new(count: &'a AtomicUsize, drained: &'a Notify) -> Self {
+        count.fetch_add(1, Ordering::AcqRel);
+        Self { count, drained }
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        if self.count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.drained.notify_one();
+        }
+    }
+}
@@ -0,0 +1,84 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [bb8](https://docs.rs/bb8) connection manager of keep alive clients.
+
+use crate::{conn::KeepAlive, connect::Connect, values::ValueName, Client, ClientError};
+use bb8::ManageConnection;
+
+/// Pool of keep alive clients managed by [Manager].
+pub type Pool<C> = bb8::Pool<Manager<C>>;
+
+/// Create keep alive clients by the connector, the client is validated by
+/// `FCGI_GET_VALUES` on checking out (bb8 `test_on_check_out`, enabled by
+/// default).
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{
+///     connect::TcpConnector,
+///     pool::bb8::{Manager, Pool},
+///     Params, Request,
+/// };
+/// use tokio::io;
+///
+/// async fn pool() {
+///     let manager = Manager::new(TcpConnector::new("127.0.0.1:9000"));
+///     let pool = Pool::builder().max_size(8).build(manager).await.unwrap();
+///
+///     let mut client = pool.get().await.unwrap();
+///     let output = client
+///         .execute(Request::new(Params::default(), io::empty()))
+///         .await
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Manager<C> {
+    connector: C,
+}
+
+impl<C: Connect> Manager<C> {
+    pub fn new(connector: C) -> Self {
+        Self { connector }
+    }
+
+    pub fn connector(&self) -> &C {
+        &self.connector
+    }
+}
+
+impl<C: Connect> ManageConnection for Manager<C> {
+    type Connection = Client<C::Stream, KeepAlive>;
+    type Error = ClientError;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        let stream = self
+            .connector
+            .connect()
+            .await
+            .map_err(ClientError::connect)?;
+        Ok(Client::new_keep_alive(stream))
+    }
+
+    async fn is_valid(&self, client: &mut Self::Connection) -> Result<(), Self::Error> {
+        client.get_values(&[ValueName::MaxConns]).await?;
+        Ok(())
+    }
+
+    fn has_broken(&self, client: &mut Self::Connection) -> bool {
+        client.is_poisoned()
+    }
+}
@@ -0,0 +1,82 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [deadpool](https://docs.rs/deadpool) manager of keep alive clients.
+
+use crate::{conn::KeepAlive, connect::Connect, values::ValueName, Client, ClientError};
+use deadpool::managed::{self, Metrics, RecycleResult};
+
+/// Pool of keep alive clients managed by [Manager].
+pub type Pool<C> = managed::Pool<Manager<C>>;
+
+/// Create keep alive clients by the connector, the client is checked by
+/// `FCGI_GET_VALUES` before reused, so the connections closed by server are
+/// dropped.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{
+///     connect::TcpConnector,
+///     pool::deadpool::{Manager, Pool},
+///     Params, Request,
+/// };
+/// use tokio::io;
+///
+/// async fn pool() {
+///     let manager = Manager::new(TcpConnector::new("127.0.0.1:9000"));
+///     let pool = Pool::builder(manager).max_size(8).build().unwrap();
+///
+///     let mut client = pool.get().await.unwrap();
+///     let output = client
+///         .execute(Request::new(Params::default(), io::empty()))
+///         .await
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Manager<C> {
+    connector: C,
+}
+
+impl<C: Connect> Manager<C> {
+    pub fn new(connector: C) -> Self {
+        Self { connector }
+    }
+
+    pub fn connector(&self) -> &C {
+        &self.connector
+    }
+}
+
+impl<C: Connect> managed::Manager for Manager<C> {
+    type Error = ClientError;
+    type Type = Client<C::Stream, KeepAlive>;
+
+    async fn create(&self) -> Result<Self::Type, Self::Error> {
+        let stream = self
+            .connector
+            .connect()
+            .await
+            .map_err(ClientError::connect)?;
+        Ok(Client::new_keep_alive(stream))
+    }
+
+    async fn recycle(
+        &self, client: &mut Self::Type, _metrics: &Metrics,
+    ) -> RecycleResult<Self::Error> {
+        client.get_values(&[ValueName::MaxConns]).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Spawn and supervise the external fastcgi applications, such as `php-cgi`,
+//! like `spawn-fcgi`, the listening socket is created and passed as stdin
+//! (`FCGI_LISTENSOCK_FILENO`), and the crashed processes are restarted.
+
+use crate::connect::Address;
+use std::{
+    ffi::OsString,
+    future::{poll_fn, Future},
+    io,
+    os::unix::io::OwnedFd,
+    path::PathBuf,
+    pin::pin,
+    process::Stdio,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::Poll,
+    time::{Duration, Instant},
+};
+use tokio::{
+    process::{Child, Command},
+    task::JoinHandle,
+    time,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+/// Config of [ProcessManager].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ProcessConfig {
+    /// The program of fastcgi application.
+    pub program: PathBuf,
+    pub args: Vec<OsString>,
+    /// The environments added to the processes, such as
+    /// `PHP_FCGI_CHILDREN`.
+    pub envs: Vec<(OsString, OsString)>,
+    /// The count of processes sharing the listening socket, like
+    /// `spawn-fcgi -F`.
+    pub processes: usize,
+    /// The backoff before the first restart of crashed process, doubled on
+    /// every restart, reset after the process ran longer than
+    /// `max_backoff`.
+    pub initial_backoff: Duration,
+    /// The upper bound of backoff.
+    pub max_backoff: Duration,
+}
+
+impl ProcessConfig {
+    pub fn new(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            envs: Vec::new(),
+            processes: 1,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+
+    pub fn arg(mut self, arg: impl Into<OsString>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn env(mut self, key: impl Into<OsString>, value: impl Into<OsString>) -> Self {
+        self.envs.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn processes(mut self, processes: usize) -> Self {
+        self.processes = processes;
+        self
+    }
+
+    pub fn initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+}
+
+/// Manager of the fastcgi application processes, the processes are killed
+/// when the manager is shut down or dropped.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{
+///     pool::Pool,
+///     process::{ProcessConfig, ProcessManager},
+///     Params, Request,
+/// };
+///
+/// async fn process() {
+///     let config = ProcessConfig::new("php-cgi").env("PHP_FCGI_CHILDREN", "4");
+///     let manager = ProcessManager::spawn(config, "127.0.0.1:0".parse().unwrap()).unwrap();
+///
+///     let pool = Pool::new(manager.address().clone());
+///     let output = pool
+///         .execute(Request::new(Params::default(), tokio::io::empty()))
+///         .await
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ProcessManager {
+    address: Address,
+    restarts: Arc<AtomicUsize>,
+    shutdown: CancellationToken,
+    supervisors: Vec<JoinHandle<()>>,
+}
+
+impl ProcessManager {
+    /// Bind the listening socket of the address, the port `0` of tcp address
+    /// is replaced by the port assigned, and the existing unix socket file is
+    /// removed, then spawn the processes.
+    pub fn spawn(config: ProcessConfig, address: Address) -> io::Result<Self> {
+        let (socket, address) = bind(address)?;
+        let restarts = Arc::new(AtomicUsize::new(0));
+        let shutdown = CancellationToken::new();
+        let config = Arc::new(config);
+
+        let supervisors = (0..config.processes)
+            .map(|index| {
+                let child = spawn_child(&config, &socket)?;
+                Ok(tokio::spawn(supervise(
+                    index,
+                    child,
+                    config.clone(),
+                    socket.try_clone()?,
+                    restarts.clone(),
+                    shutdown.clone(),
+                )))
+            })
+            .collect::<io::Result<_>>();
+        let supervisors = match supervisors {
+            Ok(supervisors) => supervisors,
+            Err(err) => {
+                // Kill the processes spawned.
+                shutdown.cancel();
+                return Err(err);
+            }
+        };
+
+        Ok(Self {
+            address,
+            restarts,
+            shutdown,
+            supervisors,
+        })
+    }
+
+    /// The address of listening socket, which can be connected by the
+    /// clients and pools.
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+
+    /// The count of restarts of the crashed processes.
+    pub fn restarts(&self) -> usize {
+        self.restarts.load(Ordering::Relaxed)
+    }
+
+    /// Kill the processes and wait for them to exit.
+    pub async fn shutdown(mut self) {
+        self.shutdown.cancel();
+        for supervisor in self.supervisors.drain(..) {
+            let _ = supervisor.await;
+        }
+    }
+}
+
+impl Drop for ProcessManager {
+    fn drop(&mut self) {
+        self.shutdown.cancel();
+    }
+}
+
+/// Bind the listening socket, return the address can be connected.
+fn bind(address: Address) -> io::Result<(OwnedFd, Address)> {
+    match address {
+        Address::Tcp(addr) => {
+            let listener = std::net::TcpListener::bind(&addr)?;
+            let addr = listener.local_addr()?.to_string();
+            Ok((listener.into(), Address::Tcp(addr)))
+        }
+        Address::Unix(path) => {
+            match std::fs::remove_file(&path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+            let listener = std::os::unix::net::UnixListener::bind(&path)?;
+            Ok((listener.into(), Address::Unix(path)))
+        }
+    }
+}
+
+fn spawn_child(config: &ProcessConfig, socket: &OwnedFd) -> io::Result<Child> {
+    let child = Command::new(&config.program)
+        .args(&config.args)
+        .envs(config.envs.iter().map(|(key, value)| (key, value)))
+        .stdin(Stdio::from(socket.try_clone()?))
+        .stdout(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()?;
+    debug!(program = ?config.program, pid = child.id(), "Spawn process.");
+    Ok(child)
+}
+
+/// Wait for the process to exit, and restart it after backoff, until shut
+/// down.
+async fn supervise(
+    index: usize, mut child: Child, config: Arc<ProcessConfig>, socket: OwnedFd,
+    restarts: Arc<AtomicUsize>, shutdown: CancellationToken,
+) {
+    let mut backoff = config.initial_backoff;
+    loop {
+        let started = Instant::now();
+        let status = {
+            let mut cancelled = pin!(shutdown.cancelled());
+            let mut wait = pin!(child.wait());
+            poll_fn(|cx| match cancelled.as_mut().poll(cx) {
+                Poll::Ready(()) => Poll::Ready(None),
+                Poll::Pending => wait.as_mut().poll(cx).map(Some),
+            })
+            .await
+        };
+        let Some(status) = status else {
+            debug!(index, pid = child.id(), "Kill process.");
+            let _ = child.kill().await;
+            return;
+        };
+        warn!(index, ?status, "Process exited.");
+
+        if started.elapsed() > config.max_backoff {
+            backoff = config.initial_backoff;
+        }
+        if time::timeout(backoff, shutdown.cancelled()).await.is_ok() {
+            return;
+        }
+        backoff = (backoff * 2).min(config.max_backoff);
+
+        child = loop {
+            match spawn_child(&config, &socket) {
+                Ok(child) => break child,
+                Err(err) => {
+                    warn!(index, ?err, "Restart process failed.");
+                    if time::timeout(backoff, shutdown.cancelled()).await.is_ok() {
+                        return;
+                    }
+                    backoff = (backoff * 2).min(config.max_backoff);
+                }
+            }
+        };
+        restarts.fetch_add(1, Ordering::Relaxed);
+    }
+}
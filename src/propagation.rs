@@ -0,0 +1,58 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Propagate the OpenTelemetry trace context to fastcgi server, enabled by the
+//! `opentelemetry` feature, so the PHP APM agents can join the distributed
+//! trace started in Rust.
+
+use crate::Params;
+use opentelemetry::{trace::TraceContextExt, Context};
+
+/// Inject the trace context of `cx` as the `HTTP_TRACEPARENT` and
+/// `HTTP_TRACESTATE` params, that is the W3C `traceparent` and `tracestate`
+/// headers passed by CGI, nothing injected if the span context is invalid.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{propagation, Params};
+/// use opentelemetry::Context;
+///
+/// let mut params = Params::default().request_method("GET");
+/// propagation::inject_context(&mut params, &Context::current());
+/// ```
+pub fn inject_context(params: &mut Params<'_>, cx: &Context) {
+    let span = cx.span();
+    let span_context = span.span_context();
+    if !span_context.is_valid() {
+        return;
+    }
+
+    let traceparent = format!(
+        "00-{:032x}-{:016x}-{:02x}",
+        span_context.trace_id(),
+        span_context.span_id(),
+        span_context.trace_flags()
+    );
+    params.insert("HTTP_TRACEPARENT".into(), traceparent.into());
+    let tracestate = span_context.trace_state().header();
+    if !tracestate.is_empty() {
+        params.insert("HTTP_TRACESTATE".into(), tracestate.into());
+    }
+}
+
+/// Inject the trace context of the current span, see [inject_context].
+pub fn inject(params: &mut Params<'_>) {
+    inject_context(params, &Context::current());
+}
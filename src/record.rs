@@ -0,0 +1,197 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Functions encoding and decoding the records without sockets, so the
+//! encoding can be compared with golden bytes, and the decoding can be
+//! exercised by tests and fuzzers.
+
+use crate::{
+    client::{handle_request, request_id},
+    meta::{
+        handle_management_record, EndRequestRec, Header, RequestType, HEADER_LEN, NULL_REQUEST_ID,
+    },
+    request::Request,
+    ClientError, ClientResult, Response,
+};
+use std::io::Cursor;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Record decoded by [parse_record], borrowing the content from the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawRecord<'a> {
+    pub version: u8,
+    /// The raw record type, such as `6` for `FCGI_STDOUT`.
+    pub r#type: u8,
+    pub request_id: u16,
+    pub reserved: u8,
+    pub content: &'a [u8],
+    pub padding: &'a [u8],
+}
+
+impl RawRecord<'_> {
+    /// The length of the record in bytes, including header, content and
+    /// padding.
+    pub fn len(&self) -> usize {
+        HEADER_LEN + self.content.len() + self.padding.len()
+    }
+
+    /// Always false, the record has the header at least.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+/// Decode a record from the beginning of `buf`, return the record and the
+/// count of bytes consumed.
+///
+/// Returns [ClientError::IncompleteRecord] if `buf` doesn't contain the whole
+/// record, the version and reserved byte aren't checked, like
+/// [Client](crate::Client).
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::record::parse_record;
+///
+/// let buf = [1, 6, 0, 1, 0, 2, 6, 0, b'o', b'k', 0, 0, 0, 0, 0, 0];
+/// let (record, read) = parse_record(&buf).unwrap();
+/// assert_eq!(record.r#type, 6);
+/// assert_eq!(record.content, b"ok");
+/// assert_eq!(read, 16);
+/// ```
+pub fn parse_record(buf: &[u8]) -> ClientResult<(RawRecord<'_>, usize)> {
+    let Some(header) = buf.first_chunk::<HEADER_LEN>() else {
+        return Err(ClientError::IncompleteRecord {
+            needed: HEADER_LEN - buf.len(),
+        });
+    };
+    let content_length = u16::from_be_bytes([header[4], header[5]]) as usize;
+    let padding_start = HEADER_LEN + content_length;
+    let len = padding_start + header[6] as usize;
+    if buf.len() < len {
+        return Err(ClientError::IncompleteRecord {
+            needed: len - buf.len(),
+        });
+    }
+
+    let record = RawRecord {
+        version: header[0],
+        r#type: header[1],
+        request_id: u16::from_be_bytes([header[2], header[3]]),
+        reserved: header[7],
+        content: &buf[HEADER_LEN..padding_start],
+        padding: &buf[padding_start..len],
+    };
+    Ok((record, len))
+}
+
+/// Decode the response of request `id` from the records in `buf`, like the
+/// response received by [Client](crate::Client), the management records are
+/// skipped, return the response and the count of bytes consumed.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::record::parse_response;
+///
+/// let mut buf = vec![1, 6, 0, 1, 0, 2, 6, 0, b'o', b'k', 0, 0, 0, 0, 0, 0];
+/// buf.extend_from_slice(&[1, 3, 0, 1, 0, 8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+/// let (response, read) = parse_response(&buf, 1).unwrap();
+/// assert_eq!(response.stdout.unwrap(), b"ok");
+/// assert_eq!(read, buf.len());
+/// ```
+pub fn parse_response(buf: &[u8], id: u16) -> ClientResult<(Response, usize)> {
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    let mut pos = 0;
+
+    loop {
+        let (record, read) = parse_record(&buf[pos..])?;
+        let header = Header::new_from_buf(buf[pos..pos + HEADER_LEN].try_into().unwrap());
+        pos += read;
+
+        if record.request_id == NULL_REQUEST_ID {
+            handle_management_record(&header, record.content);
+            continue;
+        }
+        if record.request_id != id {
+            return Err(ClientError::ResponseNotFound { id });
+        }
+
+        match header.r#type {
+            RequestType::Stdout => stdout.extend_from_slice(record.content),
+            RequestType::Stderr => stderr.extend_from_slice(record.content),
+            RequestType::EndRequest => {
+                if record.content.len() < EndRequestRec::CONTENT_LEN {
+                    return Err(ClientError::ProtocolError {
+                        detail: "EndRequest content too short".to_owned(),
+                    });
+                }
+                let end_request = EndRequestRec::new_from_buf(header, record.content).end_request;
+                end_request
+                    .protocol_status
+                    .convert_to_client_result(end_request.app_status)?;
+
+                let response = Response {
+                    stdout: Some(stdout).filter(|stdout| !stdout.is_empty()),
+                    stderr: Some(stderr).filter(|stderr| !stderr.is_empty()),
+                    app_status: end_request.app_status,
+                };
+                return Ok((response, pos));
+            }
+            request_type => return Err(ClientError::UnknownRequestType { request_type }),
+        }
+    }
+}
+
+/// Encode the whole request, that is the records of begin request, params,
+/// stdin and data, as sent by [Client](crate::Client).
+///
+/// The encoding is deterministic, the params are encoded in insertion order,
+/// and the stdin and data are read to end first, so the records are split by
+/// length only, regardless of how the readers return. The request id is `1`
+/// unless specified, and the connection isn't kept alive unless specified.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{record::encode_request, Params, Request};
+///
+/// async fn encode() {
+///     let params = Params::default().request_method("GET");
+///     let buf = encode_request(Request::new(params, tokio::io::empty()))
+///         .await
+///         .unwrap();
+///     assert_eq!(&buf[..8], [1, 1, 0, 1, 0, 8, 0, 0]);
+/// }
+/// ```
+pub async fn encode_request<I: AsyncRead + Unpin>(
+    mut request: Request<'_, I>,
+) -> ClientResult<Vec<u8>> {
+    let id = request_id(&request);
+    let keep_alive = request.keep_alive.unwrap_or(false);
+
+    let mut stdin = Vec::new();
+    request.stdin.read_to_end(&mut stdin).await?;
+    let mut request = request.map_stdin(|_| Cursor::new(stdin));
+    if let Some(data) = &mut request.data {
+        let mut buf = Vec::new();
+        data.read_to_end(&mut buf).await?;
+        *data = Box::new(Cursor::new(buf));
+    }
+
+    let mut buf = Vec::new();
+    handle_request(&mut buf, id, keep_alive, &mut request).await?;
+    Ok(buf)
+}
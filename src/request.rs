@@ -12,26 +12,213 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::Params;
-use tokio::io::AsyncRead;
+use crate::{params::ParamsLimits, Params, Role};
+use std::{borrow::Cow, num::NonZeroU16, time::Duration};
+use tokio::io::{self, AsyncRead, Empty};
+
+/// The `FCGI_DATA` stream of Filter role request.
+pub(crate) type BoxedData = Box<dyn AsyncRead + Send + Unpin>;
+
+/// The stdin of request sent to background tasks.
+pub(crate) type BoxedStdin = Box<dyn AsyncRead + Send + Unpin>;
+
+/// Priority of request, the high priority requests are dispatched before the
+/// low priority ones by [SharedClient](crate::shared::SharedClient) when the
+/// connection is contended.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Batch requests, such as cron jobs.
+    Low,
+    #[default]
+    Normal,
+    /// Interactive requests.
+    High,
+}
 
 /// fastcgi request.
 pub struct Request<'a, I: AsyncRead + Unpin> {
-    pub(crate) params: Params<'a>,
+    pub(crate) role: Role,
+    pub(crate) params: Cow<'a, Params<'a>>,
     pub(crate) stdin: I,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) strict: bool,
+    pub(crate) params_limits: ParamsLimits,
+    pub(crate) stdin_len: Option<usize>,
+    pub(crate) data: Option<BoxedData>,
+    pub(crate) keep_alive: Option<bool>,
+    pub(crate) request_id: Option<NonZeroU16>,
+    pub(crate) priority: Priority,
+}
+
+impl<'a> Request<'a, Empty> {
+    /// Create the [RequestBuilder] of Responder role with empty params and
+    /// stdin.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fastcgi_client::{Params, Request, Role};
+    /// use std::time::Duration;
+    ///
+    /// let request = Request::builder()
+    ///     .role(Role::Filter)
+    ///     .params(Params::default().request_method("GET"))
+    ///     .stdin(&b"stdin"[..])
+    ///     .data(&b"data"[..])
+    ///     .timeout(Duration::from_secs(3))
+    ///     .build();
+    /// assert!(request.has_data());
+    /// ```
+    pub fn builder() -> RequestBuilder<'a, Empty> {
+        RequestBuilder {
+            request: Request::new(Params::default(), io::empty()),
+        }
+    }
 }
 
 impl<'a, I: AsyncRead + Unpin> Request<'a, I> {
-    pub fn new(params: Params<'a>, stdin: I) -> Self {
-        Self { params, stdin }
+    /// Construct the request of Responder role, the params can be borrowed,
+    /// so the same params can be reused across requests without cloning.
+    pub fn new(params: impl Into<Cow<'a, Params<'a>>>, stdin: I) -> Self {
+        Self {
+            role: Role::Responder,
+            params: params.into(),
+            stdin,
+            timeout: None,
+            strict: false,
+            params_limits: ParamsLimits::default(),
+            stdin_len: None,
+            data: None,
+            keep_alive: None,
+            request_id: None,
+            priority: Priority::Normal,
+        }
+    }
+
+    /// Construct the request of Responder role with the stdin of known
+    /// length, see [with_stdin_len](Request::with_stdin_len), the
+    /// `CONTENT_LENGTH` param is set automatically if absent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fastcgi_client::{Params, Request};
+    ///
+    /// let body = b"name=jmjoy";
+    /// let request = Request::new_sized(
+    ///     Params::default().request_method("POST"),
+    ///     &body[..],
+    ///     body.len(),
+    /// );
+    /// assert_eq!(request.params()["CONTENT_LENGTH"], "10");
+    /// ```
+    pub fn new_sized(params: impl Into<Cow<'a, Params<'a>>>, stdin: I, stdin_len: usize) -> Self {
+        let mut request = Self::new(params, stdin).with_stdin_len(stdin_len);
+        if !request.params.contains_key("CONTENT_LENGTH") {
+            request
+                .params_mut()
+                .insert("CONTENT_LENGTH".into(), stdin_len.to_string().into());
+        }
+        request
+    }
+
+    /// Bound the whole exchange of the request by the timeout, exceeded
+    /// returns [ClientError::RequestTimeout](crate::ClientError::RequestTimeout).
+    ///
+    /// The connection is left in an unknown state after timeout, so shouldn't
+    /// be reused.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Enable strict mode, the params are checked by
+    /// [Params::validate](crate::Params::validate) before sending.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Limits of params size, the default only limits each name and value to
+    /// [MAX_PARAM_LENGTH](crate::params::MAX_PARAM_LENGTH).
+    pub fn with_params_limits(mut self, params_limits: ParamsLimits) -> Self {
+        self.params_limits = params_limits;
+        self
+    }
+
+    pub fn params_limits(&self) -> &ParamsLimits {
+        &self.params_limits
+    }
+
+    /// The length of stdin if known, such as `&[u8]` or `Cursor`, then the
+    /// stdin is sent in optimally sized records, without the read loop until
+    /// the end.
+    ///
+    /// Sending fails with [ClientError::Io](crate::ClientError::Io) of
+    /// `UnexpectedEof` if the stdin is shorter, and the excess is ignored if
+    /// longer, and fails with
+    /// [ClientError::ContentLengthMismatch](crate::ClientError::ContentLengthMismatch)
+    /// if the `CONTENT_LENGTH` param is different.
+    pub fn with_stdin_len(mut self, stdin_len: usize) -> Self {
+        self.stdin_len = Some(stdin_len);
+        self
+    }
+
+    pub fn stdin_len(&self) -> Option<usize> {
+        self.stdin_len
+    }
+
+    /// Override the keep alive flag of `FCGI_BEGIN_REQUEST`, which defaults to
+    /// the connection mode of [Client](crate::Client).
+    pub fn keep_alive(&self) -> Option<bool> {
+        self.keep_alive
+    }
+
+    /// The request id used by [Client](crate::Client) instead of the
+    /// allocated one, the multiplexed clients allocate the ids themselves and
+    /// ignore it.
+    pub fn request_id(&self) -> Option<NonZeroU16> {
+        self.request_id
+    }
+
+    /// The priority of dispatching, see [Priority].
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    /// Whether the `FCGI_DATA` stream is attached, which is sent after stdin.
+    pub fn has_data(&self) -> bool {
+        self.data.is_some()
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    pub fn role_mut(&mut self) -> &mut Role {
+        &mut self.role
     }
 
     pub fn params(&self) -> &Params<'a> {
         &self.params
     }
 
+    /// The borrowed params are cloned before mutating.
     pub fn params_mut(&mut self) -> &mut Params<'a> {
-        &mut self.params
+        self.params.to_mut()
     }
 
     pub fn stdin(&self) -> &I {
@@ -41,4 +228,115 @@ impl<'a, I: AsyncRead + Unpin> Request<'a, I> {
     pub fn stdin_mut(&mut self) -> &mut I {
         &mut self.stdin
     }
+
+    /// Convert into the `'static` version, see
+    /// [Params::into_owned](crate::Params::into_owned).
+    pub fn into_owned(self) -> Request<'static, I> {
+        Request {
+            role: self.role,
+            params: Cow::Owned(self.params.into_owned().into_owned()),
+            stdin: self.stdin,
+            timeout: self.timeout,
+            strict: self.strict,
+            params_limits: self.params_limits,
+            stdin_len: self.stdin_len,
+            data: self.data,
+            keep_alive: self.keep_alive,
+            request_id: self.request_id,
+            priority: self.priority,
+        }
+    }
+
+    /// Replace the stdin, keep the other fields.
+    pub(crate) fn map_stdin<J: AsyncRead + Unpin>(self, f: impl FnOnce(I) -> J) -> Request<'a, J> {
+        Request {
+            role: self.role,
+            params: self.params,
+            stdin: f(self.stdin),
+            timeout: self.timeout,
+            strict: self.strict,
+            params_limits: self.params_limits,
+            stdin_len: self.stdin_len,
+            data: self.data,
+            keep_alive: self.keep_alive,
+            request_id: self.request_id,
+            priority: self.priority,
+        }
+    }
+}
+
+/// Builder of [Request], created by [Request::builder].
+pub struct RequestBuilder<'a, I: AsyncRead + Unpin> {
+    request: Request<'a, I>,
+}
+
+impl<'a, I: AsyncRead + Unpin> RequestBuilder<'a, I> {
+    pub fn role(mut self, role: Role) -> Self {
+        self.request.role = role;
+        self
+    }
+
+    pub fn params(mut self, params: impl Into<Cow<'a, Params<'a>>>) -> Self {
+        self.request.params = params.into();
+        self
+    }
+
+    pub fn stdin<J: AsyncRead + Unpin>(self, stdin: J) -> RequestBuilder<'a, J> {
+        RequestBuilder {
+            request: self.request.map_stdin(|_| stdin),
+        }
+    }
+
+    /// See [Request::with_stdin_len].
+    pub fn stdin_len(mut self, stdin_len: usize) -> Self {
+        self.request.stdin_len = Some(stdin_len);
+        self
+    }
+
+    /// The `FCGI_DATA` stream of Filter role, such as the content of file to
+    /// be filtered.
+    pub fn data(mut self, data: impl AsyncRead + Send + Unpin + 'static) -> Self {
+        self.request.data = Some(Box::new(data));
+        self
+    }
+
+    /// See [Request::keep_alive].
+    pub fn keep_alive(mut self, keep_alive: bool) -> Self {
+        self.request.keep_alive = Some(keep_alive);
+        self
+    }
+
+    /// See [Request::request_id].
+    pub fn request_id(mut self, request_id: NonZeroU16) -> Self {
+        self.request.request_id = Some(request_id);
+        self
+    }
+
+    /// See [Request::with_priority].
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.request.priority = priority;
+        self
+    }
+
+    /// See [Request::with_timeout].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.request.timeout = Some(timeout);
+        self
+    }
+
+    /// See [Request::strict].
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.request.strict = strict;
+        self
+    }
+
+    /// See [Request::with_params_limits].
+    pub fn params_limits(mut self, params_limits: ParamsLimits) -> Self {
+        self.request.params_limits = params_limits;
+        self
+    }
+
+    pub fn build(self) -> Request<'a, I> {
+        self.request
+    }
 }
@@ -12,12 +12,33 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod authorizer;
+pub mod parse;
+
 use crate::{
-    meta::{EndRequestRec, Header, RequestType},
+    meta::{
+        handle_management_record, EndRequest, EndRequestRec, Header, ProtocolStatus, RequestType,
+        HEADER_LEN, MAX_LENGTH, NULL_REQUEST_ID,
+    },
     ClientError, ClientResult,
 };
-use std::{cmp::min, fmt, fmt::Debug, str};
-use tokio::io::{AsyncRead, AsyncReadExt};
+use bytes::{Bytes, BytesMut};
+use std::{
+    borrow::Cow,
+    cmp::min,
+    fmt,
+    fmt::Debug,
+    future::poll_fn,
+    io,
+    pin::Pin,
+    str,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{ready, Context, Poll},
+};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
 use tracing::debug;
 
 /// Output of fastcgi request, contains STDOUT and STDERR.
@@ -26,6 +47,8 @@ use tracing::debug;
 pub struct Response {
     pub stdout: Option<Vec<u8>>,
     pub stderr: Option<Vec<u8>>,
+    /// The application status of `FCGI_END_REQUEST`, like the exit status.
+    pub app_status: u32,
 }
 
 impl Debug for Response {
@@ -33,15 +56,142 @@ impl Debug for Response {
         f.debug_struct("Response")
             .field("stdout", &self.stdout.as_deref().map(str::from_utf8))
             .field("stderr", &self.stderr.as_deref().map(str::from_utf8))
+            .field("app_status", &self.app_status)
             .finish()
     }
 }
 
+impl Response {
+    /// The stdout as UTF-8 string, empty if none.
+    pub fn stdout_str(&self) -> Result<&str, str::Utf8Error> {
+        str::from_utf8(self.stdout.as_deref().unwrap_or_default())
+    }
+
+    /// The stdout as string, the invalid UTF-8 sequences are replaced by
+    /// `U+FFFD`.
+    pub fn stdout_str_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(self.stdout.as_deref().unwrap_or_default())
+    }
+
+    /// The stderr as UTF-8 string, empty if none.
+    pub fn stderr_str(&self) -> Result<&str, str::Utf8Error> {
+        str::from_utf8(self.stderr.as_deref().unwrap_or_default())
+    }
+
+    /// The stderr as string, the invalid UTF-8 sequences are replaced by
+    /// `U+FFFD`.
+    pub fn stderr_str_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(self.stderr.as_deref().unwrap_or_default())
+    }
+
+    /// The stdout after the CGI header section, without parsing the headers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fastcgi_client::Response;
+    ///
+    /// let mut response = Response::default();
+    /// response.stdout = Some(b"Content-type: text/plain\r\n\r\nhello".to_vec());
+    /// assert_eq!(response.body().unwrap(), b"hello");
+    /// ```
+    pub fn body(&self) -> ClientResult<&[u8]> {
+        Ok(parse::split_header_section(self.stdout.as_deref().unwrap_or_default())?.1)
+    }
+
+    /// Parse the stdout as CGI response, and convert to `http::Response`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fastcgi_client::Response;
+    ///
+    /// fn into_http(response: Response) {
+    ///     let response = response.into_http().unwrap();
+    ///     let content_type = response.headers().get("content-type");
+    /// }
+    /// ```
+    #[cfg(feature = "http")]
+    pub fn into_http(self) -> ClientResult<http::Response<Vec<u8>>> {
+        self.try_into()
+    }
+
+    /// Like [into_http](Response::into_http), but the stdout is parsed as raw
+    /// http response of NPH script, see [parse_nph](parse::parse_nph).
+    #[cfg(feature = "http")]
+    pub fn into_http_nph(self) -> ClientResult<http::Response<Vec<u8>>> {
+        parse::parse_nph(self.stdout.as_deref().unwrap_or_default())?.try_into()
+    }
+}
+
+#[cfg(feature = "http")]
+impl TryFrom<Response> for http::Response<Vec<u8>> {
+    type Error = ClientError;
+
+    fn try_from(response: Response) -> Result<Self, Self::Error> {
+        parse::parse(response.stdout.as_deref().unwrap_or_default())?.try_into()
+    }
+}
+
 pub enum Content<'a> {
     Stdout(&'a [u8]),
     Stderr(&'a [u8]),
+    /// The request is completed, always the last content of the stream.
+    End {
+        app_status: u32,
+        protocol_status: ProtocolStatus,
+    },
+}
+
+/// Owned version of [Content], generated by
+/// [ResponseStream::next_bytes].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BytesContent {
+    Stdout(Bytes),
+    Stderr(Bytes),
+    /// The request is completed, always the last content of the stream.
+    End {
+        app_status: u32,
+        protocol_status: ProtocolStatus,
+    },
+}
+
+/// The `FCGI_END_REQUEST` received by [ResponseStream], see
+/// [ResponseStream::end_request].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestEnd {
+    pub app_status: u32,
+    pub protocol_status: ProtocolStatus,
 }
 
+#[derive(Clone, Copy)]
+pub(crate) enum ContentKind {
+    Stdout,
+    Stderr,
+    End {
+        app_status: u32,
+        protocol_status: ProtocolStatus,
+    },
+}
+
+impl ContentKind {
+    fn content(self, buf: &[u8]) -> Content<'_> {
+        match self {
+            ContentKind::Stdout => Content::Stdout(buf),
+            ContentKind::Stderr => Content::Stderr(buf),
+            ContentKind::End {
+                app_status,
+                protocol_status,
+            } => Content::End {
+                app_status,
+                protocol_status,
+            },
+        }
+    }
+}
+
+const CONTENT_BUF_LEN: usize = 4096;
+
 #[derive(PartialEq)]
 enum ReadStep {
     Content,
@@ -52,6 +202,11 @@ enum ReadStep {
 /// [Client::execute_once_stream](crate::client::Client::execute_once_stream) or
 /// [Client::execute_stream](crate::client::Client::execute_stream).
 ///
+/// The content is returned as soon as it's read from the stream, without
+/// waiting for the whole record or coalescing the records, so the output
+/// flushed by the script, such as the server-sent events by PHP `flush()`,
+/// reaches the consumer immediately. The chunks don't align with the records.
+///
 /// The [ResponseStream] does not implement `futures::Stream`, because
 /// `futures::Stream` does not yet support GAT, so manually provide the
 /// [next](ResponseStream::next) method, which support the `while let` syntax.
@@ -59,14 +214,22 @@ pub struct ResponseStream<S: AsyncRead + Unpin> {
     stream: S,
     id: u16,
 
-    ended: bool,
+    pub(crate) ended: bool,
+    end_request: Option<RequestEnd>,
 
+    header_buf: [u8; HEADER_LEN],
+    header_read: usize,
     header: Option<Header>,
 
-    content_buf: Vec<u8>,
+    content_buf: BytesMut,
     content_read: usize,
+    max_content_length: usize,
 
     read_step: ReadStep,
+
+    /// The poisoned mark of client, cleared after the response is read
+    /// completely.
+    poisoned: Option<Arc<AtomicBool>>,
 }
 
 impl<S: AsyncRead + Unpin> ResponseStream<S> {
@@ -76,138 +239,358 @@ impl<S: AsyncRead + Unpin> ResponseStream<S> {
             stream,
             id,
             ended: false,
+            end_request: None,
+            header_buf: [0; HEADER_LEN],
+            header_read: 0,
             header: None,
-            content_buf: vec![0; 4096],
+            content_buf: BytesMut::zeroed(CONTENT_BUF_LEN),
             content_read: 0,
+            max_content_length: MAX_LENGTH,
             read_step: ReadStep::Content,
+            poisoned: None,
         }
     }
 
+    pub(crate) fn poisoned(mut self, poisoned: Arc<AtomicBool>) -> Self {
+        self.poisoned = Some(poisoned);
+        self
+    }
+
+    /// Limit the content length of the records received, the stream fails
+    /// with [ClientError::ProtocolError] if exceeded, so the server can't
+    /// force large allocations, the stdout and stderr are read in chunks, and
+    /// the other records, such as `FCGI_END_REQUEST`, are buffered whole. The
+    /// limit is the protocol max `65535` by default.
+    pub fn max_content_length(mut self, max_content_length: usize) -> Self {
+        self.max_content_length = max_content_length;
+        self
+    }
+
+    /// The `FCGI_END_REQUEST` of response, available after the stream is read
+    /// to the end, by [next](ResponseStream::next) or the readers, even if
+    /// the protocol status is an error.
+    pub fn end_request(&self) -> Option<RequestEnd> {
+        self.end_request
+    }
+
     pub async fn next(&mut self) -> Option<ClientResult<Content<'_>>> {
-        if self.ended {
-            return None;
+        let chunk = poll_fn(|cx| self.poll_chunk(cx)).await;
+        chunk.map(|result| result.map(|(kind, read)| kind.content(self.chunk(read))))
+    }
+
+    /// Like [next](ResponseStream::next), but the content is `Bytes` backed by
+    /// the shared read buffer, which can be held without borrowing the stream.
+    pub async fn next_bytes(&mut self) -> Option<ClientResult<BytesContent>> {
+        let chunk = poll_fn(|cx| self.poll_chunk(cx)).await;
+        chunk.map(|result| {
+            result.map(|(kind, read)| match kind {
+                ContentKind::Stdout => BytesContent::Stdout(self.split_chunk(read)),
+                ContentKind::Stderr => BytesContent::Stderr(self.split_chunk(read)),
+                ContentKind::End {
+                    app_status,
+                    protocol_status,
+                } => BytesContent::End {
+                    app_status,
+                    protocol_status,
+                },
+            })
+        })
+    }
+
+    /// Drain the stream into [Response], like the response of
+    /// [Client::execute](crate::Client::execute), the output received before
+    /// is kept in [ClientError::PartialResponse] if the connection fails.
+    pub async fn collect(mut self) -> ClientResult<Response> {
+        let mut response = Response::default();
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        while let Some(content) = self.next().await {
+            match content {
+                Ok(Content::Stdout(out)) => stdout.extend_from_slice(out),
+                Ok(Content::Stderr(err)) => stderr.extend_from_slice(err),
+                Ok(Content::End { app_status, .. }) => response.app_status = app_status,
+                Err(ClientError::Io(err) | ClientError::ConnectionClosedByPeer { source: err }) => {
+                    return Err(ClientError::partial_response(err, stdout, stderr));
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        response.stdout = Some(stdout).filter(|stdout| !stdout.is_empty());
+        response.stderr = Some(stderr).filter(|stderr| !stderr.is_empty());
+        Ok(response)
+    }
+
+    /// Adapter implementing `AsyncRead` over the stdout, so the body can be
+    /// piped by `tokio::io::copy`, the stderr is discarded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fastcgi_client::{Client, Params, Request};
+    /// use tokio::{fs::File, io, net::TcpStream};
+    ///
+    /// async fn copy() {
+    ///     let stream = TcpStream::connect(("127.0.0.1", 9000)).await.unwrap();
+    ///     let mut stream = Client::new(stream)
+    ///         .execute_once_stream(Request::new(Params::default(), io::empty()))
+    ///         .await
+    ///         .unwrap();
+    ///     let mut file = File::create("output").await.unwrap();
+    ///     io::copy(&mut stream.stdout_reader(), &mut file)
+    ///         .await
+    ///         .unwrap();
+    /// }
+    /// ```
+    pub fn stdout_reader(&mut self) -> StdoutReader<'_, S> {
+        StdoutReader {
+            stream: self,
+            pos: 0,
+            len: 0,
+        }
+    }
+
+    /// The chunk of length `read` returned by
+    /// [poll_chunk](ResponseStream::poll_chunk).
+    #[inline]
+    pub(crate) fn chunk(&self, read: usize) -> &[u8] {
+        &self.content_buf[..read]
+    }
+
+    /// Split the chunk of length `read` returned by
+    /// [poll_chunk](ResponseStream::poll_chunk) as `Bytes` without copying,
+    /// the buffer is reclaimed after the `Bytes` are dropped.
+    pub(crate) fn split_chunk(&mut self, read: usize) -> Bytes {
+        let chunk = self.content_buf.split_to(read).freeze();
+        self.content_buf.resize(CONTENT_BUF_LEN, 0);
+        chunk
+    }
+
+    /// Poll the next non-empty chunk of stdout or stderr, the content is placed
+    /// at the front of `content_buf`, return the kind and the length, the
+    /// length of `End` is always zero.
+    pub(crate) fn poll_chunk(
+        &mut self, cx: &mut Context<'_>,
+    ) -> Poll<Option<ClientResult<(ContentKind, usize)>>> {
+        match self.poll_chunk_inner(cx) {
+            Poll::Ready(Some(Err(err))) => {
+                self.ended = true;
+                Poll::Ready(Some(Err(err)))
+            }
+            poll => poll,
         }
+    }
 
+    fn poll_chunk_inner(
+        &mut self, cx: &mut Context<'_>,
+    ) -> Poll<Option<ClientResult<(ContentKind, usize)>>> {
         loop {
+            if self.ended {
+                return Poll::Ready(None);
+            }
+
             if self.header.is_none() {
-                match Header::new_from_stream(&mut self.stream).await {
-                    Ok(header) => {
-                        self.header = Some(header);
+                while self.header_read < HEADER_LEN {
+                    let mut buf = ReadBuf::new(&mut self.header_buf[self.header_read..]);
+                    ready!(Pin::new(&mut self.stream).poll_read(cx, &mut buf))?;
+                    if buf.filled().is_empty() {
+                        return Poll::Ready(Some(Err(unexpected_eof().into())));
                     }
-                    Err(err) => {
-                        self.ended = true;
-                        return Some(Err(err.into()));
-                    }
-                };
+                    self.header_read += buf.filled().len();
+                }
+                self.header_read = 0;
+                let header = Header::new_from_buf(&self.header_buf);
+                if header.content_length as usize > self.max_content_length {
+                    return Poll::Ready(Some(Err(ClientError::ProtocolError {
+                        detail: format!(
+                            "record content length {} exceeds limit {}",
+                            header.content_length, self.max_content_length
+                        ),
+                    })));
+                }
+                self.header = Some(header);
             }
 
-            let header = self.header.as_ref().unwrap();
-
-            match header.r#type.clone() {
-                RequestType::Stdout => match self.read_step {
-                    ReadStep::Content => {
-                        return self
-                            .read_to_content(
-                                header.content_length as usize,
-                                Content::Stdout,
-                                Self::prepare_for_read_padding,
-                            )
-                            .await;
-                    }
-                    ReadStep::Padding => {
-                        self.read_to_content(
-                            header.padding_length as usize,
-                            Content::Stdout,
-                            Self::prepare_for_read_header,
-                        )
-                        .await;
-                        continue;
-                    }
-                },
-                RequestType::Stderr => match self.read_step {
-                    ReadStep::Content => {
-                        return self
-                            .read_to_content(
-                                header.content_length as usize,
-                                Content::Stderr,
-                                Self::prepare_for_read_padding,
-                            )
-                            .await;
-                    }
-                    ReadStep::Padding => {
-                        self.read_to_content(
-                            header.padding_length as usize,
-                            Content::Stderr,
-                            Self::prepare_for_read_header,
+            let header = self.header.clone().unwrap();
+
+            if header.request_id == NULL_REQUEST_ID {
+                let length = header.content_length as usize + header.padding_length as usize;
+                ready!(self.poll_read_content(cx, length))?;
+                handle_management_record(&header, self.chunk(header.content_length as usize));
+                self.header = None;
+                continue;
+            }
+
+            let kind = match header.r#type {
+                RequestType::Stdout => ContentKind::Stdout,
+                RequestType::Stderr => ContentKind::Stderr,
+                RequestType::EndRequest => {
+                    if (header.content_length as usize) < EndRequestRec::CONTENT_LEN {
+                        self.ended = true;
+                        return Poll::Ready(Some(Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "EndRequest content too short",
                         )
-                        .await;
-                        continue;
+                        .into())));
                     }
-                },
-                RequestType::EndRequest => {
-                    let end_request_rec =
-                        match EndRequestRec::from_header(header, &mut self.stream).await {
-                            Ok(rec) => rec,
-                            Err(err) => {
-                                self.ended = true;
-                                return Some(Err(err.into()));
-                            }
-                        };
+                    let length = header.content_length as usize + header.padding_length as usize;
+                    ready!(self.poll_read_content(cx, length))?;
+
+                    let end_request_rec = EndRequestRec::new_from_buf(header, &self.content_buf);
                     debug!(id = self.id, ?end_request_rec, "Receive from stream.");
 
                     self.ended = true;
+                    if let Some(poisoned) = &self.poisoned {
+                        poisoned.store(false, Ordering::Release);
+                    }
 
-                    return match end_request_rec
-                        .end_request
-                        .protocol_status
-                        .convert_to_client_result(end_request_rec.end_request.app_status)
-                    {
-                        Ok(_) => None,
-                        Err(err) => Some(Err(err)),
-                    };
+                    let EndRequest {
+                        app_status,
+                        protocol_status,
+                        ..
+                    } = end_request_rec.end_request;
+                    self.end_request = Some(RequestEnd {
+                        app_status,
+                        protocol_status,
+                    });
+                    protocol_status.convert_to_client_result(app_status)?;
+                    return Poll::Ready(Some(Ok((
+                        ContentKind::End {
+                            app_status,
+                            protocol_status,
+                        },
+                        0,
+                    ))));
                 }
                 r#type => {
                     self.ended = true;
-                    return Some(Err(ClientError::UnknownRequestType {
+                    return Poll::Ready(Some(Err(ClientError::UnknownRequestType {
                         request_type: r#type,
-                    }));
+                    })));
+                }
+            };
+
+            match self.read_step {
+                ReadStep::Content => {
+                    let length = header.content_length as usize;
+                    if self.content_read >= length {
+                        self.content_read = 0;
+                        self.read_step = ReadStep::Padding;
+                        continue;
+                    }
+
+                    let read_len = min(self.content_buf.len(), length - self.content_read);
+                    let mut buf = ReadBuf::new(&mut self.content_buf[..read_len]);
+                    ready!(Pin::new(&mut self.stream).poll_read(cx, &mut buf))?;
+                    let read = buf.filled().len();
+                    if read == 0 {
+                        return Poll::Ready(Some(Err(unexpected_eof().into())));
+                    }
+
+                    self.content_read += read;
+                    if self.content_read >= length {
+                        self.content_read = 0;
+                        self.read_step = ReadStep::Padding;
+                    }
+                    return Poll::Ready(Some(Ok((kind, read))));
+                }
+                ReadStep::Padding => {
+                    ready!(self.poll_read_content(cx, header.padding_length as usize))?;
+                    self.header = None;
+                    self.read_step = ReadStep::Content;
                 }
             }
         }
     }
 
-    async fn read_to_content<'a, T: 'a>(
-        &'a mut self, length: usize, content_fn: impl FnOnce(&'a [u8]) -> T,
-        prepare_for_next_fn: impl FnOnce(&mut Self),
-    ) -> Option<ClientResult<T>> {
-        let content_len = self.content_buf.len();
-        let read = match self
-            .stream
-            .read(&mut self.content_buf[..min(content_len, length - self.content_read)])
-            .await
-        {
-            Ok(read) => read,
-            Err(err) => {
-                self.ended = true;
-                return Some(Err(err.into()));
+    /// Read exact `length` bytes into the front of `content_buf`, the buffer
+    /// is grown if shorter.
+    fn poll_read_content(&mut self, cx: &mut Context<'_>, length: usize) -> Poll<io::Result<()>> {
+        if length > self.content_buf.len() {
+            self.content_buf.resize(length, 0);
+        }
+        while self.content_read < length {
+            let mut buf = ReadBuf::new(&mut self.content_buf[self.content_read..length]);
+            ready!(Pin::new(&mut self.stream).poll_read(cx, &mut buf))?;
+            if buf.filled().is_empty() {
+                return Poll::Ready(Err(unexpected_eof()));
             }
-        };
+            self.content_read += buf.filled().len();
+        }
+        self.content_read = 0;
+        Poll::Ready(Ok(()))
+    }
+}
 
-        self.content_read += read;
-        if self.content_read >= length {
-            self.content_read = 0;
-            prepare_for_next_fn(self);
+impl<S: AsyncRead + AsyncWrite + Unpin> ResponseStream<S> {
+    /// Send `FCGI_ABORT_REQUEST` for the in-flight request, such as the http
+    /// client has disconnected, then drain the remaining output until
+    /// `EndRequest`, so the keep alive connection can be reused.
+    ///
+    /// Do nothing if the response has already ended.
+    pub async fn abort(mut self) -> ClientResult<()> {
+        if self.ended {
+            return Ok(());
         }
 
-        Some(Ok(content_fn(&self.content_buf[..read])))
+        debug!(id = self.id, "Send AbortRequest to stream.");
+        Header::write_record(RequestType::AbortRequest, self.id, &mut self.stream, &[]).await?;
+        self.stream.flush().await?;
+
+        while let Some(chunk) = poll_fn(|cx| self.poll_chunk(cx)).await {
+            chunk?;
+        }
+        Ok(())
     }
+}
 
-    fn prepare_for_read_padding(&mut self) {
-        self.read_step = ReadStep::Padding;
+/// Generated by [ResponseStream::stdout_reader].
+///
+/// The unread part of the current chunk is lost if dropped.
+pub struct StdoutReader<'a, S: AsyncRead + Unpin> {
+    stream: &'a mut ResponseStream<S>,
+    pos: usize,
+    len: usize,
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for StdoutReader<'_, S> {
+    fn poll_read(
+        self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.stream
+            .poll_read_stdout(cx, &mut this.pos, &mut this.len, buf)
     }
+}
+
+impl<S: AsyncRead + Unpin> ResponseStream<S> {
+    /// Read the stdout into `buf`, from `pos` of the current chunk of length
+    /// `len`, the next chunk is polled after the current one is consumed, the
+    /// stderr is discarded.
+    pub(crate) fn poll_read_stdout(
+        &mut self, cx: &mut Context<'_>, pos: &mut usize, len: &mut usize, buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        while *pos >= *len {
+            match ready!(self.poll_chunk(cx)) {
+                Some(Ok((ContentKind::Stdout, read))) => {
+                    *pos = 0;
+                    *len = read;
+                }
+                Some(Ok((ContentKind::Stderr, read))) => {
+                    debug!(stderr = ?String::from_utf8_lossy(self.chunk(read)), "Discard stderr.");
+                }
+                Some(Ok((ContentKind::End { .. }, _))) | None => return Poll::Ready(Ok(())),
+                Some(Err(err)) => return Poll::Ready(Err(err.into())),
+            }
+        }
 
-    fn prepare_for_read_header(&mut self) {
-        self.header = None;
-        self.read_step = ReadStep::Content;
+        let chunk = &self.chunk(*len)[*pos..];
+        let n = min(chunk.len(), buf.remaining());
+        buf.put_slice(&chunk[..n]);
+        *pos += n;
+        Poll::Ready(Ok(()))
     }
 }
+
+fn unexpected_eof() -> io::Error {
+    io::ErrorKind::UnexpectedEof.into()
+}
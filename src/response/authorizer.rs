@@ -0,0 +1,70 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Response of the Authorizer role, see
+//! [fastcgi spec section 6.3](https://fastcgi-archives.github.io/FastCGI_Specification.html#S6.3).
+
+use super::parse::{parse, ParsedResponse};
+use crate::{ClientResult, Params};
+use std::borrow::Cow;
+
+const VARIABLE_PREFIX: &str = "variable-";
+
+/// Typed result of the Authorizer request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Authorization {
+    /// The authorizer responded with status 200, the access is allowed.
+    ///
+    /// The variables come from the `Variable-*` headers with the prefix
+    /// stripped and the names uppercased, which can be merged into the params
+    /// of subsequent Responder requests.
+    Allowed { variables: Params<'static> },
+
+    /// The authorizer responded with non-200 status, the access is denied, and
+    /// the response should be sent to the http client.
+    Denied(ParsedResponse),
+}
+
+impl Authorization {
+    #[inline]
+    pub fn is_allowed(&self) -> bool {
+        matches!(self, Authorization::Allowed { .. })
+    }
+}
+
+/// Parse the stdout of Authorizer response.
+pub fn parse_authorization(stdout: &[u8]) -> ClientResult<Authorization> {
+    let parsed = parse(stdout)?;
+    if parsed.status != 200 {
+        return Ok(Authorization::Denied(parsed));
+    }
+
+    let variables = parsed
+        .headers
+        .iter()
+        .filter_map(|(name, value)| {
+            let prefix = name.get(..VARIABLE_PREFIX.len())?;
+            if !prefix.eq_ignore_ascii_case(VARIABLE_PREFIX) {
+                return None;
+            }
+            let name = &name[VARIABLE_PREFIX.len()..];
+            Some((
+                Cow::Owned(name.to_ascii_uppercase()),
+                Cow::Owned(value.to_owned()),
+            ))
+        })
+        .collect();
+
+    Ok(Authorization::Allowed { variables })
+}
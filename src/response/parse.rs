@@ -0,0 +1,577 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Parse the stdout of fastcgi response as CGI response (headers and body),
+//! see [RFC 3875 section 6](https://www.rfc-editor.org/rfc/rfc3875#section-6).
+
+use super::{Content, RequestEnd, ResponseStream};
+use crate::{ClientError, ClientResult};
+use std::{
+    fmt,
+    fmt::Debug,
+    io,
+    pin::Pin,
+    str,
+    task::{Context, Poll},
+};
+use tokio::io::{AsyncRead, ReadBuf};
+use tracing::debug;
+
+/// Status code used when the CGI response has no `Status` header.
+const DEFAULT_STATUS: u16 = 200;
+
+/// Status code used when the CGI response only has `Location` header.
+const REDIRECT_STATUS: u16 = 302;
+
+/// Limits of the header section of CGI response, checked in parsing, so the
+/// gateways are protected from the scripts emitting unbounded headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct HeaderLimits {
+    /// Max length of the header section in bytes, exceeded then the parsing
+    /// fails with [ClientError::HeadersTooLarge].
+    pub max_bytes: usize,
+    /// Max count of the headers, exceeded then the parsing fails with
+    /// [ClientError::TooManyHeaders].
+    pub max_count: usize,
+}
+
+impl Default for HeaderLimits {
+    fn default() -> Self {
+        Self {
+            max_bytes: 64 * 1024,
+            max_count: 100,
+        }
+    }
+}
+
+impl HeaderLimits {
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn max_count(mut self, max_count: usize) -> Self {
+        self.max_count = max_count;
+        self
+    }
+}
+
+/// Parsed CGI response.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ParsedResponse {
+    /// Status code from the `Status` header.
+    pub status: u16,
+    /// Headers without the `Status` header.
+    pub headers: Headers,
+    /// Content after the header section.
+    pub body: Vec<u8>,
+}
+
+impl Debug for ParsedResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("ParsedResponse")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .field("body", &str::from_utf8(&self.body))
+            .finish()
+    }
+}
+
+impl ParsedResponse {
+    /// The local path if the response is a CGI local redirect, that is only
+    /// the `Location` header of path beginning with `/` and no body, see
+    /// [RFC 3875 section 6.2.2](https://www.rfc-editor.org/rfc/rfc3875#section-6.2.2).
+    pub fn local_redirect(&self) -> Option<&str> {
+        let location = self.headers.get("location")?;
+        (self.status == REDIRECT_STATUS
+            && self.headers.len() == 1
+            && self.body.is_empty()
+            && location.starts_with('/')
+            && !location.starts_with("//"))
+        .then_some(location)
+    }
+
+    /// The status code as `http::StatusCode`, `200 OK` if the CGI response
+    /// has no `Status` header.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fastcgi_client::response::parse::parse;
+    ///
+    /// let parsed = parse(b"Status: 404 Not Found\r\n\r\n").unwrap();
+    /// assert_eq!(parsed.status_code().unwrap(), http::StatusCode::NOT_FOUND);
+    /// ```
+    #[cfg(feature = "http")]
+    pub fn status_code(&self) -> ClientResult<http::StatusCode> {
+        Ok(http::StatusCode::from_u16(self.status).map_err(http::Error::from)?)
+    }
+
+    /// Decode the body by the `Content-Encoding` header, `gzip` and `deflate`
+    /// are supported, the `Content-Encoding` and `Content-Length` headers are
+    /// removed after decoded.
+    ///
+    /// Returns [ClientError::InvalidCgiResponse] if the encoding is
+    /// unsupported or the body is corrupted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fastcgi_client::response::parse::parse;
+    ///
+    /// let parsed = parse(b"Content-type: text/plain\r\n\r\nhello").unwrap();
+    /// assert_eq!(parsed.decode_body().unwrap().body, b"hello");
+    /// ```
+    #[cfg(feature = "flate2")]
+    pub fn decode_body(mut self) -> ClientResult<Self> {
+        use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+        use std::io::Read;
+
+        let Some(encoding) = self.headers.get("content-encoding") else {
+            return Ok(self);
+        };
+        let encoding = encoding.trim().to_ascii_lowercase();
+        let mut body = Vec::new();
+        let result = match encoding.as_str() {
+            "identity" => return Ok(self),
+            "gzip" | "x-gzip" => GzDecoder::new(&*self.body).read_to_end(&mut body),
+            // The `deflate` is zlib format by spec, but some servers send raw
+            // deflate.
+            "deflate" if is_zlib(&self.body) => {
+                ZlibDecoder::new(&*self.body).read_to_end(&mut body)
+            }
+            "deflate" => DeflateDecoder::new(&*self.body).read_to_end(&mut body),
+            _ => {
+                return Err(invalid(format!(
+                    "unsupported content encoding `{}`",
+                    encoding
+                )))
+            }
+        };
+        result.map_err(|err| invalid(format!("failed to decode {} body: {}", encoding, err)))?;
+
+        self.headers.0.retain(|(name, _)| {
+            !name.eq_ignore_ascii_case("content-encoding")
+                && !name.eq_ignore_ascii_case("content-length")
+        });
+        self.body = body;
+        Ok(self)
+    }
+}
+
+#[cfg(feature = "http")]
+impl TryFrom<ParsedResponse> for http::Response<Vec<u8>> {
+    type Error = ClientError;
+
+    fn try_from(parsed: ParsedResponse) -> Result<Self, Self::Error> {
+        let mut response = http::Response::builder()
+            .status(parsed.status)
+            .body(parsed.body)?;
+        *response.headers_mut() = parsed.headers.to_header_map()?;
+        Ok(response)
+    }
+}
+
+/// CGI response headers, the names keep the original casing and the lookups
+/// are case-insensitive, duplicate headers are kept in order.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Headers(Vec<(String, String)>);
+
+impl Headers {
+    /// Get the first value of header name, case-insensitive.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Get all values of header name, case-insensitive.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.0
+            .iter()
+            .filter(move |(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Iterate all headers as (name, value) pairs, the names are of the
+    /// original casing.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Convert to `http::HeaderMap`, the duplicate headers such as
+    /// `Set-Cookie` are appended rather than replaced.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fastcgi_client::response::parse::parse;
+    ///
+    /// let parsed = parse(b"Set-Cookie: a=1\r\nSet-Cookie: b=2\r\n\r\n").unwrap();
+    /// let headers = parsed.headers.to_header_map().unwrap();
+    /// assert_eq!(headers.get_all("set-cookie").iter().count(), 2);
+    /// ```
+    #[cfg(feature = "http")]
+    pub fn to_header_map(&self) -> ClientResult<http::HeaderMap> {
+        let mut map = http::HeaderMap::with_capacity(self.len());
+        for (name, value) in self.iter() {
+            let name = http::HeaderName::from_bytes(name.as_bytes()).map_err(http::Error::from)?;
+            let value = http::HeaderValue::from_str(value).map_err(http::Error::from)?;
+            map.append(name, value);
+        }
+        Ok(map)
+    }
+}
+
+#[cfg(feature = "http")]
+impl TryFrom<&Headers> for http::HeaderMap {
+    type Error = ClientError;
+
+    fn try_from(headers: &Headers) -> Result<Self, Self::Error> {
+        headers.to_header_map()
+    }
+}
+
+/// Parse the stdout bytes of fastcgi response, within the default
+/// [HeaderLimits].
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::response::parse::parse;
+///
+/// let parsed = parse(b"Status: 404 Not Found\r\nContent-type: text/html\r\n\r\nhello").unwrap();
+/// assert_eq!(parsed.status, 404);
+/// assert_eq!(parsed.headers.get("Content-Type"), Some("text/html"));
+/// assert_eq!(parsed.body, b"hello");
+/// ```
+pub fn parse(stdout: &[u8]) -> ClientResult<ParsedResponse> {
+    parse_with_limits(stdout, &HeaderLimits::default())
+}
+
+/// Like [parse], but within the `limits`.
+pub fn parse_with_limits(stdout: &[u8], limits: &HeaderLimits) -> ClientResult<ParsedResponse> {
+    let (header_section, body) = split_header_section(stdout)?;
+    let ResponseHead { status, headers } = parse_header_section(header_section, limits)?;
+    Ok(ParsedResponse {
+        status,
+        headers,
+        body: body.to_vec(),
+    })
+}
+
+/// Parse the header section of CGI response, the `Status` header is taken as
+/// the status code.
+pub(crate) fn parse_header_section(
+    header_section: &[u8], limits: &HeaderLimits,
+) -> ClientResult<ResponseHead> {
+    let header_section = header_section_str(header_section, limits)?;
+    let mut headers = parse_headers(header_section.split('\n'), limits)?;
+
+    let mut status = None;
+    headers.retain(|(name, value)| {
+        if name.eq_ignore_ascii_case("status") {
+            status = Some(value.clone());
+            false
+        } else {
+            true
+        }
+    });
+    let headers = Headers(headers);
+
+    let status = match status {
+        Some(status) => parse_status(&status)?,
+        None if headers.get("location").is_some() => REDIRECT_STATUS,
+        None => DEFAULT_STATUS,
+    };
+
+    Ok(ResponseHead { status, headers })
+}
+
+/// Parse the stdout bytes of NPH (non-parsed headers) script, which is a raw
+/// http response beginning with the status line, such as `HTTP/1.1 200 OK`,
+/// rather than CGI headers, the headers are kept as is, within the default
+/// [HeaderLimits].
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::response::parse::parse_nph;
+///
+/// let parsed =
+///     parse_nph(b"HTTP/1.1 404 Not Found\r\nContent-type: text/html\r\n\r\nhello").unwrap();
+/// assert_eq!(parsed.status, 404);
+/// assert_eq!(parsed.headers.get("Content-Type"), Some("text/html"));
+/// assert_eq!(parsed.body, b"hello");
+/// ```
+pub fn parse_nph(stdout: &[u8]) -> ClientResult<ParsedResponse> {
+    let limits = HeaderLimits::default();
+    let (header_section, body) = split_header_section(stdout)?;
+    let header_section = header_section_str(header_section, &limits)?;
+    let mut lines = header_section.split('\n');
+
+    let status_line = lines.next().unwrap_or_default();
+    let status_line = status_line.strip_suffix('\r').unwrap_or(status_line);
+    let status = match status_line.split_once(' ') {
+        Some((version, status)) if version.starts_with("HTTP/") => parse_status(status)?,
+        _ => return Err(invalid(format!("invalid status line `{}`", status_line))),
+    };
+
+    Ok(ParsedResponse {
+        status,
+        headers: Headers(parse_headers(lines, &limits)?),
+        body: body.to_vec(),
+    })
+}
+
+/// Parse the header lines, the names are kept as is, the folded lines are
+/// joined to the previous header.
+fn parse_headers<'a>(
+    lines: impl Iterator<Item = &'a str>, limits: &HeaderLimits,
+) -> ClientResult<Vec<(String, String)>> {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in lines {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if line.is_empty() {
+            continue;
+        }
+
+        // Folded header, continuation of the previous header value.
+        if line.starts_with([' ', '\t']) {
+            let (_, value) = headers
+                .last_mut()
+                .ok_or_else(|| invalid("folded line without header"))?;
+            value.push(' ');
+            value.push_str(line.trim());
+            continue;
+        }
+
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| invalid(format!("malformed header line `{}`", line)))?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(invalid("empty header name"));
+        }
+        if headers.len() == limits.max_count {
+            return Err(ClientError::TooManyHeaders {
+                limit: limits.max_count,
+            });
+        }
+        headers.push((name.to_owned(), value.trim().to_owned()));
+    }
+    Ok(headers)
+}
+
+/// Drain the [ResponseStream] and parse the stdout, the stderr is discarded.
+pub async fn parse_stream<S: AsyncRead + Unpin>(
+    mut stream: ResponseStream<S>,
+) -> ClientResult<ParsedResponse> {
+    let mut stdout = Vec::new();
+    while let Some(content) = stream.next().await {
+        if let Content::Stdout(out) = content? {
+            stdout.extend_from_slice(out);
+        }
+    }
+    parse(&stdout)
+}
+
+/// Read the [ResponseStream] until the end of header section, return the
+/// parsed status and headers, and the reader of the remaining stdout as body,
+/// so the headers can be forwarded before the body is received.
+///
+/// Returns [ClientError::InvalidCgiResponse] if the stdout ends before the
+/// header section ends, the header section is read within the default
+/// [HeaderLimits].
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{response::parse::parse_head, Client, Params, Request};
+/// use tokio::{io, net::TcpStream};
+///
+/// async fn head() {
+///     let stream = TcpStream::connect(("127.0.0.1", 9000)).await.unwrap();
+///     let stream = Client::new(stream)
+///         .execute_once_stream(Request::new(Params::default(), io::empty()))
+///         .await
+///         .unwrap();
+///     let (head, mut body) = parse_head(stream).await.unwrap();
+///     println!("{} {:?}", head.status, head.headers);
+///     io::copy(&mut body, &mut io::stdout()).await.unwrap();
+/// }
+/// ```
+pub async fn parse_head<S: AsyncRead + Unpin>(
+    stream: ResponseStream<S>,
+) -> ClientResult<(ResponseHead, BodyReader<S>)> {
+    parse_head_with_limits(stream, &HeaderLimits::default()).await
+}
+
+/// Like [parse_head], but within the `limits`, the reading stops once the
+/// header section exceeds the limit.
+pub async fn parse_head_with_limits<S: AsyncRead + Unpin>(
+    mut stream: ResponseStream<S>, limits: &HeaderLimits,
+) -> ClientResult<(ResponseHead, BodyReader<S>)> {
+    let mut buf = Vec::new();
+    loop {
+        if let Some((header_end, body_start)) = find_header_end(&buf) {
+            let head = parse_header_section(&buf[..header_end], limits)?;
+            buf.drain(..body_start);
+            let body = BodyReader {
+                stream,
+                buf,
+                buf_pos: 0,
+                pos: 0,
+                len: 0,
+            };
+            return Ok((head, body));
+        }
+        if buf.len() > limits.max_bytes {
+            return Err(ClientError::HeadersTooLarge {
+                limit: limits.max_bytes,
+            });
+        }
+
+        match stream.next().await {
+            Some(Ok(Content::Stdout(out))) => buf.extend_from_slice(out),
+            Some(Ok(Content::Stderr(err))) => {
+                debug!(stderr = ?String::from_utf8_lossy(err), "Discard stderr.");
+            }
+            Some(Ok(Content::End { .. })) | None => {
+                return Err(invalid("header section isn't terminated by empty line"));
+            }
+            Some(Err(err)) => return Err(err),
+        }
+    }
+}
+
+/// Status and headers of CGI response, returned by [parse_head].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseHead {
+    /// Status code from the `Status` header.
+    pub status: u16,
+    /// Headers without the `Status` header.
+    pub headers: Headers,
+}
+
+impl ResponseHead {
+    /// The status code as `http::StatusCode`, `200 OK` if the CGI response
+    /// has no `Status` header.
+    #[cfg(feature = "http")]
+    pub fn status_code(&self) -> ClientResult<http::StatusCode> {
+        Ok(http::StatusCode::from_u16(self.status).map_err(http::Error::from)?)
+    }
+}
+
+/// Body of CGI response returned by [parse_head], implementing `AsyncRead`
+/// over the stdout after the header section, the stderr is discarded.
+pub struct BodyReader<S: AsyncRead + Unpin> {
+    stream: ResponseStream<S>,
+    /// The body read along with the header section.
+    buf: Vec<u8>,
+    buf_pos: usize,
+    pos: usize,
+    len: usize,
+}
+
+impl<S: AsyncRead + Unpin> BodyReader<S> {
+    /// See [ResponseStream::end_request].
+    pub fn end_request(&self) -> Option<RequestEnd> {
+        self.stream.end_request()
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for BodyReader<S> {
+    fn poll_read(
+        self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.buf_pos < this.buf.len() {
+            let n = buf.remaining().min(this.buf.len() - this.buf_pos);
+            buf.put_slice(&this.buf[this.buf_pos..this.buf_pos + n]);
+            this.buf_pos += n;
+            return Poll::Ready(Ok(()));
+        }
+        this.stream
+            .poll_read_stdout(cx, &mut this.pos, &mut this.len, buf)
+    }
+}
+
+/// Split stdout by the first empty line, both `\r\n\r\n` and `\n\n` are
+/// accepted.
+pub(crate) fn split_header_section(stdout: &[u8]) -> ClientResult<(&[u8], &[u8])> {
+    let (header_end, body_start) = find_header_end(stdout)
+        .ok_or_else(|| invalid("header section isn't terminated by empty line"))?;
+    Ok((&stdout[..header_end], &stdout[body_start..]))
+}
+
+/// Find the first empty line, return the end of header section and the start
+/// of body.
+fn find_header_end(stdout: &[u8]) -> Option<(usize, usize)> {
+    let mut start = 0;
+    while let Some(pos) = stdout[start..].iter().position(|b| *b == b'\n') {
+        let line_end = start + pos;
+        let line = &stdout[start..line_end];
+        if line.is_empty() || line == b"\r" {
+            return Some((start, line_end + 1));
+        }
+        start = line_end + 1;
+    }
+    None
+}
+
+/// Whether begins with valid zlib header, of deflate method and checksum.
+#[cfg(feature = "flate2")]
+fn is_zlib(body: &[u8]) -> bool {
+    match body {
+        [cmf, flg, ..] => cmf & 0x0f == 8 && u16::from_be_bytes([*cmf, *flg]) % 31 == 0,
+        _ => false,
+    }
+}
+
+/// Check the length of header section, which must be valid UTF-8.
+fn header_section_str<'a>(
+    header_section: &'a [u8], limits: &HeaderLimits,
+) -> ClientResult<&'a str> {
+    if header_section.len() > limits.max_bytes {
+        return Err(ClientError::HeadersTooLarge {
+            limit: limits.max_bytes,
+        });
+    }
+    str::from_utf8(header_section).map_err(|_| invalid("non UTF-8 headers"))
+}
+
+fn parse_status(status: &str) -> ClientResult<u16> {
+    let code = status.split_whitespace().next().unwrap_or_default();
+    match code.parse::<u16>() {
+        Ok(code) if (100..=999).contains(&code) => Ok(code),
+        _ => Err(invalid(format!("invalid status `{}`", status))),
+    }
+}
+
+fn invalid(reason: impl Into<String>) -> ClientError {
+    ClientError::InvalidCgiResponse {
+        reason: reason.into(),
+    }
+}
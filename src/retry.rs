@@ -0,0 +1,141 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Retry the requests rejected by the overloaded fastcgi server.
+
+use crate::{ClientError, ClientResult};
+use std::{
+    collections::hash_map::RandomState,
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+use tokio::time;
+use tracing::debug;
+
+/// Policy of retrying the request when the server answers
+/// `FCGI_OVERLOADED`, the backoff is doubled on every retry.
+///
+/// The request is built again for every attempt, because the stdin is
+/// consumed.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{connect::TcpConnector, pool::Pool, retry::RetryPolicy, Params, Request};
+/// use std::time::Duration;
+/// use tokio::io;
+///
+/// async fn retry() {
+///     let pool = Pool::new(TcpConnector::new("127.0.0.1:9000"));
+///     let policy = RetryPolicy::default()
+///         .max_retries(5)
+///         .initial_backoff(Duration::from_millis(50));
+///     let params = Params::default();
+///
+///     let output = policy
+///         .retry(|| pool.execute(Request::new(&params, io::empty())))
+///         .await
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct RetryPolicy {
+    /// The max times of retrying, the error is returned after exceeded.
+    pub max_retries: u32,
+    /// The backoff before the first retry.
+    pub initial_backoff: Duration,
+    /// The upper bound of backoff.
+    pub max_backoff: Duration,
+    /// Randomize the backoff in `[backoff / 2, backoff]`, so the retries of
+    /// concurrent requests are spread.
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Run the request future built by `f`, run again after backoff if the
+    /// server is overloaded.
+    pub async fn retry<F, Fut, T>(&self, mut f: F) -> ClientResult<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = ClientResult<T>>,
+    {
+        let mut retries = 0;
+        loop {
+            match f().await {
+                Err(err) if self.should_retry(&err, retries) => {
+                    self.sleep(retries).await;
+                    retries += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    pub(crate) fn should_retry(&self, err: &ClientError, retries: u32) -> bool {
+        retries < self.max_retries && matches!(err, ClientError::EndRequestOverloaded { .. })
+    }
+
+    pub(crate) async fn sleep(&self, retries: u32) {
+        let backoff = self.backoff(retries);
+        debug!(retries, ?backoff, "Server overloaded, retry after backoff.");
+        time::sleep(backoff).await;
+    }
+
+    /// The backoff before the retry, `retries` is the count of retries done.
+    fn backoff(&self, retries: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(retries))
+            .min(self.max_backoff);
+        if self.jitter {
+            let random = RandomState::new().build_hasher().finish();
+            backoff / 2 + backoff.mul_f64((random % 1000) as f64 / 2000.)
+        } else {
+            backoff
+        }
+    }
+}
@@ -0,0 +1,732 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fastcgi server, run rust applications behind web servers like nginx as
+//! fastcgi application, of Responder, Authorizer or Filter role.
+
+pub mod listen;
+#[cfg(feature = "tower")]
+pub mod tower;
+
+use self::listen::Listener;
+use crate::{
+    meta::{
+        BeginRequest, EndRequest, Header, ParamPairs, ProtocolStatus, RequestType, Role,
+        HEADER_LEN, NULL_REQUEST_ID,
+    },
+    values::Values,
+    Params,
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    fmt::Debug,
+    future::{poll_fn, Future},
+    io,
+    pin::{pin, Pin},
+    str,
+    sync::{Arc, Mutex},
+    task::Poll,
+    time::Duration,
+};
+use tokio::{
+    io::{split, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpListener,
+    sync::mpsc,
+    time,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+/// Request received by fastcgi server.
+#[non_exhaustive]
+pub struct ServerRequest {
+    pub id: u16,
+    pub role: Role,
+    pub keep_alive: bool,
+    pub params: Params<'static>,
+    pub stdin: Vec<u8>,
+    /// The file data of Filter role, empty for the other roles.
+    pub data: Vec<u8>,
+    /// Cancelled when the request is aborted by `FCGI_ABORT_REQUEST`, then
+    /// the handler is dropped, the tasks spawned by handler can stop by it.
+    pub cancel_token: CancellationToken,
+}
+
+impl Debug for ServerRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("ServerRequest")
+            .field("id", &self.id)
+            .field("role", &self.role)
+            .field("keep_alive", &self.keep_alive)
+            .field("params", &self.params)
+            .field("stdin", &str::from_utf8(&self.stdin))
+            .field("data", &str::from_utf8(&self.data))
+            .field("cancelled", &self.cancel_token.is_cancelled())
+            .finish()
+    }
+}
+
+/// Response replied by fastcgi server, the stdout should be CGI response.
+#[derive(Default, Clone)]
+#[non_exhaustive]
+pub struct ServerResponse {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub app_status: u32,
+}
+
+impl ServerResponse {
+    pub fn new(stdout: impl Into<Vec<u8>>) -> Self {
+        Self {
+            stdout: stdout.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn stderr(mut self, stderr: impl Into<Vec<u8>>) -> Self {
+        self.stderr = stderr.into();
+        self
+    }
+
+    pub fn app_status(mut self, app_status: u32) -> Self {
+        self.app_status = app_status;
+        self
+    }
+}
+
+impl Debug for ServerResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("ServerResponse")
+            .field("stdout", &str::from_utf8(&self.stdout))
+            .field("stderr", &str::from_utf8(&self.stderr))
+            .field("app_status", &self.app_status)
+            .finish()
+    }
+}
+
+/// Handler of fastcgi requests, implemented for async functions
+/// `Fn(ServerRequest) -> impl Future<Output = ServerResponse>`.
+pub trait Handler: Send + Sync + 'static {
+    type Future: Future<Output = ServerResponse> + Send + 'static;
+
+    fn call(&self, request: ServerRequest) -> Self::Future;
+}
+
+impl<F, Fut> Handler for F
+where
+    F: Fn(ServerRequest) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ServerResponse> + Send + 'static,
+{
+    type Future = Fut;
+
+    fn call(&self, request: ServerRequest) -> Self::Future {
+        self(request)
+    }
+}
+
+/// Type-erased [Handler], for the roles other than Responder.
+type BoxHandler = Arc<
+    dyn Fn(ServerRequest) -> Pin<Box<dyn Future<Output = ServerResponse> + Send>> + Send + Sync,
+>;
+
+/// Compute the values replied to `FCGI_GET_VALUES`.
+type ValuesFn = Arc<dyn Fn() -> Values + Send + Sync>;
+
+fn box_handler(handler: impl Handler) -> BoxHandler {
+    Arc::new(move |request| Box::pin(handler.call(request)))
+}
+
+/// Limits of each connection served by [Server], so the memory used by the
+/// misbehaving peer is bounded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ServerLimits {
+    /// Max requests receiving or running concurrently, the new requests
+    /// exceeded are replied `FCGI_OVERLOADED`.
+    pub max_requests: usize,
+    /// Max length of the encoded params of each request.
+    pub max_params_length: usize,
+    /// Max length of the stdin, and of the data for Filter role, of each
+    /// request.
+    pub max_stdin_length: usize,
+}
+
+impl Default for ServerLimits {
+    fn default() -> Self {
+        Self {
+            max_requests: 128,
+            max_params_length: 1024 * 1024,
+            max_stdin_length: 16 * 1024 * 1024,
+        }
+    }
+}
+
+impl ServerLimits {
+    pub fn max_requests(mut self, max_requests: usize) -> Self {
+        self.max_requests = max_requests;
+        self
+    }
+
+    pub fn max_params_length(mut self, max_params_length: usize) -> Self {
+        self.max_params_length = max_params_length;
+        self
+    }
+
+    pub fn max_stdin_length(mut self, max_stdin_length: usize) -> Self {
+        self.max_stdin_length = max_stdin_length;
+        self
+    }
+}
+
+/// Fastcgi server, handle the Responder requests by the handler, and the
+/// Authorizer and Filter requests by the handlers registered by
+/// [authorizer](Server::authorizer) and [filter](Server::filter), the
+/// requests of roles without handler are replied `FCGI_UNKNOWN_ROLE`.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::server::{Server, ServerRequest, ServerResponse};
+/// use tokio::net::TcpListener;
+///
+/// async fn serve() {
+///     let listener = TcpListener::bind(("127.0.0.1", 9000)).await.unwrap();
+///     let server = Server::new(|request: ServerRequest| async move {
+///         ServerResponse::new("Content-type: text/plain\r\n\r\nhello")
+///     });
+///     server.serve_tcp(listener).await.unwrap();
+/// }
+/// ```
+pub struct Server<H> {
+    handler: Arc<H>,
+    authorizer: Option<BoxHandler>,
+    filter: Option<BoxHandler>,
+    values: ValuesFn,
+    limits: ServerLimits,
+}
+
+impl<H> Clone for Server<H> {
+    fn clone(&self) -> Self {
+        Self {
+            handler: self.handler.clone(),
+            authorizer: self.authorizer.clone(),
+            filter: self.filter.clone(),
+            values: self.values.clone(),
+            limits: self.limits,
+        }
+    }
+}
+
+impl<H: Handler> Server<H> {
+    pub fn new(handler: H) -> Self {
+        Self {
+            handler: Arc::new(handler),
+            authorizer: None,
+            filter: None,
+            values: Arc::new(|| Values {
+                mpxs_conns: Some(true),
+                ..Default::default()
+            }),
+            limits: ServerLimits::default(),
+        }
+    }
+
+    /// Handle the Authorizer requests by the handler, the status `200` of
+    /// response authorizes the request, see the `Variable-` headers in
+    /// fastcgi spec.
+    pub fn authorizer(mut self, handler: impl Handler) -> Self {
+        self.authorizer = Some(box_handler(handler));
+        self
+    }
+
+    /// Handle the Filter requests by the handler, the file data is in
+    /// [ServerRequest::data].
+    pub fn filter(mut self, handler: impl Handler) -> Self {
+        self.filter = Some(box_handler(handler));
+        self
+    }
+
+    /// Reply `FCGI_GET_VALUES` by the values, the values which are `None`
+    /// are omitted, only `FCGI_MPXS_CONNS=1` is replied by default.
+    pub fn values(self, values: Values) -> Self {
+        self.values_with(move || values.clone())
+    }
+
+    /// Like [values](Server::values), but the values are computed for each
+    /// query, such as from the current limits of runtime.
+    pub fn values_with(mut self, f: impl Fn() -> Values + Send + Sync + 'static) -> Self {
+        self.values = Arc::new(f);
+        self
+    }
+
+    /// Limits of each connection, see [ServerLimits].
+    pub fn limits(mut self, limits: ServerLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    fn has_role(&self, role: Role) -> bool {
+        match role {
+            Role::Responder => true,
+            Role::Authorizer => self.authorizer.is_some(),
+            Role::Filter => self.filter.is_some(),
+        }
+    }
+
+    async fn dispatch(&self, request: ServerRequest) -> ServerResponse {
+        match (request.role, &self.authorizer, &self.filter) {
+            (Role::Authorizer, Some(authorizer), _) => authorizer(request).await,
+            (Role::Filter, _, Some(filter)) => filter(request).await,
+            _ => self.handler.call(request).await,
+        }
+    }
+
+    /// Accept connections from the listener, serve each connection in a new
+    /// task.
+    pub async fn serve(&self, listener: Listener) -> io::Result<()> {
+        match listener {
+            Listener::Tcp(listener) => self.serve_tcp(listener).await,
+            #[cfg(unix)]
+            Listener::Unix(listener) => self.serve_unix(listener).await,
+        }
+    }
+
+    /// Accept connections from tcp listener, serve each connection in a new
+    /// task.
+    ///
+    /// The errors of accepting, such as too many open files, are logged and
+    /// retried after backoff, so the serving isn't stopped.
+    pub async fn serve_tcp(&self, listener: TcpListener) -> io::Result<()> {
+        let mut backoff = AcceptBackoff::default();
+        loop {
+            match listener.accept().await {
+                Ok((stream, addr)) => {
+                    backoff.reset();
+                    debug!(%addr, "Accept connection.");
+                    self.spawn_connection(stream);
+                }
+                Err(err) => backoff.wait(err).await,
+            }
+        }
+    }
+
+    /// Accept connections from unix socket listener, serve each connection in
+    /// a new task, the errors of accepting are retried like
+    /// [serve_tcp](Server::serve_tcp).
+    #[cfg(unix)]
+    pub async fn serve_unix(&self, listener: tokio::net::UnixListener) -> io::Result<()> {
+        let mut backoff = AcceptBackoff::default();
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    backoff.reset();
+                    debug!("Accept unix connection.");
+                    self.spawn_connection(stream);
+                }
+                Err(err) => backoff.wait(err).await,
+            }
+        }
+    }
+
+    fn spawn_connection<S: AsyncRead + AsyncWrite + Send + Unpin + 'static>(&self, stream: S) {
+        let server = self.clone();
+        tokio::spawn(async move {
+            if let Err(err) = server.serve_connection(stream).await {
+                warn!(?err, "Serve connection failed.");
+            }
+        });
+    }
+
+    /// Serve the requests of a connection, until the connection is closed by
+    /// peer or a request isn't keep alive.
+    ///
+    /// The connection is multiplexed, the records of the requests can be
+    /// interleaved, the handlers of complete requests are run concurrently,
+    /// and each response is written as soon as the handler returns. The
+    /// request aborted by `FCGI_ABORT_REQUEST` is replied `EndRequest` at
+    /// once, and the handler is cancelled, see
+    /// [ServerRequest::cancel_token].
+    pub async fn serve_connection<S: AsyncRead + AsyncWrite + Unpin>(
+        &self, stream: S,
+    ) -> io::Result<()> {
+        let (reader, writer) = split(stream);
+        let (reply_tx, reply_rx) = mpsc::unbounded_channel();
+
+        // The writing ends after the reading ended, or the request not keep
+        // alive received, and all the handlers replied, as the senders are
+        // dropped.
+        let mut read = pin!(self.read_records(reader, reply_tx));
+        let mut write = pin!(write_replies(writer, reply_rx));
+        let mut read_done = false;
+        poll_fn(|cx| {
+            if !read_done {
+                if let Poll::Ready(result) = read.as_mut().poll(cx) {
+                    result?;
+                    read_done = true;
+                }
+            }
+            write.as_mut().poll(cx)
+        })
+        .await
+    }
+
+    /// Receive the records of requests, and spawn the handlers of the complete
+    /// requests, the encoded records to reply are sent to `reply_tx`.
+    ///
+    /// After the request not keep alive received, `reply_tx` is dropped, and
+    /// only the abort of it is handled.
+    async fn read_records<R: AsyncRead + Unpin>(
+        &self, mut reader: R, reply_tx: mpsc::UnboundedSender<Vec<u8>>,
+    ) -> io::Result<()> {
+        let mut reply_tx = Some(reply_tx);
+        let mut pending: HashMap<u16, PendingRequest> = HashMap::new();
+        let running: Running = Default::default();
+
+        loop {
+            // Keep the raw type, which is lost in `Header` if unknown.
+            let mut buf = [0; HEADER_LEN];
+            match reader.read_exact(&mut buf).await {
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+                Err(err) => return Err(err),
+            }
+            let header = Header::new_from_buf(&buf);
+            let content = header.read_content_from_stream(&mut reader).await?;
+            let id = header.request_id;
+            debug!(id, ?header, "Receive from stream.");
+
+            if let RequestType::AbortRequest = header.r#type {
+                let cancel_token = running.lock().unwrap().remove(&id);
+                if let Some(cancel_token) = cancel_token {
+                    debug!(id, "Cancel aborted request.");
+                    cancel_token.cancel();
+                } else if let Some(request) = pending.remove(&id) {
+                    debug!(id, "Abort receiving request.");
+                    let mut reply = Vec::new();
+                    write_end_request(&mut reply, id, 0, ProtocolStatus::RequestComplete).await?;
+                    send_reply(&mut reply_tx, reply, request.keep_alive);
+                }
+                continue;
+            }
+            let Some(tx) = reply_tx.clone() else {
+                debug!(id, ?header, "Ignore record after request not keep alive.");
+                continue;
+            };
+
+            if id == NULL_REQUEST_ID {
+                let mut reply = Vec::new();
+                match header.r#type {
+                    RequestType::GetValues => {
+                        let values = (self.values)();
+                        write_get_values_result(&mut reply, &values, &content).await?;
+                    }
+                    _ => write_unknown_type(&mut reply, buf[1]).await?,
+                }
+                let _ = tx.send(reply);
+                continue;
+            }
+
+            match header.r#type {
+                RequestType::BeginRequest => {
+                    let (role, keep_alive) = BeginRequest::parse_content(&content)?;
+                    if pending.contains_key(&id) || running.lock().unwrap().contains_key(&id) {
+                        debug!(id, "Ignore begin of active request.");
+                        continue;
+                    }
+                    let requests = pending.len() + running.lock().unwrap().len();
+                    if requests >= self.limits.max_requests {
+                        warn!(id, requests, "Reply overloaded, too many requests.");
+                        let mut reply = Vec::new();
+                        write_end_request(&mut reply, id, 0, ProtocolStatus::Overloaded).await?;
+                        send_reply(&mut reply_tx, reply, keep_alive);
+                        continue;
+                    }
+                    match Role::from_u16(role).filter(|role| self.has_role(*role)) {
+                        Some(role) => {
+                            pending.insert(id, PendingRequest::new(id, role, keep_alive));
+                        }
+                        None => {
+                            debug!(id, role, "Reply unknown role.");
+                            let mut reply = Vec::new();
+                            write_end_request(&mut reply, id, 0, ProtocolStatus::UnknownRole)
+                                .await?;
+                            send_reply(&mut reply_tx, reply, keep_alive);
+                        }
+                    }
+                }
+                RequestType::Params => {
+                    let Some(request) = pending.get_mut(&id) else {
+                        debug!(id, "Ignore params of unknown request.");
+                        continue;
+                    };
+                    if request.params.len() + content.len() > self.limits.max_params_length {
+                        let request = pending.remove(&id).unwrap();
+                        warn!(id, "Reject request, params too large.");
+                        reject_too_large(&mut reply_tx, request).await?;
+                        continue;
+                    }
+                    request.params.extend(content);
+                }
+                RequestType::Stdin | RequestType::Data => {
+                    let Some(request) = pending.get_mut(&id) else {
+                        debug!(id, "Ignore stream of unknown request.");
+                        continue;
+                    };
+                    let is_data = matches!(header.r#type, RequestType::Data);
+                    let stream = if is_data {
+                        &mut request.data
+                    } else {
+                        &mut request.stdin
+                    };
+                    if stream.len() + content.len() > self.limits.max_stdin_length {
+                        let request = pending.remove(&id).unwrap();
+                        warn!(id, "Reject request, stdin too large.");
+                        reject_too_large(&mut reply_tx, request).await?;
+                        continue;
+                    }
+                    stream.extend(&content);
+                    // The request is complete at the end of stdin, or the end
+                    // of data which follows stdin for Filter role.
+                    let is_filter = matches!(request.role, Role::Filter);
+                    if !content.is_empty() || is_data != is_filter {
+                        continue;
+                    }
+
+                    let request = pending.remove(&id).unwrap().into_request()?;
+                    running
+                        .lock()
+                        .unwrap()
+                        .insert(id, request.cancel_token.clone());
+                    if !request.keep_alive {
+                        reply_tx = None;
+                    }
+                    self.spawn_handler(request, tx, running.clone());
+                }
+                r#type => {
+                    debug!(id, %r#type, "Ignore record.");
+                }
+            }
+        }
+    }
+
+    /// Run the handler in a new task, and send the encoded response to
+    /// `reply_tx`, or only `EndRequest` if the request is aborted.
+    fn spawn_handler(
+        &self, request: ServerRequest, reply_tx: mpsc::UnboundedSender<Vec<u8>>, running: Running,
+    ) {
+        let server = self.clone();
+        let id = request.id;
+        let cancel_token = request.cancel_token.clone();
+        let mut handler = tokio::spawn(async move { server.dispatch(request).await });
+        tokio::spawn(async move {
+            let result = {
+                let mut cancelled = pin!(cancel_token.cancelled());
+                poll_fn(|cx| match cancelled.as_mut().poll(cx) {
+                    Poll::Ready(()) => Poll::Ready(None),
+                    Poll::Pending => Pin::new(&mut handler).poll(cx).map(Some),
+                })
+                .await
+            };
+            running.lock().unwrap().remove(&id);
+
+            let mut reply = Vec::new();
+            let written = match result {
+                Some(result) => {
+                    // Reply the request anyway, so the web server isn't
+                    // waiting for the panicked handler.
+                    let response = result.unwrap_or_else(|err| {
+                        warn!(id, ?err, "Handler failed.");
+                        ServerResponse::default().app_status(1)
+                    });
+                    write_response(&mut reply, id, response).await
+                }
+                None => {
+                    handler.abort();
+                    write_end_request(&mut reply, id, 0, ProtocolStatus::RequestComplete).await
+                }
+            };
+            if written.is_ok() {
+                let _ = reply_tx.send(reply);
+            }
+        });
+    }
+}
+
+/// Backoff of accepting after errors, doubled from 5ms up to 1s like Go
+/// `net/http`, and reset once accepted.
+#[derive(Default)]
+struct AcceptBackoff(Option<Duration>);
+
+impl AcceptBackoff {
+    const INITIAL: Duration = Duration::from_millis(5);
+    const MAX: Duration = Duration::from_secs(1);
+
+    fn reset(&mut self) {
+        self.0 = None;
+    }
+
+    async fn wait(&mut self, err: io::Error) {
+        let backoff = self
+            .0
+            .map_or(Self::INITIAL, |backoff| (backoff * 2).min(Self::MAX));
+        warn!(
+            ?err,
+            ?backoff,
+            "Accept connection failed, retry after backoff."
+        );
+        self.0 = Some(backoff);
+        time::sleep(backoff).await;
+    }
+}
+
+/// The cancel tokens of the requests which handlers are running.
+type Running = Arc<Mutex<HashMap<u16, CancellationToken>>>;
+
+/// Send the reply, and drop the sender if the request isn't keep alive.
+fn send_reply(
+    reply_tx: &mut Option<mpsc::UnboundedSender<Vec<u8>>>, reply: Vec<u8>, keep_alive: bool,
+) {
+    if let Some(tx) = reply_tx {
+        let _ = tx.send(reply);
+    }
+    if !keep_alive {
+        *reply_tx = None;
+    }
+}
+
+/// Reply the request exceeding [ServerLimits] by `413` without running the
+/// handler, the records of it received after are ignored.
+async fn reject_too_large(
+    reply_tx: &mut Option<mpsc::UnboundedSender<Vec<u8>>>, request: PendingRequest,
+) -> io::Result<()> {
+    let response = ServerResponse::new("Status: 413 Payload Too Large\r\n\r\n").app_status(1);
+    let mut reply = Vec::new();
+    write_response(&mut reply, request.id, response).await?;
+    send_reply(reply_tx, reply, request.keep_alive);
+    Ok(())
+}
+
+/// Request which records are receiving.
+struct PendingRequest {
+    id: u16,
+    role: Role,
+    keep_alive: bool,
+    params: Vec<u8>,
+    stdin: Vec<u8>,
+    data: Vec<u8>,
+}
+
+impl PendingRequest {
+    fn new(id: u16, role: Role, keep_alive: bool) -> Self {
+        Self {
+            id,
+            role,
+            keep_alive,
+            params: Vec::new(),
+            stdin: Vec::new(),
+            data: Vec::new(),
+        }
+    }
+
+    fn into_request(self) -> io::Result<ServerRequest> {
+        Ok(ServerRequest {
+            id: self.id,
+            role: self.role,
+            keep_alive: self.keep_alive,
+            params: ParamPairs::from_content(&self.params)?.into_params(),
+            stdin: self.stdin,
+            data: self.data,
+            cancel_token: CancellationToken::new(),
+        })
+    }
+}
+
+/// Write the encoded replies to stream, until all the senders are dropped.
+async fn write_replies<W: AsyncWrite + Unpin>(
+    mut writer: W, mut reply_rx: mpsc::UnboundedReceiver<Vec<u8>>,
+) -> io::Result<()> {
+    while let Some(reply) = reply_rx.recv().await {
+        writer.write_all(&reply).await?;
+        writer.flush().await?;
+    }
+    Ok(())
+}
+
+async fn write_response<W: AsyncWrite + Unpin>(
+    writer: &mut W, id: u16, response: ServerResponse,
+) -> io::Result<()> {
+    write_stream(writer, RequestType::Stdout, id, &response.stdout).await?;
+    if !response.stderr.is_empty() {
+        write_stream(writer, RequestType::Stderr, id, &response.stderr).await?;
+    }
+    write_end_request(
+        writer,
+        id,
+        response.app_status,
+        ProtocolStatus::RequestComplete,
+    )
+    .await
+}
+
+/// Write the content as stream records, terminated by an empty record.
+async fn write_stream<W: AsyncWrite + Unpin>(
+    writer: &mut W, r#type: RequestType, id: u16, content: &[u8],
+) -> io::Result<()> {
+    for chunk in content.chunks(crate::meta::MAX_LENGTH) {
+        Header::write_record(r#type.clone(), id, writer, chunk).await?;
+    }
+    Header::write_record(r#type, id, writer, &[]).await
+}
+
+/// Reply the values of the names queried by `FCGI_GET_VALUES`, the values
+/// unknown are omitted.
+async fn write_get_values_result<W: AsyncWrite + Unpin>(
+    writer: &mut W, values: &Values, content: &[u8],
+) -> io::Result<()> {
+    let names = Values::decode_names(content)?;
+    debug!(?names, "Reply get values.");
+    let content = values.encode(&names).await?;
+    Header::write_record(
+        RequestType::GetValuesResult,
+        NULL_REQUEST_ID,
+        writer,
+        &content,
+    )
+    .await?;
+    writer.flush().await
+}
+
+/// Reply the management record of `r#type` isn't supported.
+async fn write_unknown_type<W: AsyncWrite + Unpin>(writer: &mut W, r#type: u8) -> io::Result<()> {
+    debug!(r#type, "Reply unknown type.");
+    let content = [r#type, 0, 0, 0, 0, 0, 0, 0];
+    Header::write_record(RequestType::UnknownType, NULL_REQUEST_ID, writer, &content).await?;
+    writer.flush().await
+}
+
+async fn write_end_request<W: AsyncWrite + Unpin>(
+    writer: &mut W, id: u16, app_status: u32, protocol_status: ProtocolStatus,
+) -> io::Result<()> {
+    let end_request = EndRequest::new(app_status, protocol_status);
+    debug!(id, ?end_request, "Send to stream.");
+    Header::write_record(
+        RequestType::EndRequest,
+        id,
+        writer,
+        &end_request.to_content(),
+    )
+    .await?;
+    writer.flush().await
+}
@@ -0,0 +1,231 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Listeners of [Server](crate::server::Server), bound to tcp address, unix
+//! socket, or inherited from the process launching the application, such as
+//! the web server (`FCGI_LISTENSOCK_FILENO`) or systemd socket activation
+//! (`LISTEN_FDS`).
+
+use std::{io, net::SocketAddr};
+use tokio::net::{TcpListener, ToSocketAddrs};
+#[cfg(unix)]
+use {
+    socket2::{SockRef, Socket},
+    std::{
+        fs,
+        os::unix::{
+            fs::PermissionsExt,
+            io::{FromRawFd, OwnedFd, RawFd},
+        },
+        path::Path,
+        sync::atomic::{AtomicBool, Ordering},
+    },
+    tokio::net::UnixListener,
+};
+
+/// The fd of listening socket passed by web server, which is the stdin.
+#[cfg(unix)]
+const FCGI_LISTENSOCK_FILENO: RawFd = 0;
+
+/// The first fd passed by systemd socket activation.
+#[cfg(unix)]
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// Whether the inherited fds are taken, so they aren't owned twice.
+#[cfg(unix)]
+static STDIN_TAKEN: AtomicBool = AtomicBool::new(false);
+#[cfg(unix)]
+static LISTEN_FDS_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Options of binding unix socket.
+#[cfg(unix)]
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct UnixOptions {
+    /// The permission bits of socket file, like php-fpm `listen.mode`, the
+    /// web server must have the write permission to connect.
+    pub mode: Option<u32>,
+    /// The owner uid of socket file, like php-fpm `listen.owner`.
+    pub uid: Option<u32>,
+    /// The owner gid of socket file, like php-fpm `listen.group`.
+    pub gid: Option<u32>,
+    /// Remove the existing socket file before binding, such as left by the
+    /// previous process.
+    pub remove_existing: bool,
+}
+
+#[cfg(unix)]
+impl UnixOptions {
+    pub fn mode(mut self, mode: u32) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    pub fn owner(mut self, uid: u32, gid: u32) -> Self {
+        self.uid = Some(uid);
+        self.gid = Some(gid);
+        self
+    }
+
+    pub fn remove_existing(mut self, remove_existing: bool) -> Self {
+        self.remove_existing = remove_existing;
+        self
+    }
+}
+
+/// Listener of tcp or unix socket, served by
+/// [Server::serve](crate::server::Server::serve).
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::server::{listen::Listener, Server, ServerRequest, ServerResponse};
+///
+/// async fn serve() {
+///     // Launched by web server, or listen on tcp port for development.
+///     let listener = match Listener::from_fcgi_stdin().unwrap() {
+///         Some(listener) => listener,
+///         None => Listener::bind_tcp(("127.0.0.1", 9000)).await.unwrap(),
+///     };
+///     let server = Server::new(|request: ServerRequest| async move {
+///         ServerResponse::new("Content-type: text/plain\r\n\r\nhello")
+///     });
+///     server.serve(listener).await.unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl Listener {
+    pub async fn bind_tcp(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Listener::Tcp(TcpListener::bind(addr).await?))
+    }
+
+    /// Bind the unix socket, then apply the mode and ownership of socket
+    /// file.
+    #[cfg(unix)]
+    pub fn bind_unix(path: impl AsRef<Path>, options: &UnixOptions) -> io::Result<Self> {
+        let path = path.as_ref();
+        if options.remove_existing {
+            match fs::remove_file(path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        let listener = UnixListener::bind(path)?;
+        if let Some(mode) = options.mode {
+            fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+        }
+        if options.uid.is_some() || options.gid.is_some() {
+            std::os::unix::fs::chown(path, options.uid, options.gid)?;
+        }
+        Ok(Listener::Unix(listener))
+    }
+
+    /// Take the listening socket passed as stdin by the web server launching
+    /// the application, as fastcgi spec `FCGI_LISTENSOCK_FILENO`, returns
+    /// `None` if stdin isn't a listening socket, or is taken already, even if
+    /// the taking failed.
+    #[cfg(unix)]
+    pub fn from_fcgi_stdin() -> io::Result<Option<Self>> {
+        if STDIN_TAKEN.swap(true, Ordering::AcqRel) {
+            return Ok(None);
+        }
+        // Check the fd before owned, so it isn't closed otherwise. The
+        // listening socket isn't connected, which is tested by `getpeername`
+        // failed with `ENOTCONN` as fastcgi spec.
+        let stdin = io::stdin();
+        let listening = matches!(
+            SockRef::from(&stdin).peer_addr(),
+            Err(err) if err.kind() == io::ErrorKind::NotConnected
+        );
+        if !listening {
+            STDIN_TAKEN.store(false, Ordering::Release);
+            return Ok(None);
+        }
+        // SAFETY: The stdin is taken once, guarded by `STDIN_TAKEN`, which is
+        // left set even if failed, as the fd is closed then and may be reused
+        // by an unrelated descriptor.
+        unsafe { Self::from_raw_fd(FCGI_LISTENSOCK_FILENO) }.map(Some)
+    }
+
+    /// Take the listening sockets passed by systemd socket activation, as
+    /// `LISTEN_PID` and `LISTEN_FDS` environments, returns empty if not
+    /// activated, or taken already.
+    #[cfg(unix)]
+    pub fn from_listen_fds() -> io::Result<Vec<Self>> {
+        let var = |name| {
+            std::env::var(name)
+                .ok()
+                .and_then(|var| var.parse::<u32>().ok())
+        };
+        if var("LISTEN_PID") != Some(std::process::id()) {
+            return Ok(Vec::new());
+        }
+        let count = var("LISTEN_FDS").unwrap_or_default() as RawFd;
+        if LISTEN_FDS_TAKEN.swap(true, Ordering::AcqRel) {
+            return Ok(Vec::new());
+        }
+        (SD_LISTEN_FDS_START..SD_LISTEN_FDS_START + count)
+            // SAFETY: The fds are passed to this process, and taken once,
+            // guarded by `LISTEN_FDS_TAKEN`.
+            .map(|fd| unsafe { Self::from_raw_fd(fd) })
+            .collect()
+    }
+
+    /// Own the listening socket of the fd, the tcp or unix socket is
+    /// detected by the address.
+    ///
+    /// # Safety
+    ///
+    /// The fd must be a valid listening socket, and not owned by others.
+    #[cfg(unix)]
+    pub unsafe fn from_raw_fd(fd: RawFd) -> io::Result<Self> {
+        let socket = Socket::from(OwnedFd::from_raw_fd(fd));
+        socket.set_nonblocking(true)?;
+        if socket.local_addr()?.is_unix() {
+            let listener = std::os::unix::net::UnixListener::from(OwnedFd::from(socket));
+            Ok(Listener::Unix(UnixListener::from_std(listener)?))
+        } else {
+            let listener = std::net::TcpListener::from(socket);
+            Ok(Listener::Tcp(TcpListener::from_std(listener)?))
+        }
+    }
+
+    /// The local address of tcp listener, `None` for unix socket.
+    pub fn local_addr(&self) -> io::Result<Option<SocketAddr>> {
+        match self {
+            Listener::Tcp(listener) => listener.local_addr().map(Some),
+            #[cfg(unix)]
+            Listener::Unix(_) => Ok(None),
+        }
+    }
+}
+
+impl From<TcpListener> for Listener {
+    fn from(listener: TcpListener) -> Self {
+        Listener::Tcp(listener)
+    }
+}
+
+#[cfg(unix)]
+impl From<UnixListener> for Listener {
+    fn from(listener: UnixListener) -> Self {
+        Listener::Unix(listener)
+    }
+}
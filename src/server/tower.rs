@@ -0,0 +1,192 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Adapter serving `tower::Service` of http types as fastcgi Responder, so
+//! the http applications, such as axum routers, can be deployed behind
+//! nginx.
+
+use crate::server::{Handler, ServerRequest, ServerResponse};
+use bytes::Bytes;
+use http_body::Body;
+use http_body_util::{BodyExt, Full};
+use std::{error::Error, fmt::Display, future::Future, pin::Pin};
+use tower_service::Service;
+use tracing::debug;
+
+/// Implement [Handler] on top of `tower::Service<http::Request<Full<Bytes>>>`.
+///
+/// The params are mapped into the method, uri (`REQUEST_URI`, or
+/// `SCRIPT_NAME`, `PATH_INFO` and `QUERY_STRING`), version and headers
+/// (`CONTENT_TYPE`, `CONTENT_LENGTH` and `HTTP_*`) of `http::Request`, the
+/// whole params are inserted into the extensions as well, and the
+/// `http::Response` is written to stdout as CGI response.
+///
+/// The service is cloned for each request, the errors of service are
+/// replied as `500 Internal Server Error`, with the error in stderr.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::server::{tower::TowerHandler, Handler, Server};
+/// use tokio::net::TcpListener;
+///
+/// // Such as `axum::Router`.
+/// async fn serve<S>(service: S)
+/// where
+///     TowerHandler<S>: Handler,
+/// {
+///     let listener = TcpListener::bind(("127.0.0.1", 9000)).await.unwrap();
+///     let server = Server::new(TowerHandler::new(service));
+///     server.serve_tcp(listener).await.unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TowerHandler<S> {
+    service: S,
+}
+
+impl<S> TowerHandler<S> {
+    pub fn new(service: S) -> Self {
+        Self { service }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.service
+    }
+
+    pub fn into_inner(self) -> S {
+        self.service
+    }
+}
+
+impl<S, B> Handler for TowerHandler<S>
+where
+    S: Service<http::Request<Full<Bytes>>, Response = http::Response<B>>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+    S::Error: Display + Send,
+    S::Future: Send,
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn Error + Send + Sync>>,
+{
+    type Future = Pin<Box<dyn Future<Output = ServerResponse> + Send>>;
+
+    fn call(&self, request: ServerRequest) -> Self::Future {
+        let mut service = self.service.clone();
+
+        Box::pin(async move {
+            let request = match http_request(request) {
+                Ok(request) => request,
+                Err(err) => return error_response(http::StatusCode::BAD_REQUEST, err),
+            };
+            if let Err(err) = std::future::poll_fn(|cx| service.poll_ready(cx)).await {
+                return error_response(http::StatusCode::INTERNAL_SERVER_ERROR, err);
+            }
+            match service.call(request).await {
+                Ok(response) => cgi_response(response).await,
+                Err(err) => error_response(http::StatusCode::INTERNAL_SERVER_ERROR, err),
+            }
+        })
+    }
+}
+
+/// Map the params and stdin into `http::Request`.
+fn http_request(request: ServerRequest) -> http::Result<http::Request<Full<Bytes>>> {
+    let params = request.params;
+    let param = |name: &str| params.get(name).map(|value| &**value).unwrap_or_default();
+
+    let uri = match param("REQUEST_URI") {
+        "" => {
+            let mut uri = format!("{}{}", param("SCRIPT_NAME"), param("PATH_INFO"));
+            if !uri.starts_with('/') {
+                uri.insert(0, '/');
+            }
+            if !param("QUERY_STRING").is_empty() {
+                uri = format!("{}?{}", uri, param("QUERY_STRING"));
+            }
+            uri
+        }
+        request_uri => request_uri.to_owned(),
+    };
+    let version = match param("SERVER_PROTOCOL") {
+        "HTTP/0.9" => http::Version::HTTP_09,
+        "HTTP/1.0" => http::Version::HTTP_10,
+        "HTTP/2" | "HTTP/2.0" => http::Version::HTTP_2,
+        "HTTP/3" | "HTTP/3.0" => http::Version::HTTP_3,
+        _ => http::Version::HTTP_11,
+    };
+
+    let mut builder = http::Request::builder()
+        .method(match param("REQUEST_METHOD") {
+            "" => "GET",
+            method => method,
+        })
+        .uri(uri)
+        .version(version);
+    for (name, value) in params.iter() {
+        let name = match name {
+            "CONTENT_TYPE" | "CONTENT_LENGTH" if !value.is_empty() => name.replace('_', "-"),
+            name => match name.strip_prefix("HTTP_") {
+                Some(name) => name.replace('_', "-"),
+                None => continue,
+            },
+        };
+        match (
+            http::HeaderName::try_from(name.to_ascii_lowercase()),
+            http::HeaderValue::try_from(value),
+        ) {
+            (Ok(name), Ok(value)) => builder = builder.header(name, value),
+            _ => debug!(%name, "Skip invalid header."),
+        }
+    }
+
+    builder
+        .extension(params)
+        .body(Full::new(Bytes::from(request.stdin)))
+}
+
+/// Write `http::Response` as CGI response.
+async fn cgi_response<B>(response: http::Response<B>) -> ServerResponse
+where
+    B: Body,
+    B::Error: Into<Box<dyn Error + Send + Sync>>,
+{
+    let (parts, body) = response.into_parts();
+    let body = match body.collect().await {
+        Ok(body) => body.to_bytes(),
+        Err(err) => {
+            let err: Box<dyn Error + Send + Sync> = err.into();
+            return error_response(http::StatusCode::INTERNAL_SERVER_ERROR, err);
+        }
+    };
+
+    let mut stdout = format!("Status: {}\r\n", parts.status).into_bytes();
+    for (name, value) in &parts.headers {
+        stdout.extend_from_slice(name.as_str().as_bytes());
+        stdout.extend_from_slice(b": ");
+        stdout.extend_from_slice(value.as_bytes());
+        stdout.extend_from_slice(b"\r\n");
+    }
+    stdout.extend_from_slice(b"\r\n");
+    stdout.extend_from_slice(&body);
+    ServerResponse::new(stdout)
+}
+
+fn error_response(status: http::StatusCode, err: impl Display) -> ServerResponse {
+    debug!(%status, %err, "Reply error.");
+    ServerResponse::new(format!("Status: {}\r\n\r\n", status)).stderr(err.to_string())
+}
@@ -0,0 +1,99 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `tower::Service` adapter, so the tower middleware ecosystem can wrap
+//! fastcgi calls.
+
+use crate::{
+    body::{self, BoxBody},
+    gateway::http_params,
+    shared::SharedClient,
+    ClientError, Params, Request,
+};
+use http_body::Body;
+use http_body_util::BodyExt;
+use std::{
+    future::Future,
+    io::Cursor,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tower_service::Service;
+
+/// Implement `tower::Service<http::Request<B>>` on top of [SharedClient].
+///
+/// The `http::Request` is mapped into fastcgi params (`REQUEST_METHOD`,
+/// `REQUEST_URI`, `QUERY_STRING`, `CONTENT_TYPE`, `CONTENT_LENGTH` and
+/// `HTTP_*`) merged into the base params, which should contain the script
+/// params like `SCRIPT_FILENAME`, and the stdout is parsed as CGI response.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{service::FastCgiService, shared::SharedClient, Params};
+/// use tokio::net::TcpStream;
+///
+/// async fn service() {
+///     let stream = TcpStream::connect(("127.0.0.1", 9000)).await.unwrap();
+///     let params = Params::default().script_filename("/var/www/index.php");
+///     let service = FastCgiService::new(SharedClient::new(stream), params);
+/// }
+/// ```
+#[derive(Clone)]
+pub struct FastCgiService {
+    client: SharedClient,
+    params: Params<'static>,
+}
+
+impl FastCgiService {
+    pub fn new(client: SharedClient, params: Params<'static>) -> Self {
+        Self { client, params }
+    }
+}
+
+impl<B> Service<http::Request<B>> for FastCgiService
+where
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    type Error = ClientError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+    type Response = http::Response<BoxBody>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let client = self.client.clone();
+        let params = self.params.clone();
+
+        Box::pin(async move {
+            let (parts, body) = request.into_parts();
+            let body = body
+                .collect()
+                .await
+                .map_err(|err| ClientError::RequestBody(err.into()))?
+                .to_bytes();
+
+            let params = http_params(params, &parts, body.len());
+            let response = client
+                .execute(Request::new(params, Cursor::new(body)))
+                .await?;
+
+            Ok(response.into_http()?.map(body::full))
+        })
+    }
+}
@@ -0,0 +1,256 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Cloneable client handle, the connection is owned by a background task.
+
+use crate::{
+    conn::KeepAlive,
+    limit::{ConcurrencyLimiter, LimitConfig},
+    request::{BoxedStdin, Priority, Request},
+    Client, ClientError, ClientResult, Response,
+};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::{mpsc, oneshot},
+    time,
+};
+
+const CHANNEL_CAPACITY: usize = 128;
+
+enum Message {
+    Request(
+        Box<Request<'static, BoxedStdin>>,
+        oneshot::Sender<ClientResult<Response>>,
+    ),
+    /// Close the connection after the requests received before.
+    Shutdown(oneshot::Sender<()>),
+}
+
+/// Request waiting in the background task, ordered by priority, then by the
+/// order of arrival.
+struct Queued {
+    priority: Priority,
+    seq: u64,
+    request: Box<Request<'static, BoxedStdin>>,
+    responder: oneshot::Sender<ClientResult<Response>>,
+}
+
+impl Queued {
+    fn key(&self) -> (Priority, Reverse<u64>) {
+        (self.priority, Reverse(self.seq))
+    }
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// Handle of a keep alive client running in a background task, which is
+/// `Clone + Send`, so can be shared across tasks without `Mutex`.
+///
+/// Requests are sent to the background task by channel and executed one by
+/// one on the keep alive connection, the waiting requests are executed in the
+/// order of [Priority], then the order of arrival.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{shared::SharedClient, Params, Request};
+/// use tokio::{io, net::TcpStream};
+///
+/// async fn shared() {
+///     let stream = TcpStream::connect(("127.0.0.1", 9000)).await.unwrap();
+///     let client = SharedClient::new(stream);
+///
+///     for _ in 0..3 {
+///         let client = client.clone();
+///         tokio::spawn(async move {
+///             client
+///                 .execute(Request::new(Params::default(), io::empty()))
+///                 .await
+///         });
+///     }
+/// }
+/// ```
+#[derive(Clone)]
+pub struct SharedClient {
+    sender: mpsc::Sender<Message>,
+    limiter: Option<Arc<ConcurrencyLimiter>>,
+}
+
+impl SharedClient {
+    /// Construct a `SharedClient` with stream, spawn the background task which
+    /// owns the stream, so must be called in the context of tokio runtime.
+    pub fn new<S>(stream: S) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let (sender, mut receiver) = mpsc::channel::<Message>(CHANNEL_CAPACITY);
+
+        tokio::spawn(async move {
+            let mut client = Client::<S, KeepAlive>::new_keep_alive(stream);
+            let mut queue = BinaryHeap::new();
+            let mut seq = 0;
+            let mut shutdown = None;
+
+            loop {
+                // Collect the requests arrived, so the one of highest priority
+                // is executed next, wait if none.
+                while shutdown.is_none() {
+                    let message = if queue.is_empty() {
+                        match receiver.recv().await {
+                            Some(message) => message,
+                            None => break,
+                        }
+                    } else {
+                        match receiver.try_recv() {
+                            Ok(message) => message,
+                            Err(_) => break,
+                        }
+                    };
+                    match message {
+                        Message::Request(request, responder) => {
+                            queue.push(Queued {
+                                priority: request.priority,
+                                seq,
+                                request,
+                                responder,
+                            });
+                            seq += 1;
+                        }
+                        Message::Shutdown(done) => {
+                            receiver.close();
+                            shutdown = Some(done);
+                        }
+                    }
+                }
+
+                let Some(queued) = queue.pop() else {
+                    break;
+                };
+                let result = client.execute(*queued.request).await;
+                // The connection isn't reusable once closed or broken, such as
+                // timed out in the middle.
+                let broken = result.as_ref().is_err_and(|err| !err.is_clean());
+                let _ = queued.responder.send(result);
+                if broken {
+                    break;
+                }
+            }
+
+            if let Some(done) = shutdown {
+                let _ = client.close().await;
+                let _ = done.send(());
+            }
+        });
+
+        Self {
+            sender,
+            limiter: None,
+        }
+    }
+
+    /// Limit the requests in flight, including the ones waiting in channel,
+    /// by [ConcurrencyLimiter], the limiter is shared by the clones made
+    /// after.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fastcgi_client::{
+    ///     limit::{LimitConfig, Overflow},
+    ///     shared::SharedClient,
+    /// };
+    /// use tokio::net::TcpStream;
+    ///
+    /// async fn limit() {
+    ///     let stream = TcpStream::connect(("127.0.0.1", 9000)).await.unwrap();
+    ///     let client = SharedClient::new(stream).limit(
+    ///         LimitConfig::default()
+    ///             .max_in_flight(8)
+    ///             .overflow(Overflow::FailFast),
+    ///     );
+    /// }
+    /// ```
+    pub fn limit(mut self, config: LimitConfig) -> Self {
+        self.limiter = Some(Arc::new(ConcurrencyLimiter::new(config)));
+        self
+    }
+
+    /// Send request to the background task and receive response from fastcgi
+    /// server.
+    pub async fn execute<I>(&self, request: Request<'static, I>) -> ClientResult<Response>
+    where
+        I: AsyncRead + Send + Unpin + 'static,
+    {
+        match &self.limiter {
+            Some(limiter) => limiter.call(self.inner_execute(request)).await,
+            None => self.inner_execute(request).await,
+        }
+    }
+
+    async fn inner_execute<I>(&self, request: Request<'static, I>) -> ClientResult<Response>
+    where
+        I: AsyncRead + Send + Unpin + 'static,
+    {
+        let request = request.map_stdin(|stdin| Box::new(stdin) as BoxedStdin);
+        let (responder, receiver) = oneshot::channel();
+
+        self.sender
+            .send(Message::Request(Box::new(request), responder))
+            .await
+            .map_err(|_| ClientError::ClientClosed)?;
+
+        receiver.await.map_err(|_| ClientError::ClientClosed)?
+    }
+
+    /// Stop accepting requests, then wait for the requests sent before to
+    /// complete within the deadline, and close the connection gracefully.
+    ///
+    /// Returns [ClientError::RequestTimeout] if not completed before deadline,
+    /// the connection is closed after completed anyway.
+    pub async fn shutdown(&self, deadline: Duration) -> ClientResult<()> {
+        let (done, receiver) = oneshot::channel();
+        time::timeout(deadline, async {
+            // The background task is already stopped if failed.
+            if self.sender.send(Message::Shutdown(done)).await.is_ok() {
+                let _ = receiver.await;
+            }
+        })
+        .await
+        .map_err(|_| ClientError::RequestTimeout)
+    }
+}
@@ -0,0 +1,227 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The php-fpm status page, configured by `pm.status_path`, see
+//! [php-fpm status page](https://www.php.net/manual/en/fpm.status.php).
+
+use crate::{ClientError, ClientResult};
+use std::{iter::Peekable, str::FromStr};
+
+/// Pool status of php-fpm, parsed from the plain text or json output of
+/// status page, the unknown fields are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::status::FpmStatus;
+///
+/// let status = "pool: www\nactive processes: 2\nlisten queue: 0\n"
+///     .parse::<FpmStatus>()
+///     .unwrap();
+/// assert_eq!(status.pool, "www");
+/// assert_eq!(status.active_processes, 2);
+///
+/// let status = r#"{"pool":"www","active processes":2,"listen queue":0}"#
+///     .parse::<FpmStatus>()
+///     .unwrap();
+/// assert_eq!(status.active_processes, 2);
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FpmStatus {
+    /// The name of the pool.
+    pub pool: String,
+    /// `static`, `dynamic` or `ondemand`.
+    pub process_manager: String,
+    /// The date and time the pool was last started, or the unix timestamp in
+    /// json output.
+    pub start_time: String,
+    /// The seconds since the pool was last started.
+    pub start_since: u64,
+    /// The number of accepted connections.
+    pub accepted_conn: u64,
+    /// The number of requests in the queue of pending connections.
+    pub listen_queue: u64,
+    /// The max number of requests in the queue of pending connections.
+    pub max_listen_queue: u64,
+    /// The size of the socket queue of pending connections.
+    pub listen_queue_len: u64,
+    /// The number of idle processes.
+    pub idle_processes: u64,
+    /// The number of active processes.
+    pub active_processes: u64,
+    /// The number of idle and active processes.
+    pub total_processes: u64,
+    /// The max number of active processes.
+    pub max_active_processes: u64,
+    /// The times the process limit has been reached.
+    pub max_children_reached: u64,
+    /// The number of requests exceeded `request_slowlog_timeout`.
+    pub slow_requests: u64,
+}
+
+impl FpmStatus {
+    fn set(&mut self, name: &str, value: &str) -> ClientResult<()> {
+        let number = || {
+            value
+                .parse::<u64>()
+                .map_err(|_| ClientError::InvalidFpmStatus {
+                    reason: format!("invalid value of `{}`: {}", name, value),
+                })
+        };
+        match name {
+            "pool" => self.pool = value.to_owned(),
+            "process manager" => self.process_manager = value.to_owned(),
+            "start time" => self.start_time = value.to_owned(),
+            "start since" => self.start_since = number()?,
+            "accepted conn" => self.accepted_conn = number()?,
+            "listen queue" => self.listen_queue = number()?,
+            "max listen queue" => self.max_listen_queue = number()?,
+            "listen queue len" => self.listen_queue_len = number()?,
+            "idle processes" => self.idle_processes = number()?,
+            "active processes" => self.active_processes = number()?,
+            "total processes" => self.total_processes = number()?,
+            "max active processes" => self.max_active_processes = number()?,
+            "max children reached" => self.max_children_reached = number()?,
+            "slow requests" => self.slow_requests = number()?,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for FpmStatus {
+    type Err = ClientError;
+
+    fn from_str(s: &str) -> ClientResult<Self> {
+        let mut status = FpmStatus::default();
+        if s.trim_start().starts_with('{') {
+            let fields = json_fields(s).ok_or_else(|| ClientError::InvalidFpmStatus {
+                reason: "invalid json".to_owned(),
+            })?;
+            for (name, value) in fields {
+                status.set(&name, &value)?;
+            }
+        } else {
+            for line in s.lines() {
+                let Some((name, value)) = line.split_once(':') else {
+                    continue;
+                };
+                status.set(name.trim(), value.trim())?;
+            }
+        }
+        if status.pool.is_empty() {
+            return Err(ClientError::InvalidFpmStatus {
+                reason: "missing pool".to_owned(),
+            });
+        }
+        Ok(status)
+    }
+}
+
+type Chars<'a> = Peekable<std::str::Chars<'a>>;
+
+/// Parse the fields of the flat json object, the nested values such as
+/// `processes` of the full status are skipped.
+fn json_fields(s: &str) -> Option<Vec<(String, String)>> {
+    let mut chars = s.trim().chars().peekable();
+    if chars.next()? != '{' {
+        return None;
+    }
+    let mut fields = Vec::new();
+    loop {
+        skip_whitespace(&mut chars);
+        match chars.next()? {
+            '}' if fields.is_empty() => return Some(fields),
+            '"' => {}
+            _ => return None,
+        }
+        let name = json_string(&mut chars)?;
+        skip_whitespace(&mut chars);
+        if chars.next()? != ':' {
+            return None;
+        }
+        skip_whitespace(&mut chars);
+        match chars.peek()? {
+            '"' => {
+                chars.next();
+                let value = json_string(&mut chars)?;
+                fields.push((name, value));
+            }
+            '{' | '[' => skip_json_nested(&mut chars)?,
+            _ => {
+                let mut value = String::new();
+                while let Some(c) = chars.next_if(|c| !matches!(c, ',' | '}') && !c.is_whitespace())
+                {
+                    value.push(c);
+                }
+                fields.push((name, value));
+            }
+        }
+        skip_whitespace(&mut chars);
+        match chars.next()? {
+            ',' => {}
+            '}' => return Some(fields),
+            _ => return None,
+        }
+    }
+}
+
+fn skip_whitespace(chars: &mut Chars<'_>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+/// Parse the json string after the opening quote.
+fn json_string(chars: &mut Chars<'_>) -> Option<String> {
+    let mut s = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(s),
+            '\\' => s.push(match chars.next()? {
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                'b' => '\u{8}',
+                'f' => '\u{c}',
+                'u' => {
+                    let code = (0..4).map(|_| chars.next()).collect::<Option<String>>()?;
+                    char::from_u32(u32::from_str_radix(&code, 16).ok()?)
+                        .unwrap_or(char::REPLACEMENT_CHARACTER)
+                }
+                c => c,
+            }),
+            c => s.push(c),
+        }
+    }
+}
+
+/// Skip the json object or array, the strings inside are skipped as a whole.
+fn skip_json_nested(chars: &mut Chars<'_>) -> Option<()> {
+    let mut depth = 0usize;
+    loop {
+        match chars.next()? {
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(());
+                }
+            }
+            '"' => {
+                json_string(chars)?;
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,121 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Handle the stderr of requests as soon as received, such as reporting to
+//! Sentry or writing to files, instead of collecting into
+//! [Response::stderr](crate::Response::stderr).
+
+use crate::builder::ClientConfig;
+use std::{future::Future, pin::Pin};
+use tracing::{debug, error, info, trace, warn, Level};
+
+/// The stderr record content of request, passed to [StderrHandler].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct StderrChunk {
+    pub id: u16,
+    /// The `SCRIPT_FILENAME` of request.
+    pub script: Option<String>,
+    pub content: Vec<u8>,
+}
+
+/// Async handler invoked for every stderr chunk of requests executed by
+/// [Client](crate::Client), the response waits for the handler, so it
+/// shouldn't take long.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{stderr::StderrChunk, Client, Params, Request};
+/// use tokio::{io, net::TcpStream};
+///
+/// async fn stderr() {
+///     let stream = TcpStream::connect(("127.0.0.1", 9000)).await.unwrap();
+///     let client = Client::builder()
+///         .on_stderr(|chunk: StderrChunk| async move {
+///             eprintln!(
+///                 "{:?}: {}",
+///                 chunk.script,
+///                 String::from_utf8_lossy(&chunk.content)
+///             );
+///         })
+///         .build(stream);
+///     let output = client
+///         .execute_once(Request::new(Params::default(), io::empty()))
+///         .await
+///         .unwrap();
+/// }
+/// ```
+pub trait StderrHandler: Send + Sync {
+    fn handle(&self, chunk: StderrChunk) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+}
+
+impl<F, Fut> StderrHandler for F
+where
+    F: Fn(StderrChunk) -> Fut + Send + Sync,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    fn handle(&self, chunk: StderrChunk) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(self(chunk))
+    }
+}
+
+/// Where the stderr of request goes, see [ClientConfig::log_stderr] and
+/// [ClientConfig::stderr_handler], it's collected if neither.
+#[derive(Clone, Copy)]
+pub(crate) struct StderrRoute<'a> {
+    log: Option<Level>,
+    handler: Option<&'a dyn StderrHandler>,
+    script: Option<&'a str>,
+}
+
+impl<'a> StderrRoute<'a> {
+    pub(crate) fn new(config: &'a ClientConfig, script: Option<&'a str>) -> Self {
+        Self {
+            log: config.log_stderr,
+            handler: config.stderr_handler.as_deref(),
+            script,
+        }
+    }
+
+    pub(crate) fn is_collecting(&self) -> bool {
+        self.log.is_none() && self.handler.is_none()
+    }
+
+    pub(crate) async fn route(&self, id: u16, content: Vec<u8>) {
+        if let Some(level) = self.log {
+            log(level, id, self.script, &content);
+        }
+        if let Some(handler) = self.handler {
+            let chunk = StderrChunk {
+                id,
+                script: self.script.map(ToOwned::to_owned),
+                content,
+            };
+            handler.handle(chunk).await;
+        }
+    }
+}
+
+fn log(level: Level, id: u16, script: Option<&str>, content: &[u8]) {
+    let stderr = String::from_utf8_lossy(content);
+    let stderr = stderr.trim_end();
+    match level {
+        Level::ERROR => error!(id, script, stderr, "Receive stderr."),
+        Level::WARN => warn!(id, script, stderr, "Receive stderr."),
+        Level::INFO => info!(id, script, stderr, "Receive stderr."),
+        Level::DEBUG => debug!(id, script, stderr, "Receive stderr."),
+        _ => trace!(id, script, stderr, "Receive stderr."),
+    }
+}
@@ -0,0 +1,50 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tracing spans of requests, enabled by the `trace` feature.
+
+use crate::{ClientResult, Params, Response};
+use std::{future::Future, time::Instant};
+use tracing::{field, info_span, Instrument, Span};
+
+/// Create the span of request, the `duration_ms`, `app_status` and `error`
+/// are recorded after completed by [instrument].
+pub(crate) fn request_span(id: u16, params: &Params<'_>, upstream: Option<&str>) -> Span {
+    let script = params
+        .get("SCRIPT_NAME")
+        .or_else(|| params.get("SCRIPT_FILENAME"));
+    info_span!(
+        "fastcgi.request",
+        request_id = id,
+        upstream,
+        script = script.map(|script| &**script),
+        duration_ms = field::Empty,
+        app_status = field::Empty,
+        error = field::Empty,
+    )
+}
+
+/// Run the request in the span, and record the result.
+pub(crate) async fn instrument(
+    span: Span, fut: impl Future<Output = ClientResult<Response>>,
+) -> ClientResult<Response> {
+    let start = Instant::now();
+    let result = fut.instrument(span.clone()).await;
+    span.record("duration_ms", start.elapsed().as_millis() as u64);
+    match &result {
+        Ok(response) => span.record("app_status", response.app_status),
+        Err(err) => span.record("error", field::display(err)),
+    };
+    result
+}
@@ -0,0 +1,134 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Load balancing over several fastcgi servers, like nginx `upstream`.
+
+use crate::{
+    connect::Connect,
+    pool::{Pool, PoolConfig},
+    request::Request,
+    ClientResult, Response,
+};
+use std::{
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use tokio::io::AsyncRead;
+
+/// Strategy of choosing the upstream for each request.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Balance {
+    /// Choose the upstreams in turn.
+    #[default]
+    RoundRobin,
+    /// Choose the upstream with the fewest requests in flight, the ties are
+    /// broken in turn.
+    LeastConnections,
+}
+
+/// Several upstreams (such as multiple php-fpm pools), every upstream has its
+/// own [Pool], so the connections are reused per upstream.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{connect::Address, upstream::Upstreams, Params, Request};
+/// use tokio::io;
+///
+/// async fn upstreams() {
+///     let upstreams = Upstreams::new(
+///         ["tcp://127.0.0.1:9000", "unix:///run/php/php-fpm.sock"]
+///             .map(|address| address.parse::<Address>().unwrap()),
+///     );
+///
+///     let output = upstreams
+///         .execute(Request::new(Params::default(), io::empty()))
+///         .await
+///         .unwrap();
+/// }
+/// ```
+pub struct Upstreams<C: Connect> {
+    pools: Vec<Pool<C>>,
+    balance: Balance,
+    next: AtomicUsize,
+}
+
+impl<C: Connect> Upstreams<C> {
+    /// Construct with the connectors of upstreams and default pool config.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the connectors are empty.
+    pub fn new(connectors: impl IntoIterator<Item = C>) -> Self {
+        Self::with_config(connectors, PoolConfig::default())
+    }
+
+    /// Like [new](Upstreams::new), but the pool of every upstream is
+    /// configured by `config`.
+    pub fn with_config(connectors: impl IntoIterator<Item = C>, config: PoolConfig) -> Self {
+        let pools = connectors
+            .into_iter()
+            .map(|connector| Pool::with_config(connector, config.clone()))
+            .collect::<Vec<_>>();
+        assert!(!pools.is_empty(), "upstreams can't be empty");
+        Self {
+            pools,
+            balance: Balance::default(),
+            next: Default::default(),
+        }
+    }
+
+    pub fn with_balance(mut self, balance: Balance) -> Self {
+        self.balance = balance;
+        self
+    }
+
+    pub fn balance(&self) -> Balance {
+        self.balance
+    }
+
+    pub fn pools(&self) -> &[Pool<C>] {
+        &self.pools
+    }
+
+    /// Choose the upstream for the next request by the balance strategy, the
+    /// upstreams with open circuit breaker are skipped unless all are open.
+    pub fn select(&self) -> &Pool<C> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % self.pools.len();
+        let mut candidates = (0..self.pools.len())
+            .map(|i| &self.pools[(start + i) % self.pools.len()])
+            .filter(|pool| !pool.is_open());
+        let selected = match self.balance {
+            Balance::RoundRobin => candidates.next(),
+            Balance::LeastConnections => candidates.min_by_key(|pool| pool.in_flight()),
+        };
+        selected.unwrap_or(&self.pools[start])
+    }
+
+    /// Send request to the chosen upstream and receive response.
+    pub async fn execute<I: AsyncRead + Unpin>(
+        &self, request: Request<'_, I>,
+    ) -> ClientResult<Response> {
+        self.select().execute(request).await
+    }
+}
+
+impl<C: Connect + fmt::Debug> fmt::Debug for Upstreams<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Upstreams")
+            .field("pools", &self.pools)
+            .field("balance", &self.balance)
+            .finish()
+    }
+}
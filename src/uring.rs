@@ -0,0 +1,215 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Transport backed by io_uring of [tokio_uring], the reads and writes are
+//! submitted to the ring instead of the readiness based syscalls, which
+//! improves the throughput of the syscall bound gateways.
+//!
+//! The streams are `!Send`, so the client runs in the thread local runtime
+//! started by `tokio_uring::start`, spawn by `tokio_uring::spawn` instead of
+//! `tokio::spawn`.
+
+use std::{
+    fmt,
+    future::Future,
+    io,
+    net::{Shutdown, SocketAddr},
+    path::Path,
+    pin::Pin,
+    rc::Rc,
+    task::{ready, Context, Poll},
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_uring::net::{TcpStream, UnixStream};
+
+/// Capacity of the buffer submitted to read.
+const READ_CAPACITY: usize = 8192;
+
+type Op = Pin<Box<dyn Future<Output = (io::Result<usize>, Vec<u8>)>>>;
+
+enum Inner {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl Inner {
+    async fn read(&self, buf: Vec<u8>) -> (io::Result<usize>, Vec<u8>) {
+        match self {
+            Inner::Tcp(stream) => stream.read(buf).await,
+            Inner::Unix(stream) => stream.read(buf).await,
+        }
+    }
+
+    async fn write_all(&self, buf: Vec<u8>) -> (io::Result<usize>, Vec<u8>) {
+        let len = buf.len();
+        let (result, buf) = match self {
+            Inner::Tcp(stream) => stream.write_all(buf).await,
+            Inner::Unix(stream) => stream.write_all(buf).await,
+        };
+        (result.map(|_| len), buf)
+    }
+
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        match self {
+            Inner::Tcp(stream) => stream.shutdown(how),
+            Inner::Unix(stream) => stream.shutdown(how),
+        }
+    }
+}
+
+/// Stream over [tokio_uring] tcp or unix stream, implements the tokio
+/// `AsyncRead` and `AsyncWrite`, so it can be used by
+/// [Client](crate::Client).
+///
+/// The written bytes are copied into an owned buffer and submitted in
+/// background, the errors are returned by the next write or flush, the
+/// client always flushes after the request.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{uring::UringStream, Client, Params, Request};
+///
+/// fn uring() {
+///     tokio_uring::start(async {
+///         let stream = UringStream::connect("127.0.0.1:9000".parse().unwrap())
+///             .await
+///             .unwrap();
+///         let mut client = Client::new_keep_alive(stream);
+///         let output = client
+///             .execute(Request::new(Params::default(), tokio::io::empty()))
+///             .await
+///             .unwrap();
+///     })
+/// }
+/// ```
+pub struct UringStream {
+    inner: Rc<Inner>,
+    /// The bytes read but not consumed yet.
+    read_buf: Vec<u8>,
+    read_pos: usize,
+    read_op: Option<Op>,
+    write_op: Option<Op>,
+    /// The buffer returned by the previous write, reused for the next one.
+    write_buf: Vec<u8>,
+}
+
+impl UringStream {
+    /// Connect to fastcgi server by tcp.
+    pub async fn connect(addr: SocketAddr) -> io::Result<Self> {
+        Ok(TcpStream::connect(addr).await?.into())
+    }
+
+    /// Connect to fastcgi server by unix domain socket.
+    pub async fn connect_unix(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(UnixStream::connect(path).await?.into())
+    }
+
+    fn new(inner: Inner) -> Self {
+        Self {
+            inner: Rc::new(inner),
+            read_buf: Vec::with_capacity(READ_CAPACITY),
+            read_pos: 0,
+            read_op: None,
+            write_op: None,
+            write_buf: Vec::new(),
+        }
+    }
+
+    /// Wait for the write in background to complete.
+    fn poll_write_op(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let Some(op) = &mut self.write_op else {
+            return Poll::Ready(Ok(()));
+        };
+        let (result, mut buf) = ready!(op.as_mut().poll(cx));
+        self.write_op = None;
+        buf.clear();
+        self.write_buf = buf;
+        Poll::Ready(result.map(|_| ()))
+    }
+}
+
+impl fmt::Debug for UringStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match *self.inner {
+            Inner::Tcp(_) => "tcp",
+            Inner::Unix(_) => "unix",
+        };
+        f.debug_struct("UringStream").field("kind", &kind).finish()
+    }
+}
+
+impl From<TcpStream> for UringStream {
+    fn from(stream: TcpStream) -> Self {
+        Self::new(Inner::Tcp(stream))
+    }
+}
+
+impl From<UnixStream> for UringStream {
+    fn from(stream: UnixStream) -> Self {
+        Self::new(Inner::Unix(stream))
+    }
+}
+
+impl AsyncRead for UringStream {
+    fn poll_read(
+        self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        if this.read_pos == this.read_buf.len() {
+            let op = this.read_op.get_or_insert_with(|| {
+                let inner = this.inner.clone();
+                let mut read_buf = std::mem::take(&mut this.read_buf);
+                read_buf.clear();
+                this.read_pos = 0;
+                Box::pin(async move { inner.read(read_buf).await })
+            });
+            let (result, read_buf) = ready!(op.as_mut().poll(cx));
+            this.read_op = None;
+            this.read_buf = read_buf;
+            result?;
+        }
+
+        let n = buf.remaining().min(this.read_buf.len() - this.read_pos);
+        buf.put_slice(&this.read_buf[this.read_pos..this.read_pos + n]);
+        this.read_pos += n;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for UringStream {
+    fn poll_write(
+        self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_op(cx))?;
+
+        let inner = this.inner.clone();
+        let mut write_buf = std::mem::take(&mut this.write_buf);
+        write_buf.extend_from_slice(buf);
+        this.write_op = Some(Box::pin(async move { inner.write_all(write_buf).await }));
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_write_op(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_op(cx))?;
+        Poll::Ready(this.inner.shutdown(Shutdown::Write))
+    }
+}
@@ -0,0 +1,113 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Management values queried by `FCGI_GET_VALUES`, see
+//! [fastcgi spec section 4.1](https://fastcgi-archives.github.io/FastCGI_Specification.html#S4.1).
+
+use crate::{meta::ParamPairs, Params};
+use std::{borrow::Cow, io};
+
+/// Name of the management value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueName {
+    /// `FCGI_MAX_CONNS`, the maximum number of concurrent transport
+    /// connections.
+    MaxConns,
+    /// `FCGI_MAX_REQS`, the maximum number of concurrent requests.
+    MaxReqs,
+    /// `FCGI_MPXS_CONNS`, whether multiplex connections are supported.
+    MpxsConns,
+}
+
+impl ValueName {
+    pub const ALL: [ValueName; 3] = [
+        ValueName::MaxConns,
+        ValueName::MaxReqs,
+        ValueName::MpxsConns,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ValueName::MaxConns => "FCGI_MAX_CONNS",
+            ValueName::MaxReqs => "FCGI_MAX_REQS",
+            ValueName::MpxsConns => "FCGI_MPXS_CONNS",
+        }
+    }
+
+    pub(crate) fn from_str(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|n| n.as_str() == name)
+    }
+}
+
+/// Management values, the value is `None` if the server doesn't reply it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Values {
+    pub max_conns: Option<u32>,
+    pub max_reqs: Option<u32>,
+    pub mpxs_conns: Option<bool>,
+}
+
+impl Values {
+    /// Encode the names as the content of `GetValues` record.
+    pub(crate) async fn encode_names(names: &[ValueName]) -> io::Result<Vec<u8>> {
+        let params = names
+            .iter()
+            .map(|name| (Cow::Borrowed(name.as_str()), Cow::Borrowed("")))
+            .collect::<Params<'_>>();
+        ParamPairs::new(&params).to_content().await
+    }
+
+    /// Decode the names of `GetValues` record, unknown names are ignored.
+    pub(crate) fn decode_names(content: &[u8]) -> io::Result<Vec<ValueName>> {
+        Ok(ParamPairs::from_content(content)?
+            .into_params()
+            .iter()
+            .filter_map(|(name, _)| ValueName::from_str(name))
+            .collect())
+    }
+
+    /// Encode the values of the names as the content of `GetValuesResult`
+    /// record, the values which are `None` are omitted.
+    pub(crate) async fn encode(&self, names: &[ValueName]) -> io::Result<Vec<u8>> {
+        let params = names
+            .iter()
+            .filter_map(|name| {
+                let value = match name {
+                    ValueName::MaxConns => self.max_conns?.to_string(),
+                    ValueName::MaxReqs => self.max_reqs?.to_string(),
+                    ValueName::MpxsConns => (self.mpxs_conns? as u8).to_string(),
+                };
+                Some((Cow::Borrowed(name.as_str()), Cow::Owned(value)))
+            })
+            .collect::<Params<'_>>();
+        ParamPairs::new(&params).to_content().await
+    }
+
+    /// Decode the content of `GetValuesResult` record, unknown names and
+    /// unparsable values are ignored.
+    pub(crate) fn decode(content: &[u8]) -> io::Result<Self> {
+        let mut values = Values::default();
+        for (name, value) in ParamPairs::from_content(content)?.into_params().iter() {
+            match ValueName::from_str(name) {
+                Some(ValueName::MaxConns) => values.max_conns = value.parse().ok(),
+                Some(ValueName::MaxReqs) => values.max_reqs = value.parse().ok(),
+                Some(ValueName::MpxsConns) => {
+                    values.mpxs_conns = value.parse::<u32>().ok().map(|v| v != 0)
+                }
+                None => {}
+            }
+        }
+        Ok(values)
+    }
+}
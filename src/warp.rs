@@ -0,0 +1,165 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Warp filter proxying the matched requests to fastcgi server, for the
+//! applications on the warp stack.
+
+use crate::{
+    connect::Connect,
+    gateway::{self, GatewayConfig, Upstream},
+    pool::Pool,
+    ClientError, ClientResult, Request, Response as FcgiResponse,
+};
+use bytes::Bytes;
+use std::{future::Future, io::Cursor, sync::Arc};
+use tracing::warn;
+use warp::{
+    filters::path::FullPath,
+    http::{self as http02, HeaderMap, Method},
+    hyper::Body,
+    reply::Response,
+    Filter, Rejection,
+};
+
+/// Filter proxying the request to fastcgi server by the pool, the script is
+/// resolved from the full path by [GatewayConfig], and the stdout is parsed
+/// as CGI response. The local redirects and the cache are handled like
+/// [forward](crate::gateway::forward).
+///
+/// The body longer than [GatewayConfig::max_body_size] is rejected with `413
+/// Payload Too Large`, and the body without `Content-Length` is rejected with
+/// `411 Length Required`.
+///
+/// The filter never rejects once the body is received, the invalid request
+/// is replied `400 Bad Request`, [ClientError::RequestTimeout] is replied
+/// `504 Gateway Timeout`, and the other errors are replied `502 Bad
+/// Gateway`, the errors are logged.
+///
+/// # Examples
+///
+/// ```
+/// use fastcgi_client::{
+///     connect::TcpConnector, gateway::GatewayConfig, pool::Pool, warp::fastcgi,
+/// };
+/// use warp::Filter;
+///
+/// async fn serve() {
+///     let pool = Pool::new(TcpConnector::new("127.0.0.1:9000"));
+///     let php = warp::path("php").and(fastcgi(pool, GatewayConfig::new("/var/www")));
+///     warp::serve(php).run(([127, 0, 0, 1], 8080)).await;
+/// }
+/// ```
+pub fn fastcgi<C: Connect>(
+    pool: Pool<C>, config: GatewayConfig,
+) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone {
+    let config = Arc::new(config);
+    warp::method()
+        .and(warp::path::full())
+        .and(warp::query::raw().or(warp::any().map(String::new)).unify())
+        .and(warp::header::headers_cloned())
+        .and(body(config.get_max_body_size()))
+        .then(
+            move |method: Method,
+                  path: FullPath,
+                  query: String,
+                  headers: HeaderMap,
+                  body: Bytes| {
+                let pool = pool.clone();
+                let config = config.clone();
+                async move {
+                    let result = match http_parts(&method, path.as_str(), &query, &headers) {
+                        Ok(parts) => gateway::forward_collected(&pool, &config, parts, body).await,
+                        Err(err) => return error_response(http02::StatusCode::BAD_REQUEST, err),
+                    };
+                    match result.and_then(reply) {
+                        Ok(response) => response,
+                        Err(err @ ClientError::InvalidPath { .. })
+                        | Err(err @ ClientError::InvalidParam { .. })
+                        | Err(err @ ClientError::ParamsTooLarge { .. }) => {
+                            error_response(http02::StatusCode::BAD_REQUEST, err)
+                        }
+                        Err(err @ ClientError::RequestTimeout) => {
+                            error_response(http02::StatusCode::GATEWAY_TIMEOUT, err)
+                        }
+                        Err(err) => error_response(http02::StatusCode::BAD_GATEWAY, err),
+                    }
+                }
+            },
+        )
+}
+
+impl<C: Connect> Upstream for Pool<C> {
+    fn execute(
+        &self, request: Request<'static, Cursor<Bytes>>,
+    ) -> impl Future<Output = ClientResult<FcgiResponse>> + Send {
+        Pool::execute(self, request)
+    }
+}
+
+/// The body no longer than `limit`, the request without `Content-Length` is
+/// accepted only if it has no body.
+fn body(limit: u64) -> impl Filter<Extract = (Bytes,), Error = Rejection> + Copy {
+    let empty = warp::header::optional::<String>("content-length")
+        .and(warp::header::optional::<String>("transfer-encoding"))
+        .and_then(
+            |length: Option<String>, encoding: Option<String>| async move {
+                match (length, encoding) {
+                    (None, None) => Ok(Bytes::new()),
+                    _ => Err(warp::reject::not_found()),
+                }
+            },
+        );
+    warp::body::content_length_limit(limit)
+        .and(warp::body::bytes())
+        .or(empty)
+        .unify()
+}
+
+/// Convert the request of warp, which is of `http` 0.2, into the parts of
+/// `http` 1.
+fn http_parts(
+    method: &Method, path: &str, query: &str, headers: &HeaderMap,
+) -> ClientResult<http::request::Parts> {
+    let uri = if query.is_empty() {
+        path.to_owned()
+    } else {
+        format!("{}?{}", path, query)
+    };
+    let mut builder = http::Request::builder().method(method.as_str()).uri(uri);
+    for (name, value) in headers {
+        builder = builder.header(name.as_str(), value.as_bytes());
+    }
+    Ok(builder.body(())?.into_parts().0)
+}
+
+/// Convert the response of `http` 1 into the one of warp.
+fn reply(response: http::Response<Bytes>) -> ClientResult<Response> {
+    let (parts, body) = response.into_parts();
+    let mut builder = http02::Response::builder().status(parts.status.as_u16());
+    for (name, value) in &parts.headers {
+        builder = builder.header(name.as_str(), value.as_bytes());
+    }
+    builder
+        .body(Body::from(body))
+        .map_err(|err| ClientError::InvalidCgiResponse {
+            reason: err.to_string(),
+        })
+}
+
+fn error_response(status: http02::StatusCode, err: ClientError) -> Response {
+    warn!(%status, ?err, "Failed to proxy request to fastcgi server.");
+    let mut response = Response::new(Body::from(status.canonical_reason().unwrap_or_default()));
+    *response.status_mut() = status;
+    response
+}
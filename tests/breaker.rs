@@ -0,0 +1,123 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{
+    breaker::BreakerConfig,
+    pool::{Pool, PoolConfig},
+    server::{Server, ServerRequest, ServerResponse},
+    upstream::Upstreams,
+    ClientError, Params, Request,
+};
+use std::{
+    future::Ready,
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::io::{duplex, DuplexStream};
+
+mod common;
+
+async fn hello(_request: ServerRequest) -> ServerResponse {
+    ServerResponse::new("Content-type: text/plain\r\n\r\nhello")
+}
+
+type Connector = Box<dyn Fn() -> Ready<io::Result<DuplexStream>> + Send + Sync>;
+
+/// Connector refused while `down` is set.
+fn connector(down: Arc<AtomicBool>) -> Connector {
+    Box::new(move || {
+        if down.load(Ordering::SeqCst) {
+            return std::future::ready(Err(io::ErrorKind::ConnectionRefused.into()));
+        }
+        let (client_stream, server_stream) = duplex(4096);
+        tokio::spawn(async move { Server::new(hello).serve_connection(server_stream).await });
+        std::future::ready(Ok(client_stream))
+    })
+}
+
+fn config() -> PoolConfig {
+    PoolConfig::default().breaker(
+        BreakerConfig::default()
+            .failure_threshold(2)
+            .cooldown(Duration::from_millis(100)),
+    )
+}
+
+async fn execute(pool: &Pool<Connector>) -> Result<(), ClientError> {
+    pool.execute(Request::new(Params::default(), tokio::io::empty()))
+        .await
+        .map(|_| ())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn pool_breaker() {
+    common::setup();
+
+    let down = Arc::new(AtomicBool::new(true));
+    let pool = Pool::with_config(connector(down.clone()), config());
+
+    for _ in 0..2 {
+        assert!(matches!(execute(&pool).await, Err(ClientError::Io(_))));
+    }
+    assert!(pool.is_open());
+    assert!(matches!(
+        execute(&pool).await,
+        Err(ClientError::CircuitOpen)
+    ));
+
+    // The trial request after cooldown fails, then open again.
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(matches!(execute(&pool).await, Err(ClientError::Io(_))));
+    assert!(matches!(
+        execute(&pool).await,
+        Err(ClientError::CircuitOpen)
+    ));
+
+    // The trial request after cooldown succeeds, then closed.
+    down.store(false, Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    execute(&pool).await.unwrap();
+    assert!(!pool.is_open());
+    execute(&pool).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn upstreams_skip_open() {
+    common::setup();
+
+    let upstreams = Upstreams::with_config(
+        [
+            connector(Arc::new(AtomicBool::new(true))),
+            connector(Arc::new(AtomicBool::new(false))),
+        ],
+        config(),
+    );
+
+    let mut failures = 0;
+    for _ in 0..8 {
+        if upstreams
+            .execute(Request::new(Params::default(), tokio::io::empty()))
+            .await
+            .is_err()
+        {
+            failures += 1;
+        }
+    }
+    assert_eq!(failures, 2);
+    assert!(upstreams.pools()[0].is_open());
+}
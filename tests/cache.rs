@@ -0,0 +1,203 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(feature = "http-body")]
+
+use bytes::Bytes;
+use fastcgi_client::{
+    cache::{CacheConfig, ResponseCache},
+    gateway::{forward, GatewayConfig},
+    server::{Server, ServerRequest, ServerResponse},
+    shared::SharedClient,
+    ClientError,
+};
+use http_body_util::BodyExt;
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::io::duplex;
+
+mod common;
+
+fn parts(method: &str, uri: &str, language: &str) -> http::request::Parts {
+    http::Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("accept-language", language)
+        .body(())
+        .unwrap()
+        .into_parts()
+        .0
+}
+
+/// Fetch responding the count of fetches.
+fn fetch(
+    count: &Arc<AtomicUsize>, headers: &'static [(&'static str, &'static str)],
+) -> impl FnOnce() -> std::future::Ready<Result<http::Response<Bytes>, ClientError>> {
+    let count = count.clone();
+    move || {
+        let count = count.fetch_add(1, Ordering::SeqCst) + 1;
+        let mut response = http::Response::builder();
+        for (name, value) in headers {
+            response = response.header(*name, *value);
+        }
+        std::future::ready(Ok(response.body(Bytes::from(count.to_string())).unwrap()))
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn cache_fresh_and_stale() {
+    let cache = ResponseCache::new(
+        CacheConfig::default()
+            .ttl(Duration::from_millis(100))
+            .stale_while_revalidate(Duration::from_millis(200)),
+    );
+    let count = Arc::new(AtomicUsize::new(0));
+    let get = parts("GET", "/index.php?page=1", "en");
+
+    let response = cache.get_or_fetch(&get, fetch(&count, &[])).await.unwrap();
+    assert_eq!(response.body(), "1");
+    let response = cache.get_or_fetch(&get, fetch(&count, &[])).await.unwrap();
+    assert_eq!(response.body(), "1");
+    assert_eq!(cache.len(), 1);
+
+    // Not cached.
+    let post = parts("POST", "/index.php?page=1", "en");
+    let response = cache.get_or_fetch(&post, fetch(&count, &[])).await.unwrap();
+    assert_eq!(response.body(), "2");
+
+    // Stale served, and revalidated in background.
+    tokio::time::sleep(Duration::from_millis(120)).await;
+    let response = cache.get_or_fetch(&get, fetch(&count, &[])).await.unwrap();
+    assert_eq!(response.body(), "1");
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    let response = cache.get_or_fetch(&get, fetch(&count, &[])).await.unwrap();
+    assert_eq!(response.body(), "3");
+
+    // Expired beyond stale.
+    tokio::time::sleep(Duration::from_millis(320)).await;
+    let response = cache.get_or_fetch(&get, fetch(&count, &[])).await.unwrap();
+    assert_eq!(response.body(), "4");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn cache_key_and_policy() {
+    let cache = ResponseCache::new(
+        CacheConfig::default()
+            .vary(http::header::ACCEPT_LANGUAGE)
+            .max_entries(2),
+    );
+    let count = Arc::new(AtomicUsize::new(0));
+
+    let en = parts("GET", "/", "en");
+    let fr = parts("GET", "/", "fr");
+    assert_ne!(cache.key(&en), cache.key(&fr));
+    assert_eq!(cache.key(&parts("PUT", "/", "en")), None);
+
+    cache.get_or_fetch(&en, fetch(&count, &[])).await.unwrap();
+    let response = cache.get_or_fetch(&fr, fetch(&count, &[])).await.unwrap();
+    assert_eq!(response.body(), "2");
+    assert_eq!(cache.len(), 2);
+
+    // The oldest is evicted.
+    cache
+        .get_or_fetch(&parts("GET", "/other", "en"), fetch(&count, &[]))
+        .await
+        .unwrap();
+    assert_eq!(cache.len(), 2);
+    let response = cache.get_or_fetch(&en, fetch(&count, &[])).await.unwrap();
+    assert_eq!(response.body(), "4");
+
+    cache.clear();
+    for headers in [
+        &[("set-cookie", "id=1")][..],
+        &[("cache-control", "public, no-store")],
+        &[("vary", "*")],
+    ] {
+        cache
+            .get_or_fetch(&en, fetch(&count, headers))
+            .await
+            .unwrap();
+        assert!(cache.is_empty());
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn cache_bypass_credentials() {
+    let cache = ResponseCache::new(CacheConfig::default());
+    let count = Arc::new(AtomicUsize::new(0));
+
+    let mut authorized = parts("GET", "/", "en");
+    authorized
+        .headers
+        .insert("authorization", "Bearer abc".parse().unwrap());
+    let mut cookie = parts("GET", "/", "en");
+    cookie.headers.insert("cookie", "id=1".parse().unwrap());
+
+    for parts in [&authorized, &cookie] {
+        assert_eq!(cache.key(parts), None);
+        cache.get_or_fetch(parts, fetch(&count, &[])).await.unwrap();
+        assert!(cache.is_empty());
+    }
+
+    // Cached per cookie if varied by it.
+    let cache = ResponseCache::new(CacheConfig::default().vary(http::header::COOKIE));
+    assert_eq!(cache.key(&authorized), None);
+    cache
+        .get_or_fetch(&cookie, fetch(&count, &[]))
+        .await
+        .unwrap();
+    let response = cache
+        .get_or_fetch(&cookie, fetch(&count, &[]))
+        .await
+        .unwrap();
+    assert_eq!(response.body(), "3");
+    assert_eq!(cache.len(), 1);
+}
+
+async fn counter(request: ServerRequest) -> ServerResponse {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let count = COUNT.fetch_add(1, Ordering::SeqCst) + 1;
+    ServerResponse::new(format!(
+        "Content-type: text/plain\r\n\r\n{} {}",
+        request.params["REQUEST_URI"], count
+    ))
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn forward_cached() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(async move { Server::new(counter).serve_connection(server_stream).await });
+    let client = SharedClient::new(client_stream);
+    let cache = ResponseCache::new(CacheConfig::default().ttl(Duration::from_secs(10)));
+    let config = GatewayConfig::new("/var/www").cache(cache.clone());
+
+    for _ in 0..2 {
+        let request = http::Request::get("/index.php")
+            .body(String::new())
+            .unwrap();
+        let response = forward(&client, &config, request).await.unwrap();
+        assert_eq!(
+            response.into_body().collect().await.unwrap().to_bytes(),
+            "/index.php 1"
+        );
+    }
+    assert_eq!(cache.len(), 1);
+}
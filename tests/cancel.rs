@@ -0,0 +1,149 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{multiplex::MultiplexClient, Client, ClientError, Params, Request};
+use std::time::Duration;
+use tokio::{
+    io::{duplex, empty, DuplexStream},
+    time,
+};
+
+mod common;
+
+/// Answer the first request partially, then answer the next ones completely.
+async fn server(mut stream: DuplexStream) {
+    let request = common::read_request(&mut stream).await.unwrap();
+    common::write_record(
+        &mut stream,
+        6,
+        request.id,
+        b"Content-type: text/plain\r\n\r\n",
+    )
+    .await
+    .unwrap();
+    while let Ok(request) = common::read_request(&mut stream).await {
+        common::write_record(&mut stream, 6, request.id, b"hello")
+            .await
+            .unwrap();
+        common::write_end_request(&mut stream, request.id, 0, 0)
+            .await
+            .unwrap();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn cancel_execute() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(server(server_stream));
+
+    let mut client = Client::new_keep_alive(client_stream);
+    let cancelled = time::timeout(
+        Duration::from_millis(50),
+        client.execute(Request::new(Params::default(), empty())),
+    )
+    .await;
+    assert!(cancelled.is_err());
+    assert!(client.is_poisoned());
+
+    let result = client
+        .execute(Request::new(Params::default(), empty()))
+        .await;
+    assert!(matches!(result, Err(ClientError::ConnectionPoisoned)));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn cancel_execute_stream() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+    tokio::spawn(async move {
+        for _ in 0..2 {
+            let request = common::read_request(&mut server_stream).await.unwrap();
+            common::write_record(&mut server_stream, 6, request.id, b"hello")
+                .await
+                .unwrap();
+            common::write_end_request(&mut server_stream, request.id, 0, 0)
+                .await
+                .unwrap();
+        }
+    });
+
+    let mut client = Client::new_keep_alive(client_stream);
+
+    // Read completely, the connection is reusable.
+    let mut stream = client
+        .execute_stream(Request::new(Params::default(), empty()))
+        .await
+        .unwrap();
+    while let Some(content) = stream.next().await {
+        content.unwrap();
+    }
+    assert!(!client.is_poisoned());
+
+    // Dropped before the end.
+    let mut stream = client
+        .execute_stream(Request::new(Params::default(), empty()))
+        .await
+        .unwrap();
+    stream.next().await.unwrap().unwrap();
+    drop(stream);
+    assert!(client.is_poisoned());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn overloaded_not_poisoned() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+    tokio::spawn(async move {
+        let request = common::read_request(&mut server_stream).await.unwrap();
+        common::write_end_request(&mut server_stream, request.id, 0, 2)
+            .await
+            .unwrap();
+    });
+
+    let mut client = Client::new_keep_alive(client_stream);
+    let result = client
+        .execute(Request::new(Params::default(), empty()))
+        .await;
+    assert!(matches!(
+        result,
+        Err(ClientError::EndRequestOverloaded { .. })
+    ));
+    assert!(!client.is_poisoned());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn cancel_multiplex() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(server(server_stream));
+
+    let client = MultiplexClient::new(client_stream);
+    let cancelled = time::timeout(
+        Duration::from_millis(50),
+        client.execute(Request::new(Params::default(), empty())),
+    )
+    .await;
+    assert!(cancelled.is_err());
+    assert!(client.is_poisoned());
+
+    let result = client
+        .execute(Request::new(Params::default(), empty()))
+        .await;
+    assert!(matches!(result, Err(ClientError::ConnectionPoisoned)));
+}
@@ -0,0 +1,99 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{
+    capture::{read_dump, Capture, CaptureSink, Direction, DumpSink},
+    server::{Server, ServerRequest, ServerResponse},
+    Client, Params, Request,
+};
+use std::sync::{Arc, Mutex};
+use tokio::io::{self, duplex};
+
+mod common;
+
+async fn hello(_request: ServerRequest) -> ServerResponse {
+    ServerResponse::new("Content-type: text/plain\r\n\r\nhello")
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn capture_records() {
+    common::setup();
+
+    // Small buffer, so the records are split across reads and writes.
+    let (client_stream, server_stream) = duplex(5);
+    tokio::spawn(async move { Server::new(hello).serve_connection(server_stream).await });
+
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let sink = {
+        let records = records.clone();
+        move |direction: Direction, record: &[u8]| {
+            records.lock().unwrap().push((direction, record.to_vec()));
+        }
+    };
+
+    let client = Client::new(Capture::new(client_stream, sink));
+    let params = Params::default().script_name("/index.php");
+    let output = client
+        .execute_once(Request::new(params, io::empty()))
+        .await
+        .unwrap();
+    assert_eq!(
+        output.stdout.unwrap(),
+        b"Content-type: text/plain\r\n\r\nhello"
+    );
+
+    let records = records.lock().unwrap();
+    let sent = records
+        .iter()
+        .filter(|(direction, _)| *direction == Direction::Sent)
+        .map(|(_, record)| record[1])
+        .collect::<Vec<_>>();
+    // BeginRequest, Params, empty Params and empty Stdin.
+    assert_eq!(sent, [1, 4, 4, 5]);
+
+    let received = records
+        .iter()
+        .filter(|(direction, _)| *direction == Direction::Received)
+        .map(|(_, record)| record[1])
+        .collect::<Vec<_>>();
+    assert_eq!(received.first(), Some(&6));
+    assert_eq!(received.last(), Some(&3));
+
+    for (_, record) in records.iter() {
+        let content_length = u16::from_be_bytes([record[4], record[5]]) as usize;
+        assert_eq!(record.len(), 8 + content_length + record[6] as usize);
+    }
+}
+
+#[test]
+fn dump_roundtrip() {
+    let mut sink = DumpSink::new(Vec::new()).unwrap();
+    sink.capture(Direction::Sent, &[1, 5, 0, 1, 0, 0, 0, 0]);
+    sink.capture(
+        Direction::Received,
+        &[1, 6, 0, 1, 0, 2, 6, 0, b'o', b'k', 0, 0, 0, 0, 0, 0],
+    );
+    let dump = sink.into_inner();
+    assert!(dump.starts_with(b"FCGIDUMP"));
+
+    let entries = read_dump(dump.as_slice()).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].direction, Direction::Sent);
+    assert_eq!(entries[0].record, [1, 5, 0, 1, 0, 0, 0, 0]);
+    assert_eq!(entries[1].direction, Direction::Received);
+    assert_eq!(entries[1].record.len(), 16);
+    assert!(entries[0].timestamp <= entries[1].timestamp);
+
+    assert!(read_dump(&b"NOTADUMP"[..]).is_err());
+}
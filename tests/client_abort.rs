@@ -0,0 +1,75 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{response::Content, Client, Params, Request};
+use tokio::io::{self, duplex};
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn abort() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    tokio::spawn(async move {
+        let request = common::read_request(&mut server_stream).await.unwrap();
+        common::write_record(
+            &mut server_stream,
+            6,
+            request.id,
+            b"Content-type: text/plain\r\n\r\n",
+        )
+        .await
+        .unwrap();
+
+        loop {
+            let (r#type, id, _) = common::read_record(&mut server_stream).await.unwrap();
+            if r#type == 2 {
+                assert_eq!(id, request.id);
+                break;
+            }
+        }
+
+        common::write_record(&mut server_stream, 6, request.id, b"partial")
+            .await
+            .unwrap();
+        common::write_end_request(&mut server_stream, request.id, 1, 0)
+            .await
+            .unwrap();
+
+        let request = common::read_request(&mut server_stream).await.unwrap();
+        common::write_record(&mut server_stream, 6, request.id, b"ok")
+            .await
+            .unwrap();
+        common::write_end_request(&mut server_stream, request.id, 0, 0)
+            .await
+            .unwrap();
+    });
+
+    let mut client = Client::new_keep_alive(client_stream);
+
+    let mut stream = client
+        .execute_stream(Request::new(Params::default(), io::empty()))
+        .await
+        .unwrap();
+    assert!(matches!(stream.next().await, Some(Ok(Content::Stdout(_)))));
+    stream.abort().await.unwrap();
+
+    let response = client
+        .execute(Request::new(Params::default(), io::empty()))
+        .await
+        .unwrap();
+    assert_eq!(response.stdout.as_deref(), Some(&b"ok"[..]));
+}
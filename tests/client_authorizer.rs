@@ -0,0 +1,62 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{response::authorizer::Authorization, Client, Params};
+use tokio::io::duplex;
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn authorize() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    tokio::spawn(async move {
+        for stdout in [
+            &b"Status: 200\r\nVariable-Auth-User: jmjoy\r\nX-Other: 1\r\n\r\n"[..],
+            &b"Status: 403 Forbidden\r\nContent-type: text/plain\r\n\r\ndenied"[..],
+        ] {
+            let request = common::read_request(&mut server_stream).await.unwrap();
+            assert_eq!(request.role, 2);
+            assert!(request.keep_alive);
+            assert!(request.stdin.is_empty());
+
+            common::write_record(&mut server_stream, 6, request.id, stdout)
+                .await
+                .unwrap();
+            common::write_end_request(&mut server_stream, request.id, 0, 0)
+                .await
+                .unwrap();
+        }
+    });
+
+    let mut client = Client::new_keep_alive(client_stream);
+
+    match client.authorize(Params::default()).await.unwrap() {
+        Authorization::Allowed { variables } => {
+            assert_eq!(variables.len(), 1);
+            assert_eq!(variables["AUTH-USER"], "jmjoy");
+        }
+        authorization => panic!("unexpected {:?}", authorization),
+    }
+
+    match client.authorize(Params::default()).await.unwrap() {
+        Authorization::Denied(response) => {
+            assert_eq!(response.status, 403);
+            assert_eq!(response.body, b"denied");
+        }
+        authorization => panic!("unexpected {:?}", authorization),
+    }
+}
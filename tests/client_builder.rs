@@ -0,0 +1,242 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{
+    params::ParamsLimits,
+    server::{Server, ServerRequest, ServerResponse},
+    stderr::StderrChunk,
+    Client, ClientError, Params, Request,
+};
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    io::{self, duplex},
+    sync::mpsc,
+};
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::{layer::Context, prelude::*, Layer};
+
+mod common;
+
+async fn echo(request: ServerRequest) -> ServerResponse {
+    ServerResponse::new(request.stdin)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn builder_keep_alive_buffered() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(async move { Server::new(echo).serve_connection(server_stream).await });
+
+    let completions = Arc::new(Mutex::new(Vec::new()));
+    let mut client = Client::builder()
+        .keep_alive()
+        .buffer_capacity(1024)
+        .on_complete({
+            let completions = completions.clone();
+            move |completion| {
+                completions
+                    .lock()
+                    .unwrap()
+                    .push((completion.id, completion.result.is_ok()));
+            }
+        })
+        .build_buffered(client_stream);
+    assert!(client.config().completion_hook.is_some());
+
+    for _ in 0..2 {
+        let response = client
+            .execute(Request::new(Params::default(), &b"hello"[..]))
+            .await
+            .unwrap();
+        assert_eq!(response.stdout.unwrap(), b"hello");
+    }
+    assert_eq!(*completions.lock().unwrap(), [(1, true), (1, true)]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn builder_request_timeout() {
+    common::setup();
+
+    // The server never responds.
+    let (client_stream, _server_stream) = duplex(4096);
+    let client = Client::builder()
+        .request_timeout(Duration::from_millis(50))
+        .build(client_stream);
+
+    assert!(matches!(
+        client
+            .execute_once(Request::new(Params::default(), io::empty()))
+            .await,
+        Err(ClientError::RequestTimeout)
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn builder_params_limits() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(async move { Server::new(echo).serve_connection(server_stream).await });
+
+    let mut client = Client::builder()
+        .keep_alive()
+        .strict(true)
+        .params_limits(ParamsLimits::default().max_value_length(8))
+        .build(client_stream);
+
+    let params = Params::default().request_uri("/index.php?long=query");
+    assert!(matches!(
+        client
+            .execute(Request::new(params.clone(), io::empty()))
+            .await,
+        Err(ClientError::ParamsTooLarge { .. })
+    ));
+    assert!(matches!(
+        client
+            .execute(Request::new(
+                Params::default().request_method("G\0T"),
+                io::empty()
+            ))
+            .await,
+        Err(ClientError::InvalidParam { .. })
+    ));
+
+    // The limits of request take precedence.
+    let request = Request::new(params, io::empty())
+        .with_params_limits(ParamsLimits::default().max_value_length(64));
+    client.execute(request).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn builder_connect() {
+    let result = Client::builder()
+        .connect_timeout(Duration::from_secs(1))
+        .connect("ftp://127.0.0.1:9000")
+        .await;
+    assert!(matches!(result, Err(ClientError::InvalidAddress { .. })));
+}
+
+type Fields = Vec<(String, String)>;
+
+/// Collect the level and fields of all events.
+#[derive(Clone, Default)]
+struct EventsLayer(Arc<Mutex<Vec<(Level, Fields)>>>);
+
+struct FieldsVisitor(Fields);
+
+impl Visit for FieldsVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .push((field.name().to_owned(), format!("{:?}", value)));
+    }
+}
+
+impl<S: Subscriber> Layer<S> for EventsLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = FieldsVisitor(Vec::new());
+        event.record(&mut visitor);
+        self.0
+            .lock()
+            .unwrap()
+            .push((*event.metadata().level(), visitor.0));
+    }
+}
+
+#[tokio::test]
+async fn builder_log_stderr() {
+    let events = EventsLayer::default();
+    let _guard = tracing_subscriber::registry()
+        .with(events.clone())
+        .set_default();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(async move {
+        Server::new(|_| async { ServerResponse::new("hello").stderr("PHP Notice: oops\n") })
+            .serve_connection(server_stream)
+            .await
+    });
+
+    let client = Client::builder()
+        .log_stderr(Level::WARN)
+        .build(client_stream);
+    let response = client
+        .execute_once(Request::new(
+            Params::default().script_filename("/var/www/index.php"),
+            io::empty(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.stdout.unwrap(), b"hello");
+    assert!(response.stderr.is_none());
+
+    let events = events.0.lock().unwrap();
+    let (_, fields) = events
+        .iter()
+        .find(|(level, _)| *level == Level::WARN)
+        .unwrap();
+    let field = |name| {
+        fields
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    };
+    assert_eq!(field("id"), Some("1"));
+    assert_eq!(field("script"), Some("\"/var/www/index.php\""));
+    assert_eq!(field("stderr"), Some("\"PHP Notice: oops\""));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn builder_on_stderr() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(async move {
+        Server::new(|_| async { ServerResponse::new("hello").stderr("PHP Notice: oops") })
+            .serve_connection(server_stream)
+            .await
+    });
+
+    let (chunk_tx, mut chunk_rx) = mpsc::unbounded_channel();
+    let client = Client::builder()
+        .on_stderr(move |chunk: StderrChunk| {
+            let chunk_tx = chunk_tx.clone();
+            async move {
+                let _ = chunk_tx.send(chunk);
+            }
+        })
+        .build(client_stream);
+    assert!(client.config().stderr_handler.is_some());
+    let response = client
+        .execute_once(Request::new(
+            Params::default().script_filename("/var/www/index.php"),
+            io::empty(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.stdout.unwrap(), b"hello");
+    assert!(response.stderr.is_none());
+
+    let chunk = chunk_rx.recv().await.unwrap();
+    assert_eq!(chunk.id, 1);
+    assert_eq!(chunk.script.as_deref(), Some("/var/www/index.php"));
+    assert_eq!(chunk.content, b"PHP Notice: oops");
+}
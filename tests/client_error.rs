@@ -0,0 +1,81 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{
+    pool::{Pool, PoolConfig},
+    Client, ClientError, Params, Request,
+};
+use std::{future::Ready, io, time::Duration};
+use tokio::io::{duplex, empty, DuplexStream};
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn connect_timeout() {
+    common::setup();
+
+    let pool = Pool::new(|| -> Ready<io::Result<DuplexStream>> {
+        std::future::ready(Err(io::ErrorKind::TimedOut.into()))
+    });
+    let result = pool.execute(Request::new(Params::default(), empty())).await;
+    assert!(matches!(result, Err(ClientError::ConnectTimeout)));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn pool_connect_timeout() {
+    common::setup();
+
+    // The connecting hangs, like the php-fpm is wedged.
+    let pool = Pool::with_config(
+        std::future::pending::<io::Result<DuplexStream>>,
+        PoolConfig::default().connect_timeout(Duration::from_millis(50)),
+    );
+    let result = pool.execute(Request::new(Params::default(), empty())).await;
+    assert!(matches!(result, Err(ClientError::ConnectTimeout)));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn protocol_error() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+    tokio::spawn(async move {
+        let request = common::read_request(&mut server_stream).await.unwrap();
+        // The content of `EndRequest` is too short.
+        common::write_record(&mut server_stream, 3, request.id, &[0; 4])
+            .await
+            .unwrap();
+    });
+
+    let result = Client::new(client_stream)
+        .execute_once(Request::new(Params::default(), empty()))
+        .await;
+    assert!(matches!(result, Err(ClientError::ProtocolError { .. })));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn connection_closed_by_peer() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    drop(server_stream);
+
+    let result = Client::new(client_stream)
+        .execute_once(Request::new(Params::default(), empty()))
+        .await;
+    assert!(matches!(
+        result,
+        Err(ClientError::ConnectionClosedByPeer { .. })
+    ));
+}
@@ -0,0 +1,111 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{params::ParamsLimits, Client, ClientError, Params, Request};
+use tokio::io::{duplex, DuplexStream};
+
+mod common;
+
+/// Echo the stdin of requests until `count` requests are served.
+async fn server(mut stream: DuplexStream, count: usize) {
+    for _ in 0..count {
+        let request = common::read_request(&mut stream).await.unwrap();
+        common::write_record(&mut stream, 6, request.id, &request.stdin)
+            .await
+            .unwrap();
+        common::write_record(&mut stream, 6, request.id, b"")
+            .await
+            .unwrap();
+        common::write_end_request(&mut stream, request.id, 0, 0)
+            .await
+            .unwrap();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn execute_many() {
+    common::setup();
+
+    // The buffer is smaller than the requests, so the responses must be read
+    // while sending.
+    let (client_stream, server_stream) = duplex(64);
+    tokio::spawn(server(server_stream, 3));
+
+    let stdins = [vec![b'a'; 1000], vec![b'b'; 1000], vec![b'c'; 1000]];
+    let requests = stdins
+        .iter()
+        .map(|stdin| Request::new(Params::default(), &stdin[..]))
+        .collect();
+    let mut client = Client::new_keep_alive(client_stream);
+    let results = client.execute_many(requests).await;
+
+    assert_eq!(results.len(), 3);
+    for (result, stdin) in results.into_iter().zip(&stdins) {
+        assert_eq!(&result.unwrap().stdout.unwrap(), stdin);
+    }
+    assert!(!client.is_poisoned());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn execute_many_rejected() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(server(server_stream, 2));
+
+    let limits = ParamsLimits::default().max_value_length(8);
+    let requests = vec![
+        Request::new(Params::default(), &b"first"[..]),
+        Request::new(Params::default(), &b"second"[..]).with_params_limits(limits),
+        Request::new(Params::default(), &b"third"[..]),
+    ];
+    let mut client = Client::new_keep_alive(client_stream);
+    let results = client.execute_many(requests).await;
+
+    assert_eq!(
+        results[0].as_ref().unwrap().stdout.as_deref(),
+        Some(&b"first"[..])
+    );
+    assert!(matches!(
+        results[1],
+        Err(ClientError::ParamsTooLarge { .. })
+    ));
+    assert_eq!(
+        results[2].as_ref().unwrap().stdout.as_deref(),
+        Some(&b"third"[..])
+    );
+    assert!(!client.is_poisoned());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn execute_many_closed() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(server(server_stream, 1));
+
+    let requests = (0..3)
+        .map(|_| Request::new(Params::default(), &b"stdin"[..]))
+        .collect();
+    let mut client = Client::new_keep_alive(client_stream);
+    let results = client.execute_many(requests).await;
+
+    assert!(results[0].is_ok());
+    assert!(matches!(
+        results[1],
+        Err(ClientError::ConnectionClosedByPeer { .. })
+    ));
+    assert!(matches!(results[2], Err(ClientError::ConnectionPoisoned)));
+    assert!(client.is_poisoned());
+}
@@ -114,6 +114,9 @@ async fn test_client_stream<S: AsyncRead + AsyncWrite + Unpin>(client: Client<S,
             Content::Stderr(_) => {
                 panic!("stderr should not happened");
             }
+            Content::End { app_status, .. } => {
+                assert_eq!(app_status, 0);
+            }
         }
     }
 
@@ -173,6 +176,9 @@ async fn test_client_big_response_stream<S: AsyncRead + AsyncWrite + Unpin>(
             Content::Stderr(_) => {
                 panic!("stderr should not happened");
             }
+            Content::End { app_status, .. } => {
+                assert_eq!(app_status, 0);
+            }
         }
     }
 
@@ -0,0 +1,78 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{
+    values::{ValueName, Values},
+    Client, ClientError,
+};
+use tokio::io::duplex;
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn get_values() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    tokio::spawn(async move {
+        let (r#type, id, content) = common::read_record(&mut server_stream).await.unwrap();
+        assert_eq!(r#type, 9);
+        assert_eq!(id, 0);
+        let names = common::decode_params(&content);
+        assert_eq!(names.len(), 2);
+        assert_eq!(names["FCGI_MAX_REQS"], "");
+        assert_eq!(names["FCGI_MPXS_CONNS"], "");
+
+        let content = common::encode_params(&[("FCGI_MAX_REQS", "50"), ("FCGI_MPXS_CONNS", "0")]);
+        common::write_record(&mut server_stream, 10, 0, &content)
+            .await
+            .unwrap();
+    });
+
+    let mut client = Client::new_keep_alive(client_stream);
+    let values = client
+        .get_values(&[ValueName::MaxReqs, ValueName::MpxsConns])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        values,
+        Values {
+            max_conns: None,
+            max_reqs: Some(50),
+            mpxs_conns: Some(false),
+        }
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn get_values_unsupported() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    tokio::spawn(async move {
+        let (r#type, _, _) = common::read_record(&mut server_stream).await.unwrap();
+        common::write_record(&mut server_stream, 11, 0, &[r#type, 0, 0, 0, 0, 0, 0, 0])
+            .await
+            .unwrap();
+    });
+
+    let mut client = Client::new_keep_alive(client_stream);
+    match client.get_values(&ValueName::ALL).await {
+        Err(ClientError::UnsupportedRecordType { request_type }) => assert_eq!(request_type, 9),
+        result => panic!("unexpected {:?}", result),
+    }
+}
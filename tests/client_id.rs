@@ -0,0 +1,153 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{
+    id::{AllocRequestId, AtomicRequestIdAllocator, PooledRequestIdAllocator},
+    Client, ClientError, ClientResult, Params, Request,
+};
+use std::{
+    collections::HashSet,
+    num::NonZeroU16,
+    sync::{Arc, Mutex},
+};
+use tokio::io::{duplex, DuplexStream};
+
+mod common;
+
+/// Allocate the fixed id `7`, and record the released ids.
+#[derive(Default)]
+struct RecordingAllocator {
+    released: Arc<Mutex<Vec<u16>>>,
+}
+
+impl AllocRequestId for RecordingAllocator {
+    fn alloc(&self) -> ClientResult<u16> {
+        Ok(7)
+    }
+
+    fn release(&self, id: u16) {
+        self.released.lock().unwrap().push(id);
+    }
+}
+
+/// Echo the request id as stdout until `count` requests are served.
+async fn server(mut stream: DuplexStream, count: usize) {
+    for _ in 0..count {
+        let request = common::read_request(&mut stream).await.unwrap();
+        let id = request.id.to_string();
+        common::write_record(&mut stream, 6, request.id, id.as_bytes())
+            .await
+            .unwrap();
+        common::write_end_request(&mut stream, request.id, 0, 0)
+            .await
+            .unwrap();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn custom_id_allocator() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(server(server_stream, 2));
+
+    let allocator = RecordingAllocator::default();
+    let released = allocator.released.clone();
+    let mut client = Client::new_keep_alive(client_stream).with_id_allocator(allocator);
+
+    let output = client
+        .execute(Request::new(Params::default(), tokio::io::empty()))
+        .await
+        .unwrap();
+    assert_eq!(output.stdout.unwrap(), b"7");
+    assert_eq!(*released.lock().unwrap(), [7]);
+
+    // The id specified by request isn't allocated nor released.
+    let output = client
+        .execute(
+            Request::builder()
+                .request_id(NonZeroU16::new(3).unwrap())
+                .build(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(output.stdout.unwrap(), b"3");
+    assert_eq!(*released.lock().unwrap(), [7]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn pooled_id_allocator_many() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(server(server_stream, 3));
+
+    let mut client = Client::new_keep_alive(client_stream)
+        .with_id_allocator(PooledRequestIdAllocator::default());
+    let requests = (0..3)
+        .map(|_| Request::new(Params::default(), tokio::io::empty()))
+        .collect();
+    let results = client.execute_many(requests).await;
+
+    let ids = results
+        .into_iter()
+        .map(|result| result.unwrap().stdout.unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(ids, [b"1", b"2", b"3"]);
+}
+
+#[test]
+fn atomic_id_allocator() {
+    let allocator = AtomicRequestIdAllocator::default();
+
+    let ids = (0..u16::MAX)
+        .map(|_| allocator.alloc().unwrap())
+        .collect::<HashSet<_>>();
+    assert_eq!(ids.len(), u16::MAX as usize);
+    assert!(!ids.contains(&0));
+    assert!(matches!(
+        allocator.alloc(),
+        Err(ClientError::RequestIdExhausted)
+    ));
+
+    allocator.release(300);
+    assert_eq!(allocator.alloc().unwrap(), 300);
+    assert_eq!(allocator.in_use(), u16::MAX as usize);
+}
+
+#[test]
+fn atomic_id_allocator_concurrent() {
+    let allocator = Arc::new(AtomicRequestIdAllocator::default());
+
+    let handles = (0..4)
+        .map(|_| {
+            let allocator = allocator.clone();
+            std::thread::spawn(move || {
+                (0..1000)
+                    .map(|_| allocator.alloc().unwrap())
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect::<Vec<_>>();
+    let ids = handles
+        .into_iter()
+        .flat_map(|handle| handle.join().unwrap())
+        .collect::<HashSet<_>>();
+    assert_eq!(ids.len(), 4000);
+
+    for id in ids {
+        allocator.release(id);
+    }
+    assert_eq!(allocator.in_use(), 0);
+}
@@ -136,6 +136,7 @@ async fn single_stream() {
                 Content::Stderr(err) => {
                     stderr.extend_from_slice(err);
                 }
+                Content::End { .. } => {}
             }
         }
 
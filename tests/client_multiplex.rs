@@ -0,0 +1,160 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{multiplex::MultiplexClient, request::Request, ClientError, Params};
+use tokio::io::{duplex, DuplexStream};
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn interleaved_responses() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    let server = tokio::spawn(async move {
+        let first = common::read_request(&mut server_stream).await.unwrap();
+        let second = common::read_request(&mut server_stream).await.unwrap();
+        let (first_id, first_stdin) = (first.id, first.stdin);
+        let (second_id, second_stdin) = (second.id, second.stdin);
+        assert_ne!(first_id, second_id);
+
+        // Reply in reverse order, with interleaved records.
+        for (id, stdin) in [(second_id, &second_stdin), (first_id, &first_stdin)] {
+            common::write_record(&mut server_stream, 6, id, b"out-")
+                .await
+                .unwrap();
+            common::write_record(&mut server_stream, 7, id, stdin)
+                .await
+                .unwrap();
+        }
+        for (id, stdin) in [(first_id, &first_stdin), (second_id, &second_stdin)] {
+            common::write_record(&mut server_stream, 6, id, stdin)
+                .await
+                .unwrap();
+            common::write_end_request(&mut server_stream, id, 0, 0)
+                .await
+                .unwrap();
+        }
+        server_stream
+    });
+
+    let client = MultiplexClient::new(client_stream);
+    let (first, second) = tokio::join!(
+        client.execute(Request::new(Params::default(), &b"first"[..])),
+        client.execute(Request::new(Params::default(), &b"second"[..])),
+    );
+    let (first, second) = (first.unwrap(), second.unwrap());
+
+    assert_eq!(first.stdout.as_deref(), Some(&b"out-first"[..]));
+    assert_eq!(first.stderr.as_deref(), Some(&b"first"[..]));
+    assert_eq!(second.stdout.as_deref(), Some(&b"out-second"[..]));
+    assert_eq!(second.stderr.as_deref(), Some(&b"second"[..]));
+
+    server.await.unwrap();
+}
+
+type Values = Option<&'static [(&'static str, &'static str)]>;
+
+/// Answer `FCGI_GET_VALUES` with `values`, or `FCGI_UNKNOWN_TYPE` if `None`,
+/// then answer the requests one by one.
+async fn discover_server(mut stream: DuplexStream, values: Values) {
+    let (r#type, id, content) = common::read_record(&mut stream).await.unwrap();
+    assert_eq!((r#type, id), (9, 0));
+    let names = common::decode_params(&content);
+    assert!(names.contains_key("FCGI_MPXS_CONNS"));
+    assert!(names.contains_key("FCGI_MAX_REQS"));
+    match values {
+        Some(values) => {
+            common::write_record(&mut stream, 10, 0, &common::encode_params(values))
+                .await
+                .unwrap();
+        }
+        None => {
+            common::write_record(&mut stream, 11, 0, &[9, 0, 0, 0, 0, 0, 0, 0])
+                .await
+                .unwrap();
+        }
+    }
+
+    while let Ok(request) = common::read_request(&mut stream).await {
+        common::write_record(&mut stream, 6, request.id, &request.stdin)
+            .await
+            .unwrap();
+        common::write_end_request(&mut stream, request.id, 0, 0)
+            .await
+            .unwrap();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn discover() {
+    common::setup();
+
+    let cases: [(Values, Option<usize>); 4] = [
+        (
+            Some(&[("FCGI_MPXS_CONNS", "1"), ("FCGI_MAX_REQS", "10")]),
+            Some(10),
+        ),
+        (Some(&[("FCGI_MPXS_CONNS", "1")]), None),
+        (
+            Some(&[("FCGI_MPXS_CONNS", "0"), ("FCGI_MAX_REQS", "10")]),
+            Some(1),
+        ),
+        (None, Some(1)),
+    ];
+    for (values, max_concurrency) in cases {
+        let (client_stream, server_stream) = duplex(4096);
+        tokio::spawn(discover_server(server_stream, values));
+
+        let client = MultiplexClient::discover(client_stream).await.unwrap();
+        assert_eq!(client.max_concurrency(), max_concurrency);
+
+        let (first, second) = tokio::join!(
+            client.execute(Request::new(Params::default(), &b"first"[..])),
+            client.execute(Request::new(Params::default(), &b"second"[..])),
+        );
+        assert_eq!(first.unwrap().stdout.as_deref(), Some(&b"first"[..]));
+        assert_eq!(second.unwrap().stdout.as_deref(), Some(&b"second"[..]));
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn short_end_request() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    tokio::spawn(async move {
+        let request = common::read_request(&mut server_stream).await.unwrap();
+        common::write_record(&mut server_stream, 6, request.id, b"out")
+            .await
+            .unwrap();
+        common::write_record(&mut server_stream, 3, request.id, b"")
+            .await
+            .unwrap();
+    });
+
+    let client = MultiplexClient::new(client_stream);
+    let result = client
+        .execute(Request::new(Params::default(), &b""[..]))
+        .await;
+    match result {
+        Err(ClientError::PartialResponse { source, response }) => {
+            assert_eq!(source.kind(), std::io::ErrorKind::InvalidData);
+            assert_eq!(response.stdout.as_deref(), Some(&b"out"[..]));
+        }
+        result => panic!("unexpected {:?}", result),
+    }
+}
@@ -0,0 +1,48 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{
+    server::{Server, ServerRequest, ServerResponse},
+    Client, ClientError,
+};
+use tokio::io::duplex;
+
+mod common;
+
+async fn ping(request: ServerRequest) -> ServerResponse {
+    if request.params["SCRIPT_NAME"] == "/ping" {
+        ServerResponse::new("Content-type: text/plain\r\n\r\npong")
+    } else {
+        ServerResponse::new("Status: 404 Not Found\r\n\r\nFile not found.")
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn client_ping() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(async move { Server::new(ping).serve_connection(server_stream).await });
+
+    let mut client = Client::new_keep_alive(client_stream);
+    client.ping("/ping").await.unwrap();
+
+    match client.ping("/status").await {
+        Err(ClientError::PingFailed { status, body }) => {
+            assert_eq!(status, 404);
+            assert_eq!(body, "File not found.");
+        }
+        result => panic!("unexpected {:?}", result),
+    }
+}
@@ -0,0 +1,78 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{retry::RetryPolicy, Client, ClientError, Params, Request};
+use std::time::Duration;
+use tokio::io::duplex;
+
+mod common;
+
+/// `FCGI_OVERLOADED` protocol status.
+const OVERLOADED: u8 = 2;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn retry_overloaded() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    tokio::spawn(async move {
+        for _ in 0..2 {
+            let request = common::read_request(&mut server_stream).await.unwrap();
+            common::write_end_request(&mut server_stream, request.id, 0, OVERLOADED)
+                .await
+                .unwrap();
+        }
+        let request = common::read_request(&mut server_stream).await.unwrap();
+        assert_eq!(request.stdin, b"body");
+        common::write_record(&mut server_stream, 6, request.id, b"hello")
+            .await
+            .unwrap();
+        common::write_end_request(&mut server_stream, request.id, 0, 0)
+            .await
+            .unwrap();
+
+        let request = common::read_request(&mut server_stream).await.unwrap();
+        common::write_end_request(&mut server_stream, request.id, 0, OVERLOADED)
+            .await
+            .unwrap();
+    });
+
+    let mut client = Client::new_keep_alive(client_stream);
+    let params = Params::default();
+    let policy = RetryPolicy::default()
+        .initial_backoff(Duration::from_millis(1))
+        .jitter(false);
+
+    let mut attempts = 0;
+    let response = client
+        .execute_with_retry(&policy, || {
+            attempts += 1;
+            Request::new(&params, &b"body"[..])
+        })
+        .await
+        .unwrap();
+    assert_eq!(attempts, 3);
+    assert_eq!(response.stdout.unwrap(), b"hello");
+
+    let result = client
+        .execute_with_retry(&policy.max_retries(0), || {
+            Request::new(&params, tokio::io::empty())
+        })
+        .await;
+    assert!(matches!(
+        result,
+        Err(ClientError::EndRequestOverloaded { .. })
+    ));
+}
@@ -0,0 +1,182 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{
+    request::{Priority, Request},
+    shared::SharedClient,
+    ClientError, Params,
+};
+use std::{io::Cursor, time::Duration};
+use tokio::{io::duplex, sync::oneshot};
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn shared_across_tasks() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    tokio::spawn(async move {
+        loop {
+            let request = match common::read_request(&mut server_stream).await {
+                Ok(request) => request,
+                Err(_) => break,
+            };
+            let id = request.id;
+            common::write_record(&mut server_stream, 6, id, &request.stdin)
+                .await
+                .unwrap();
+            common::write_end_request(&mut server_stream, id, 0, 0)
+                .await
+                .unwrap();
+        }
+    });
+
+    let client = SharedClient::new(client_stream);
+
+    let tasks = (0..3)
+        .map(|i| {
+            let client = client.clone();
+            tokio::spawn(async move {
+                let body = format!("body-{}", i).into_bytes();
+                let response = client
+                    .execute(Request::new(Params::default(), Cursor::new(body.clone())))
+                    .await
+                    .unwrap();
+                assert_eq!(response.stdout, Some(body));
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for task in tasks {
+        task.await.unwrap();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn shared_shutdown() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    let server = tokio::spawn(async move {
+        let request = common::read_request(&mut server_stream).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        common::write_end_request(&mut server_stream, request.id, 0, 0)
+            .await
+            .unwrap();
+        // The connection is closed after the request completed.
+        assert!(common::read_record(&mut server_stream).await.is_err());
+    });
+
+    let client = SharedClient::new(client_stream);
+
+    let in_flight = {
+        let client = client.clone();
+        tokio::spawn(async move {
+            client
+                .execute(Request::new(Params::default(), tokio::io::empty()))
+                .await
+        })
+    };
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    client.shutdown(Duration::from_secs(1)).await.unwrap();
+    in_flight.await.unwrap().unwrap();
+    server.await.unwrap();
+
+    assert!(matches!(
+        client
+            .execute(Request::new(Params::default(), tokio::io::empty()))
+            .await,
+        Err(ClientError::ClientClosed)
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn shared_priority() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+    let (release, released) = oneshot::channel::<()>();
+
+    // Hold the first request until released, return the order of stdin.
+    let server = tokio::spawn(async move {
+        let mut released = Some(released);
+        let mut order = Vec::new();
+        for _ in 0..4 {
+            let request = common::read_request(&mut server_stream).await.unwrap();
+            if let Some(released) = released.take() {
+                released.await.unwrap();
+            }
+            order.push(String::from_utf8(request.stdin).unwrap());
+            common::write_end_request(&mut server_stream, request.id, 0, 0)
+                .await
+                .unwrap();
+        }
+        order
+    });
+
+    let client = SharedClient::new(client_stream);
+    let mut tasks = Vec::new();
+    for (stdin, priority) in [
+        ("first", Priority::Normal),
+        ("low", Priority::Low),
+        ("normal", Priority::Normal),
+        ("high", Priority::High),
+    ] {
+        let client = client.clone();
+        let request = Request::builder()
+            .stdin(stdin.as_bytes())
+            .priority(priority)
+            .build();
+        tasks.push(tokio::spawn(async move { client.execute(request).await }));
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    release.send(()).unwrap();
+    for task in tasks {
+        task.await.unwrap().unwrap();
+    }
+    assert_eq!(server.await.unwrap(), ["first", "high", "normal", "low"]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn shared_closed_by_peer() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    tokio::spawn(async move {
+        common::read_request(&mut server_stream).await.unwrap();
+        // Close the connection without response.
+    });
+
+    let client = SharedClient::new(client_stream);
+
+    assert!(matches!(
+        client
+            .execute(Request::new(Params::default(), tokio::io::empty()))
+            .await,
+        Err(ClientError::ConnectionClosedByPeer { .. })
+    ));
+    assert!(matches!(
+        client
+            .clone()
+            .execute(Request::new(Params::default(), tokio::io::empty()))
+            .await,
+        Err(ClientError::ClientClosed)
+    ));
+}
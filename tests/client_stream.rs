@@ -0,0 +1,415 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bytes::Bytes;
+use fastcgi_client::{
+    response::{
+        parse::{parse_head_with_limits, HeaderLimits},
+        BytesContent, Content, RequestEnd,
+    },
+    Client, ClientError, Params, ProtocolStatus, Request,
+};
+use std::time::Duration;
+use tokio::{
+    io::{self, duplex, AsyncReadExt},
+    sync::mpsc,
+    time,
+};
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn stream_end() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    tokio::spawn(async move {
+        let id = common::read_request(&mut server_stream).await.unwrap().id;
+        common::write_record(&mut server_stream, 6, id, b"Status: 500\r\n\r\n")
+            .await
+            .unwrap();
+        common::write_end_request(&mut server_stream, id, 255, 0)
+            .await
+            .unwrap();
+    });
+
+    let mut stream = Client::new(client_stream)
+        .execute_once_stream(Request::new(Params::default(), io::empty()))
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        stream.next().await,
+        Some(Ok(Content::Stdout(b"Status: 500\r\n\r\n")))
+    ));
+    assert!(matches!(
+        stream.next().await,
+        Some(Ok(Content::End {
+            app_status: 255,
+            protocol_status: ProtocolStatus::RequestComplete,
+        }))
+    ));
+    assert!(stream.next().await.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn stream_bytes() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    tokio::spawn(async move {
+        let id = common::read_request(&mut server_stream).await.unwrap().id;
+        common::write_record(
+            &mut server_stream,
+            6,
+            id,
+            b"Content-type: text/plain\r\n\r\n",
+        )
+        .await
+        .unwrap();
+        common::write_record(&mut server_stream, 7, id, b"notice")
+            .await
+            .unwrap();
+        common::write_record(&mut server_stream, 6, id, &[b'x'; 5000])
+            .await
+            .unwrap();
+        common::write_end_request(&mut server_stream, id, 0, 0)
+            .await
+            .unwrap();
+    });
+
+    let mut stream = Client::new(client_stream)
+        .execute_once_stream(Request::new(Params::default(), io::empty()))
+        .await
+        .unwrap();
+
+    let mut contents = Vec::new();
+    while let Some(content) = stream.next_bytes().await {
+        contents.push(content.unwrap());
+    }
+
+    assert_eq!(
+        contents[0],
+        BytesContent::Stdout(Bytes::from_static(b"Content-type: text/plain\r\n\r\n"))
+    );
+    assert_eq!(
+        contents[1],
+        BytesContent::Stderr(Bytes::from_static(b"notice"))
+    );
+
+    // The body may be split into several chunks.
+    let (end, body) = contents[2..].split_last().unwrap();
+    let body = body
+        .iter()
+        .flat_map(|content| match content {
+            BytesContent::Stdout(out) => out.to_vec(),
+            content => panic!("unexpected {:?}", content),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(body, [b'x'; 5000]);
+    assert_eq!(
+        *end,
+        BytesContent::End {
+            app_status: 0,
+            protocol_status: ProtocolStatus::RequestComplete,
+        }
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn parsed_head_then_body() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    let server = tokio::spawn(async move {
+        for _ in 0..2 {
+            let id = common::read_request(&mut server_stream).await.unwrap().id;
+            // The header section is split across records, and the first body
+            // bytes are in the same record as the end of it.
+            common::write_record(&mut server_stream, 6, id, b"Status: 201 Created\r\nX-")
+                .await
+                .unwrap();
+            common::write_record(&mut server_stream, 7, id, b"notice")
+                .await
+                .unwrap();
+            common::write_record(&mut server_stream, 6, id, b"Foo: bar\r\n\r\nhello")
+                .await
+                .unwrap();
+            common::write_record(&mut server_stream, 6, id, b" world")
+                .await
+                .unwrap();
+            common::write_end_request(&mut server_stream, id, 0, 0)
+                .await
+                .unwrap();
+        }
+    });
+
+    let mut client = Client::new_keep_alive(client_stream);
+    for _ in 0..2 {
+        let (head, mut body) = client
+            .execute_parsed(Request::new(Params::default(), io::empty()))
+            .await
+            .unwrap();
+        assert_eq!(head.status, 201);
+        assert_eq!(head.headers.get("x-foo"), Some("bar"));
+
+        let mut content = String::new();
+        body.read_to_string(&mut content).await.unwrap();
+        assert_eq!(content, "hello world");
+    }
+    server.await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn parsed_head_unterminated() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    tokio::spawn(async move {
+        let id = common::read_request(&mut server_stream).await.unwrap().id;
+        common::write_record(&mut server_stream, 6, id, b"Content-type: text/plain\r\n")
+            .await
+            .unwrap();
+        common::write_end_request(&mut server_stream, id, 0, 0)
+            .await
+            .unwrap();
+    });
+
+    let result = Client::new(client_stream)
+        .execute_once_parsed(Request::new(Params::default(), io::empty()))
+        .await;
+    assert!(matches!(
+        result,
+        Err(ClientError::InvalidCgiResponse { .. })
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn stream_progressive() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+    let (received_tx, mut received_rx) = mpsc::channel::<()>(1);
+
+    // The next event is sent only after the previous one is received, so the
+    // request hangs if the stdout is held back.
+    tokio::spawn(async move {
+        let id = common::read_request(&mut server_stream).await.unwrap().id;
+        for event in [&b"data: 1\n\n"[..], b"data: 2\n\n"] {
+            common::write_record(&mut server_stream, 6, id, event)
+                .await
+                .unwrap();
+            received_rx.recv().await.unwrap();
+        }
+        common::write_end_request(&mut server_stream, id, 0, 0)
+            .await
+            .unwrap();
+    });
+
+    let client = Client::builder()
+        .low_latency(true)
+        .build_buffered(client_stream);
+    assert!(client.config().low_latency);
+    let mut stream = client
+        .execute_once_stream(Request::new(Params::default(), io::empty()))
+        .await
+        .unwrap();
+
+    let mut events = Vec::new();
+    time::timeout(Duration::from_secs(5), async {
+        while let Some(content) = stream.next().await {
+            if let Content::Stdout(out) = content.unwrap() {
+                events.push(out.to_vec());
+                received_tx.send(()).await.unwrap();
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(events, [&b"data: 1\n\n"[..], b"data: 2\n\n"]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn parsed_head_too_large() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    // The header section never ends, the reading stops at the limit.
+    tokio::spawn(async move {
+        let id = common::read_request(&mut server_stream).await.unwrap().id;
+        loop {
+            let written = common::write_record(&mut server_stream, 6, id, b"X-Foo: bar\r\n").await;
+            if written.is_err() {
+                break;
+            }
+        }
+    });
+
+    let stream = Client::new(client_stream)
+        .execute_once_stream(Request::new(Params::default(), io::empty()))
+        .await
+        .unwrap();
+    let result = parse_head_with_limits(stream, &HeaderLimits::default().max_bytes(1024)).await;
+    assert!(matches!(
+        result,
+        Err(ClientError::HeadersTooLarge { limit: 1024 })
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn stream_end_request() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    tokio::spawn(async move {
+        let id = common::read_request(&mut server_stream).await.unwrap().id;
+        common::write_record(&mut server_stream, 6, id, b"Status: 500\r\n\r\nfailed")
+            .await
+            .unwrap();
+        common::write_end_request(&mut server_stream, id, 255, 0)
+            .await
+            .unwrap();
+    });
+
+    let mut stream = Client::new(client_stream)
+        .execute_once_stream(Request::new(Params::default(), io::empty()))
+        .await
+        .unwrap();
+    assert_eq!(stream.end_request(), None);
+
+    let mut stdout = Vec::new();
+    stream
+        .stdout_reader()
+        .read_to_end(&mut stdout)
+        .await
+        .unwrap();
+    assert_eq!(stdout, b"Status: 500\r\n\r\nfailed");
+    assert_eq!(
+        stream.end_request(),
+        Some(RequestEnd {
+            app_status: 255,
+            protocol_status: ProtocolStatus::RequestComplete,
+        })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn stream_collect() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    tokio::spawn(async move {
+        for partial in [false, true] {
+            let id = common::read_request(&mut server_stream).await.unwrap().id;
+            common::write_record(
+                &mut server_stream,
+                6,
+                id,
+                b"Content-type: text/plain\r\n\r\n",
+            )
+            .await
+            .unwrap();
+            common::write_record(&mut server_stream, 7, id, b"notice")
+                .await
+                .unwrap();
+            common::write_record(&mut server_stream, 6, id, b"hello")
+                .await
+                .unwrap();
+            if partial {
+                return;
+            }
+            common::write_end_request(&mut server_stream, id, 3, 0)
+                .await
+                .unwrap();
+        }
+    });
+
+    let mut client = Client::new_keep_alive(client_stream);
+    let response = client
+        .execute_stream(Request::new(Params::default(), io::empty()))
+        .await
+        .unwrap()
+        .collect()
+        .await
+        .unwrap();
+    assert_eq!(
+        response.stdout.unwrap(),
+        b"Content-type: text/plain\r\n\r\nhello"
+    );
+    assert_eq!(response.stderr.unwrap(), b"notice");
+    assert_eq!(response.app_status, 3);
+
+    let result = client
+        .execute_stream(Request::new(Params::default(), io::empty()))
+        .await
+        .unwrap()
+        .collect()
+        .await;
+    match result {
+        Err(ClientError::PartialResponse { response, .. }) => {
+            assert_eq!(response.stderr.unwrap(), b"notice");
+        }
+        result => panic!("unexpected {:?}", result),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn stream_max_content_length() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    tokio::spawn(async move {
+        // The oversize `EndRequest` is accepted within the default limit.
+        let id = common::read_request(&mut server_stream).await.unwrap().id;
+        let mut end_request = vec![0; 5000];
+        end_request[3] = 7;
+        common::write_record(&mut server_stream, 3, id, &end_request)
+            .await
+            .unwrap();
+
+        let id = common::read_request(&mut server_stream).await.unwrap().id;
+        common::write_record(&mut server_stream, 6, id, &[b'x'; 5000])
+            .await
+            .unwrap();
+    });
+
+    let mut client = Client::new_keep_alive(client_stream);
+    let response = client
+        .execute_stream(Request::new(Params::default(), io::empty()))
+        .await
+        .unwrap()
+        .collect()
+        .await
+        .unwrap();
+    assert_eq!(response.app_status, 7);
+
+    let mut stream = client
+        .execute_stream(Request::new(Params::default(), io::empty()))
+        .await
+        .unwrap()
+        .max_content_length(4096);
+    assert!(matches!(
+        stream.next().await,
+        Some(Err(ClientError::ProtocolError { .. }))
+    ));
+    assert!(stream.next().await.is_none());
+}
@@ -0,0 +1,42 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{Client, ClientError, Params, Request};
+use std::time::Duration;
+use tokio::io::{self, duplex};
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn timeout() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    let server = tokio::spawn(async move {
+        // Receive the request but never respond.
+        common::read_request(&mut server_stream).await.unwrap();
+        server_stream
+    });
+
+    let client = Client::new(client_stream);
+    let request =
+        Request::new(Params::default(), io::empty()).with_timeout(Duration::from_millis(100));
+    assert_eq!(request.timeout(), Some(Duration::from_millis(100)));
+
+    let result = client.execute_once(request).await;
+    assert!(matches!(result, Err(ClientError::RequestTimeout)));
+
+    server.await.unwrap();
+}
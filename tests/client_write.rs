@@ -0,0 +1,223 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{
+    server::{Server, ServerRequest, ServerResponse},
+    Client, ClientError, Params, Request,
+};
+use std::{
+    io::{self, IoSlice},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+use tokio::io::{duplex, AsyncRead, AsyncWrite, DuplexStream, ReadBuf};
+
+mod common;
+
+/// Stream counting the write calls.
+struct CountingStream {
+    inner: DuplexStream,
+    writes: Arc<AtomicUsize>,
+}
+
+impl AsyncRead for CountingStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for CountingStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.writes.fetch_add(1, Ordering::SeqCst);
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.writes.fetch_add(1, Ordering::SeqCst);
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn coalesce_request() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(0x10000);
+
+    tokio::spawn(async move {
+        let request = common::read_request(&mut server_stream).await.unwrap();
+        assert_eq!(request.stdin, b"body");
+        common::write_end_request(&mut server_stream, request.id, 0, 0)
+            .await
+            .unwrap();
+    });
+
+    let writes = Arc::new(AtomicUsize::new(0));
+    let mut client = Client::new_keep_alive(CountingStream {
+        inner: client_stream,
+        writes: writes.clone(),
+    });
+    client
+        .execute(Request::new(
+            Params::default().request_method("POST"),
+            &b"body"[..],
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(writes.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn buffered_client() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(async move {
+        Server::new(|request: ServerRequest| async move {
+            let mut stdout = b"Content-type: text/plain\r\n\r\n".to_vec();
+            stdout.extend(request.stdin);
+            ServerResponse::new(stdout)
+        })
+        .serve_connection(server_stream)
+        .await
+    });
+
+    let mut client = Client::new_keep_alive_buffered(client_stream);
+    for body in ["foo", "bar"] {
+        let response = client
+            .execute(Request::new(Params::default(), body.as_bytes()))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.stdout.unwrap(),
+            format!("Content-type: text/plain\r\n\r\n{}", body).as_bytes()
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn sized_stdin() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(0x10000);
+
+    tokio::spawn(async move {
+        let mut stdin_lengths = Vec::new();
+        loop {
+            let (r#type, id, content) = common::read_record(&mut server_stream).await.unwrap();
+            if r#type == 5 {
+                stdin_lengths.push(content.len());
+                if content.is_empty() {
+                    assert_eq!(stdin_lengths, [65528, 4472, 0]);
+                    common::write_end_request(&mut server_stream, id, 0, 0)
+                        .await
+                        .unwrap();
+                    break;
+                }
+            }
+        }
+    });
+
+    let body = vec![b'x'; 70000];
+    let request = Request::new(Params::default(), &body[..]).with_stdin_len(body.len());
+    assert_eq!(request.stdin_len(), Some(70000));
+    Client::new(client_stream)
+        .execute_once(request)
+        .await
+        .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn sized_stdin_32768() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(0x10000);
+
+    tokio::spawn(async move {
+        let request = common::read_request(&mut server_stream).await.unwrap();
+        assert_eq!(request.stdin, [b'a'; 32768]);
+        common::write_end_request(&mut server_stream, request.id, 0, 0)
+            .await
+            .unwrap();
+    });
+
+    let request = Request::new_sized(Params::default(), &[b'a'; 32768][..], 32768);
+    Client::new(client_stream)
+        .execute_once(request)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn sized_stdin_too_short() {
+    let (client_stream, _server_stream) = duplex(0x10000);
+
+    let request = Request::new(Params::default(), &b"body"[..]).with_stdin_len(5);
+    let result = Client::new(client_stream).execute_once(request).await;
+    assert!(
+        matches!(result, Err(ClientError::Io(err)) if err.kind() == io::ErrorKind::UnexpectedEof)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn new_sized() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(0x10000);
+
+    tokio::spawn(async move {
+        let request = common::read_request(&mut server_stream).await.unwrap();
+        assert_eq!(request.params["CONTENT_LENGTH"], "4");
+        assert_eq!(request.stdin, b"body");
+        common::write_end_request(&mut server_stream, request.id, 0, 0)
+            .await
+            .unwrap();
+    });
+
+    let mut client = Client::new_keep_alive(client_stream);
+
+    let request = Request::new_sized(Params::default(), &b"body"[..], 4);
+    client.execute(request).await.unwrap();
+
+    let request = Request::new_sized(Params::default().content_length(3), &b"body"[..], 4);
+    match client.execute(request).await {
+        Err(ClientError::ContentLengthMismatch {
+            content_length,
+            stdin_len,
+        }) => {
+            assert_eq!(content_length, "3");
+            assert_eq!(stdin_len, 4);
+        }
+        result => panic!("unexpected {:?}", result),
+    }
+}
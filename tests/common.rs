@@ -12,7 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::sync::Once;
+#![allow(dead_code)]
+
+use std::{collections::HashMap, sync::Once};
+use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tracing::Level;
 use tracing_subscriber::FmtSubscriber;
 
@@ -29,3 +32,118 @@ pub fn setup() {
             .expect("setting default subscriber failed");
     });
 }
+
+/// Read a fastcgi record, return the type, request id and content.
+pub async fn read_record<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<(u8, u16, Vec<u8>)> {
+    let mut header = [0u8; 8];
+    reader.read_exact(&mut header).await?;
+    let request_id = u16::from_be_bytes([header[2], header[3]]);
+    let content_length = u16::from_be_bytes([header[4], header[5]]) as usize;
+    let mut content = vec![0; content_length + header[6] as usize];
+    reader.read_exact(&mut content).await?;
+    content.truncate(content_length);
+    Ok((header[1], request_id, content))
+}
+
+/// Write a fastcgi record, padded to a multiple of 8 bytes.
+pub async fn write_record<W: AsyncWrite + Unpin>(
+    writer: &mut W, r#type: u8, request_id: u16, content: &[u8],
+) -> io::Result<()> {
+    let padding_length = (8 - content.len() % 8) % 8;
+    let mut buf = vec![1, r#type];
+    buf.extend_from_slice(&request_id.to_be_bytes());
+    buf.extend_from_slice(&(content.len() as u16).to_be_bytes());
+    buf.extend_from_slice(&[padding_length as u8, 0]);
+    buf.extend_from_slice(content);
+    buf.resize(buf.len() + padding_length, 0);
+    writer.write_all(&buf).await
+}
+
+/// Write a `FCGI_END_REQUEST` record with the app status and protocol status.
+pub async fn write_end_request<W: AsyncWrite + Unpin>(
+    writer: &mut W, request_id: u16, app_status: u32, protocol_status: u8,
+) -> io::Result<()> {
+    let mut content = app_status.to_be_bytes().to_vec();
+    content.extend_from_slice(&[protocol_status, 0, 0, 0]);
+    write_record(writer, 3, request_id, &content).await
+}
+
+/// Request received by the mock server.
+#[derive(Debug, Default)]
+pub struct MockRequest {
+    pub id: u16,
+    pub role: u16,
+    pub keep_alive: bool,
+    pub params: HashMap<String, String>,
+    pub stdin: Vec<u8>,
+}
+
+/// Read records until the stdin of a request is terminated by an empty record.
+pub async fn read_request<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<MockRequest> {
+    let mut request = MockRequest::default();
+    let mut params = Vec::new();
+    loop {
+        let (r#type, request_id, content) = read_record(reader).await?;
+        request.id = request_id;
+        match r#type {
+            1 => {
+                request.role = u16::from_be_bytes([content[0], content[1]]);
+                request.keep_alive = content[2] & 1 == 1;
+            }
+            4 => params.extend(content),
+            5 if content.is_empty() => {
+                request.params = decode_params(&params);
+                return Ok(request);
+            }
+            5 => request.stdin.extend(content),
+            _ => {}
+        }
+    }
+}
+
+/// Encode fastcgi name-value pairs.
+pub fn encode_params(params: &[(&str, &str)]) -> Vec<u8> {
+    fn write_length(buf: &mut Vec<u8>, length: usize) {
+        if length < 128 {
+            buf.push(length as u8);
+        } else {
+            buf.extend_from_slice(&(length as u32 | 1 << 31).to_be_bytes());
+        }
+    }
+
+    let mut buf = Vec::new();
+    for (name, value) in params {
+        write_length(&mut buf, name.len());
+        write_length(&mut buf, value.len());
+        buf.extend_from_slice(name.as_bytes());
+        buf.extend_from_slice(value.as_bytes());
+    }
+    buf
+}
+
+/// Decode fastcgi name-value pairs.
+pub fn decode_params(mut buf: &[u8]) -> HashMap<String, String> {
+    fn read_length(buf: &mut &[u8]) -> usize {
+        if buf[0] >> 7 == 0 {
+            let length = buf[0] as usize;
+            *buf = &buf[1..];
+            length
+        } else {
+            let length = u32::from_be_bytes([buf[0] & 0x7f, buf[1], buf[2], buf[3]]) as usize;
+            *buf = &buf[4..];
+            length
+        }
+    }
+
+    let mut params = HashMap::new();
+    while !buf.is_empty() {
+        let name_length = read_length(&mut buf);
+        let value_length = read_length(&mut buf);
+        let name = String::from_utf8_lossy(&buf[..name_length]).into_owned();
+        let value =
+            String::from_utf8_lossy(&buf[name_length..name_length + value_length]).into_owned();
+        buf = &buf[name_length + value_length..];
+        params.insert(name, value);
+    }
+    params
+}
@@ -0,0 +1,103 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(feature = "futures-io")]
+
+use fastcgi_client::{
+    compat::Compat,
+    server::{Server, ServerRequest, ServerResponse},
+    Client, Params, Request,
+};
+use std::net::TcpListener as StdTcpListener;
+
+mod common;
+
+async fn echo(request: ServerRequest) -> ServerResponse {
+    let mut stdout = b"Content-type: text/plain\r\n\r\n".to_vec();
+    stdout.extend(request.stdin);
+    ServerResponse::new(stdout)
+}
+
+#[test]
+fn async_std_stream() {
+    common::setup();
+
+    // Run fastcgi server in tokio runtime, and client in async-std runtime.
+    let listener = StdTcpListener::bind(("127.0.0.1", 0)).unwrap();
+    let addr = listener.local_addr().unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.spawn(async move {
+        let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+        Server::new(echo).serve_tcp(listener).await
+    });
+
+    async_std::task::block_on(async {
+        let stream = async_std::net::TcpStream::connect(addr).await.unwrap();
+        let mut client = Client::new_keep_alive(Compat::new(stream));
+
+        for body in ["foo", "bar"] {
+            let stdin = Compat::new(async_std::io::Cursor::new(body));
+            let response = client
+                .execute(Request::new(Params::default(), stdin))
+                .await
+                .unwrap();
+            assert_eq!(
+                response.stdout.unwrap(),
+                format!("Content-type: text/plain\r\n\r\n{}", body).into_bytes()
+            );
+        }
+    });
+}
+
+#[test]
+fn async_std_constructors() {
+    common::setup();
+
+    let listener = StdTcpListener::bind(("127.0.0.1", 0)).unwrap();
+    let addr = listener.local_addr().unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.spawn(async move {
+        let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+        Server::new(echo).serve_tcp(listener).await
+    });
+
+    async_std::task::block_on(async {
+        let stream = async_std::net::TcpStream::connect(addr).await.unwrap();
+        let mut client = Client::new_keep_alive_compat(stream);
+        let stdin = async_std::io::Cursor::new("foo");
+        let response = client
+            .execute(Request::new_compat(Params::default(), stdin))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.stdout.unwrap(),
+            b"Content-type: text/plain\r\n\r\nfoo"
+        );
+
+        let stream = async_std::net::TcpStream::connect(addr).await.unwrap();
+        let response = Client::new_compat(stream)
+            .execute_once(Request::new_compat(
+                Params::default(),
+                async_std::io::empty(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.stdout.unwrap(),
+            b"Content-type: text/plain\r\n\r\n"
+        );
+    });
+}
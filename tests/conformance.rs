@@ -0,0 +1,87 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(feature = "conformance")]
+
+mod common;
+
+use common::{read_request, write_end_request, write_record};
+use fastcgi_client::{
+    conformance::{self, Check},
+    server::{Server, ServerRequest, ServerResponse},
+    Params,
+};
+use tokio::io::{duplex, AsyncWriteExt};
+
+async fn echo(request: ServerRequest) -> ServerResponse {
+    ServerResponse::new(request.stdin).stderr("warning")
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn server_conformant() {
+    common::setup();
+
+    let connector = || async {
+        let (client_stream, server_stream) = duplex(4096);
+        tokio::spawn(async move { Server::new(echo).serve_connection(server_stream).await });
+        Ok(client_stream)
+    };
+    let report = conformance::run(&connector, Params::default())
+        .await
+        .unwrap();
+    assert!(report.is_conformant(), "{:?}", report.violations());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn server_violations() {
+    common::setup();
+
+    let connector = || async {
+        let (client_stream, mut server_stream) = duplex(1 << 20);
+        tokio::spawn(async move {
+            while let Ok(request) = read_request(&mut server_stream).await {
+                // Unpadded stdout without the terminating record.
+                let mut record = vec![1, 6];
+                record.extend_from_slice(&request.id.to_be_bytes());
+                record.extend_from_slice(&[0, 2, 0, 0, b'o', b'k']);
+                server_stream.write_all(&record).await.unwrap();
+                write_record(&mut server_stream, 7, request.id + 1, b"")
+                    .await
+                    .unwrap();
+                write_end_request(&mut server_stream, request.id, 0, 0)
+                    .await
+                    .unwrap();
+                if !request.keep_alive {
+                    break;
+                }
+            }
+        });
+        Ok(client_stream)
+    };
+    let report = conformance::run(&connector, Params::default())
+        .await
+        .unwrap();
+
+    let checks = report
+        .violations()
+        .iter()
+        .map(|violation| violation.check)
+        .collect::<Vec<_>>();
+    assert!(checks.contains(&Check::PaddingAlignment));
+    assert!(checks.contains(&Check::RequestId));
+    assert!(checks.contains(&Check::StreamTermination));
+    assert!(checks.contains(&Check::EndRequest));
+    assert!(!checks.contains(&Check::ConnectionClose));
+    assert!(!checks.contains(&Check::Version));
+}
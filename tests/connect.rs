@@ -0,0 +1,194 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{
+    connect::{Address, Connect, TcpConnector, TcpOptions},
+    server::{Server, ServerRequest, ServerResponse},
+    Client, ClientError, Params, Request,
+};
+use std::{io, time::Duration};
+use tokio::{
+    io::{duplex, DuplexStream},
+    net::TcpListener,
+};
+
+mod common;
+
+async fn hello(_request: ServerRequest) -> ServerResponse {
+    ServerResponse::new("Content-type: text/plain\r\n\r\nhello")
+}
+
+async fn execute<C: Connect>(connector: &C) -> Vec<u8> {
+    let stream = connector.connect().await.unwrap();
+    Client::new(stream)
+        .execute_once(Request::new(Params::default(), tokio::io::empty()))
+        .await
+        .unwrap()
+        .stdout
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn tcp_connector() {
+    common::setup();
+
+    let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { Server::new(hello).serve_tcp(listener).await });
+
+    let connector = TcpConnector::new(addr.to_string());
+    assert_eq!(connector.address(), Some(addr.to_string()));
+    let stdout = execute(&connector).await;
+    assert_eq!(stdout, b"Content-type: text/plain\r\n\r\nhello");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn fn_connector() {
+    common::setup();
+
+    let connector = || async {
+        let (client_stream, server_stream) = duplex(4096);
+        tokio::spawn(async move { Server::new(hello).serve_connection(server_stream).await });
+        Ok::<DuplexStream, io::Error>(client_stream)
+    };
+
+    let stdout = execute(&connector).await;
+    assert_eq!(stdout, b"Content-type: text/plain\r\n\r\nhello");
+}
+
+#[test]
+fn parse_address() {
+    assert_eq!(
+        "tcp://127.0.0.1:9000".parse::<Address>().unwrap(),
+        Address::Tcp("127.0.0.1:9000".to_owned())
+    );
+    assert_eq!(
+        "localhost:9000".parse::<Address>().unwrap(),
+        Address::Tcp("localhost:9000".to_owned())
+    );
+    assert_eq!(
+        "unix:///run/php/php-fpm.sock".parse::<Address>().unwrap(),
+        Address::Unix("/run/php/php-fpm.sock".into())
+    );
+    assert_eq!(
+        "/run/php/php-fpm.sock".parse::<Address>().unwrap(),
+        Address::Unix("/run/php/php-fpm.sock".into())
+    );
+    assert_eq!(
+        "127.0.0.1:9000"
+            .parse::<Address>()
+            .unwrap()
+            .address()
+            .unwrap(),
+        "tcp://127.0.0.1:9000"
+    );
+    assert!(matches!(
+        "http://127.0.0.1:9000".parse::<Address>(),
+        Err(ClientError::InvalidAddress { .. })
+    ));
+    assert!(matches!(
+        "tcp://".parse::<Address>(),
+        Err(ClientError::InvalidAddress { .. })
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn client_connect() {
+    common::setup();
+
+    let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { Server::new(hello).serve_tcp(listener).await });
+
+    let client = Client::connect(&format!("tcp://{}", addr)).await.unwrap();
+    let output = client
+        .execute_once(Request::new(Params::default(), tokio::io::empty()))
+        .await
+        .unwrap();
+    assert_eq!(
+        output.stdout.unwrap(),
+        b"Content-type: text/plain\r\n\r\nhello"
+    );
+
+    let dir = std::env::temp_dir().join(format!("fastcgi-client-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("connect.sock");
+    let _ = std::fs::remove_file(&path);
+    let listener = tokio::net::UnixListener::bind(&path).unwrap();
+    tokio::spawn(async move { Server::new(hello).serve_unix(listener).await });
+
+    let mut client = Client::connect_keep_alive(&format!("unix://{}", path.display()))
+        .await
+        .unwrap();
+    for _ in 0..2 {
+        let output = client
+            .execute(Request::new(Params::default(), tokio::io::empty()))
+            .await
+            .unwrap();
+        assert_eq!(
+            output.stdout.unwrap(),
+            b"Content-type: text/plain\r\n\r\nhello"
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn tcp_connector_fallback() {
+    common::setup();
+
+    // The `localhost` maybe resolved to `::1` first, then fallback to
+    // `127.0.0.1`.
+    let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move { Server::new(hello).serve_tcp(listener).await });
+
+    let connector = TcpConnector::new(format!("localhost:{}", port))
+        .with_connect_timeout(Duration::from_secs(1));
+    let stdout = execute(&connector).await;
+    assert_eq!(stdout, b"Content-type: text/plain\r\n\r\nhello");
+
+    let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    drop(listener);
+    assert!(TcpConnector::new(addr.to_string()).connect().await.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn tcp_connector_options() {
+    common::setup();
+
+    let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let stream = TcpConnector::new(addr.to_string()).connect().await.unwrap();
+    assert!(stream.nodelay().unwrap());
+
+    let options = TcpOptions::default()
+        .nodelay(false)
+        .keepalive(Duration::from_secs(60))
+        .send_buffer_size(64 * 1024);
+    let stream = TcpConnector::new(addr.to_string())
+        .with_options(options)
+        .connect()
+        .await
+        .unwrap();
+    assert!(!stream.nodelay().unwrap());
+    let socket = socket2::SockRef::from(&stream);
+    assert!(socket.keepalive().unwrap());
+    assert_eq!(
+        socket.tcp_keepalive_time().unwrap(),
+        Duration::from_secs(60)
+    );
+    assert!(socket.send_buffer_size().unwrap() >= 64 * 1024);
+}
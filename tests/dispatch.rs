@@ -0,0 +1,106 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{
+    dispatch::{Dispatcher, DispatcherConfig},
+    server::{Server, ServerRequest, ServerResponse},
+    ClientError, Params, Request,
+};
+use std::{
+    future::Ready,
+    io,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tokio::io::{duplex, DuplexStream};
+
+mod common;
+
+async fn echo(request: ServerRequest) -> ServerResponse {
+    ServerResponse::new(request.stdin)
+}
+
+type Connector = Box<dyn Fn() -> Ready<io::Result<DuplexStream>> + Send + Sync>;
+
+/// Connector counting the connections, the server is spawned by `serve`.
+fn connector(connects: Arc<AtomicUsize>, serve: fn(DuplexStream)) -> Connector {
+    Box::new(move || {
+        connects.fetch_add(1, Ordering::SeqCst);
+        let (client_stream, server_stream) = duplex(4096);
+        serve(server_stream);
+        std::future::ready(Ok(client_stream))
+    })
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn dispatch() {
+    common::setup();
+
+    let connects = Arc::new(AtomicUsize::new(0));
+    let dispatcher = Dispatcher::with_config(
+        connector(connects.clone(), |stream| {
+            tokio::spawn(async move { Server::new(echo).serve_connection(stream).await });
+        }),
+        DispatcherConfig::default().workers(2),
+    );
+
+    let tasks = (0..8)
+        .map(|i| {
+            let dispatcher = dispatcher.clone();
+            tokio::spawn(async move {
+                let body = format!("body-{}", i).into_bytes();
+                let response = dispatcher
+                    .send(Request::new(
+                        Params::default(),
+                        io::Cursor::new(body.clone()),
+                    ))
+                    .await
+                    .unwrap();
+                assert_eq!(response.stdout, Some(body));
+            })
+        })
+        .collect::<Vec<_>>();
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    // The connections are kept alive by the workers.
+    assert!(connects.load(Ordering::SeqCst) <= 2);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn dispatch_reconnect() {
+    common::setup();
+
+    // Close the connection without response.
+    let connects = Arc::new(AtomicUsize::new(0));
+    let dispatcher = Dispatcher::with_config(
+        connector(connects.clone(), |mut stream| {
+            tokio::spawn(async move { common::read_request(&mut stream).await });
+        }),
+        DispatcherConfig::default().workers(1),
+    );
+
+    for i in 1..=2 {
+        assert!(matches!(
+            dispatcher
+                .send(Request::new(Params::default(), tokio::io::empty()))
+                .await,
+            Err(ClientError::ConnectionClosedByPeer { .. })
+        ));
+        assert_eq!(connects.load(Ordering::SeqCst), i);
+    }
+}
@@ -0,0 +1,104 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{
+    server::{Server, ServerRequest, ServerResponse},
+    status::FpmStatus,
+    Client, ClientError,
+};
+use tokio::io::duplex;
+
+mod common;
+
+const STATUS: &str = "pool:                 www
+process manager:      dynamic
+start time:           16/Oct/2026:08:00:00 +0000
+start since:          3600
+accepted conn:        1024
+listen queue:         3
+max listen queue:     8
+listen queue len:     511
+idle processes:       1
+active processes:     4
+total processes:      5
+max active processes: 5
+max children reached: 2
+slow requests:        1
+memory peak:          2097152
+";
+
+const STATUS_JSON: &str = r#"{"pool":"www","process manager":"dynamic","start time":1791964800,"start since":3600,"accepted conn":1024,"listen queue":3,"max listen queue":8,"listen queue len":511,"idle processes":1,"active processes":4,"total processes":5,"max active processes":5,"max children reached":2,"slow requests":1,"memory peak":2097152,"processes":[{"pid":12,"state":"Idle","request uri":"\/index.php?a={\"b\"}"}]}"#;
+
+async fn status(request: ServerRequest) -> ServerResponse {
+    assert_eq!(request.params["SCRIPT_NAME"], "/status");
+    if request.params["QUERY_STRING"] == "json" {
+        ServerResponse::new(format!(
+            "Content-type: application/json\r\n\r\n{}",
+            STATUS_JSON
+        ))
+    } else {
+        ServerResponse::new(format!("Content-type: text/plain\r\n\r\n{}", STATUS))
+    }
+}
+
+#[test]
+fn parse_fpm_status() {
+    let status = STATUS.parse::<FpmStatus>().unwrap();
+    assert_eq!(status.pool, "www");
+    assert_eq!(status.process_manager, "dynamic");
+    assert_eq!(status.start_time, "16/Oct/2026:08:00:00 +0000");
+    assert_eq!(status.start_since, 3600);
+    assert_eq!(status.accepted_conn, 1024);
+    assert_eq!(status.listen_queue, 3);
+    assert_eq!(status.max_listen_queue, 8);
+    assert_eq!(status.listen_queue_len, 511);
+    assert_eq!(status.idle_processes, 1);
+    assert_eq!(status.active_processes, 4);
+    assert_eq!(status.total_processes, 5);
+    assert_eq!(status.max_active_processes, 5);
+    assert_eq!(status.max_children_reached, 2);
+    assert_eq!(status.slow_requests, 1);
+
+    assert!(matches!(
+        "pool: www\nlisten queue: many".parse::<FpmStatus>(),
+        Err(ClientError::InvalidFpmStatus { .. })
+    ));
+    let mut json = STATUS_JSON.parse::<FpmStatus>().unwrap();
+    assert_eq!(json.start_time, "1791964800");
+    json.start_time = status.start_time.clone();
+    assert_eq!(json, status);
+
+    assert!(matches!(
+        r#"{"pool":"www","listen queue":"#.parse::<FpmStatus>(),
+        Err(ClientError::InvalidFpmStatus { .. })
+    ));
+    assert!(matches!(
+        "File not found.".parse::<FpmStatus>(),
+        Err(ClientError::InvalidFpmStatus { .. })
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn client_fpm_status() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(async move { Server::new(status).serve_connection(server_stream).await });
+
+    let mut client = Client::new_keep_alive(client_stream);
+    let status = client.fpm_status("/status").await.unwrap();
+    assert_eq!(status, STATUS.parse().unwrap());
+    let status = client.fpm_status("/status?json").await.unwrap();
+    assert_eq!(status, STATUS_JSON.parse().unwrap());
+}
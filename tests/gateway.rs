@@ -0,0 +1,294 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(feature = "http-body")]
+
+use fastcgi_client::{
+    gateway::{forward, serve_php, GatewayConfig},
+    server::{Server, ServerRequest, ServerResponse},
+    shared::SharedClient,
+    ClientError, Request,
+};
+use http_body_util::BodyExt;
+use std::{io, time::Duration};
+use tokio::io::duplex;
+
+mod common;
+
+#[test]
+fn resolve_script() {
+    let config = GatewayConfig::new("/var/www/");
+
+    let (parts, _) = http::Request::get("/app/index.php/user/1?page=2")
+        .header("host", "example.com:8080")
+        .body(())
+        .unwrap()
+        .into_parts();
+    let params = config.request_params(&parts, 0).unwrap();
+    assert_eq!(params["SCRIPT_FILENAME"], "/var/www/app/index.php");
+    assert_eq!(params["SCRIPT_NAME"], "/app/index.php");
+    assert_eq!(params["PATH_INFO"], "/user/1");
+    assert_eq!(params["DOCUMENT_ROOT"], "/var/www");
+    assert_eq!(params["DOCUMENT_URI"], "/app/index.php/user/1");
+    assert_eq!(params["REQUEST_URI"], "/app/index.php/user/1?page=2");
+    assert_eq!(params["QUERY_STRING"], "page=2");
+    assert_eq!(params["SERVER_NAME"], "example.com");
+    assert_eq!(params["HTTP_HOST"], "example.com:8080");
+
+    let (parts, _) = http::Request::get("/blog/").body(()).unwrap().into_parts();
+    let params = config.index("main.php").request_params(&parts, 0).unwrap();
+    assert_eq!(params["SCRIPT_FILENAME"], "/var/www/blog/main.php");
+    assert!(!params.contains_key("PATH_INFO"));
+}
+
+#[test]
+fn reject_path_traversal() {
+    let config = GatewayConfig::new("/var/www");
+
+    for path in [
+        "/../etc/passwd",
+        "/app/%2e%2e/%2E%2E/etc/passwd",
+        "/index.php/..",
+        "/index.php%00.jpg",
+        "/app%5c..%5cindex.php",
+        "/index%zz.php",
+    ] {
+        let (parts, _) = http::Request::get(path).body(()).unwrap().into_parts();
+        assert!(
+            matches!(
+                config.request_params(&parts, 0),
+                Err(ClientError::InvalidPath { .. })
+            ),
+            "{}",
+            path
+        );
+    }
+
+    let (parts, _) = http::Request::get("/my%20app/index.php/a..b?x=..")
+        .body(())
+        .unwrap()
+        .into_parts();
+    let params = config.request_params(&parts, 0).unwrap();
+    assert_eq!(params["SCRIPT_FILENAME"], "/var/www/my app/index.php");
+    assert_eq!(params["PATH_INFO"], "/a..b");
+    assert_eq!(params["REQUEST_URI"], "/my%20app/index.php/a..b?x=..");
+}
+
+#[test]
+fn skip_headers() {
+    let (parts, _) = http::Request::post("/index.php")
+        .header("proxy", "http://evil.example.com")
+        .header("x_forwarded_for", "127.0.0.1")
+        .header("x-forwarded-for", "10.0.0.1")
+        .header("content-type", "text/plain")
+        .header("content-length", "4")
+        .body(())
+        .unwrap()
+        .into_parts();
+    let params = GatewayConfig::new("/var/www")
+        .request_params(&parts, 4)
+        .unwrap();
+    assert!(!params.contains_key("HTTP_PROXY"));
+    assert_eq!(params["HTTP_X_FORWARDED_FOR"], "10.0.0.1");
+    assert!(!params.contains_key("HTTP_CONTENT_TYPE"));
+    assert!(!params.contains_key("HTTP_CONTENT_LENGTH"));
+    assert_eq!(params["CONTENT_TYPE"], "text/plain");
+    assert_eq!(params["CONTENT_LENGTH"], "4");
+}
+
+#[test]
+fn request_try_from() {
+    let request = http::Request::post("/index.php?page=2")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body("name=jmjoy")
+        .unwrap();
+    let request = Request::try_from((request, &GatewayConfig::new("/var/www"))).unwrap();
+    assert_eq!(request.params()["REQUEST_METHOD"], "POST");
+    assert_eq!(request.params()["QUERY_STRING"], "page=2");
+    assert_eq!(
+        request.params()["CONTENT_TYPE"],
+        "application/x-www-form-urlencoded"
+    );
+    assert_eq!(request.params()["CONTENT_LENGTH"], "10");
+    assert_eq!(request.stdin_len(), Some(10));
+    assert_eq!(*request.stdin().get_ref(), "name=jmjoy");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn forward_request() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    tokio::spawn(async move {
+        let request = common::read_request(&mut server_stream).await.unwrap();
+        assert_eq!(request.params["SCRIPT_FILENAME"], "/var/www/index.php");
+        assert_eq!(request.stdin, b"a=1");
+
+        common::write_record(
+            &mut server_stream,
+            6,
+            request.id,
+            b"Status: 404 Not Found\r\n\r\nnot found",
+        )
+        .await
+        .unwrap();
+        common::write_end_request(&mut server_stream, request.id, 0, 0)
+            .await
+            .unwrap();
+    });
+
+    let client = SharedClient::new(client_stream);
+    let request = http::Request::post("/index.php")
+        .body("a=1".to_owned())
+        .unwrap();
+    let response = forward(&client, &GatewayConfig::new("/var/www"), request)
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), http::StatusCode::NOT_FOUND);
+    assert_eq!(
+        response.into_body().collect().await.unwrap().to_bytes(),
+        "not found"
+    );
+}
+
+async fn redirect(request: ServerRequest) -> ServerResponse {
+    match &*request.params["REQUEST_URI"] {
+        "/old.php" => ServerResponse::new("Location: /new.php?from=old\r\n\r\n"),
+        "/loop.php" => ServerResponse::new("Location: /loop.php\r\n\r\n"),
+        _ => ServerResponse::new(format!(
+            "Content-type: text/plain\r\n\r\n{} {} {}",
+            request.params["REQUEST_METHOD"],
+            request.params["SCRIPT_NAME"],
+            request.params["QUERY_STRING"],
+        )),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn forward_local_redirect() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(async move { Server::new(redirect).serve_connection(server_stream).await });
+    let client = SharedClient::new(client_stream);
+
+    // Not followed by default.
+    let request = http::Request::get("/old.php").body(String::new()).unwrap();
+    let response = forward(&client, &GatewayConfig::new("/var/www"), request)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), http::StatusCode::FOUND);
+    assert_eq!(response.headers()["location"], "/new.php?from=old");
+
+    let config = GatewayConfig::new("/var/www").max_local_redirects(3);
+    let request = http::Request::post("/old.php")
+        .header("content-type", "text/plain")
+        .body("body".to_owned())
+        .unwrap();
+    let response = forward(&client, &config, request).await.unwrap();
+    assert_eq!(response.status(), http::StatusCode::OK);
+    assert_eq!(
+        response.into_body().collect().await.unwrap().to_bytes(),
+        "GET /new.php from=old"
+    );
+
+    let request = http::Request::get("/loop.php").body(String::new()).unwrap();
+    let result = forward(&client, &config, request).await;
+    assert!(matches!(
+        result,
+        Err(ClientError::TooManyRedirects { max_redirects: 3 })
+    ));
+}
+
+async fn php(request: ServerRequest) -> ServerResponse {
+    match &*request.params["SCRIPT_NAME"] {
+        "/missing.php" => ServerResponse::new("Status: 404 Not Found\r\n\r\nFile not found."),
+        _ => ServerResponse::new(format!(
+            "Content-type: text/plain\r\nX-Path-Info: {}\r\n\r\n{} {}",
+            request.params["PATH_INFO"],
+            request.params["SCRIPT_FILENAME"],
+            String::from_utf8_lossy(&request.stdin),
+        )),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn serve_php_request() {
+    common::setup();
+
+    let config = GatewayConfig::new("/var/www").timeout(Duration::from_millis(100));
+
+    let upstream = || async {
+        let (client_stream, server_stream) = duplex(4096);
+        tokio::spawn(async move { Server::new(php).serve_connection(server_stream).await });
+        Ok::<_, io::Error>(client_stream)
+    };
+
+    let request = http::Request::post("/app.php/user?id=1")
+        .body("a=1".to_owned())
+        .unwrap();
+    let response = serve_php(request, &config, &upstream).await;
+    assert_eq!(response.status(), http::StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/plain");
+    assert_eq!(response.headers()["x-path-info"], "/user");
+    assert_eq!(
+        response.into_body().collect().await.unwrap().to_bytes(),
+        "/var/www/app.php a=1"
+    );
+
+    let request = http::Request::get("/missing.php")
+        .body(String::new())
+        .unwrap();
+    let response = serve_php(request, &config, &upstream).await;
+    assert_eq!(response.status(), http::StatusCode::NOT_FOUND);
+    assert_eq!(
+        response.into_body().collect().await.unwrap().to_bytes(),
+        "File not found."
+    );
+
+    let refused =
+        || async { Err::<tokio::io::DuplexStream, _>(io::ErrorKind::ConnectionRefused.into()) };
+    let request = http::Request::get("/index.php")
+        .body(String::new())
+        .unwrap();
+    let response = serve_php(request, &config, &refused).await;
+    assert_eq!(response.status(), http::StatusCode::BAD_GATEWAY);
+
+    let request = http::Request::post("/app.php")
+        .body("a=1".to_owned())
+        .unwrap();
+    let response = serve_php(request, &config.clone().max_body_size(2), &upstream).await;
+    assert_eq!(response.status(), http::StatusCode::PAYLOAD_TOO_LARGE);
+
+    let request = http::Request::get("/../index.php")
+        .body(String::new())
+        .unwrap();
+    let response = serve_php(request, &config, &upstream).await;
+    assert_eq!(response.status(), http::StatusCode::BAD_REQUEST);
+
+    let silent = || async {
+        let (client_stream, mut server_stream) = duplex(4096);
+        tokio::spawn(
+            async move { tokio::io::copy(&mut server_stream, &mut tokio::io::sink()).await },
+        );
+        Ok::<_, io::Error>(client_stream)
+    };
+    let request = http::Request::get("/index.php")
+        .body(String::new())
+        .unwrap();
+    let response = serve_php(request, &config, &silent).await;
+    assert_eq!(response.status(), http::StatusCode::GATEWAY_TIMEOUT);
+}
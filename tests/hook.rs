@@ -0,0 +1,112 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{
+    capture::Direction,
+    hook::{Hooked, Record},
+    server::{Server, ServerRequest, ServerResponse},
+    Client, ClientError, Params, Request,
+};
+use std::io;
+use tokio::io::{duplex, empty};
+
+mod common;
+
+async fn hello(request: ServerRequest) -> ServerResponse {
+    let script_name = request
+        .params
+        .get("SCRIPT_NAME")
+        .cloned()
+        .unwrap_or_default();
+    ServerResponse::new(format!(
+        "Content-type: text/plain\r\n\r\nhello {}",
+        script_name
+    ))
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn hook_mutation() {
+    common::setup();
+
+    // Small buffer, so the records are split across reads and writes.
+    let (client_stream, server_stream) = duplex(5);
+    tokio::spawn(async move { Server::new(hello).serve_connection(server_stream).await });
+
+    let stream = Hooked::new(client_stream, |record: &mut Record<'_>| {
+        // Rewrite the body of stdout, the length is changed.
+        if record.direction() == Direction::Received && record.r#type() == 6 {
+            let content = String::from_utf8_lossy(record.content()).replace("hello", "hi");
+            *record.content_mut() = content.into_bytes();
+        }
+        Ok(())
+    });
+
+    let client = Client::new(stream);
+    let params = Params::default().script_name("/index.php");
+    let output = client
+        .execute_once(Request::new(params, empty()))
+        .await
+        .unwrap();
+    assert_eq!(
+        output.stdout.unwrap(),
+        b"Content-type: text/plain\r\n\r\nhi /index.php"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn hook_policy() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(async move { Server::new(hello).serve_connection(server_stream).await });
+
+    let stream = Hooked::new(client_stream, |record: &mut Record<'_>| {
+        // Reject the scripts outside the document root.
+        if record.r#type() == 4 && record.content().windows(2).any(|w| w == b"..") {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "forbidden"));
+        }
+        Ok(())
+    });
+
+    let client = Client::new(stream);
+    let params = Params::default().script_name("/../etc/passwd");
+    match client.execute_once(Request::new(params, empty())).await {
+        Err(ClientError::Io(err)) => assert_eq!(err.kind(), io::ErrorKind::PermissionDenied),
+        result => panic!("unexpected {:?}", result),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn hook_resize_32768() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(async move { Server::new(hello).serve_connection(server_stream).await });
+
+    let stream = Hooked::new(client_stream, |record: &mut Record<'_>| {
+        if record.direction() == Direction::Received
+            && record.r#type() == 6
+            && !record.content().is_empty()
+        {
+            *record.content_mut() = vec![b'a'; 32768];
+        }
+        Ok(())
+    });
+
+    let output = Client::new(stream)
+        .execute_once(Request::new(Params::default(), empty()))
+        .await
+        .unwrap();
+    assert_eq!(output.stdout.unwrap(), [b'a'; 32768]);
+}
@@ -0,0 +1,95 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{lenient::Anomaly, Client, ClientError, Params, Request};
+use std::sync::{Arc, Mutex};
+use tokio::io::{duplex, empty, AsyncWriteExt, DuplexStream};
+
+mod common;
+
+/// Answer the request with the quirks: nonzero reserved byte, stray padding
+/// and unknown record type.
+async fn quirky_server(mut stream: DuplexStream) {
+    let request = common::read_request(&mut stream).await.unwrap();
+
+    let content = b"Content-type: text/plain\r\n\r\nhello";
+    let mut record = vec![1, 6];
+    record.extend_from_slice(&request.id.to_be_bytes());
+    record.extend_from_slice(&(content.len() as u16).to_be_bytes());
+    record.extend_from_slice(&[10, 1]);
+    record.extend_from_slice(content);
+    record.extend_from_slice(&[0xff; 10]);
+    stream.write_all(&record).await.unwrap();
+
+    common::write_record(&mut stream, 42, request.id, b"unknown")
+        .await
+        .unwrap();
+    common::write_end_request(&mut stream, request.id, 0, 0)
+        .await
+        .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn lenient() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(quirky_server(server_stream));
+
+    let anomalies = Arc::new(Mutex::new(Vec::new()));
+    let client = Client::new(client_stream).lenient({
+        let anomalies = anomalies.clone();
+        move |anomaly| anomalies.lock().unwrap().push(anomaly.clone())
+    });
+    assert!(client.is_lenient());
+
+    let output = client
+        .execute_once(Request::new(Params::default(), empty()))
+        .await
+        .unwrap();
+    assert_eq!(
+        output.stdout.unwrap(),
+        b"Content-type: text/plain\r\n\r\nhello"
+    );
+
+    assert_eq!(
+        *anomalies.lock().unwrap(),
+        [
+            Anomaly::NonzeroReserved { id: 1, r#type: 6 },
+            Anomaly::StrayPadding {
+                id: 1,
+                r#type: 6,
+                padding_length: 10
+            },
+            Anomaly::UnknownType { id: 1, r#type: 42 },
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn not_lenient() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(quirky_server(server_stream));
+
+    let client = Client::new(client_stream);
+    let result = client
+        .execute_once(Request::new(Params::default(), empty()))
+        .await;
+    assert!(matches!(
+        result,
+        Err(ClientError::UnknownRequestType { .. })
+    ));
+}
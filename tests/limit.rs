@@ -0,0 +1,212 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{
+    limit::{ConcurrencyLimiter, LimitConfig, Overflow, RateConfig, RateLimiter},
+    multiplex::MultiplexClient,
+    pool::{Pool, PoolConfig},
+    server::{Server, ServerRequest, ServerResponse},
+    shared::SharedClient,
+    ClientError, Params, Request,
+};
+use std::{
+    io,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{duplex, DuplexStream},
+    sync::oneshot,
+};
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn limiter_fail_fast() {
+    let limiter = Arc::new(ConcurrencyLimiter::new(
+        LimitConfig::default()
+            .max_in_flight(1)
+            .overflow(Overflow::FailFast),
+    ));
+
+    let (release, released) = oneshot::channel::<()>();
+    let in_flight = {
+        let limiter = limiter.clone();
+        tokio::spawn(async move { limiter.call(async { Ok(released.await.is_ok()) }).await })
+    };
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert_eq!(limiter.in_flight(), 1);
+
+    assert!(matches!(
+        limiter.call(async { Ok(()) }).await,
+        Err(ClientError::LimitExceeded { max_in_flight: 1 })
+    ));
+
+    release.send(()).unwrap();
+    assert!(in_flight.await.unwrap().unwrap());
+    assert_eq!(limiter.in_flight(), 0);
+    limiter.call(async { Ok(()) }).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn limiter_queue() {
+    let limiter = Arc::new(ConcurrencyLimiter::new(
+        LimitConfig::default().max_in_flight(1),
+    ));
+
+    let (release, released) = oneshot::channel::<()>();
+    let in_flight = {
+        let limiter = limiter.clone();
+        tokio::spawn(async move { limiter.call(async { Ok(released.await.is_ok()) }).await })
+    };
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let queued = {
+        let limiter = limiter.clone();
+        tokio::spawn(async move { limiter.call(async { Ok(()) }).await })
+    };
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert!(!queued.is_finished());
+
+    release.send(()).unwrap();
+    assert!(in_flight.await.unwrap().unwrap());
+    queued.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn shared_limit() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+    tokio::spawn(async move {
+        while let Ok(request) = common::read_request(&mut server_stream).await {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            common::write_end_request(&mut server_stream, request.id, 0, 0)
+                .await
+                .unwrap();
+        }
+    });
+
+    let client = SharedClient::new(client_stream).limit(
+        LimitConfig::default()
+            .max_in_flight(1)
+            .overflow(Overflow::FailFast),
+    );
+
+    let in_flight = {
+        let client = client.clone();
+        tokio::spawn(async move {
+            client
+                .execute(Request::new(Params::default(), tokio::io::empty()))
+                .await
+        })
+    };
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    assert!(matches!(
+        client
+            .execute(Request::new(Params::default(), tokio::io::empty()))
+            .await,
+        Err(ClientError::LimitExceeded { .. })
+    ));
+    in_flight.await.unwrap().unwrap();
+    client
+        .execute(Request::new(Params::default(), tokio::io::empty()))
+        .await
+        .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn multiplex_limit() {
+    let (client_stream, _server_stream) = duplex(4096);
+    let client = MultiplexClient::new(client_stream);
+    assert_eq!(client.max_concurrency(), None);
+
+    let client = client.limit(LimitConfig::default().max_in_flight(8));
+    assert_eq!(client.max_concurrency(), Some(8));
+
+    // Capped by the previous limit, like the one discovered.
+    let client = client.limit(LimitConfig::default().max_in_flight(16));
+    assert_eq!(client.max_concurrency(), Some(8));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn rate_fail_fast() {
+    let limiter = RateLimiter::new(
+        RateConfig::default()
+            .rate(20.0)
+            .burst(2)
+            .overflow(Overflow::FailFast),
+    );
+
+    limiter.call(async { Ok(()) }).await.unwrap();
+    limiter.call(async { Ok(()) }).await.unwrap();
+    assert!(matches!(
+        limiter.call(async { Ok(()) }).await,
+        Err(ClientError::RateLimited { .. })
+    ));
+
+    // Refilled a token every 50ms.
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    limiter.call(async { Ok(()) }).await.unwrap();
+    assert!(matches!(
+        limiter.call(async { Ok(()) }).await,
+        Err(ClientError::RateLimited { .. })
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn rate_queue() {
+    let limiter = RateLimiter::new(RateConfig::default().rate(20.0).burst(1));
+
+    let start = Instant::now();
+    for _ in 0..3 {
+        limiter.call(async { Ok(()) }).await.unwrap();
+    }
+    assert!(start.elapsed() >= Duration::from_millis(90));
+}
+
+async fn hello(_request: ServerRequest) -> ServerResponse {
+    ServerResponse::new("Content-type: text/plain\r\n\r\nhello")
+}
+
+async fn connect() -> io::Result<DuplexStream> {
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(async move { Server::new(hello).serve_connection(server_stream).await });
+    Ok(client_stream)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn pool_rate_limit() {
+    common::setup();
+
+    let config = PoolConfig::default().rate_limit(
+        RateConfig::default()
+            .rate(1.0)
+            .burst(1)
+            .overflow(Overflow::FailFast),
+    );
+    let pool = Pool::with_config(connect, config);
+
+    pool.execute(Request::new(Params::default(), tokio::io::empty()))
+        .await
+        .unwrap();
+    assert!(matches!(
+        pool.execute(Request::new(Params::default(), tokio::io::empty()))
+            .await,
+        Err(ClientError::RateLimited { .. })
+    ));
+    // Rejected before connecting.
+    assert_eq!(pool.idle(), 1);
+}
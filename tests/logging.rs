@@ -0,0 +1,120 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{
+    logging::LogConfig,
+    pool::{Pool, PoolConfig},
+    server::{Server, ServerRequest, ServerResponse},
+    Params, Request,
+};
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::io::{duplex, DuplexStream};
+use tracing::Level;
+
+mod common;
+
+async fn php(request: ServerRequest) -> ServerResponse {
+    match &*request.params["SCRIPT_FILENAME"] {
+        "/var/www/error.php" => {
+            ServerResponse::new("Status: 503 Service Unavailable\r\n\r\n").stderr("PHP Fatal error")
+        }
+        "/var/www/slow.php" => {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            ServerResponse::new("Content-type: text/plain\r\n\r\nslow")
+        }
+        _ => ServerResponse::new("Content-type: text/plain\r\n\r\nhello"),
+    }
+}
+
+async fn connect() -> io::Result<DuplexStream> {
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(async move { Server::new(php).serve_connection(server_stream).await });
+    Ok(client_stream)
+}
+
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl Output {
+    fn take(&self) -> String {
+        String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn request(script: &str) -> Request<'static, &'static [u8]> {
+    let params = Params::default()
+        .request_method("POST")
+        .script_filename(format!("/var/www/{}", script));
+    Request::new(params, &b"a=1"[..]).with_stdin_len(3)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn log_requests() {
+    let output = Output::default();
+    let writer = output.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(Level::INFO)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let config = PoolConfig::default().log(
+        LogConfig::default()
+            .level(Level::INFO)
+            .error_level(Level::ERROR)
+            .slow_threshold(Duration::from_millis(30)),
+    );
+    let pool = Pool::with_config(connect, config);
+
+    pool.execute(request("index.php")).await.unwrap();
+    let log = output.take();
+    assert!(log.contains(" INFO "), "{}", log);
+    assert!(log.contains("method=\"POST\""), "{}", log);
+    assert!(log.contains("script=\"/var/www/index.php\""), "{}", log);
+    assert!(log.contains("request_bytes=3"), "{}", log);
+    assert!(log.contains("status=200"), "{}", log);
+    assert!(log.contains("stdout_bytes=33"), "{}", log);
+    assert!(log.contains("stderr_bytes=0"), "{}", log);
+
+    pool.execute(request("error.php")).await.unwrap();
+    let log = output.take();
+    assert!(log.contains(" ERROR "), "{}", log);
+    assert!(log.contains("status=503"), "{}", log);
+    assert!(log.contains("stderr_bytes=15"), "{}", log);
+
+    pool.execute(request("slow.php")).await.unwrap();
+    let log = output.take();
+    assert!(log.contains(" WARN "), "{}", log);
+
+    pool.shutdown(Duration::from_secs(1)).await.unwrap();
+    assert!(pool.execute(request("index.php")).await.is_err());
+    let log = output.take();
+    assert!(log.contains(" ERROR "), "{}", log);
+    assert!(log.contains("error=Client is closed"), "{}", log);
+}
@@ -0,0 +1,95 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{multiplex::MultiplexClient, response::Content, Client, Params, Request};
+use tokio::io::{duplex, empty, DuplexStream};
+
+mod common;
+
+/// Answer the request with an unsolicited management record in the middle.
+async fn server(mut stream: DuplexStream) {
+    let request = common::read_request(&mut stream).await.unwrap();
+    common::write_record(
+        &mut stream,
+        6,
+        request.id,
+        b"Content-type: text/plain\r\n\r\n",
+    )
+    .await
+    .unwrap();
+    let values = common::encode_params(&[("FCGI_MPXS_CONNS", "0")]);
+    common::write_record(&mut stream, 10, 0, &values)
+        .await
+        .unwrap();
+    common::write_record(&mut stream, 6, request.id, b"hello")
+        .await
+        .unwrap();
+    common::write_end_request(&mut stream, request.id, 0, 0)
+        .await
+        .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn management_record() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(server(server_stream));
+
+    let output = Client::new(client_stream)
+        .execute_once(Request::new(Params::default(), empty()))
+        .await
+        .unwrap();
+    assert_eq!(
+        output.stdout.unwrap(),
+        b"Content-type: text/plain\r\n\r\nhello"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn management_record_stream() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(server(server_stream));
+
+    let mut stream = Client::new(client_stream)
+        .execute_once_stream(Request::new(Params::default(), empty()))
+        .await
+        .unwrap();
+    let mut stdout = Vec::new();
+    while let Some(content) = stream.next().await {
+        if let Content::Stdout(out) = content.unwrap() {
+            stdout.extend_from_slice(out);
+        }
+    }
+    assert_eq!(stdout, b"Content-type: text/plain\r\n\r\nhello");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn management_record_multiplex() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(server(server_stream));
+
+    let output = MultiplexClient::new(client_stream)
+        .execute(Request::new(Params::default(), empty()))
+        .await
+        .unwrap();
+    assert_eq!(
+        output.stdout.unwrap(),
+        b"Content-type: text/plain\r\n\r\nhello"
+    );
+}
@@ -0,0 +1,289 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{
+    params::ParamsLimits,
+    server::{Server, ServerRequest, ServerResponse},
+    Client, ClientError, Params, Request,
+};
+use tokio::io::{self, duplex};
+
+mod common;
+
+fn build_request(script_name: &str) -> Request<'static, io::Empty> {
+    let params = Params::default()
+        .script_name(script_name)
+        .request_uri(script_name);
+    Request::new(params, io::empty()).into_owned()
+}
+
+#[tokio::test]
+async fn into_owned() {
+    let script_name = String::from("/index.php");
+    let request = build_request(&script_name);
+    drop(script_name);
+
+    let request = tokio::spawn(async move { request }).await.unwrap();
+    assert_eq!(request.params()["SCRIPT_NAME"], "/index.php");
+    assert_eq!(request.params()["REQUEST_URI"], "/index.php");
+    assert_eq!(request.params()["GATEWAY_INTERFACE"], "FastCGI/1.0");
+}
+
+async fn check_binary(request: ServerRequest) -> ServerResponse {
+    assert_eq!(request.params["REQUEST_METHOD"], "GET");
+    assert_eq!(request.params.get("SCRIPT_FILENAME"), None);
+    assert_eq!(
+        request.params.get_bytes("SCRIPT_FILENAME"),
+        Some(&b"/var/www/caf\xe9.php"[..])
+    );
+    assert_eq!(
+        request.params.get_bytes(b"HTTP_X_\xff"),
+        Some(&b"value"[..])
+    );
+    ServerResponse::new("Content-type: text/plain\r\n\r\n")
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn binary_params() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(async move {
+        Server::new(check_binary)
+            .serve_connection(server_stream)
+            .await
+    });
+
+    let params = Params::default()
+        .request_method("GET")
+        .script_filename("/var/www/cafe.php")
+        .binary_param(&b"SCRIPT_FILENAME"[..], &b"/var/www/caf\xe9.php"[..])
+        .binary_param(&b"HTTP_X_\xff"[..], &b"value"[..]);
+    assert_eq!(params.get("SCRIPT_FILENAME"), None);
+    assert_eq!(
+        params.get_bytes("SCRIPT_FILENAME"),
+        Some(&b"/var/www/caf\xe9.php"[..])
+    );
+
+    let response = Client::new(client_stream)
+        .execute_once(Request::new(params, io::empty()))
+        .await
+        .unwrap();
+    assert!(response.stdout.is_some());
+}
+
+async fn check_order(request: ServerRequest) -> ServerResponse {
+    let names = request
+        .params
+        .iter()
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            "GATEWAY_INTERFACE",
+            "SERVER_SOFTWARE",
+            "SERVER_PROTOCOL",
+            "REQUEST_METHOD",
+            "SCRIPT_NAME",
+            "HTTP_X_B",
+            "HTTP_X_A",
+        ]
+    );
+    ServerResponse::new("Content-type: text/plain\r\n\r\n")
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn ordered_params() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(async move {
+        Server::new(check_order)
+            .serve_connection(server_stream)
+            .await
+    });
+
+    let mut params = Params::default()
+        .request_method("GET")
+        .script_name("/index.php");
+    params.insert("HTTP_X_B".into(), "b".into());
+    params.insert("HTTP_X_A".into(), "a".into());
+
+    let response = Client::new(client_stream)
+        .execute_once(Request::new(params, io::empty()))
+        .await
+        .unwrap();
+    assert!(response.stdout.is_some());
+}
+
+#[test]
+fn shift_remove_keeps_order() {
+    let mut params = Params::default()
+        .request_method("GET")
+        .binary_param(&b"HTTP_X_A"[..], &b"\xff"[..])
+        .script_name("/index.php");
+    params.insert("REQUEST_METHOD".into(), "POST".into());
+    assert_eq!(
+        params.shift_remove("SERVER_SOFTWARE").as_deref(),
+        Some(&b"fastcgi-client-rs"[..])
+    );
+
+    let names = params
+        .iter_bytes()
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            &b"GATEWAY_INTERFACE"[..],
+            b"SERVER_PROTOCOL",
+            b"REQUEST_METHOD",
+            b"HTTP_X_A",
+            b"SCRIPT_NAME",
+        ]
+    );
+    assert_eq!(params["REQUEST_METHOD"], "POST");
+    assert_eq!(params.iter().count(), 4);
+}
+
+#[test]
+fn for_front_controller() {
+    let params = Params::for_front_controller("/var/www/public/", "/posts/1?page=2")
+        .request_method("GET")
+        .http_host("example.com");
+    assert_eq!(params["DOCUMENT_ROOT"], "/var/www/public/");
+    assert_eq!(params["SCRIPT_FILENAME"], "/var/www/public/index.php");
+    assert_eq!(params["SCRIPT_NAME"], "/index.php");
+    assert_eq!(params["DOCUMENT_URI"], "/index.php");
+    assert_eq!(params["REQUEST_URI"], "/posts/1?page=2");
+    assert_eq!(params["QUERY_STRING"], "page=2");
+    assert_eq!(params["HTTP_HOST"], "example.com");
+    assert!(!params.contains_key("PATH_INFO"));
+
+    let params = Params::for_front_controller("/var/www/public", "/index.php/posts/1");
+    assert_eq!(params["SCRIPT_FILENAME"], "/var/www/public/index.php");
+    assert_eq!(params["REQUEST_URI"], "/index.php/posts/1");
+    assert_eq!(params["QUERY_STRING"], "");
+    assert_eq!(params["PATH_INFO"], "/posts/1");
+}
+
+#[tokio::test]
+async fn strict_params() {
+    let (client_stream, _server_stream) = duplex(4096);
+    let mut client = Client::new_keep_alive(client_stream);
+
+    let params = Params::default().script_name("/index.php\0.jpg");
+    let request = Request::new(params, io::empty()).strict(true);
+    match client.execute(request).await {
+        Err(ClientError::InvalidParam { name, reason }) => {
+            assert_eq!(name, "SCRIPT_NAME");
+            assert_eq!(reason, "NUL byte in value");
+        }
+        result => panic!("unexpected {:?}", result),
+    }
+
+    let params = Params::default().binary_param(&b"HTTP_X=1"[..], &b"value"[..]);
+    assert!(matches!(
+        params.validate(),
+        Err(ClientError::InvalidParam { .. })
+    ));
+
+    assert!(Params::default().request_method("GET").validate().is_ok());
+}
+
+#[tokio::test]
+async fn params_limits() {
+    let (client_stream, _server_stream) = duplex(4096);
+    let mut client = Client::new_keep_alive(client_stream);
+
+    let params = Params::default().query_string("a".repeat(200));
+    let request = Request::new(params, io::empty())
+        .with_params_limits(ParamsLimits::default().max_value_length(128));
+    match client.execute(request).await {
+        Err(ClientError::ParamsTooLarge {
+            name,
+            length,
+            limit,
+        }) => {
+            assert_eq!(name.as_deref(), Some("QUERY_STRING"));
+            assert_eq!(length, 200);
+            assert_eq!(limit, 128);
+        }
+        result => panic!("unexpected {:?}", result),
+    }
+
+    let params = Params::default().query_string("a".repeat(200));
+    let limits = ParamsLimits::default().max_total_length(256);
+    match params.check_limits(&limits) {
+        Err(ClientError::ParamsTooLarge { name, limit, .. }) => {
+            assert_eq!(name, None);
+            assert_eq!(limit, 256);
+        }
+        result => panic!("unexpected {:?}", result),
+    }
+
+    assert!(params.check_limits(&ParamsLimits::default()).is_ok());
+}
+
+#[test]
+fn params_macro() {
+    let name = String::from("HTTP_X_NAME");
+    let params = fastcgi_client::params! {
+        request_method: "POST",
+        content_length: 3,
+        "HTTP_HOST" => "example.com",
+        name => String::from("value")
+    };
+    let mut expected = Params::default()
+        .request_method("POST")
+        .content_length(3)
+        .http_host("example.com");
+    expected.insert("HTTP_X_NAME".into(), "value".into());
+    assert_eq!(params, expected);
+
+    assert_eq!(fastcgi_client::params! {}, Params::default());
+}
+
+async fn echo_method(request: ServerRequest) -> ServerResponse {
+    ServerResponse::new(format!(
+        "Content-type: text/plain\r\n\r\n{}",
+        request.params["REQUEST_METHOD"]
+    ))
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn borrowed_params() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(async move {
+        Server::new(echo_method)
+            .serve_connection(server_stream)
+            .await
+    });
+
+    let params = Params::default().request_method("GET");
+    let mut client = Client::new_keep_alive(client_stream);
+    for _ in 0..3 {
+        let response = client
+            .execute(Request::new(&params, io::empty()))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.stdout.unwrap(),
+            b"Content-type: text/plain\r\n\r\nGET"
+        );
+    }
+}
@@ -0,0 +1,49 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{
+    params,
+    params::{set_redacted_patterns, DEFAULT_REDACTED_PATTERNS},
+};
+
+#[test]
+fn redact() {
+    let params = params! {
+        request_method: "GET",
+        "HTTP_AUTHORIZATION" => "Basic am1qb3k6cGFzcw==",
+        "HTTP_COOKIE" => "session=abc",
+        "HTTP_X_CSRF_TOKEN" => "xyz",
+        "HTTP_X_API_KEY" => "key",
+    };
+
+    let output = format!("{:?}", params);
+    assert!(output.contains(r#""REQUEST_METHOD": "GET""#));
+    assert!(output.contains(r#""HTTP_AUTHORIZATION": "<redacted>""#));
+    assert!(output.contains(r#""HTTP_COOKIE": "<redacted>""#));
+    assert!(output.contains(r#""HTTP_X_CSRF_TOKEN": "<redacted>""#));
+    assert!(output.contains(r#""HTTP_X_API_KEY": "key""#));
+
+    set_redacted_patterns(
+        DEFAULT_REDACTED_PATTERNS
+            .iter()
+            .copied()
+            .chain(["http_x_api_*"]),
+    );
+    let output = format!("{:?}", params);
+    assert!(output.contains(r#""HTTP_X_API_KEY": "<redacted>""#));
+
+    set_redacted_patterns::<_, String>([]);
+    let output = format!("{:?}", params);
+    assert!(output.contains(r#""HTTP_COOKIE": "session=abc""#));
+}
@@ -0,0 +1,95 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{
+    multiplex::MultiplexClient, Client, ClientError, ClientResult, Params, Request, Response,
+};
+use std::io;
+use tokio::io::{duplex, empty, DuplexStream};
+
+mod common;
+
+/// Reset the connection in the middle of response.
+async fn server(mut stream: DuplexStream) {
+    let request = common::read_request(&mut stream).await.unwrap();
+    common::write_record(
+        &mut stream,
+        6,
+        request.id,
+        b"Content-type: text/plain\r\n\r\nhel",
+    )
+    .await
+    .unwrap();
+    common::write_record(&mut stream, 7, request.id, b"warning")
+        .await
+        .unwrap();
+}
+
+fn assert_partial(result: ClientResult<Response>) {
+    match result {
+        Err(ClientError::PartialResponse { source, response }) => {
+            assert_eq!(source.kind(), io::ErrorKind::UnexpectedEof);
+            assert_eq!(
+                response.stdout.unwrap(),
+                b"Content-type: text/plain\r\n\r\nhel"
+            );
+            assert_eq!(response.stderr.unwrap(), b"warning");
+        }
+        result => panic!("unexpected {:?}", result),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn partial_response() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(server(server_stream));
+
+    let result = Client::new(client_stream)
+        .execute_once(Request::new(Params::default(), empty()))
+        .await;
+    assert_partial(result);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn partial_response_multiplex() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(server(server_stream));
+
+    let result = MultiplexClient::new(client_stream)
+        .execute(Request::new(Params::default(), empty()))
+        .await;
+    assert_partial(result);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn no_partial_response() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+    tokio::spawn(async move {
+        common::read_request(&mut server_stream).await.unwrap();
+    });
+
+    let result = Client::new(client_stream)
+        .execute_once(Request::new(Params::default(), empty()))
+        .await;
+    assert!(matches!(
+        result,
+        Err(ClientError::ConnectionClosedByPeer { .. })
+    ));
+}
@@ -0,0 +1,341 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{
+    pool::{Pool, PoolConfig},
+    server::{Server, ServerRequest, ServerResponse},
+    ClientError, Params, Request,
+};
+use std::{
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::io::{duplex, AsyncRead, AsyncWrite, DuplexStream, ReadBuf};
+
+mod common;
+
+async fn hello(_request: ServerRequest) -> ServerResponse {
+    ServerResponse::new("Content-type: text/plain\r\n\r\nhello")
+}
+
+fn connector(
+    connections: Arc<AtomicUsize>,
+) -> impl Fn() -> std::future::Ready<io::Result<DuplexStream>> + Send + Sync + 'static {
+    move || {
+        connections.fetch_add(1, Ordering::SeqCst);
+        let (client_stream, server_stream) = duplex(4096);
+        tokio::spawn(async move { Server::new(hello).serve_connection(server_stream).await });
+        std::future::ready(Ok(client_stream))
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn pool_reuse() {
+    common::setup();
+
+    let connections = Arc::new(AtomicUsize::new(0));
+    let pool = Pool::new(connector(connections.clone()));
+
+    for _ in 0..3 {
+        let output = pool
+            .execute(Request::new(Params::default(), tokio::io::empty()))
+            .await
+            .unwrap();
+        assert_eq!(
+            output.stdout.unwrap(),
+            b"Content-type: text/plain\r\n\r\nhello"
+        );
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+    assert_eq!(pool.idle(), 1);
+    assert_eq!(pool.in_flight(), 0);
+
+    // The connection closed by server isn't put back.
+    let request = Request::builder().keep_alive(false).build();
+    pool.execute(request).await.unwrap();
+    assert_eq!(pool.idle(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn pool_max_idle() {
+    common::setup();
+
+    let connections = Arc::new(AtomicUsize::new(0));
+    let pool = Pool::with_config(
+        connector(connections.clone()),
+        PoolConfig::default().max_idle(1),
+    );
+
+    let (first, second) = tokio::join!(
+        pool.execute(Request::new(Params::default(), tokio::io::empty())),
+        pool.execute(Request::new(Params::default(), tokio::io::empty())),
+    );
+    first.unwrap();
+    second.unwrap();
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+    assert_eq!(pool.idle(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn pool_shutdown() {
+    common::setup();
+
+    let connector = || async {
+        let (client_stream, server_stream) = duplex(4096);
+        tokio::spawn(async move {
+            Server::new(|_request: ServerRequest| async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                ServerResponse::new("Content-type: text/plain\r\n\r\nslow")
+            })
+            .serve_connection(server_stream)
+            .await
+        });
+        Ok::<_, io::Error>(client_stream)
+    };
+    let pool = Pool::new(connector);
+    pool.execute(Request::new(Params::default(), tokio::io::empty()))
+        .await
+        .unwrap();
+
+    let in_flight = {
+        let pool = pool.clone();
+        tokio::spawn(async move {
+            pool.execute(Request::new(Params::default(), tokio::io::empty()))
+                .await
+        })
+    };
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert_eq!(pool.in_flight(), 1);
+
+    assert!(matches!(
+        pool.shutdown(Duration::from_millis(1)).await,
+        Err(ClientError::RequestTimeout)
+    ));
+    assert!(pool.is_closed());
+    assert!(matches!(
+        pool.execute(Request::new(Params::default(), tokio::io::empty()))
+            .await,
+        Err(ClientError::ClientClosed)
+    ));
+
+    pool.shutdown(Duration::from_secs(1)).await.unwrap();
+    in_flight.await.unwrap().unwrap();
+    assert_eq!(pool.in_flight(), 0);
+    assert_eq!(pool.idle(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn pool_recycle() {
+    common::setup();
+
+    let connections = Arc::new(AtomicUsize::new(0));
+    let pool = Pool::with_config(
+        connector(connections.clone()),
+        PoolConfig::default().max_requests(2),
+    );
+    for _ in 0..5 {
+        pool.execute(Request::new(Params::default(), tokio::io::empty()))
+            .await
+            .unwrap();
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 3);
+
+    let connections = Arc::new(AtomicUsize::new(0));
+    let pool = Pool::with_config(
+        connector(connections.clone()),
+        PoolConfig::default().max_lifetime(Duration::from_millis(30)),
+    );
+    for _ in 0..2 {
+        pool.execute(Request::new(Params::default(), tokio::io::empty()))
+            .await
+            .unwrap();
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    pool.execute(Request::new(Params::default(), tokio::io::empty()))
+        .await
+        .unwrap();
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn pool_idle_timeout() {
+    common::setup();
+
+    let connections = Arc::new(AtomicUsize::new(0));
+    let pool = Pool::with_config(
+        connector(connections.clone()),
+        PoolConfig::default().idle_timeout(Duration::from_millis(30)),
+    );
+    for _ in 0..2 {
+        pool.execute(Request::new(Params::default(), tokio::io::empty()))
+            .await
+            .unwrap();
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    pool.execute(Request::new(Params::default(), tokio::io::empty()))
+        .await
+        .unwrap();
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn pool_closed_by_server() {
+    common::setup();
+
+    // The server closes the connection after every response, like the idle
+    // connection closed by php-fpm.
+    let connections = Arc::new(AtomicUsize::new(0));
+    let connector = {
+        let connections = connections.clone();
+        move || {
+            connections.fetch_add(1, Ordering::SeqCst);
+            let (client_stream, mut server_stream) = duplex(4096);
+            tokio::spawn(async move {
+                let request = common::read_request(&mut server_stream).await.unwrap();
+                common::write_record(&mut server_stream, 6, request.id, b"hello")
+                    .await
+                    .unwrap();
+                common::write_end_request(&mut server_stream, request.id, 0, 0)
+                    .await
+                    .unwrap();
+            });
+            std::future::ready(Ok::<_, io::Error>(client_stream))
+        }
+    };
+
+    let pool = Pool::new(connector);
+    for _ in 0..3 {
+        let output = pool
+            .execute(Request::new(Params::default(), tokio::io::empty()))
+            .await
+            .unwrap();
+        assert_eq!(output.stdout.unwrap(), b"hello");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 3);
+}
+
+/// Stream failing the writes with `BrokenPipe` after `closed` set, while the
+/// reads are still pending, like the socket closed by server silently.
+struct Stale {
+    stream: DuplexStream,
+    closed: Arc<AtomicBool>,
+}
+
+impl AsyncRead for Stale {
+    fn poll_read(
+        self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Stale {
+    fn poll_write(
+        self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.closed.load(Ordering::SeqCst) {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        Pin::new(&mut this.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn pool_retry_unsent() {
+    common::setup();
+
+    let connections = Arc::new(AtomicUsize::new(0));
+    let closed = Arc::new(AtomicBool::new(false));
+    let connector = {
+        let connections = connections.clone();
+        let closed = closed.clone();
+        move || {
+            connections.fetch_add(1, Ordering::SeqCst);
+            let (client_stream, server_stream) = duplex(4096);
+            tokio::spawn(async move { Server::new(hello).serve_connection(server_stream).await });
+            // Only the first connection goes stale.
+            let closed = if connections.load(Ordering::SeqCst) == 1 {
+                closed.clone()
+            } else {
+                Default::default()
+            };
+            std::future::ready(Ok::<_, io::Error>(Stale {
+                stream: client_stream,
+                closed,
+            }))
+        }
+    };
+    let pool = Pool::new(connector);
+
+    pool.execute(Request::new(Params::default(), tokio::io::empty()))
+        .await
+        .unwrap();
+    closed.store(true, Ordering::SeqCst);
+    let output = pool
+        .execute(Request::new(Params::default(), tokio::io::empty()))
+        .await
+        .unwrap();
+    assert_eq!(
+        output.stdout.unwrap(),
+        b"Content-type: text/plain\r\n\r\nhello"
+    );
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+
+    // The stdin read can't be replayed, so isn't retried.
+    let connections = Arc::new(AtomicUsize::new(0));
+    let closed = Arc::new(AtomicBool::new(false));
+    let pool = Pool::new({
+        let connections = connections.clone();
+        let closed = closed.clone();
+        move || {
+            connections.fetch_add(1, Ordering::SeqCst);
+            let (client_stream, server_stream) = duplex(4096);
+            tokio::spawn(async move { Server::new(hello).serve_connection(server_stream).await });
+            std::future::ready(Ok::<_, io::Error>(Stale {
+                stream: client_stream,
+                closed: closed.clone(),
+            }))
+        }
+    });
+    pool.execute(Request::new(Params::default(), tokio::io::empty()))
+        .await
+        .unwrap();
+    closed.store(true, Ordering::SeqCst);
+    let result = pool
+        .execute(Request::new(Params::default(), &b"body"[..]))
+        .await;
+    assert!(matches!(
+        result,
+        Err(ClientError::ConnectionClosedByPeer { .. })
+    ));
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
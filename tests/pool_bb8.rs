@@ -0,0 +1,111 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(feature = "bb8")]
+
+use fastcgi_client::{
+    pool::bb8::{Manager, Pool},
+    Params, Request,
+};
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tokio::io::{duplex, AsyncWriteExt, DuplexStream};
+
+mod common;
+
+/// Serve the requests until the count reached, and reply `FCGI_GET_VALUES`.
+async fn serve(mut stream: DuplexStream, mut requests: usize) -> io::Result<()> {
+    while requests > 0 {
+        let (r#type, id, content) = common::read_record(&mut stream).await?;
+        match r#type {
+            5 if content.is_empty() => {
+                common::write_record(&mut stream, 6, id, b"hello").await?;
+                common::write_end_request(&mut stream, id, 0, 0).await?;
+                requests -= 1;
+            }
+            9 => {
+                let content = common::encode_params(&[("FCGI_MAX_CONNS", "10")]);
+                common::write_record(&mut stream, 10, 0, &content).await?;
+            }
+            _ => {}
+        }
+    }
+    stream.shutdown().await
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn bb8_manager() {
+    common::setup();
+
+    let connections = Arc::new(AtomicUsize::new(0));
+    let connector = {
+        let connections = connections.clone();
+        move || {
+            connections.fetch_add(1, Ordering::SeqCst);
+            let (client_stream, server_stream) = duplex(4096);
+            tokio::spawn(serve(server_stream, usize::MAX));
+            std::future::ready(Ok::<_, io::Error>(client_stream))
+        }
+    };
+    let pool = Pool::builder()
+        .max_size(1)
+        .build(Manager::new(connector))
+        .await
+        .unwrap();
+
+    for _ in 0..3 {
+        let mut client = pool.get().await.unwrap();
+        let output = client
+            .execute(Request::new(Params::default(), tokio::io::empty()))
+            .await
+            .unwrap();
+        assert_eq!(output.stdout.unwrap(), b"hello");
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn bb8_recycle_closed() {
+    common::setup();
+
+    let connections = Arc::new(AtomicUsize::new(0));
+    let connector = {
+        let connections = connections.clone();
+        move || {
+            connections.fetch_add(1, Ordering::SeqCst);
+            let (client_stream, server_stream) = duplex(4096);
+            tokio::spawn(serve(server_stream, 1));
+            std::future::ready(Ok::<_, io::Error>(client_stream))
+        }
+    };
+    let pool = Pool::builder()
+        .max_size(1)
+        .build(Manager::new(connector))
+        .await
+        .unwrap();
+
+    for _ in 0..2 {
+        let mut client = pool.get().await.unwrap();
+        client
+            .execute(Request::new(Params::default(), tokio::io::empty()))
+            .await
+            .unwrap();
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
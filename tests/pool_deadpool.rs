@@ -0,0 +1,106 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(feature = "deadpool")]
+
+use fastcgi_client::{
+    pool::deadpool::{Manager, Pool},
+    Params, Request,
+};
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tokio::io::{duplex, AsyncWriteExt, DuplexStream};
+
+mod common;
+
+async fn serve(mut stream: DuplexStream) -> io::Result<()> {
+    loop {
+        let request = common::read_request(&mut stream).await?;
+        common::write_record(&mut stream, 6, request.id, b"hello").await?;
+        common::write_end_request(&mut stream, request.id, 0, 0).await?;
+
+        let (r#type, _, _) = common::read_record(&mut stream).await?;
+        assert_eq!(r#type, 9);
+        let content = common::encode_params(&[("FCGI_MAX_CONNS", "10")]);
+        common::write_record(&mut stream, 10, 0, &content).await?;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn deadpool_manager() {
+    common::setup();
+
+    let connections = Arc::new(AtomicUsize::new(0));
+    let connector = {
+        let connections = connections.clone();
+        move || {
+            connections.fetch_add(1, Ordering::SeqCst);
+            let (client_stream, server_stream) = duplex(4096);
+            tokio::spawn(serve(server_stream));
+            std::future::ready(Ok::<_, io::Error>(client_stream))
+        }
+    };
+    let pool = Pool::builder(Manager::new(connector))
+        .max_size(1)
+        .build()
+        .unwrap();
+
+    for _ in 0..3 {
+        let mut client = pool.get().await.unwrap();
+        let output = client
+            .execute(Request::new(Params::default(), tokio::io::empty()))
+            .await
+            .unwrap();
+        assert_eq!(output.stdout.unwrap(), b"hello");
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn deadpool_recycle_closed() {
+    common::setup();
+
+    let connections = Arc::new(AtomicUsize::new(0));
+    let connector = {
+        let connections = connections.clone();
+        move || {
+            connections.fetch_add(1, Ordering::SeqCst);
+            let (client_stream, mut server_stream) = duplex(4096);
+            tokio::spawn(async move {
+                let request = common::read_request(&mut server_stream).await?;
+                common::write_end_request(&mut server_stream, request.id, 0, 0).await?;
+                server_stream.shutdown().await
+            });
+            std::future::ready(Ok::<_, io::Error>(client_stream))
+        }
+    };
+    let pool = Pool::builder(Manager::new(connector))
+        .max_size(1)
+        .build()
+        .unwrap();
+
+    for _ in 0..2 {
+        let mut client = pool.get().await.unwrap();
+        client
+            .execute(Request::new(Params::default(), tokio::io::empty()))
+            .await
+            .unwrap();
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
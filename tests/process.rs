@@ -0,0 +1,123 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(all(feature = "process", unix))]
+
+use fastcgi_client::{
+    connect::{Address, Connect},
+    process::{ProcessConfig, ProcessManager},
+    server::{listen::Listener, Server, ServerRequest, ServerResponse},
+    Client, Params, Request,
+};
+use std::time::Duration;
+use tokio::time;
+
+mod common;
+
+/// The environment making the test binary run as the fastcgi application.
+const CHILD_ENV: &str = "FASTCGI_PROCESS_CHILD";
+
+async fn pid(_request: ServerRequest) -> ServerResponse {
+    ServerResponse::new(std::process::id().to_string())
+}
+
+/// Run as the fastcgi application spawned by the tests below, serve the
+/// listening socket of stdin, exit after the first connection if the
+/// environment is `exit`.
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn child() {
+    let Ok(mode) = std::env::var(CHILD_ENV) else {
+        return;
+    };
+    if mode == "connected" {
+        // The connected socket isn't taken as the listening one.
+        assert!(Listener::from_fcgi_stdin().unwrap().is_none());
+        return;
+    }
+    let listener = Listener::from_fcgi_stdin().unwrap().unwrap();
+    let server = Server::new(pid);
+    match (mode.as_str(), listener) {
+        ("exit", Listener::Tcp(listener)) => {
+            let (stream, _) = listener.accept().await.unwrap();
+            server.serve_connection(stream).await.unwrap();
+            std::process::exit(1);
+        }
+        (_, listener) => server.serve(listener).await.unwrap(),
+    }
+}
+
+#[test]
+fn stdin_not_listening() {
+    let (stdin, _peer) = std::os::unix::net::UnixStream::pair().unwrap();
+    let status = std::process::Command::new(std::env::current_exe().unwrap())
+        .args(["child", "--exact"])
+        .env(CHILD_ENV, "connected")
+        .stdin(std::os::fd::OwnedFd::from(stdin))
+        .stdout(std::process::Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+fn config(mode: &str) -> ProcessConfig {
+    ProcessConfig::new(std::env::current_exe().unwrap())
+        .arg("child")
+        .arg("--exact")
+        .env(CHILD_ENV, mode)
+}
+
+async fn request_pid(address: &Address) -> String {
+    let response = Client::new(address.connect().await.unwrap())
+        .execute_once(Request::new(Params::default(), tokio::io::empty()))
+        .await
+        .unwrap();
+    String::from_utf8(response.stdout.unwrap()).unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn spawn_processes() {
+    common::setup();
+
+    let path = std::env::temp_dir().join(format!("fastcgi-process-{}.sock", std::process::id()));
+    let manager =
+        ProcessManager::spawn(config("serve").processes(2), Address::Unix(path.clone())).unwrap();
+
+    let pid = request_pid(manager.address()).await;
+    assert_ne!(pid, std::process::id().to_string());
+
+    manager.shutdown().await;
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn restart_process() {
+    common::setup();
+
+    let config = config("exit").initial_backoff(Duration::from_millis(10));
+    let manager = ProcessManager::spawn(config, "127.0.0.1:0".parse().unwrap()).unwrap();
+    assert!(matches!(manager.address(), Address::Tcp(addr) if !addr.ends_with(":0")));
+
+    let first = request_pid(manager.address()).await;
+    time::timeout(Duration::from_secs(10), async {
+        while manager.restarts() == 0 {
+            time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    let second = request_pid(manager.address()).await;
+    assert_ne!(first, second);
+
+    manager.shutdown().await;
+}
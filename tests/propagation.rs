@@ -0,0 +1,50 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(feature = "opentelemetry")]
+
+use fastcgi_client::{propagation, Params};
+use opentelemetry::{
+    trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState},
+    Context,
+};
+
+#[test]
+fn inject_trace_context() {
+    let mut params = Params::default();
+    propagation::inject_context(&mut params, &Context::new());
+    assert!(!params.contains_key("HTTP_TRACEPARENT"));
+
+    let span_context = SpanContext::new(
+        TraceId::from(0x4bf92f3577b34da6a3ce929d0e0e4736),
+        SpanId::from(0x00f067aa0ba902b7),
+        TraceFlags::SAMPLED,
+        true,
+        TraceState::from_key_value([("vendor", "value")]).unwrap(),
+    );
+    let cx = Context::new().with_remote_span_context(span_context);
+    propagation::inject_context(&mut params, &cx);
+    assert_eq!(
+        params["HTTP_TRACEPARENT"],
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+    );
+    assert_eq!(params["HTTP_TRACESTATE"], "vendor=value");
+
+    let _guard = cx.attach();
+    let params = Params::default().trace_context();
+    assert_eq!(
+        params["HTTP_TRACEPARENT"],
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+    );
+}
@@ -0,0 +1,130 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{
+    record::{encode_request, parse_record, parse_response},
+    ClientError, Params, Request,
+};
+use tokio::io::AsyncReadExt;
+
+/// Encode a record padded to a multiple of 8 bytes.
+fn record(r#type: u8, request_id: u16, content: &[u8]) -> Vec<u8> {
+    let padding_length = (8 - content.len() % 8) % 8;
+    let mut buf = vec![1, r#type];
+    buf.extend_from_slice(&request_id.to_be_bytes());
+    buf.extend_from_slice(&(content.len() as u16).to_be_bytes());
+    buf.extend_from_slice(&[padding_length as u8, 0]);
+    buf.extend_from_slice(content);
+    buf.resize(buf.len() + padding_length, 0);
+    buf
+}
+
+#[test]
+fn parse_record_incomplete() {
+    let buf = record(6, 1, b"hello");
+    for len in 0..buf.len() {
+        match parse_record(&buf[..len]) {
+            Err(ClientError::IncompleteRecord { needed }) => assert!(needed > 0),
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+
+    let (record, read) = parse_record(&buf).unwrap();
+    assert_eq!(read, 16);
+    assert_eq!(record.len(), 16);
+    assert_eq!(record.request_id, 1);
+    assert_eq!(record.content, b"hello");
+    assert_eq!(record.padding, [0; 3]);
+}
+
+#[test]
+fn parse_response_records() {
+    let mut buf = record(6, 1, b"Content-type: text/plain\r\n\r\n");
+    buf.extend(record(10, 0, b"\x0f\x01FCGI_MPXS_CONNS0"));
+    buf.extend(record(7, 1, b"warning"));
+    buf.extend(record(6, 1, b"hello"));
+    buf.extend(record(3, 1, &[0, 0, 0, 1, 0, 0, 0, 0]));
+    let len = buf.len();
+    buf.extend(record(6, 2, b"next"));
+
+    let (response, read) = parse_response(&buf, 1).unwrap();
+    assert_eq!(read, len);
+    assert_eq!(response.app_status, 1);
+    assert_eq!(
+        response.stdout.unwrap(),
+        b"Content-type: text/plain\r\n\r\nhello"
+    );
+    assert_eq!(response.stderr.unwrap(), b"warning");
+
+    assert!(matches!(
+        parse_response(&buf[..len - 1], 1),
+        Err(ClientError::IncompleteRecord { .. })
+    ));
+    assert!(matches!(
+        parse_response(&record(3, 1, &[0; 4]), 1),
+        Err(ClientError::ProtocolError { .. })
+    ));
+    assert!(matches!(
+        parse_response(&record(3, 1, &[0, 0, 0, 0, 2, 0, 0, 0]), 1),
+        Err(ClientError::EndRequestOverloaded { .. })
+    ));
+}
+
+#[test]
+fn parse_arbitrary_bytes() {
+    // Xorshift, so the inputs are deterministic.
+    let mut state = 0x2545f4914f6cdd1du64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    for _ in 0..10000 {
+        let len = (next() % 64) as usize;
+        let mut buf = (0..len).map(|_| next() as u8).collect::<Vec<_>>();
+        if let Some(version) = buf.first_mut() {
+            *version = 1;
+        }
+        if let Ok((record, read)) = parse_record(&buf) {
+            assert_eq!(record.len(), read);
+            assert!(read <= buf.len());
+        }
+        let _ = parse_response(&buf, 1);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn encode_request_golden() {
+    let mut params = Params::default();
+    params.clear();
+    let params = params.request_method("POST").content_length(11);
+    let request = Request::builder()
+        .params(params)
+        .stdin((&b"hello "[..]).chain(&b"world"[..]))
+        .build();
+    let buf = encode_request(request).await.unwrap();
+
+    let mut expected = record(1, 1, &[0, 1, 0, 0, 0, 0, 0, 0]);
+    expected.extend(record(
+        4,
+        1,
+        b"\x0e\x04REQUEST_METHODPOST\x0e\x02CONTENT_LENGTH11",
+    ));
+    expected.extend(record(4, 1, b""));
+    expected.extend(record(5, 1, b"hello world"));
+    expected.extend(record(5, 1, b""));
+    assert_eq!(buf, expected);
+}
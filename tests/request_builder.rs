@@ -0,0 +1,89 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{Client, Params, Request, Role};
+use std::num::NonZeroU16;
+use tokio::io::duplex;
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn request_builder() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(0x10000);
+
+    let server = tokio::spawn(async move {
+        let request = common::read_request(&mut server_stream).await.unwrap();
+        assert_eq!(request.id, 7);
+        assert_eq!(request.role, Role::Filter as u16);
+        assert!(!request.keep_alive);
+        assert_eq!(request.params["REQUEST_METHOD"], "GET");
+        assert_eq!(request.stdin, b"stdin");
+
+        let mut data = Vec::new();
+        loop {
+            let (r#type, id, content) = common::read_record(&mut server_stream).await.unwrap();
+            assert_eq!((r#type, id), (8, 7));
+            if content.is_empty() {
+                break;
+            }
+            data.extend(content);
+        }
+        assert_eq!(data, b"data");
+
+        common::write_record(&mut server_stream, 6, 7, b"filtered")
+            .await
+            .unwrap();
+        common::write_end_request(&mut server_stream, 7, 0, 0)
+            .await
+            .unwrap();
+    });
+
+    let client = Client::new(client_stream);
+    let request = Request::builder()
+        .role(Role::Filter)
+        .params(Params::default().request_method("GET"))
+        .stdin(&b"stdin"[..])
+        .data(&b"data"[..])
+        .request_id(NonZeroU16::new(7).unwrap())
+        .build();
+    assert!(request.has_data());
+
+    let response = client.execute_once(request).await.unwrap();
+    assert_eq!(response.stdout.unwrap(), b"filtered");
+
+    server.await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn request_builder_keep_alive() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(0x10000);
+
+    tokio::spawn(async move {
+        let request = common::read_request(&mut server_stream).await.unwrap();
+        assert_eq!(request.id, 1);
+        assert!(request.keep_alive);
+        assert!(request.stdin.is_empty());
+        common::write_end_request(&mut server_stream, request.id, 0, 0)
+            .await
+            .unwrap();
+    });
+
+    let client = Client::new(client_stream);
+    let request = Request::builder().keep_alive(true).build();
+    client.execute_once(request).await.unwrap();
+}
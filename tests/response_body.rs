@@ -0,0 +1,102 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(feature = "http-body")]
+
+use fastcgi_client::{body::ResponseBody, request::Request, Client, ClientError, Params};
+use http_body::Body;
+use std::{future::poll_fn, pin::Pin};
+use tokio::io::{duplex, DuplexStream};
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn stream_stdout_frames() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    tokio::spawn(async move {
+        let id = common::read_request(&mut server_stream).await.unwrap().id;
+        common::write_record(
+            &mut server_stream,
+            6,
+            id,
+            b"Content-type: text/plain\r\n\r\n",
+        )
+        .await
+        .unwrap();
+        common::write_record(&mut server_stream, 7, id, b"notice")
+            .await
+            .unwrap();
+        common::write_record(&mut server_stream, 6, id, b"hello")
+            .await
+            .unwrap();
+        common::write_record(&mut server_stream, 6, id, b"")
+            .await
+            .unwrap();
+        common::write_end_request(&mut server_stream, id, 0, 0)
+            .await
+            .unwrap();
+    });
+
+    let stream = Client::new(client_stream)
+        .execute_once_stream(Request::new(Params::default(), &b"body"[..]))
+        .await
+        .unwrap();
+    let mut body = ResponseBody::new(stream);
+
+    let mut frames = Vec::new();
+    while let Some(frame) = poll_fn(|cx| Pin::new(&mut body).poll_frame(cx)).await {
+        frames.push(frame.unwrap().into_data().unwrap());
+    }
+
+    assert_eq!(frames, ["Content-type: text/plain\r\n\r\n", "hello"]);
+    assert!(body.is_end_stream());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn end_request_content_length() {
+    common::setup();
+
+    // Longer than the content buffer.
+    let mut body = end_request_body(vec![0; 5000]).await;
+    assert!(poll_fn(|cx| Pin::new(&mut body).poll_frame(cx))
+        .await
+        .is_none());
+
+    // Shorter than the `EndRequest` body.
+    let mut body = end_request_body(vec![0; 4]).await;
+    assert!(matches!(
+        poll_fn(|cx| Pin::new(&mut body).poll_frame(cx)).await,
+        Some(Err(ClientError::ProtocolError { .. }))
+    ));
+}
+
+async fn end_request_body(content: Vec<u8>) -> ResponseBody<DuplexStream> {
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    tokio::spawn(async move {
+        let id = common::read_request(&mut server_stream).await.unwrap().id;
+        common::write_record(&mut server_stream, 3, id, &content)
+            .await
+            .unwrap();
+    });
+
+    let stream = Client::new(client_stream)
+        .execute_once_stream(Request::new(Params::default(), &b""[..]))
+        .await
+        .unwrap();
+    ResponseBody::new(stream)
+}
@@ -0,0 +1,103 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(feature = "http")]
+
+use fastcgi_client::Response;
+
+#[test]
+fn into_http() {
+    let mut response = Response::default();
+    response.stdout = Some(
+        b"Status: 201 Created\r\nContent-type: application/json\r\nSet-Cookie: a=1\r\nSet-Cookie: \
+          b=2\r\n\r\n{}"
+            .to_vec(),
+    );
+
+    let response = response.into_http().unwrap();
+
+    assert_eq!(response.status(), http::StatusCode::CREATED);
+    assert_eq!(
+        response.headers().get(http::header::CONTENT_TYPE).unwrap(),
+        "application/json"
+    );
+    assert_eq!(
+        response
+            .headers()
+            .get_all(http::header::SET_COOKIE)
+            .iter()
+            .count(),
+        2
+    );
+    assert_eq!(response.body(), b"{}");
+}
+
+#[test]
+fn into_http_without_stdout() {
+    assert!(http::Response::<Vec<u8>>::try_from(Response::default()).is_err());
+}
+
+#[test]
+fn headers_to_header_map() {
+    let parsed = fastcgi_client::response::parse::parse(
+        b"Set-Cookie: a=1\r\nContent-type: text/html\r\nSet-Cookie: b=2\r\n\r\n",
+    )
+    .unwrap();
+
+    let headers = http::HeaderMap::try_from(&parsed.headers).unwrap();
+    assert_eq!(headers.len(), 3);
+    assert_eq!(
+        headers
+            .get_all(http::header::SET_COOKIE)
+            .iter()
+            .collect::<Vec<_>>(),
+        ["a=1", "b=2"]
+    );
+    assert_eq!(headers[http::header::CONTENT_TYPE], "text/html");
+
+    let parsed = fastcgi_client::response::parse::parse(b"X-Bad: a\x7fb\r\n\r\n").unwrap();
+    assert!(matches!(
+        parsed.headers.to_header_map(),
+        Err(fastcgi_client::ClientError::Http(_))
+    ));
+}
+
+#[test]
+fn status_code() {
+    let parsed = fastcgi_client::response::parse::parse(b"Status: 404 Not Found\r\n\r\n").unwrap();
+    assert_eq!(parsed.status, 404);
+    assert_eq!(parsed.status_code().unwrap(), http::StatusCode::NOT_FOUND);
+
+    let parsed =
+        fastcgi_client::response::parse::parse(b"Content-type: text/html\r\n\r\n").unwrap();
+    assert_eq!(parsed.status_code().unwrap(), http::StatusCode::OK);
+
+    let mut response = Response::default();
+    response.stdout = Some(b"Status: 503\r\n\r\n".to_vec());
+    assert_eq!(
+        response.into_http().unwrap().status(),
+        http::StatusCode::SERVICE_UNAVAILABLE
+    );
+}
+
+#[test]
+fn into_http_nph() {
+    let mut response = Response::default();
+    response.stdout = Some(b"HTTP/1.0 204 No Content\r\nX-Foo: bar\r\n\r\n".to_vec());
+
+    let response = response.into_http_nph().unwrap();
+    assert_eq!(response.status(), http::StatusCode::NO_CONTENT);
+    assert_eq!(response.headers()["x-foo"], "bar");
+    assert!(response.body().is_empty());
+}
@@ -0,0 +1,223 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{
+    response::parse::{parse, parse_nph, parse_with_limits, HeaderLimits},
+    ClientError, Response,
+};
+
+#[test]
+fn parse_php_output() {
+    let parsed = parse(
+        b"X-Powered-By: PHP/7.1.30\r\nContent-type: text/html; charset=UTF-8\r\n\r\nhello\r\n\r\n",
+    )
+    .unwrap();
+
+    assert_eq!(parsed.status, 200);
+    assert_eq!(parsed.headers.len(), 2);
+    assert_eq!(parsed.headers.get("x-powered-by"), Some("PHP/7.1.30"));
+    assert_eq!(
+        parsed.headers.get("Content-Type"),
+        Some("text/html; charset=UTF-8")
+    );
+    assert_eq!(parsed.body, b"hello\r\n\r\n");
+}
+
+#[test]
+fn parse_header_casing() {
+    let parsed = parse(b"X-Powered-By: PHP/8.3\r\nSTATUS: 201\r\netag: \"1\"\r\n\r\n").unwrap();
+
+    assert_eq!(parsed.status, 201);
+    assert_eq!(
+        parsed.headers.iter().collect::<Vec<_>>(),
+        [("X-Powered-By", "PHP/8.3"), ("etag", "\"1\"")]
+    );
+    assert_eq!(parsed.headers.get("x-powered-by"), Some("PHP/8.3"));
+    assert_eq!(parsed.headers.get("ETag"), Some("\"1\""));
+}
+
+#[test]
+fn parse_status_duplicate_and_folded() {
+    let parsed = parse(
+        b"Status: 404 Not Found\nSet-Cookie: a=1\nX-Long: first\n\tsecond\nSet-Cookie: b=2\n\nmissing",
+    )
+    .unwrap();
+
+    assert_eq!(parsed.status, 404);
+    assert_eq!(parsed.headers.get("status"), None);
+    assert_eq!(
+        parsed.headers.get_all("set-cookie").collect::<Vec<_>>(),
+        ["a=1", "b=2"]
+    );
+    assert_eq!(parsed.headers.get("x-long"), Some("first second"));
+    assert_eq!(parsed.body, b"missing");
+}
+
+#[test]
+fn parse_location_without_status() {
+    let parsed = parse(b"Location: http://example.com/\r\n\r\n").unwrap();
+
+    assert_eq!(parsed.status, 302);
+    assert!(parsed.body.is_empty());
+    assert_eq!(parsed.local_redirect(), None);
+
+    let parsed = parse(b"Location: /index.php?page=2\r\n\r\n").unwrap();
+    assert_eq!(parsed.local_redirect(), Some("/index.php?page=2"));
+
+    let parsed = parse(b"Status: 301\r\nLocation: /index.php\r\n\r\n").unwrap();
+    assert_eq!(parsed.local_redirect(), None);
+}
+
+#[test]
+fn parse_invalid() {
+    assert!(matches!(
+        parse(b"Content-type: text/html\r\nhello"),
+        Err(ClientError::InvalidCgiResponse { .. })
+    ));
+    assert!(matches!(
+        parse(b"hello\r\n\r\n"),
+        Err(ClientError::InvalidCgiResponse { .. })
+    ));
+    assert!(matches!(
+        parse(b"Status: abc\r\n\r\n"),
+        Err(ClientError::InvalidCgiResponse { .. })
+    ));
+}
+
+#[test]
+fn parse_nph_output() {
+    let parsed = parse_nph(
+        b"HTTP/1.1 201 Created\r\nStatus: 500\r\nSet-Cookie: a=1\r\nSet-Cookie: b=2\r\n\r\n{}",
+    )
+    .unwrap();
+    assert_eq!(parsed.status, 201);
+    assert_eq!(parsed.headers.get("status"), Some("500"));
+    assert_eq!(
+        parsed.headers.get_all("set-cookie").collect::<Vec<_>>(),
+        ["a=1", "b=2"]
+    );
+    assert_eq!(parsed.body, b"{}");
+
+    assert!(matches!(
+        parse_nph(b"Content-type: text/html\r\n\r\nhello"),
+        Err(ClientError::InvalidCgiResponse { .. })
+    ));
+    assert!(matches!(
+        parse_nph(b"HTTP/1.1 abc\r\n\r\n"),
+        Err(ClientError::InvalidCgiResponse { .. })
+    ));
+}
+
+#[test]
+fn response_str_and_body() {
+    let mut response = Response::default();
+    assert_eq!(response.stdout_str().unwrap(), "");
+    assert!(matches!(
+        response.body(),
+        Err(ClientError::InvalidCgiResponse { .. })
+    ));
+
+    response.stdout = Some(b"Content-type: text/plain\n\nhello".to_vec());
+    response.stderr = Some(b"PHP Notice: \xff".to_vec());
+    assert_eq!(
+        response.stdout_str().unwrap(),
+        "Content-type: text/plain\n\nhello"
+    );
+    assert_eq!(response.body().unwrap(), b"hello");
+    assert!(response.stderr_str().is_err());
+    assert_eq!(response.stderr_str_lossy(), "PHP Notice: \u{fffd}");
+}
+
+#[cfg(feature = "flate2")]
+#[test]
+fn parse_decode_body() {
+    use flate2::{
+        write::{DeflateEncoder, GzEncoder, ZlibEncoder},
+        Compression,
+    };
+    use std::io::Write;
+
+    let gzip = {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"hello gzip").unwrap();
+        encoder.finish().unwrap()
+    };
+    let zlib = {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"hello zlib").unwrap();
+        encoder.finish().unwrap()
+    };
+    let deflate = {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"hello deflate").unwrap();
+        encoder.finish().unwrap()
+    };
+
+    for (encoding, body, expected) in [
+        ("gzip", gzip, &b"hello gzip"[..]),
+        ("deflate", zlib, b"hello zlib"),
+        ("deflate", deflate, b"hello deflate"),
+    ] {
+        let mut stdout = format!(
+            "Content-Encoding: {}\r\nContent-Length: {}\r\nContent-type: text/plain\r\n\r\n",
+            encoding,
+            body.len()
+        )
+        .into_bytes();
+        stdout.extend_from_slice(&body);
+
+        let decoded = parse(&stdout).unwrap().decode_body().unwrap();
+        assert_eq!(decoded.body, expected);
+        assert_eq!(decoded.headers.len(), 1);
+        assert_eq!(decoded.headers.get("content-type"), Some("text/plain"));
+    }
+
+    let parsed = parse(b"Content-Encoding: br\r\n\r\nxxx").unwrap();
+    assert!(matches!(
+        parsed.decode_body(),
+        Err(ClientError::InvalidCgiResponse { .. })
+    ));
+    let parsed = parse(b"Content-Encoding: gzip\r\n\r\nxxx").unwrap();
+    assert!(matches!(
+        parsed.decode_body(),
+        Err(ClientError::InvalidCgiResponse { .. })
+    ));
+}
+
+#[test]
+fn parse_header_limits() {
+    let stdout = b"Status: 200\r\nA: 1\r\nB: 2\r\n\r\nbody";
+
+    let limits = HeaderLimits::default().max_count(3);
+    assert_eq!(parse_with_limits(stdout, &limits).unwrap().headers.len(), 2);
+    let limits = HeaderLimits::default().max_count(2);
+    assert!(matches!(
+        parse_with_limits(stdout, &limits),
+        Err(ClientError::TooManyHeaders { limit: 2 })
+    ));
+
+    let limits = HeaderLimits::default().max_bytes(16);
+    assert!(matches!(
+        parse_with_limits(stdout, &limits),
+        Err(ClientError::HeadersTooLarge { limit: 16 })
+    ));
+
+    let mut stdout = b"X-Long: ".to_vec();
+    stdout.resize(100 * 1024, b'x');
+    stdout.extend_from_slice(b"\r\n\r\n");
+    assert!(matches!(
+        parse(&stdout),
+        Err(ClientError::HeadersTooLarge { .. })
+    ));
+}
@@ -0,0 +1,63 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{Client, Params, Request};
+use tokio::io::{self, duplex, AsyncReadExt};
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn stdout_reader() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    let body = "x".repeat(10000);
+    let expected = format!("Content-type: text/plain\r\n\r\n{}", body);
+
+    tokio::spawn(async move {
+        let id = common::read_request(&mut server_stream).await.unwrap().id;
+        common::write_record(
+            &mut server_stream,
+            6,
+            id,
+            b"Content-type: text/plain\r\n\r\n",
+        )
+        .await
+        .unwrap();
+        common::write_record(&mut server_stream, 7, id, b"notice")
+            .await
+            .unwrap();
+        common::write_record(&mut server_stream, 6, id, body.as_bytes())
+            .await
+            .unwrap();
+        common::write_end_request(&mut server_stream, id, 0, 0)
+            .await
+            .unwrap();
+    });
+
+    let mut stream = Client::new(client_stream)
+        .execute_once_stream(Request::new(Params::default(), io::empty()))
+        .await
+        .unwrap();
+
+    let mut reader = stream.stdout_reader();
+    let mut head = [0; 10];
+    reader.read_exact(&mut head).await.unwrap();
+    assert_eq!(&head, b"Content-ty");
+
+    let mut output = head.to_vec();
+    io::copy(&mut reader, &mut output).await.unwrap();
+    assert_eq!(output, expected.as_bytes());
+}
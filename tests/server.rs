@@ -0,0 +1,512 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{
+    multiplex::MultiplexClient,
+    server::{Server, ServerLimits, ServerRequest, ServerResponse},
+    values::{ValueName, Values},
+    Client, ClientError, Params, Request, Role,
+};
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+use tokio::{
+    io::duplex,
+    net::TcpListener,
+    sync::{mpsc, Barrier, Notify},
+};
+
+mod common;
+
+async fn echo(request: ServerRequest) -> ServerResponse {
+    let mut stdout = format!(
+        "Content-type: text/plain\r\n\r\n{} ",
+        request.params["REQUEST_METHOD"]
+    )
+    .into_bytes();
+    stdout.extend(request.stdin);
+    ServerResponse::new(stdout).stderr("notice")
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn serve_keep_alive_connection() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    let server =
+        tokio::spawn(async move { Server::new(echo).serve_connection(server_stream).await });
+
+    let mut client = Client::new_keep_alive(client_stream);
+    for body in [&b"first"[..], &[b'.'; 100000][..]] {
+        let response = client
+            .execute(Request::new(Params::default().request_method("POST"), body))
+            .await
+            .unwrap();
+
+        let mut stdout = b"Content-type: text/plain\r\n\r\nPOST ".to_vec();
+        stdout.extend_from_slice(body);
+        assert_eq!(response.stdout, Some(stdout));
+        assert_eq!(response.stderr.as_deref(), Some(&b"notice"[..]));
+    }
+
+    drop(client);
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn serve_tcp() {
+    common::setup();
+
+    let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { Server::new(echo).serve_tcp(listener).await });
+
+    let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let response = Client::new(stream)
+        .execute_once(Request::new(
+            Params::default().request_method("GET"),
+            tokio::io::empty(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(
+        response.stdout.as_deref(),
+        Some(&b"Content-type: text/plain\r\n\r\nGET "[..])
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn serve_unknown_type() {
+    common::setup();
+
+    let (mut client_stream, server_stream) = duplex(4096);
+    let server =
+        tokio::spawn(async move { Server::new(echo).serve_connection(server_stream).await });
+
+    // Unknown management record type.
+    common::write_record(&mut client_stream, 42, 0, b"")
+        .await
+        .unwrap();
+    let (r#type, id, content) = common::read_record(&mut client_stream).await.unwrap();
+    assert_eq!((r#type, id), (11, 0));
+    assert_eq!(content, [42, 0, 0, 0, 0, 0, 0, 0]);
+
+    // The connection is still served.
+    let mut client = Client::new_keep_alive(client_stream);
+    let values = client.get_values(&ValueName::ALL).await.unwrap();
+    assert_eq!(values.mpxs_conns, Some(true));
+    let response = client
+        .execute(Request::new(
+            Params::default().request_method("GET"),
+            tokio::io::empty(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(
+        response.stdout.as_deref(),
+        Some(&b"Content-type: text/plain\r\n\r\nGET "[..])
+    );
+
+    drop(client);
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn serve_roles() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    let server = Server::new(echo)
+        .authorizer(|request: ServerRequest| async move {
+            let user = request.params["REMOTE_USER"].clone();
+            ServerResponse::new(format!("Status: 200\r\nVariable-USER: {}\r\n\r\n", user))
+        })
+        .filter(|request: ServerRequest| async move {
+            let mut stdout = b"Content-type: text/plain\r\n\r\n".to_vec();
+            stdout.extend(request.data.to_ascii_uppercase());
+            ServerResponse::new(stdout)
+        });
+    let server = tokio::spawn(async move { server.serve_connection(server_stream).await });
+
+    let mut client = Client::new_keep_alive(client_stream);
+
+    let mut params = Params::default();
+    params.insert("REMOTE_USER".into(), "jmjoy".into());
+    let request = Request::builder()
+        .role(Role::Authorizer)
+        .params(params)
+        .build();
+    let response = client.execute(request).await.unwrap();
+    assert_eq!(
+        response.stdout.as_deref(),
+        Some(&b"Status: 200\r\nVariable-USER: jmjoy\r\n\r\n"[..])
+    );
+
+    let request = Request::builder()
+        .role(Role::Filter)
+        .stdin(&b"ignored"[..])
+        .data(&b"filtered"[..])
+        .build();
+    let response = client.execute(request).await.unwrap();
+    assert_eq!(
+        response.stdout.as_deref(),
+        Some(&b"Content-type: text/plain\r\n\r\nFILTERED"[..])
+    );
+
+    drop(client);
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn serve_unknown_role() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    let server =
+        tokio::spawn(async move { Server::new(echo).serve_connection(server_stream).await });
+
+    let mut client = Client::new_keep_alive(client_stream);
+    let request = Request::builder().role(Role::Filter).build();
+    let err = client.execute(request).await.unwrap_err();
+    assert!(matches!(err, ClientError::EndRequestUnknownRole { .. }));
+
+    // The connection is still served.
+    let response = client
+        .execute(Request::new(
+            Params::default().request_method("GET"),
+            tokio::io::empty(),
+        ))
+        .await
+        .unwrap();
+    assert!(response.stdout.is_some());
+
+    drop(client);
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn serve_limits() {
+    common::setup();
+
+    let (mut client_stream, server_stream) = duplex(4096);
+    let server = Server::new(echo).limits(
+        ServerLimits::default()
+            .max_requests(1)
+            .max_params_length(256)
+            .max_stdin_length(4),
+    );
+    let server = tokio::spawn(async move { server.serve_connection(server_stream).await });
+
+    // The second request receiving concurrently is overloaded.
+    for id in [1, 2] {
+        common::write_record(&mut client_stream, 1, id, &[0, 1, 1, 0, 0, 0, 0, 0])
+            .await
+            .unwrap();
+    }
+    let (r#type, id, content) = common::read_record(&mut client_stream).await.unwrap();
+    assert_eq!((r#type, id, content[4]), (3, 2, 2));
+    // Abort the first one.
+    common::write_record(&mut client_stream, 2, 1, b"")
+        .await
+        .unwrap();
+    let (r#type, id, _) = common::read_record(&mut client_stream).await.unwrap();
+    assert_eq!((r#type, id), (3, 1));
+
+    let mut client = Client::new_keep_alive(client_stream);
+    for request in [
+        Request::new(Params::default().request_uri("a".repeat(256)), &b""[..]),
+        Request::new(Params::default(), &b"hello"[..]),
+    ] {
+        let response = client.execute(request).await.unwrap();
+        assert_eq!(
+            response.stdout.as_deref(),
+            Some(&b"Status: 413 Payload Too Large\r\n\r\n"[..])
+        );
+        assert_eq!(response.app_status, 1);
+    }
+
+    // The connection is still served.
+    let response = client
+        .execute(Request::new(
+            Params::default().request_method("POST"),
+            &b"abcd"[..],
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.app_status, 0);
+
+    drop(client);
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn serve_multiplexed() {
+    common::setup();
+
+    // The handlers only return if both requests are handled concurrently.
+    let barrier = Arc::new(Barrier::new(2));
+    let server = Server::new(move |request: ServerRequest| {
+        let barrier = barrier.clone();
+        async move {
+            barrier.wait().await;
+            ServerResponse::new(request.stdin)
+        }
+    });
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(async move { server.serve_connection(server_stream).await });
+
+    let client = MultiplexClient::new(client_stream);
+    let (first, second) = tokio::join!(
+        client.execute(Request::new(Params::default(), &b"first"[..])),
+        client.execute(Request::new(Params::default(), &[b'.'; 100000][..])),
+    );
+    assert_eq!(first.unwrap().stdout.as_deref(), Some(&b"first"[..]));
+    assert_eq!(second.unwrap().stdout, Some(vec![b'.'; 100000]));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn serve_abort() {
+    common::setup();
+
+    // The handler of `/hang` never returns, and reports the start and the
+    // cancellation.
+    let (events_tx, mut events_rx) = mpsc::unbounded_channel();
+    let server = Server::new(move |request: ServerRequest| {
+        let events_tx = events_tx.clone();
+        async move {
+            if request.params["REQUEST_URI"] == "/hang" {
+                let cancel_token = request.cancel_token.clone();
+                events_tx.send("started").unwrap();
+                tokio::spawn(async move {
+                    cancel_token.cancelled().await;
+                    events_tx.send("cancelled").unwrap();
+                });
+                std::future::pending::<()>().await;
+            }
+            ServerResponse::new("done")
+        }
+    });
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(async move { server.serve_connection(server_stream).await });
+
+    let mut client = Client::new_keep_alive(client_stream);
+    let stream = client
+        .execute_stream(Request::new(
+            Params::default().request_uri("/hang"),
+            tokio::io::empty(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(events_rx.recv().await, Some("started"));
+    stream.abort().await.unwrap();
+    assert_eq!(events_rx.recv().await, Some("cancelled"));
+
+    // The connection is still served.
+    let response = client
+        .execute(Request::new(
+            Params::default().request_uri("/"),
+            tokio::io::empty(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.stdout.as_deref(), Some(&b"done"[..]));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn serve_begin_running() {
+    common::setup();
+
+    // The handler of `/hang` returns after notified, and counts the calls.
+    let calls = Arc::new(AtomicU32::new(0));
+    let release = Arc::new(Notify::new());
+    let server = Server::new({
+        let calls = calls.clone();
+        let release = release.clone();
+        move |request: ServerRequest| {
+            let calls = calls.clone();
+            let release = release.clone();
+            async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                if request.params["REQUEST_URI"] == "/hang" {
+                    release.notified().await;
+                }
+                ServerResponse::new("done")
+            }
+        }
+    });
+    let (mut client_stream, server_stream) = duplex(4096);
+    tokio::spawn(async move { server.serve_connection(server_stream).await });
+
+    let params = common::encode_params(&[("REQUEST_URI", "/hang")]);
+    // The second begin reuses the id while the first handler is running.
+    for _ in 0..2 {
+        common::write_record(&mut client_stream, 1, 1, &[0, 1, 1, 0, 0, 0, 0, 0])
+            .await
+            .unwrap();
+        for (r#type, content) in [(4, &params[..]), (4, b""), (5, b"")] {
+            common::write_record(&mut client_stream, r#type, 1, content)
+                .await
+                .unwrap();
+        }
+    }
+    // Wait the records above are read by the get values reply.
+    common::write_record(&mut client_stream, 9, 0, b"")
+        .await
+        .unwrap();
+    let (r#type, _, _) = common::read_record(&mut client_stream).await.unwrap();
+    assert_eq!(r#type, 10);
+    release.notify_one();
+
+    loop {
+        let (r#type, id, _) = common::read_record(&mut client_stream).await.unwrap();
+        assert_eq!(id, 1);
+        if r#type == 3 {
+            break;
+        }
+    }
+
+    // The connection is still served, without the ignored request.
+    let mut client = Client::new_keep_alive(client_stream);
+    let response = client
+        .execute(Request::new(
+            Params::default().request_uri("/"),
+            tokio::io::empty(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.stdout.as_deref(), Some(&b"done"[..]));
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn serve_get_values() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    let server = Server::new(echo).values(Values {
+        max_conns: Some(10),
+        max_reqs: Some(50),
+        mpxs_conns: Some(false),
+    });
+    tokio::spawn(async move { server.serve_connection(server_stream).await });
+
+    let mut client = Client::new_keep_alive(client_stream);
+    let values = client.get_values(&ValueName::ALL).await.unwrap();
+    assert_eq!(
+        values,
+        Values {
+            max_conns: Some(10),
+            max_reqs: Some(50),
+            mpxs_conns: Some(false),
+        }
+    );
+
+    // Only the queried names are replied.
+    let values = client.get_values(&[ValueName::MaxReqs]).await.unwrap();
+    assert_eq!(
+        values,
+        Values {
+            max_reqs: Some(50),
+            ..Default::default()
+        }
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn serve_get_values_computed() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    let queries = AtomicU32::new(0);
+    let server = Server::new(echo).values_with(move || Values {
+        max_reqs: Some(queries.fetch_add(1, Ordering::Relaxed) + 1),
+        ..Default::default()
+    });
+    tokio::spawn(async move { server.serve_connection(server_stream).await });
+
+    let mut client = Client::new_keep_alive(client_stream);
+    for max_reqs in 1..=2 {
+        let values = client.get_values(&ValueName::ALL).await.unwrap();
+        assert_eq!(
+            values,
+            Values {
+                max_reqs: Some(max_reqs),
+                ..Default::default()
+            }
+        );
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn serve_listener_unix() {
+    use fastcgi_client::server::listen::{Listener, UnixOptions};
+    use std::os::unix::fs::PermissionsExt;
+
+    common::setup();
+
+    let path = std::env::temp_dir().join(format!("fastcgi-client-{}.sock", std::process::id()));
+    std::fs::write(&path, "stale").unwrap();
+    let options = UnixOptions::default().mode(0o600).remove_existing(true);
+    let listener = Listener::bind_unix(&path, &options).unwrap();
+    assert_eq!(
+        std::fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+        0o600
+    );
+    tokio::spawn(async move { Server::new(echo).serve(listener).await });
+
+    let stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+    let response = Client::new(stream)
+        .execute_once(Request::new(
+            Params::default().request_method("GET"),
+            tokio::io::empty(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(
+        response.stdout.as_deref(),
+        Some(&b"Content-type: text/plain\r\n\r\nGET "[..])
+    );
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn serve_listener_fd() {
+    use fastcgi_client::server::listen::Listener;
+    use std::os::unix::io::IntoRawFd;
+
+    common::setup();
+
+    let listener = std::net::TcpListener::bind(("127.0.0.1", 0)).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let listener = unsafe { Listener::from_raw_fd(listener.into_raw_fd()) }.unwrap();
+    assert_eq!(listener.local_addr().unwrap(), Some(addr));
+    tokio::spawn(async move { Server::new(echo).serve(listener).await });
+
+    let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let response = Client::new(stream)
+        .execute_once(Request::new(
+            Params::default().request_method("GET"),
+            tokio::io::empty(),
+        ))
+        .await
+        .unwrap();
+    assert!(response.stdout.is_some());
+
+    // Not activated by systemd.
+    assert!(Listener::from_listen_fds().unwrap().is_empty());
+}
@@ -0,0 +1,158 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(feature = "tower")]
+
+use bytes::Bytes;
+use fastcgi_client::{
+    server::{tower::TowerHandler, Server},
+    Client, Params, Request,
+};
+use http_body_util::{BodyExt, Full};
+use std::{
+    borrow::Cow,
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::io::duplex;
+use tower_service::Service;
+
+mod common;
+
+/// Echo the request as response.
+#[derive(Clone)]
+struct Echo;
+
+impl Service<http::Request<Full<Bytes>>> for Echo {
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+    type Response = http::Response<Full<Bytes>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<Full<Bytes>>) -> Self::Future {
+        Box::pin(async move {
+            let (parts, body) = request.into_parts();
+            let body = body.collect().await.unwrap().to_bytes();
+            let script =
+                parts.extensions.get::<Params<'static>>().unwrap()["SCRIPT_FILENAME"].clone();
+
+            let mut stdout = format!(
+                "{} {} {:?} {}\n",
+                parts.method, parts.uri, parts.version, script
+            );
+            for (name, value) in &parts.headers {
+                stdout.push_str(&format!("{}={}\n", name, value.to_str().unwrap()));
+            }
+            stdout.push_str(&String::from_utf8_lossy(&body));
+
+            Ok(http::Response::builder()
+                .status(http::StatusCode::CREATED)
+                .header("x-echo", "1")
+                .body(Full::new(Bytes::from(stdout)))
+                .unwrap())
+        })
+    }
+}
+
+/// Fail all the requests.
+#[derive(Clone)]
+struct Fail;
+
+impl Service<http::Request<Full<Bytes>>> for Fail {
+    type Error = &'static str;
+    type Future = std::future::Ready<Result<Self::Response, Self::Error>>;
+    type Response = http::Response<Full<Bytes>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _request: http::Request<Full<Bytes>>) -> Self::Future {
+        std::future::ready(Err("broken"))
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn serve_tower_service() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(async move {
+        Server::new(TowerHandler::new(Echo))
+            .serve_connection(server_stream)
+            .await
+    });
+
+    let mut params = Params::default()
+        .request_method("POST")
+        .request_uri("/path?a=1")
+        .script_filename("/app/index.php")
+        .content_type("text/plain")
+        .content_length(5);
+    params.insert(
+        Cow::Borrowed("HTTP_X_FORWARDED_FOR"),
+        Cow::Borrowed("10.0.0.1"),
+    );
+
+    let response = Client::new(client_stream)
+        .execute_once(Request::new(params, &b"hello"[..]))
+        .await
+        .unwrap()
+        .into_http()
+        .unwrap();
+
+    assert_eq!(response.status(), http::StatusCode::CREATED);
+    assert_eq!(response.headers()["x-echo"], "1");
+    assert_eq!(
+        String::from_utf8(response.into_body()).unwrap(),
+        [
+            "POST /path?a=1 HTTP/1.1 /app/index.php",
+            "content-type=text/plain",
+            "content-length=5",
+            "x-forwarded-for=10.0.0.1",
+            "hello",
+        ]
+        .join("\n")
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn serve_tower_service_error() {
+    common::setup();
+
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(async move {
+        Server::new(TowerHandler::new(Fail))
+            .serve_connection(server_stream)
+            .await
+    });
+
+    let params = Params::default()
+        .request_method("GET")
+        .script_name("/index.php")
+        .query_string("a=1");
+    let response = Client::new(client_stream)
+        .execute_once(Request::new(params, tokio::io::empty()))
+        .await
+        .unwrap();
+
+    assert_eq!(response.stderr.as_deref(), Some(&b"broken"[..]));
+    let response = response.into_http().unwrap();
+    assert_eq!(response.status(), http::StatusCode::INTERNAL_SERVER_ERROR);
+}
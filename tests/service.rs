@@ -0,0 +1,73 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(feature = "tower")]
+
+use fastcgi_client::{service::FastCgiService, shared::SharedClient, Params};
+use http_body_util::BodyExt;
+use tokio::io::duplex;
+use tower_service::Service;
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn call_service() {
+    common::setup();
+
+    let (client_stream, mut server_stream) = duplex(4096);
+
+    tokio::spawn(async move {
+        let request = common::read_request(&mut server_stream).await.unwrap();
+        assert_eq!(request.params["SCRIPT_FILENAME"], "/var/www/index.php");
+        assert_eq!(request.params["REQUEST_METHOD"], "POST");
+        assert_eq!(request.params["REQUEST_URI"], "/index.php?a=1");
+        assert_eq!(request.params["QUERY_STRING"], "a=1");
+        assert_eq!(request.params["CONTENT_TYPE"], "text/plain");
+        assert_eq!(request.params["CONTENT_LENGTH"], "5");
+        assert_eq!(request.params["HTTP_X_CUSTOM_HEADER"], "a, b");
+        assert_eq!(request.stdin, b"hello");
+
+        common::write_record(
+            &mut server_stream,
+            6,
+            request.id,
+            b"Status: 202 Accepted\r\nContent-type: text/plain\r\n\r\nworld",
+        )
+        .await
+        .unwrap();
+        common::write_end_request(&mut server_stream, request.id, 0, 0)
+            .await
+            .unwrap();
+    });
+
+    let mut service = FastCgiService::new(
+        SharedClient::new(client_stream),
+        Params::default().script_filename("/var/www/index.php"),
+    );
+
+    let request = http::Request::post("http://localhost/index.php?a=1")
+        .header("content-type", "text/plain")
+        .header("x-custom-header", "a")
+        .header("x-custom-header", "b")
+        .body("hello".to_owned())
+        .unwrap();
+    let response = service.call(request).await.unwrap();
+
+    assert_eq!(response.status(), http::StatusCode::ACCEPTED);
+    assert_eq!(response.headers()["content-type"], "text/plain");
+    assert_eq!(
+        response.into_body().collect().await.unwrap().to_bytes(),
+        "world"
+    );
+}
@@ -0,0 +1,90 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(feature = "trace")]
+
+use fastcgi_client::{
+    pool::Pool,
+    server::{Server, ServerRequest, ServerResponse},
+    Params, Request,
+};
+use std::{
+    collections::HashMap,
+    fmt, io,
+    sync::{Arc, Mutex},
+};
+use tokio::io::duplex;
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Subscriber,
+};
+use tracing_subscriber::{layer::Context, prelude::*, registry::LookupSpan, Layer};
+
+mod common;
+
+type Fields = Arc<Mutex<HashMap<String, String>>>;
+
+/// Collect the fields of all spans.
+struct FieldsLayer(Fields);
+
+impl Visit for FieldsLayer {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .lock()
+            .unwrap()
+            .insert(field.name().to_owned(), format!("{:?}", value));
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for FieldsLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+        attrs.record(&mut FieldsLayer(self.0.clone()));
+    }
+
+    fn on_record(&self, _id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+        values.record(&mut FieldsLayer(self.0.clone()));
+    }
+}
+
+async fn hello(_request: ServerRequest) -> ServerResponse {
+    ServerResponse::new("Content-type: text/plain\r\n\r\nhello").app_status(3)
+}
+
+#[tokio::test]
+async fn request_span() {
+    let fields = Fields::default();
+    let _guard = tracing_subscriber::registry()
+        .with(FieldsLayer(fields.clone()))
+        .set_default();
+
+    let connector = || async {
+        let (client_stream, server_stream) = duplex(4096);
+        tokio::spawn(async move { Server::new(hello).serve_connection(server_stream).await });
+        Ok::<_, io::Error>(client_stream)
+    };
+    let pool = Pool::new(connector);
+    pool.execute(Request::new(
+        Params::default().script_name("/index.php"),
+        tokio::io::empty(),
+    ))
+    .await
+    .unwrap();
+
+    let fields = fields.lock().unwrap();
+    assert_eq!(fields["request_id"], "1");
+    assert_eq!(fields["script"], "\"/index.php\"");
+    assert_eq!(fields["app_status"], "3");
+    assert!(fields.contains_key("duration_ms"));
+}
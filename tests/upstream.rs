@@ -0,0 +1,85 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastcgi_client::{
+    server::{Server, ServerRequest, ServerResponse},
+    upstream::{Balance, Upstreams},
+    Params, Request,
+};
+use std::{future::Ready, io};
+use tokio::io::{duplex, DuplexStream};
+
+mod common;
+
+async fn first(_request: ServerRequest) -> ServerResponse {
+    ServerResponse::new("Content-type: text/plain\r\n\r\nfirst")
+}
+
+async fn second(_request: ServerRequest) -> ServerResponse {
+    ServerResponse::new("Content-type: text/plain\r\n\r\nsecond")
+}
+
+type Connector = Box<dyn Fn() -> Ready<io::Result<DuplexStream>> + Send + Sync>;
+
+fn connector(second_upstream: bool) -> Connector {
+    Box::new(move || {
+        let (client_stream, server_stream) = duplex(4096);
+        tokio::spawn(async move {
+            if second_upstream {
+                Server::new(second).serve_connection(server_stream).await
+            } else {
+                Server::new(first).serve_connection(server_stream).await
+            }
+        });
+        std::future::ready(Ok(client_stream))
+    })
+}
+
+async fn execute(upstreams: &Upstreams<Connector>) -> String {
+    let output = upstreams
+        .execute(Request::new(Params::default(), tokio::io::empty()))
+        .await
+        .unwrap();
+    String::from_utf8(output.stdout.unwrap()).unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn round_robin() {
+    common::setup();
+
+    let upstreams = Upstreams::new([connector(false), connector(true)]);
+    assert_eq!(upstreams.balance(), Balance::RoundRobin);
+
+    let mut bodies = Vec::new();
+    for _ in 0..4 {
+        bodies.push(execute(&upstreams).await);
+    }
+    assert!(bodies[0].ends_with("first"));
+    assert!(bodies[1].ends_with("second"));
+    assert!(bodies[2].ends_with("first"));
+    assert!(bodies[3].ends_with("second"));
+    assert_eq!(upstreams.pools()[0].idle(), 1);
+    assert_eq!(upstreams.pools()[1].idle(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn least_connections() {
+    common::setup();
+
+    let upstreams =
+        Upstreams::new([connector(false), connector(true)]).with_balance(Balance::LeastConnections);
+
+    let (a, b) = tokio::join!(execute(&upstreams), execute(&upstreams));
+    assert_ne!(a, b);
+}
@@ -0,0 +1,63 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(all(feature = "tokio-uring", target_os = "linux"))]
+
+use fastcgi_client::{
+    server::{Server, ServerRequest, ServerResponse},
+    uring::UringStream,
+    Client, Params, Request,
+};
+use std::{net::TcpListener, thread};
+
+mod common;
+
+async fn hello(_request: ServerRequest) -> ServerResponse {
+    ServerResponse::new("Content-type: text/plain\r\n\r\nhello")
+}
+
+#[test]
+fn uring_stream() {
+    common::setup();
+
+    // The server runs in the tokio runtime of another thread.
+    let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+    let addr = listener.local_addr().unwrap();
+    listener.set_nonblocking(true).unwrap();
+    thread::spawn(move || {
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(async move {
+                let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                Server::new(hello).serve_tcp(listener).await
+            })
+    });
+
+    tokio_uring::start(async move {
+        let stream = UringStream::connect(addr).await.unwrap();
+        let mut client = Client::new_keep_alive(stream);
+        for _ in 0..3 {
+            let stdin = vec![b'a'; 100000];
+            let output = client
+                .execute(Request::new(Params::default(), &stdin[..]))
+                .await
+                .unwrap();
+            assert_eq!(
+                output.stdout.unwrap(),
+                b"Content-type: text/plain\r\n\r\nhello"
+            );
+        }
+        client.close().await.unwrap();
+    });
+}
@@ -0,0 +1,130 @@
+// Copyright 2022 jmjoy
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(feature = "warp")]
+
+use fastcgi_client::{
+    gateway::GatewayConfig,
+    pool::Pool,
+    server::{Server, ServerRequest, ServerResponse},
+    warp::fastcgi,
+};
+use std::{io, time::Duration};
+use tokio::io::{duplex, DuplexStream};
+use warp::{http::StatusCode, Filter};
+
+mod common;
+
+async fn php(request: ServerRequest) -> ServerResponse {
+    if request.params["SCRIPT_NAME"] == "/php/slow.php" {
+        tokio::time::sleep(Duration::from_secs(10)).await;
+    }
+    if request.params["SCRIPT_NAME"] == "/php/redirect.php" {
+        return ServerResponse::new("Location: /php/index.php?page=3\r\n\r\n");
+    }
+    ServerResponse::new(format!(
+        "Status: 201 Created\r\nX-Script: {}\r\n\r\n{} {} {}",
+        request.params["SCRIPT_FILENAME"],
+        request.params["QUERY_STRING"],
+        request.params["HTTP_X_NAME"],
+        String::from_utf8_lossy(&request.stdin),
+    ))
+}
+
+async fn connect() -> io::Result<DuplexStream> {
+    let (client_stream, server_stream) = duplex(4096);
+    tokio::spawn(async move { Server::new(php).serve_connection(server_stream).await });
+    Ok(client_stream)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn proxy_filter() {
+    common::setup();
+
+    let config = GatewayConfig::new("/var/www").timeout(Duration::from_millis(100));
+    let filter = warp::path("php").and(fastcgi(Pool::new(connect), config));
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/php/index.php?page=2")
+        .header("x-name", "jmjoy")
+        .body("a=1")
+        .reply(&filter)
+        .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(response.headers()["x-script"], "/var/www/php/index.php");
+    assert_eq!(response.body(), "page=2 jmjoy a=1");
+
+    let response = warp::test::request()
+        .path("/php/slow.php")
+        .reply(&filter)
+        .await;
+    assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+
+    // Not matched.
+    let response = warp::test::request()
+        .path("/static/index.html")
+        .reply(&filter)
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn proxy_filter_unavailable() {
+    common::setup();
+
+    let refused =
+        || async { Err::<DuplexStream, _>(io::Error::from(io::ErrorKind::ConnectionRefused)) };
+    let filter = fastcgi(Pool::new(refused), GatewayConfig::new("/var/www"));
+
+    let response = warp::test::request()
+        .path("/index.php")
+        .reply(&filter)
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn proxy_filter_forward() {
+    common::setup();
+
+    let config = GatewayConfig::new("/var/www")
+        .max_local_redirects(1)
+        .max_body_size(4);
+    let filter = warp::path("php").and(fastcgi(Pool::new(connect), config));
+
+    let response = warp::test::request()
+        .path("/php/redirect.php")
+        .header("x-name", "jmjoy")
+        .reply(&filter)
+        .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(response.body(), "page=3 jmjoy ");
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/php/index.php")
+        .body("a=1&b=2")
+        .reply(&filter)
+        .await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/php/index.php")
+        .header("transfer-encoding", "chunked")
+        .reply(&filter)
+        .await;
+    assert_eq!(response.status(), StatusCode::LENGTH_REQUIRED);
+}